@@ -40,6 +40,8 @@ async function main() {
     mint: MINT,
     authority: wallet.publicKey,
     tokenAccount,
+    depositorRecord: null,
+    depositAllowance: null,
   }).rpc();
 
 }
@@ -0,0 +1 @@
+drop table distributions;
\ No newline at end of file
@@ -0,0 +1,13 @@
+CREATE TABLE distributions (
+  id bigserial PRIMARY KEY,
+  signature varchar(88) NOT NULL,
+  share_size bigint NOT NULL,
+  burned bigint NOT NULL,
+  holders_number bigint NOT NULL,
+  winners varchar(44)[] NOT NULL,
+  fee_lamports bigint NOT NULL,
+  rent_lamports bigint NOT NULL,
+  report_markdown text,
+  report_html text,
+  created_at  timestamp with time zone DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,2 @@
+drop table disqualifications;
+drop table wallet_flags;
\ No newline at end of file
@@ -0,0 +1,14 @@
+CREATE TABLE wallet_flags (
+  wallet varchar(44) NOT NULL PRIMARY KEY,
+  reason text,
+  created_at  timestamp with time zone DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE disqualifications (
+  id bigserial PRIMARY KEY,
+  distribution_id bigint NOT NULL REFERENCES distributions (id),
+  wallet varchar(44) NOT NULL,
+  reason text NOT NULL,
+  replacement varchar(44) NOT NULL,
+  created_at  timestamp with time zone DEFAULT CURRENT_TIMESTAMP
+);
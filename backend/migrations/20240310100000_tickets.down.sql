@@ -0,0 +1 @@
+drop table tickets;
\ No newline at end of file
@@ -0,0 +1,9 @@
+CREATE TABLE tickets (
+  id bigserial PRIMARY KEY,
+  campaign varchar(64) NOT NULL,
+  wallet varchar(44) NOT NULL,
+  weight bigint NOT NULL CHECK (weight > 0),
+  created_at  timestamp with time zone DEFAULT CURRENT_TIMESTAMP,
+  updated_at  timestamp with time zone DEFAULT CURRENT_TIMESTAMP,
+  UNIQUE (campaign, wallet)
+);
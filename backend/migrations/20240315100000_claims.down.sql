@@ -0,0 +1 @@
+drop table claims;
\ No newline at end of file
@@ -0,0 +1,13 @@
+CREATE TABLE claims (
+  id bigserial PRIMARY KEY,
+  distributor_state varchar(44) NOT NULL,
+  round bigint NOT NULL,
+  claim_index bigint NOT NULL,
+  wallet varchar(44) NOT NULL,
+  amount bigint NOT NULL,
+  proof bytea[] NOT NULL,
+  created_at  timestamp with time zone DEFAULT CURRENT_TIMESTAMP,
+  UNIQUE (distributor_state, round, claim_index)
+);
+
+CREATE INDEX claims_wallet_idx ON claims (wallet);
@@ -0,0 +1 @@
+alter table distributions drop column announce_at;
\ No newline at end of file
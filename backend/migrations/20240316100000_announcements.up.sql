@@ -0,0 +1 @@
+ALTER TABLE distributions ADD COLUMN announce_at timestamp with time zone NOT NULL DEFAULT CURRENT_TIMESTAMP;
@@ -0,0 +1 @@
+alter table distributions drop column public_report_markdown, drop column public_report_html;
\ No newline at end of file
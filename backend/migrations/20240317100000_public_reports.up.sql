@@ -0,0 +1 @@
+ALTER TABLE distributions ADD COLUMN public_report_markdown text, ADD COLUMN public_report_html text;
\ No newline at end of file
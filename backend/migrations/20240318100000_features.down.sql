@@ -0,0 +1 @@
+drop table features;
\ No newline at end of file
@@ -0,0 +1,8 @@
+CREATE TABLE features (
+  distributor_state varchar(44) NOT NULL,
+  name varchar(64) NOT NULL,
+  enabled boolean NOT NULL,
+  created_at  timestamp with time zone DEFAULT CURRENT_TIMESTAMP,
+  updated_at  timestamp with time zone DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (distributor_state, name)
+);
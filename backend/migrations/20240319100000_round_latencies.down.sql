@@ -0,0 +1,6 @@
+ALTER TABLE distributions
+  DROP COLUMN webhook_received_at,
+  DROP COLUMN snapshot_done_at,
+  DROP COLUMN draw_done_at,
+  DROP COLUMN tx_sent_at,
+  DROP COLUMN tx_confirmed_at;
@@ -0,0 +1,6 @@
+ALTER TABLE distributions
+  ADD COLUMN webhook_received_at timestamp with time zone,
+  ADD COLUMN snapshot_done_at timestamp with time zone,
+  ADD COLUMN draw_done_at timestamp with time zone,
+  ADD COLUMN tx_sent_at timestamp with time zone,
+  ADD COLUMN tx_confirmed_at timestamp with time zone;
@@ -0,0 +1 @@
+drop table runtime_config;
\ No newline at end of file
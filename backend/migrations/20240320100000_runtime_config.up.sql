@@ -0,0 +1,8 @@
+CREATE TABLE runtime_config (
+  distributor_state varchar(44) NOT NULL,
+  name varchar(64) NOT NULL,
+  value text NOT NULL,
+  created_at  timestamp with time zone DEFAULT CURRENT_TIMESTAMP,
+  updated_at  timestamp with time zone DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (distributor_state, name)
+);
@@ -0,0 +1 @@
+drop table shadow_draws;
\ No newline at end of file
@@ -0,0 +1,11 @@
+CREATE TABLE shadow_draws (
+  id bigserial PRIMARY KEY,
+  distribution_id bigint NOT NULL REFERENCES distributions (id),
+  distributor_state varchar(44) NOT NULL,
+  strategy varchar(32) NOT NULL,
+  entrants bigint NOT NULL,
+  current_indices bigint[] NOT NULL,
+  candidate_indices bigint[] NOT NULL,
+  common_winners integer NOT NULL,
+  created_at  timestamp with time zone DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1 @@
+drop table round_transactions;
\ No newline at end of file
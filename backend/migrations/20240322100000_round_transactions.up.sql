@@ -0,0 +1,10 @@
+CREATE TABLE round_transactions (
+  id bigserial PRIMARY KEY,
+  distributor_state varchar(44) NOT NULL,
+  round bigint NOT NULL,
+  signature varchar(88) NOT NULL,
+  status varchar(16) NOT NULL,
+  attempts integer NOT NULL,
+  error text,
+  created_at  timestamp with time zone DEFAULT CURRENT_TIMESTAMP
+);
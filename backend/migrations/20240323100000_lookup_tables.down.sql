@@ -0,0 +1 @@
+drop table lookup_tables;
\ No newline at end of file
@@ -0,0 +1,5 @@
+CREATE TABLE lookup_tables (
+  distributor_state varchar(44) PRIMARY KEY,
+  address varchar(44) NOT NULL,
+  updated_at  timestamp with time zone DEFAULT CURRENT_TIMESTAMP
+);
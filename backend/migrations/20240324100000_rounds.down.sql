@@ -0,0 +1,2 @@
+drop table winners;
+drop table rounds;
\ No newline at end of file
@@ -0,0 +1,21 @@
+CREATE TABLE rounds (
+  id bigserial PRIMARY KEY,
+  distributor_state varchar(44) NOT NULL,
+  round bigint NOT NULL,
+  status varchar(16) NOT NULL,
+  signature varchar(88),
+  error text,
+  triggered_at  timestamp with time zone DEFAULT CURRENT_TIMESTAMP,
+  updated_at  timestamp with time zone DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE winners (
+  id bigserial PRIMARY KEY,
+  round_id bigint NOT NULL REFERENCES rounds (id),
+  position integer NOT NULL,
+  wallet varchar(44) NOT NULL,
+  amount bigint NOT NULL,
+  created_at  timestamp with time zone DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX winners_wallet_idx ON winners (wallet);
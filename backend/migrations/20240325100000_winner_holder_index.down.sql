@@ -0,0 +1 @@
+ALTER TABLE winners DROP COLUMN holder_index;
\ No newline at end of file
@@ -0,0 +1 @@
+ALTER TABLE winners ADD COLUMN holder_index bigint NOT NULL DEFAULT 0;
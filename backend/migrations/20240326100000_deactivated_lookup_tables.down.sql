@@ -0,0 +1 @@
+DROP TABLE deactivated_lookup_tables;
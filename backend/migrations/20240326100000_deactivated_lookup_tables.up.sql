@@ -0,0 +1,5 @@
+CREATE TABLE deactivated_lookup_tables (
+  address varchar(44) PRIMARY KEY,
+  distributor_state varchar(44) NOT NULL,
+  deactivated_at  timestamp with time zone DEFAULT CURRENT_TIMESTAMP
+);
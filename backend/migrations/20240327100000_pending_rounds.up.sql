@@ -0,0 +1,13 @@
+CREATE TABLE pending_rounds (
+  distributor_state varchar(44) PRIMARY KEY,
+  round bigint NOT NULL,
+  transaction bytea NOT NULL,
+  holders_number bigint NOT NULL,
+  randomness_kind varchar(16) NOT NULL,
+  randomness bytea NOT NULL,
+  winners varchar(44)[] NOT NULL,
+  holder_indices bigint[] NOT NULL,
+  amounts bigint[] NOT NULL,
+  token_accounts varchar(44)[] NOT NULL,
+  created_at  timestamp with time zone DEFAULT CURRENT_TIMESTAMP
+);
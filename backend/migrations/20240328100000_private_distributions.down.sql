@@ -0,0 +1 @@
+ALTER TABLE distributions DROP COLUMN private;
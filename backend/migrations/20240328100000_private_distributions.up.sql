@@ -0,0 +1,2 @@
+ALTER TABLE distributions ADD COLUMN private boolean NOT NULL DEFAULT false;
+UPDATE distributions SET private = public_report_markdown IS NOT NULL;
@@ -0,0 +1,214 @@
+use crate::ticket::TicketEntry;
+use anyhow::{ensure, Context};
+use arrow_array::{
+    builder::{ListBuilder, StringBuilder},
+    Array, ArrayRef, Float64Array, Int64Array, RecordBatch, StringArray, UInt64Array,
+};
+use parquet::arrow::{arrow_reader::ParquetRecordBatchReaderBuilder, ArrowWriter};
+use std::sync::Arc;
+
+/// Content type of the exported files
+pub const PARQUET_CONTENT_TYPE: &str = "application/vnd.apache.parquet";
+
+/// Round of the `distributions` table as the data team sees it, without the rendered reports
+#[derive(Debug, Clone, PartialEq)]
+pub struct RoundRow {
+    pub id: i64,
+    pub signature: String,
+    pub share_size: i64,
+    pub burned: i64,
+    pub holders_number: i64,
+    pub winners: Vec<String>,
+    pub fee_lamports: i64,
+    pub rent_lamports: i64,
+    /// Seconds since the epoch
+    pub created_at: f64,
+}
+
+/// Tickets of the campaign as a Parquet file with the `wallet` and `weight` columns
+pub fn tickets_to_parquet(entries: &[TicketEntry]) -> anyhow::Result<Vec<u8>> {
+    let wallets = StringArray::from_iter_values(entries.iter().map(|entry| entry.wallet.to_string()));
+    let weights = UInt64Array::from_iter_values(entries.iter().map(|entry| entry.weight));
+    let batch = RecordBatch::try_from_iter([
+        ("wallet", Arc::new(wallets) as ArrayRef),
+        ("weight", Arc::new(weights) as ArrayRef),
+    ])
+    .context("Failed to build tickets batch")?;
+    write_parquet(&batch)
+}
+
+/// Reads an externally computed snapshot, a Parquet file with the `wallet` string column and the `weight` column
+/// of any integer type
+pub fn tickets_from_parquet(file: bytes::Bytes) -> anyhow::Result<Vec<TicketEntry>> {
+    let reader = ParquetRecordBatchReaderBuilder::try_new(file)
+        .context("Invalid Parquet file")?
+        .build()
+        .context("Invalid Parquet file")?;
+
+    let mut entries = vec![];
+    for batch in reader {
+        let batch = batch.context("Failed to read Parquet batch")?;
+        let wallets = batch
+            .column_by_name("wallet")
+            .and_then(|column| column.as_any().downcast_ref::<StringArray>())
+            .context("Snapshot has no string `wallet` column")?;
+        let weights = batch
+            .column_by_name("weight")
+            .context("Snapshot has no `weight` column")?;
+        let weights = arrow_cast::cast(weights, &arrow_schema::DataType::UInt64).context("Invalid `weight` column")?;
+        let weights = weights
+            .as_any()
+            .downcast_ref::<UInt64Array>()
+            .context("Invalid `weight` column")?;
+        ensure!(
+            wallets.null_count() == 0 && weights.null_count() == 0,
+            "Snapshot can't have empty cells"
+        );
+
+        for row in 0..batch.num_rows() {
+            let wallet = wallets.value(row);
+            entries.push(TicketEntry {
+                wallet: wallet
+                    .parse()
+                    .with_context(|| format!("Invalid snapshot wallet {wallet}"))?,
+                weight: weights.value(row),
+            });
+        }
+    }
+    Ok(entries)
+}
+
+/// Rounds as a Parquet file, the winners of a round are a list column
+pub fn rounds_to_parquet(rounds: &[RoundRow]) -> anyhow::Result<Vec<u8>> {
+    let mut winners = ListBuilder::new(StringBuilder::new());
+    for round in rounds {
+        for winner in &round.winners {
+            winners.values().append_value(winner);
+        }
+        winners.append(true);
+    }
+    let int64 =
+        |column: fn(&RoundRow) -> i64| Arc::new(Int64Array::from_iter_values(rounds.iter().map(column))) as ArrayRef;
+    let batch = RecordBatch::try_from_iter([
+        ("id", int64(|round| round.id)),
+        (
+            "signature",
+            Arc::new(StringArray::from_iter_values(
+                rounds.iter().map(|round| &round.signature),
+            )),
+        ),
+        ("share_size", int64(|round| round.share_size)),
+        ("burned", int64(|round| round.burned)),
+        ("holders_number", int64(|round| round.holders_number)),
+        ("winners", Arc::new(winners.finish())),
+        ("fee_lamports", int64(|round| round.fee_lamports)),
+        ("rent_lamports", int64(|round| round.rent_lamports)),
+        (
+            "created_at",
+            Arc::new(Float64Array::from_iter_values(
+                rounds.iter().map(|round| round.created_at),
+            )),
+        ),
+    ])
+    .context("Failed to build rounds batch")?;
+    write_parquet(&batch)
+}
+
+/// distributions.id, signature, share_size, burned, holders_number, winners, fee_lamports, rent_lamports, created_at
+type DistributionRow = (i64, String, i64, i64, i64, Vec<String>, i64, i64, f64);
+
+/// Every recorded round in the order they were sent
+pub async fn fetch_rounds(pool: &sqlx::PgPool) -> anyhow::Result<Vec<RoundRow>> {
+    let rows: Vec<DistributionRow> = sqlx::query_as(
+        "SELECT id, signature, share_size, burned, holders_number, winners, fee_lamports, rent_lamports, \
+         EXTRACT(EPOCH FROM created_at)::float8 FROM distributions ORDER BY id",
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch rounds")?;
+
+    Ok(rows
+        .into_iter()
+        .map(
+            |(id, signature, share_size, burned, holders_number, winners, fee_lamports, rent_lamports, created_at)| {
+                RoundRow {
+                    id,
+                    signature,
+                    share_size,
+                    burned,
+                    holders_number,
+                    winners,
+                    fee_lamports,
+                    rent_lamports,
+                    created_at,
+                }
+            },
+        )
+        .collect())
+}
+
+fn write_parquet(batch: &RecordBatch) -> anyhow::Result<Vec<u8>> {
+    let mut file = vec![];
+    let mut writer =
+        ArrowWriter::try_new(&mut file, batch.schema(), None).context("Failed to create Parquet writer")?;
+    writer.write(batch).context("Failed to write Parquet batch")?;
+    writer.close().context("Failed to finish Parquet file")?;
+    Ok(file)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        analytics::{rounds_to_parquet, tickets_from_parquet, tickets_to_parquet, RoundRow},
+        ticket::TicketEntry,
+    };
+    use parquet::file::reader::{FileReader, SerializedFileReader};
+    use solana_sdk::pubkey::Pubkey;
+
+    #[test]
+    fn should_round_trip_tickets() {
+        let entries = [3, 1, 7].map(|weight| TicketEntry {
+            wallet: Pubkey::new_unique(),
+            weight,
+        });
+
+        let file = tickets_to_parquet(&entries).unwrap();
+        let imported = tickets_from_parquet(file.into()).unwrap();
+
+        assert_eq!(entries.len(), imported.len());
+        for (entry, imported) in entries.iter().zip(&imported) {
+            assert_eq!(entry.wallet, imported.wallet);
+            assert_eq!(entry.weight, imported.weight);
+        }
+    }
+
+    #[test]
+    fn should_export_rounds() {
+        let round = RoundRow {
+            id: 1,
+            signature: "signature".to_string(),
+            share_size: 100,
+            burned: 100,
+            holders_number: 42,
+            winners: vec![Pubkey::new_unique().to_string(), Pubkey::new_unique().to_string()],
+            fee_lamports: 5000,
+            rent_lamports: 0,
+            created_at: 1_700_000_000.0,
+        };
+
+        let file = rounds_to_parquet(&[round.clone(), RoundRow { id: 2, ..round }]).unwrap();
+        let reader = SerializedFileReader::new(bytes::Bytes::from(file)).unwrap();
+
+        assert_eq!(2, reader.metadata().file_metadata().num_rows());
+        assert_eq!(
+            9,
+            reader
+                .metadata()
+                .file_metadata()
+                .schema_descr()
+                .root_schema()
+                .get_fields()
+                .len()
+        );
+    }
+}
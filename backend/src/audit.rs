@@ -0,0 +1,156 @@
+use crate::{round::Payout, token_holder::Winner};
+use anyhow::Context;
+use solana_sdk::pubkey::Pubkey;
+
+/// Status of a round in the `rounds` table
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoundStatus {
+    /// Threshold is reached, the round is being drawn and paid
+    Triggered,
+    Confirmed,
+    /// Round will be tried again, nothing was paid
+    Rescheduled,
+    Failed,
+}
+
+impl RoundStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RoundStatus::Triggered => "triggered",
+            RoundStatus::Confirmed => "confirmed",
+            RoundStatus::Rescheduled => "rescheduled",
+            RoundStatus::Failed => "failed",
+        }
+    }
+}
+
+/// Final status of the round by the result of paying it and the error it failed with. The payout is `None` if the
+/// round is rescheduled
+pub fn round_status<T>(paid: &anyhow::Result<Option<(T, Payout)>>) -> (RoundStatus, Option<String>) {
+    match paid {
+        Ok(Some(_)) => (RoundStatus::Confirmed, None),
+        Ok(None) => (RoundStatus::Rescheduled, None),
+        Err(err) => (RoundStatus::Failed, Some(format!("{err:#}"))),
+    }
+}
+
+/// Audit trail of every triggered round of the campaign, the paid ones and the ones which failed or were
+/// rescheduled, with the drawn winners. It survives the restarts, unlike the logs
+pub struct RoundAudit<'a> {
+    pub pool: &'a sqlx::PgPool,
+    pub distributor_state: &'a Pubkey,
+}
+
+impl RoundAudit<'_> {
+    /// Records the trigger of the round, returns the id of its row
+    pub async fn begin(&self, round: u64) -> anyhow::Result<i64> {
+        sqlx::query_scalar("INSERT INTO rounds (distributor_state, round, status) VALUES ($1, $2, $3) RETURNING id")
+            .bind(self.distributor_state.to_string())
+            .bind(round as i64)
+            .bind(RoundStatus::Triggered.as_str())
+            .fetch_one(self.pool)
+            .await
+            .context("Failed to record round")
+    }
+
+    /// Records the drawn winners of the round and the amounts they are paid, both in the winners order
+    pub async fn store_winners(&self, id: i64, winners: &[Winner], amounts: &[u64]) -> anyhow::Result<()> {
+        sqlx::query(
+            "INSERT INTO winners (round_id, position, wallet, amount, holder_index) \
+             SELECT $1, * FROM UNNEST($2::integer[], $3::varchar[], $4::bigint[], $5::bigint[])",
+        )
+        .bind(id)
+        .bind((0..winners.len() as i32).collect::<Vec<_>>())
+        .bind(
+            winners
+                .iter()
+                .map(|winner| winner.wallet.to_string())
+                .collect::<Vec<_>>(),
+        )
+        .bind(amounts.iter().map(|amount| *amount as i64).collect::<Vec<_>>())
+        .bind(winners.iter().map(|winner| winner.index as i64).collect::<Vec<_>>())
+        .execute(self.pool)
+        .await
+        .context("Failed to record winners")?;
+        Ok(())
+    }
+
+    /// Winners recorded by the latest attempt of the round which drew any, in the winners order. A chunked round
+    /// left open on-chain is resumed with them, its paid chunks can't be drawn again
+    pub async fn recorded_winners(&self, round: u64) -> anyhow::Result<Option<Vec<Winner>>> {
+        let rows: Vec<(i64, String)> = sqlx::query_as(
+            "SELECT holder_index, wallet FROM winners WHERE round_id = ( \
+               SELECT id FROM rounds WHERE distributor_state = $1 AND round = $2 \
+               AND EXISTS (SELECT 1 FROM winners WHERE round_id = rounds.id) \
+               ORDER BY id DESC LIMIT 1 \
+             ) ORDER BY position",
+        )
+        .bind(self.distributor_state.to_string())
+        .bind(round as i64)
+        .fetch_all(self.pool)
+        .await
+        .context("Failed to fetch recorded winners")?;
+        if rows.is_empty() {
+            return Ok(None);
+        }
+        rows.into_iter()
+            .map(|(index, wallet)| {
+                Ok(Winner {
+                    index: index as u64,
+                    wallet: wallet.parse().context("Invalid recorded winner")?,
+                })
+            })
+            .collect::<anyhow::Result<_>>()
+            .map(Some)
+    }
+
+    /// Records how the round ended, the signature is the one of the last transaction of a paid round
+    pub async fn finish<T>(&self, id: i64, paid: &anyhow::Result<Option<(T, Payout)>>) -> anyhow::Result<()> {
+        let (status, error) = round_status(paid);
+        let signature = match paid {
+            Ok(Some((_, payout))) => Some(payout.signature.to_string()),
+            _ => None,
+        };
+        sqlx::query(
+            "UPDATE rounds SET status = $2, signature = $3, error = $4, updated_at = CURRENT_TIMESTAMP WHERE id = $1",
+        )
+        .bind(id)
+        .bind(status.as_str())
+        .bind(signature)
+        .bind(error)
+        .execute(self.pool)
+        .await
+        .context("Failed to record round status")?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        audit::{round_status, RoundStatus},
+        round::Payout,
+    };
+    use anyhow::anyhow;
+    use solana_sdk::signature::Signature;
+
+    #[test]
+    fn should_map_payment_to_round_status() {
+        let payout = Payout {
+            signature: Signature::default(),
+            fee_lamports: 5000,
+            ata_rent: 0,
+            atas_created: vec![],
+        };
+        assert_eq!((RoundStatus::Confirmed, None), round_status(&Ok(Some(((), payout)))));
+        assert_eq!((RoundStatus::Rescheduled, None), round_status::<()>(&Ok(None)));
+        assert_eq!(
+            (
+                RoundStatus::Failed,
+                Some("Failed to send transaction: timeout".to_string())
+            ),
+            round_status::<()>(&Err(anyhow!("timeout").context("Failed to send transaction")))
+        );
+        assert_eq!("rescheduled", RoundStatus::Rescheduled.as_str());
+    }
+}
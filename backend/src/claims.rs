@@ -0,0 +1,123 @@
+use crate::token_holder::Winner;
+use anyhow::Context;
+use distributor::client::MerkleTree;
+use serde::Serialize;
+use serde_with::{serde_as, DisplayFromStr};
+use solana_sdk::pubkey::Pubkey;
+
+/// Everything a winner needs to call `claim` for the round
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ClaimEntry {
+    #[serde_as(as = "DisplayFromStr")]
+    pub distributor_state: Pubkey,
+    pub round: u64,
+    /// Leaf index of the claim, the position of the winner in the round
+    pub index: u64,
+    #[serde_as(as = "DisplayFromStr")]
+    pub wallet: Pubkey,
+    pub amount: u64,
+    pub proof: Vec<[u8; 32]>,
+}
+
+/// Claims of the round paying `amount` to every winner and the merkle root committing to them
+pub fn claim_entries(
+    distributor_state: &Pubkey,
+    round: u64,
+    winners: &[Winner],
+    amount: u64,
+) -> ([u8; 32], Vec<ClaimEntry>) {
+    let leaves = winners.iter().map(|winner| (winner.wallet, amount)).collect::<Vec<_>>();
+    let tree = MerkleTree::new(&leaves);
+    let entries = winners
+        .iter()
+        .enumerate()
+        .map(|(index, winner)| ClaimEntry {
+            distributor_state: *distributor_state,
+            round,
+            index: index as u64,
+            wallet: winner.wallet,
+            amount,
+            proof: tree.proof(index),
+        })
+        .collect();
+    (tree.root(), entries)
+}
+
+pub async fn store_claims(pool: &sqlx::PgPool, entries: &[ClaimEntry]) -> anyhow::Result<()> {
+    let mut tx = pool.begin().await.context("Failed to begin transaction")?;
+    for entry in entries {
+        sqlx::query(
+            "INSERT INTO claims (distributor_state, round, claim_index, wallet, amount, proof) \
+             VALUES ($1, $2, $3, $4, $5, $6) ON CONFLICT (distributor_state, round, claim_index) DO NOTHING",
+        )
+        .bind(entry.distributor_state.to_string())
+        .bind(entry.round as i64)
+        .bind(entry.index as i64)
+        .bind(entry.wallet.to_string())
+        .bind(entry.amount as i64)
+        .bind(entry.proof.iter().map(|hash| hash.to_vec()).collect::<Vec<_>>())
+        .execute(&mut *tx)
+        .await
+        .context("Failed to store claim")?;
+    }
+    tx.commit().await.context("Failed to commit claims")?;
+    Ok(())
+}
+
+/// distributor_state, round, claim_index, amount, proof
+type ClaimRow = (String, i64, i64, i64, Vec<Vec<u8>>);
+
+/// Claims of the wallet in every round, newest first
+pub async fn fetch_claims(pool: &sqlx::PgPool, wallet: &Pubkey) -> anyhow::Result<Vec<ClaimEntry>> {
+    let rows: Vec<ClaimRow> = sqlx::query_as(
+        "SELECT distributor_state, round, claim_index, amount, proof FROM claims WHERE wallet = $1 \
+         ORDER BY round DESC",
+    )
+    .bind(wallet.to_string())
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch claims")?;
+
+    rows.into_iter()
+        .map(|(distributor_state, round, index, amount, proof)| {
+            Ok(ClaimEntry {
+                distributor_state: distributor_state.parse().context("Invalid claim distributor state")?,
+                round: round as u64,
+                index: index as u64,
+                wallet: *wallet,
+                amount: amount as u64,
+                proof: proof
+                    .into_iter()
+                    .map(|hash| hash.try_into().map_err(|_| anyhow::anyhow!("Invalid claim proof")))
+                    .collect::<anyhow::Result<_>>()?,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{claims::claim_entries, token_holder::Winner};
+    use distributor::merkle;
+    use solana_sdk::pubkey::Pubkey;
+
+    #[test]
+    fn should_build_verifiable_claims() {
+        let winners = (0..5)
+            .map(|index| Winner {
+                index: index * 10,
+                wallet: Pubkey::new_unique(),
+            })
+            .collect::<Vec<_>>();
+        let (root, entries) = claim_entries(&Pubkey::new_unique(), 3, &winners, 331);
+
+        assert_eq!(5, entries.len());
+        for (position, entry) in entries.iter().enumerate() {
+            assert_eq!(position as u64, entry.index);
+            assert_eq!(winners[position].wallet, entry.wallet);
+            let leaf = merkle::leaf(entry.index, &entry.wallet, entry.amount);
+            assert!(merkle::verify(&entry.proof, &root, leaf));
+        }
+    }
+}
@@ -0,0 +1,429 @@
+use crate::{report::Randomness, token_holder::Winner};
+use anyhow::Context;
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind},
+    nonblocking::rpc_client::RpcClient,
+    nonce_utils,
+};
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    hash::Hash,
+    message::VersionedMessage,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature},
+    system_instruction::SystemInstruction,
+    system_program,
+    transaction::{TransactionError, VersionedTransaction},
+};
+use solana_transaction_status::TransactionStatus;
+use std::time::Duration;
+
+/// Interval the signature status of a sent transaction is polled at
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// Delay before a durable nonce transaction is broadcast again after the RPC failed to take it
+const BROADCAST_RETRY_DELAY: Duration = Duration::from_secs(10);
+
+/// Final status of a sent transaction
+#[derive(Debug, Clone, PartialEq)]
+pub enum TxOutcome {
+    Confirmed,
+    /// Landed but failed, the fee is paid
+    Failed(TransactionError),
+    /// Blockhash of the last attempt expired before the transaction landed, it can't land anymore
+    Expired,
+}
+
+impl TxOutcome {
+    /// Status of the `round_transactions` table
+    pub fn status(&self) -> &'static str {
+        match self {
+            TxOutcome::Confirmed => "confirmed",
+            TxOutcome::Failed(_) => "failed",
+            TxOutcome::Expired => "expired",
+        }
+    }
+}
+
+/// Sent transaction of a round and how it ended
+#[derive(Debug, Clone, PartialEq)]
+pub struct Confirmation {
+    /// Signature of the last attempt
+    pub signature: Signature,
+    pub outcome: TxOutcome,
+    pub attempts: u32,
+}
+
+/// Outcome of a sent transaction by its signature status, `None` while it may still land. The blockhash validity has
+/// to be checked before the status, a transaction which isn't found with an already expired blockhash never lands
+pub fn poll_outcome(status: Option<&TransactionStatus>, blockhash_valid: bool) -> Option<TxOutcome> {
+    match status {
+        Some(TransactionStatus { err: Some(err), .. }) => Some(TxOutcome::Failed(err.clone())),
+        Some(status) if status.satisfies_commitment(CommitmentConfig::confirmed()) => Some(TxOutcome::Confirmed),
+        Some(_) => None,
+        None if blockhash_valid => None,
+        None => Some(TxOutcome::Expired),
+    }
+}
+
+/// Nonce account the message advances by its first instruction, `None` if it is valid by a recent blockhash
+pub fn durable_nonce_account(message: &VersionedMessage) -> Option<Pubkey> {
+    let keys = message.static_account_keys();
+    let ix = message.instructions().first()?;
+    if keys.get(ix.program_id_index as usize) != Some(&system_program::ID) {
+        return None;
+    }
+    match bincode::deserialize(&ix.data) {
+        Ok(SystemInstruction::AdvanceNonceAccount) => keys.get(*ix.accounts.first()? as usize).copied(),
+        _ => None,
+    }
+}
+
+/// Durable nonce the nonce account holds, the blockhash of the next transaction advancing it
+pub async fn fetch_durable_nonce(rpc_client: &RpcClient, nonce_account: &Pubkey) -> anyhow::Result<Hash> {
+    let account =
+        nonce_utils::nonblocking::get_account_with_commitment(rpc_client, nonce_account, CommitmentConfig::confirmed())
+            .await
+            .context("Failed to fetch nonce account")?;
+    let data = nonce_utils::data_from_account(&account).context("Invalid nonce account")?;
+    Ok(data.blockhash())
+}
+
+/// Whether the transaction can still land: its blockhash is recent, or the nonce account still holds its durable
+/// nonce
+async fn is_valid(rpc_client: &RpcClient, message: &VersionedMessage) -> Result<bool, ClientError> {
+    match durable_nonce_account(message) {
+        Some(nonce_account) => Ok(fetch_durable_nonce(rpc_client, &nonce_account)
+            .await
+            .map_err(|err| ClientErrorKind::Custom(format!("{err:#}")))?
+            == *message.recent_blockhash()),
+        None => {
+            rpc_client
+                .is_blockhash_valid(message.recent_blockhash(), CommitmentConfig::processed())
+                .await
+        },
+    }
+}
+
+/// Broadcasts the transaction. A durable nonce transaction stays valid while the RPC is down, so it is broadcast
+/// again every `BROADCAST_RETRY_DELAY` until the RPC takes it or its nonce moves, instead of failing the round
+async fn broadcast(rpc_client: &RpcClient, tx: &VersionedTransaction) -> Result<Signature, ClientError> {
+    let durable = durable_nonce_account(&tx.message).is_some();
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        match rpc_client.send_transaction(tx).await {
+            // the nonce can't be checked while the RPC is down either, the transaction is taken as valid then
+            Err(err)
+                if durable && is_transport_error(&err) && is_valid(rpc_client, &tx.message).await.unwrap_or(true) =>
+            {
+                tracing::warn!(
+                    ?err,
+                    attempts,
+                    "Failed to broadcast the durable nonce transaction, retrying"
+                );
+                tokio::time::sleep(BROADCAST_RETRY_DELAY).await;
+            },
+            result => return result,
+        }
+    }
+}
+
+/// RPC couldn't be reached, the transaction wasn't looked at
+fn is_transport_error(err: &ClientError) -> bool {
+    matches!(err.kind(), ClientErrorKind::Io(_) | ClientErrorKind::Reqwest(_))
+}
+
+/// Sends the transaction and polls its status until it is confirmed, fails or expires. A transaction expires with its
+/// blockhash, or once its nonce account is advanced without it if it is signed against a durable nonce. An expired
+/// transaction is signed by `signers` again with a fresh blockhash, up to `max_attempts` sends in total. A durable
+/// nonce transaction isn't, it is persisted as a `PendingRound` and only that very transaction may pay the round. A
+/// rejected preflight is returned as the error, so the caller can tell what the program rejected. The RPC failing
+/// while the status is polled doesn't end the tracking, the transaction may still land
+pub async fn send_and_track(
+    rpc_client: &RpcClient,
+    mut tx: VersionedTransaction,
+    signers: &[&Keypair],
+    max_attempts: u32,
+) -> Result<Confirmation, ClientError> {
+    let durable = durable_nonce_account(&tx.message).is_some();
+    let mut attempts = 0;
+    loop {
+        attempts += 1;
+        let signature = broadcast(rpc_client, &tx).await?;
+        let outcome = loop {
+            tokio::time::sleep(POLL_INTERVAL).await;
+            let blockhash_valid = match is_valid(rpc_client, &tx.message).await {
+                Ok(valid) => valid,
+                Err(err) => {
+                    tracing::warn!(?err, %signature, "Failed to check the transaction validity, polling again");
+                    continue;
+                },
+            };
+            let status = match rpc_client.get_signature_statuses(&[signature]).await {
+                Ok(statuses) => statuses.value.into_iter().next().flatten(),
+                Err(err) => {
+                    tracing::warn!(?err, %signature, "Failed to fetch the transaction status, polling again");
+                    continue;
+                },
+            };
+            if let Some(outcome) = poll_outcome(status.as_ref(), blockhash_valid) {
+                break outcome;
+            }
+        };
+        if outcome != TxOutcome::Expired || durable || attempts >= max_attempts {
+            return Ok(Confirmation {
+                signature,
+                outcome,
+                attempts,
+            });
+        }
+
+        tracing::warn!(%signature, attempts, "Transaction expired, signing it with a fresh blockhash");
+        let blockhash = rpc_client.get_latest_blockhash().await?;
+        let mut message = tx.message;
+        message.set_recent_blockhash(blockhash);
+        tx = VersionedTransaction::try_new(message, signers)?;
+    }
+}
+
+/// Durable nonce transaction of a single transaction round with the draw it pays, persisted before it is sent. It
+/// stays valid until its nonce moves, so a round interrupted by an RPC outage, a reschedule or a restart sends the
+/// same transaction again instead of drawing new winners
+pub struct PendingRound {
+    pub round: u64,
+    pub transaction: VersionedTransaction,
+    pub holders_number: u64,
+    pub randomness: Randomness,
+    pub winners: Vec<Winner>,
+    pub amounts: Vec<u64>,
+    pub token_accounts: Vec<Pubkey>,
+}
+
+impl PendingRound {
+    /// Whether the transaction can still pay the round, its nonce account still holds its nonce
+    pub async fn is_valid(&self, rpc_client: &RpcClient) -> anyhow::Result<bool> {
+        is_valid(rpc_client, &self.transaction.message)
+            .await
+            .context("Failed to check the pending transaction")
+    }
+}
+
+/// The pending round of the campaign, there is at most one
+pub struct PendingRounds<'a> {
+    pub pool: &'a sqlx::PgPool,
+    pub distributor_state: &'a Pubkey,
+}
+
+impl PendingRounds<'_> {
+    pub async fn save(&self, pending: &PendingRound) -> anyhow::Result<()> {
+        let (randomness_kind, randomness) = pending.randomness.to_parts();
+        sqlx::query(
+            "INSERT INTO pending_rounds (distributor_state, round, transaction, holders_number, randomness_kind, \
+             randomness, winners, holder_indices, amounts, token_accounts) \
+             VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) \
+             ON CONFLICT (distributor_state) DO UPDATE SET round = EXCLUDED.round, \
+             transaction = EXCLUDED.transaction, holders_number = EXCLUDED.holders_number, \
+             randomness_kind = EXCLUDED.randomness_kind, randomness = EXCLUDED.randomness, \
+             winners = EXCLUDED.winners, holder_indices = EXCLUDED.holder_indices, amounts = EXCLUDED.amounts, \
+             token_accounts = EXCLUDED.token_accounts, created_at = CURRENT_TIMESTAMP",
+        )
+        .bind(self.distributor_state.to_string())
+        .bind(pending.round as i64)
+        .bind(bincode::serialize(&pending.transaction).context("Failed to serialize pending transaction")?)
+        .bind(pending.holders_number as i64)
+        .bind(randomness_kind)
+        .bind(randomness)
+        .bind(
+            pending
+                .winners
+                .iter()
+                .map(|winner| winner.wallet.to_string())
+                .collect::<Vec<_>>(),
+        )
+        .bind(
+            pending
+                .winners
+                .iter()
+                .map(|winner| winner.index as i64)
+                .collect::<Vec<_>>(),
+        )
+        .bind(pending.amounts.iter().map(|amount| *amount as i64).collect::<Vec<_>>())
+        .bind(
+            pending
+                .token_accounts
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>(),
+        )
+        .execute(self.pool)
+        .await
+        .context("Failed to save pending round")?;
+        Ok(())
+    }
+
+    pub async fn fetch(&self) -> anyhow::Result<Option<PendingRound>> {
+        type Row = (
+            i64,
+            Vec<u8>,
+            i64,
+            String,
+            Vec<u8>,
+            Vec<String>,
+            Vec<i64>,
+            Vec<i64>,
+            Vec<String>,
+        );
+        let row: Option<Row> = sqlx::query_as(
+            "SELECT round, transaction, holders_number, randomness_kind, randomness, winners, holder_indices, \
+             amounts, token_accounts FROM pending_rounds WHERE distributor_state = $1",
+        )
+        .bind(self.distributor_state.to_string())
+        .fetch_optional(self.pool)
+        .await
+        .context("Failed to fetch pending round")?;
+        let Some((
+            round,
+            transaction,
+            holders_number,
+            randomness_kind,
+            randomness,
+            winners,
+            indices,
+            amounts,
+            accounts,
+        )) = row
+        else {
+            return Ok(None);
+        };
+        Ok(Some(PendingRound {
+            round: round as u64,
+            transaction: bincode::deserialize(&transaction).context("Invalid pending transaction")?,
+            holders_number: holders_number as u64,
+            randomness: Randomness::from_parts(&randomness_kind, &randomness)?,
+            winners: winners
+                .iter()
+                .zip(indices)
+                .map(|(wallet, index)| {
+                    Ok(Winner {
+                        index: index as u64,
+                        wallet: wallet.parse().context("Invalid pending winner")?,
+                    })
+                })
+                .collect::<anyhow::Result<_>>()?,
+            amounts: amounts.into_iter().map(|amount| amount as u64).collect(),
+            token_accounts: accounts
+                .iter()
+                .map(|account| account.parse().context("Invalid pending token account"))
+                .collect::<anyhow::Result<_>>()?,
+        }))
+    }
+
+    /// Forgets the pending round once its transaction landed or can't land anymore
+    pub async fn delete(&self) -> anyhow::Result<()> {
+        sqlx::query("DELETE FROM pending_rounds WHERE distributor_state = $1")
+            .bind(self.distributor_state.to_string())
+            .execute(self.pool)
+            .await
+            .context("Failed to delete pending round")?;
+        Ok(())
+    }
+}
+
+/// Records the final status of a sent transaction of the round
+pub async fn record_transaction(
+    pool: &sqlx::PgPool,
+    distributor_state: &Pubkey,
+    round: u64,
+    confirmation: &Confirmation,
+) -> anyhow::Result<()> {
+    let error = match &confirmation.outcome {
+        TxOutcome::Failed(err) => Some(err.to_string()),
+        TxOutcome::Confirmed | TxOutcome::Expired => None,
+    };
+    sqlx::query(
+        "INSERT INTO round_transactions (distributor_state, round, signature, status, attempts, error) \
+         VALUES ($1, $2, $3, $4, $5, $6)",
+    )
+    .bind(distributor_state.to_string())
+    .bind(round as i64)
+    .bind(confirmation.signature.to_string())
+    .bind(confirmation.outcome.status())
+    .bind(confirmation.attempts as i32)
+    .bind(error)
+    .execute(pool)
+    .await
+    .context("Failed to record round transaction")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::confirmation::{durable_nonce_account, poll_outcome, TxOutcome};
+    use solana_sdk::{
+        compute_budget::ComputeBudgetInstruction,
+        hash::Hash,
+        message::{Message, VersionedMessage},
+        pubkey::Pubkey,
+        system_instruction,
+        transaction::TransactionError,
+    };
+    use solana_transaction_status::{TransactionConfirmationStatus, TransactionStatus};
+
+    fn status(confirmation_status: TransactionConfirmationStatus, err: Option<TransactionError>) -> TransactionStatus {
+        TransactionStatus {
+            slot: 1,
+            confirmations: Some(0),
+            status: err.clone().map_or(Ok(()), Err),
+            err,
+            confirmation_status: Some(confirmation_status),
+        }
+    }
+
+    #[test]
+    fn should_wait_until_confirmed_or_expired() {
+        assert_eq!(None, poll_outcome(None, true));
+        assert_eq!(Some(TxOutcome::Expired), poll_outcome(None, false));
+
+        let processed = status(TransactionConfirmationStatus::Processed, None);
+        assert_eq!(None, poll_outcome(Some(&processed), false));
+
+        let confirmed = status(TransactionConfirmationStatus::Confirmed, None);
+        assert_eq!(Some(TxOutcome::Confirmed), poll_outcome(Some(&confirmed), false));
+    }
+
+    #[test]
+    fn should_report_landed_failure() {
+        let failed = status(
+            TransactionConfirmationStatus::Processed,
+            Some(TransactionError::InsufficientFundsForFee),
+        );
+        assert_eq!(
+            Some(TxOutcome::Failed(TransactionError::InsufficientFundsForFee)),
+            poll_outcome(Some(&failed), true)
+        );
+        assert_eq!("failed", poll_outcome(Some(&failed), true).unwrap().status());
+    }
+
+    #[test]
+    fn should_detect_durable_nonce() {
+        let (payer, nonce_account) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let compute_budget = ComputeBudgetInstruction::set_compute_unit_limit(200_000);
+        let advance_nonce = system_instruction::advance_nonce_account(&nonce_account, &payer);
+        let message =
+            |ixns: &[_]| VersionedMessage::Legacy(Message::new_with_blockhash(ixns, Some(&payer), &Hash::default()));
+
+        assert_eq!(
+            Some(nonce_account),
+            durable_nonce_account(&message(&[advance_nonce.clone(), compute_budget.clone()]))
+        );
+        assert_eq!(
+            None,
+            durable_nonce_account(&message(&[compute_budget.clone(), advance_nonce]))
+        );
+        assert_eq!(
+            None,
+            durable_nonce_account(&message(&[system_instruction::transfer(&payer, &nonce_account, 1)]))
+        );
+        assert_eq!(None, durable_nonce_account(&message(&[compute_budget])));
+    }
+}
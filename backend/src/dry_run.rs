@@ -0,0 +1,145 @@
+use anyhow::Context;
+use distributor::DistributorState;
+use serde::Serialize;
+use serde_with::{serde_as, DisplayFromStr};
+use solana_sdk::pubkey::Pubkey;
+
+/// What a winner of the previewed round would receive
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct WinnerPreview {
+    /// Position of the winner in the entrants list the draw was made from
+    pub index: u64,
+    #[serde_as(as = "DisplayFromStr")]
+    pub wallet: Pubkey,
+    /// Native shares are paid to the wallet itself, so there is no token account to create
+    pub ata_exists: bool,
+    /// Rent the payer spends on the winner's token account
+    pub rent_lamports: u64,
+    pub amount: u64,
+    /// Amount the winner ends up with, the SPL Token mints the backend distributes charge no transfer fees
+    pub received: u64,
+}
+
+/// Sums of the previewed round
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DryRunTotals {
+    pub winners: u64,
+    pub amount: u64,
+    pub received: u64,
+    pub atas_created: u64,
+    pub rent_lamports: u64,
+    pub fee_lamports: u64,
+    /// Parts of the terminal share after the protocol fee
+    pub burned: u64,
+    pub treasury: u64,
+    pub rollover: u64,
+    pub protocol_fee: u64,
+}
+
+/// Simulation of a transaction of the previewed round
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TransactionSimulation {
+    pub compute_units: Option<u64>,
+    pub error: Option<String>,
+}
+
+/// Round which would be sent now, nothing is sent or stored
+#[derive(Debug, Serialize)]
+pub struct DryRun {
+    pub round: u64,
+    pub holders_number: u64,
+    /// Whether the winners are a sample draw: the real round draws its own winners with a fresh seed. The winners of a
+    /// revealed VRF draw, a committed draw and a dividend are the ones the round pays
+    pub sample_draw: bool,
+    pub winners: Vec<WinnerPreview>,
+    /// Winners which were disqualified and replaced by new draws
+    pub replacements: usize,
+    pub totals: DryRunTotals,
+    /// Simulations of the transactions of the round in order. Each runs against the current state, so a later one
+    /// fails where it depends on what the earlier ones would have done
+    pub simulations: Vec<TransactionSimulation>,
+}
+
+/// Winners preview, `token_accounts` tells which payout accounts exist in the winners order
+pub fn preview_winners(
+    winners: impl IntoIterator<Item = (u64, Pubkey)>,
+    token_accounts: &[bool],
+    share_size: u64,
+    ata_rent: u64,
+) -> Vec<WinnerPreview> {
+    winners
+        .into_iter()
+        .zip(token_accounts)
+        .map(|((index, wallet), ata_exists)| WinnerPreview {
+            index,
+            wallet,
+            ata_exists: *ata_exists,
+            rent_lamports: if *ata_exists { 0 } else { ata_rent },
+            amount: share_size,
+            received: share_size,
+        })
+        .collect()
+}
+
+impl DryRunTotals {
+    pub fn new(
+        winners: &[WinnerPreview],
+        fee_lamports: u64,
+        distributor_state: &DistributorState,
+    ) -> anyhow::Result<Self> {
+        let protocol_fee = distributor_state.protocol_fee().context("Protocol fee overflows")?;
+        let terminal_amount = distributor_state
+            .terminal_amount()
+            .context("Terminal amount overflows")?;
+        let (burned, treasury, rollover) = distributor_state
+            .remainder_policy
+            .apply(terminal_amount.saturating_sub(protocol_fee));
+        Ok(Self {
+            winners: winners.len() as u64,
+            amount: winners.iter().map(|winner| winner.amount).sum(),
+            received: winners.iter().map(|winner| winner.received).sum(),
+            atas_created: winners.iter().filter(|winner| !winner.ata_exists).count() as u64,
+            rent_lamports: winners.iter().map(|winner| winner.rent_lamports).sum(),
+            fee_lamports,
+            burned,
+            treasury,
+            rollover,
+            protocol_fee,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::dry_run::{preview_winners, WinnerPreview};
+    use solana_sdk::pubkey::Pubkey;
+
+    #[test]
+    fn should_charge_rent_for_missing_token_accounts_only() {
+        let existing = Pubkey::new_unique();
+        let missing = Pubkey::new_unique();
+        let preview = preview_winners([(3, existing), (7, missing)], &[true, false], 1000, 2_039_280);
+        assert_eq!(
+            vec![
+                WinnerPreview {
+                    index: 3,
+                    wallet: existing,
+                    ata_exists: true,
+                    rent_lamports: 0,
+                    amount: 1000,
+                    received: 1000,
+                },
+                WinnerPreview {
+                    index: 7,
+                    wallet: missing,
+                    ata_exists: false,
+                    rent_lamports: 2_039_280,
+                    amount: 1000,
+                    received: 1000,
+                },
+            ],
+            preview
+        );
+    }
+}
@@ -0,0 +1,93 @@
+use anyhow::Context;
+use solana_sdk::pubkey::Pubkey;
+
+/// Toggles of a campaign (a distributor state). The settings are the defaults, rows of the `features` table
+/// override them per campaign and are resolved before every round
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Features {
+    /// Create the on-chain history record of every round
+    pub record_rounds: bool,
+    /// Draw the holders by the VRF randomness revealed for the round
+    pub vrf_draw: bool,
+    /// Truncate winner addresses in public reports
+    pub privacy_mode: bool,
+    /// Pay the single transaction rounds by `distribute_v2`, so the distributors are switched one by one once the
+    /// program supporting it is deployed
+    pub distribute_v2: bool,
+    /// Run the winners through the simulated `validate_winners` view before the round is built, so a single bad
+    /// winner is redrawn instead of failing the whole transaction
+    pub validate_winners: bool,
+    /// Split the paid shares of a `distribute_v2` round over the winners by their marker balances instead of equal
+    /// shares, the distributor has to require a minimal marker balance
+    pub pro_rata: bool,
+}
+
+impl Features {
+    /// Applies the overrides by feature name, unknown names are skipped
+    pub fn with_overrides(mut self, overrides: impl IntoIterator<Item = (String, bool)>) -> Self {
+        for (name, enabled) in overrides {
+            match name.as_str() {
+                "record_rounds" => self.record_rounds = enabled,
+                "vrf_draw" => self.vrf_draw = enabled,
+                "privacy_mode" => self.privacy_mode = enabled,
+                "distribute_v2" => self.distribute_v2 = enabled,
+                "validate_winners" => self.validate_winners = enabled,
+                "pro_rata" => self.pro_rata = enabled,
+                _ => tracing::debug!(%name, "Unknown feature"),
+            }
+        }
+        self
+    }
+}
+
+/// Features of the campaign with its overrides from the database applied to `defaults`
+pub async fn resolve_features(
+    pool: &sqlx::PgPool,
+    distributor_state: &Pubkey,
+    defaults: Features,
+) -> anyhow::Result<Features> {
+    let overrides: Vec<(String, bool)> =
+        sqlx::query_as("SELECT name, enabled FROM features WHERE distributor_state = $1")
+            .bind(distributor_state.to_string())
+            .fetch_all(pool)
+            .await
+            .context("Failed to fetch features")?;
+    Ok(defaults.with_overrides(overrides))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::features::Features;
+
+    #[test]
+    fn should_override_known_features() {
+        let defaults = Features {
+            record_rounds: true,
+            vrf_draw: false,
+            privacy_mode: false,
+            distribute_v2: false,
+            validate_winners: false,
+            pro_rata: false,
+        };
+        let features = defaults.with_overrides([
+            ("privacy_mode".to_string(), true),
+            ("record_rounds".to_string(), false),
+            ("distribute_v2".to_string(), true),
+            ("validate_winners".to_string(), true),
+            ("pro_rata".to_string(), true),
+            ("notifications".to_string(), true),
+        ]);
+
+        assert_eq!(
+            Features {
+                record_rounds: false,
+                vrf_draw: false,
+                privacy_mode: true,
+                distribute_v2: true,
+                validate_winners: true,
+                pro_rata: true,
+            },
+            features
+        );
+    }
+}
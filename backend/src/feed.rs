@@ -0,0 +1,378 @@
+use crate::{
+    report::{ui_amount, ExplorerLinks},
+    service::fetch_distributor_state,
+    vault_monitor::fetch_vault,
+};
+use anyhow::Context;
+use serde::Serialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::Mutex;
+
+/// Most recent rounds listed by the feeds
+const FEED_ROUNDS: i64 = 20;
+/// Clients may cache the feeds this long, the progress toward the next round is refreshed as often
+pub const FEED_MAX_AGE: Duration = Duration::from_secs(60);
+pub const FEED_CACHE_CONTROL: &str = "public, max-age=60";
+pub const JSON_FEED_CONTENT_TYPE: &str = "application/feed+json; charset=utf-8";
+pub const ATOM_CONTENT_TYPE: &str = "application/atom+xml; charset=utf-8";
+
+/// Announced round of the `distributions` table as the feeds list it, without the winner addresses
+#[derive(Debug, Clone, PartialEq)]
+pub struct FeedRound {
+    pub id: i64,
+    pub signature: String,
+    pub share_size: u64,
+    pub winners: u64,
+    pub holders_number: u64,
+    /// Seconds since the epoch the round was announced at
+    pub announced_at: i64,
+}
+
+/// Live progress of the vault toward the threshold of the next round
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct Progress {
+    /// Next round of the distributor
+    pub round: u64,
+    pub vault_balance: u64,
+    pub threshold: u64,
+    pub paused: bool,
+}
+
+impl Progress {
+    /// Percentage of the threshold the vault holds, capped at 100
+    pub fn percent(&self) -> u64 {
+        if self.threshold == 0 {
+            return 100;
+        }
+        (self.vault_balance as u128 * 100 / self.threshold as u128).min(100) as u64
+    }
+}
+
+/// Recent rounds and the progress of the campaign as the public JSON Feed and Atom feeds. The progress is fetched
+/// at most once per `FEED_MAX_AGE`, so polling bots don't reach the RPC
+pub struct Feed {
+    rpc_client: RpcClient,
+    distributor_state: Pubkey,
+    decimals: u8,
+    explorer: ExplorerLinks,
+    progress: Mutex<Option<(Instant, Progress)>>,
+}
+
+impl Feed {
+    pub fn new(rpc_client: RpcClient, distributor_state: Pubkey, decimals: u8, explorer: ExplorerLinks) -> Self {
+        Self {
+            rpc_client,
+            distributor_state,
+            decimals,
+            explorer,
+            progress: Mutex::new(None),
+        }
+    }
+
+    pub async fn snapshot(&self, pool: &sqlx::PgPool) -> anyhow::Result<FeedSnapshot> {
+        let rounds = fetch_feed_rounds(pool).await?;
+        let progress = self.progress().await?;
+        Ok(FeedSnapshot {
+            distributor_state: self.distributor_state,
+            decimals: self.decimals,
+            rounds,
+            progress,
+            generated_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |elapsed| elapsed.as_secs() as i64),
+        })
+    }
+
+    async fn progress(&self) -> anyhow::Result<Progress> {
+        let mut cached = self.progress.lock().await;
+        if let Some((fetched_at, progress)) = *cached {
+            if fetched_at.elapsed() < FEED_MAX_AGE {
+                return Ok(progress);
+            }
+        }
+        let distributor_state = fetch_distributor_state(&self.rpc_client, &self.distributor_state).await?;
+        let (vault, _) = fetch_vault(&self.rpc_client, &self.distributor_state, &distributor_state).await?;
+        let progress = Progress {
+            round: distributor_state.round,
+            vault_balance: vault.map_or(0, |vault| vault.amount),
+            threshold: distributor_state.threshold().context("Threshold overflows")?,
+            paused: distributor_state.paused,
+        };
+        *cached = Some((Instant::now(), progress));
+        Ok(progress)
+    }
+
+    pub fn transaction_link(&self, signature: &str) -> Option<String> {
+        let signature: Signature = signature.parse().ok()?;
+        Some(self.explorer.transaction(&signature))
+    }
+}
+
+/// Content of the feeds at one moment
+#[derive(Debug, Clone)]
+pub struct FeedSnapshot {
+    pub distributor_state: Pubkey,
+    pub decimals: u8,
+    /// Newest first
+    pub rounds: Vec<FeedRound>,
+    pub progress: Progress,
+    /// Seconds since the epoch
+    pub generated_at: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JsonFeed {
+    pub version: &'static str,
+    pub title: String,
+    pub description: String,
+    pub items: Vec<JsonFeedItem>,
+    /// Extension of the JSON Feed with the live progress toward the next round
+    #[serde(rename = "_distributor")]
+    pub progress: JsonFeedProgress,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JsonFeedItem {
+    pub id: String,
+    pub title: String,
+    pub content_text: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_url: Option<String>,
+    pub date_published: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JsonFeedProgress {
+    #[serde(flatten)]
+    pub progress: Progress,
+    pub percent: u64,
+}
+
+impl FeedSnapshot {
+    fn title(&self) -> String {
+        format!("Distributor {}", self.distributor_state)
+    }
+
+    fn description(&self) -> String {
+        let progress = &self.progress;
+        let status = if progress.paused { ", paused" } else { "" };
+        format!(
+            "Round {} is {}% funded: {} of {}{status}",
+            progress.round,
+            progress.percent(),
+            ui_amount(progress.vault_balance, self.decimals),
+            ui_amount(progress.threshold, self.decimals),
+        )
+    }
+
+    fn round_id(&self, round: &FeedRound) -> String {
+        format!("urn:distributor:{}:distribution:{}", self.distributor_state, round.id)
+    }
+
+    fn round_title(&self, round: &FeedRound) -> String {
+        format!(
+            "Distribution #{}: {} winners got {} each",
+            round.id,
+            round.winners,
+            ui_amount(round.share_size, self.decimals)
+        )
+    }
+
+    fn round_text(&self, round: &FeedRound) -> String {
+        let total = round.share_size as u128 * round.winners as u128;
+        let total = u64::try_from(total).map_or_else(|_| "?".to_string(), |total| ui_amount(total, self.decimals));
+        format!(
+            "{} winners were drawn from {} holders and received {} in total",
+            round.winners, round.holders_number, total
+        )
+    }
+
+    /// JSON Feed 1.1, see https://www.jsonfeed.org/version/1.1/
+    pub fn to_json_feed(&self, transaction_link: impl Fn(&str) -> Option<String>) -> JsonFeed {
+        JsonFeed {
+            version: "https://jsonfeed.org/version/1.1",
+            title: self.title(),
+            description: self.description(),
+            items: self
+                .rounds
+                .iter()
+                .map(|round| JsonFeedItem {
+                    id: self.round_id(round),
+                    title: self.round_title(round),
+                    content_text: self.round_text(round),
+                    external_url: transaction_link(&round.signature),
+                    date_published: rfc3339(round.announced_at),
+                })
+                .collect(),
+            progress: JsonFeedProgress {
+                progress: self.progress,
+                percent: self.progress.percent(),
+            },
+        }
+    }
+
+    /// Atom feed, the progress toward the next round is its subtitle
+    pub fn to_atom(&self, transaction_link: impl Fn(&str) -> Option<String>) -> String {
+        let updated = self
+            .rounds
+            .first()
+            .map_or(self.generated_at, |round| round.announced_at);
+        let mut atom = format!(
+            "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<feed xmlns=\"http://www.w3.org/2005/Atom\">\n  \
+             <id>urn:distributor:{}</id>\n  <title>{}</title>\n  <subtitle>{}</subtitle>\n  <updated>{}</updated>\n",
+            self.distributor_state,
+            escape_xml(&self.title()),
+            escape_xml(&self.description()),
+            rfc3339(updated),
+        );
+        for round in &self.rounds {
+            let link = transaction_link(&round.signature)
+                .map(|link| format!("    <link rel=\"related\" href=\"{}\"/>\n", escape_xml(&link)))
+                .unwrap_or_default();
+            atom.push_str(&format!(
+                "  <entry>\n    <id>{}</id>\n    <title>{}</title>\n    <updated>{}</updated>\n{link}    \
+                 <summary>{}</summary>\n  </entry>\n",
+                self.round_id(round),
+                escape_xml(&self.round_title(round)),
+                rfc3339(round.announced_at),
+                escape_xml(&self.round_text(round)),
+            ));
+        }
+        atom.push_str("</feed>\n");
+        atom
+    }
+}
+
+/// distributions.id, signature, share_size, winners count, holders_number, announce_at
+type FeedRoundRow = (i64, String, i64, i32, i64, i64);
+
+/// Most recent announced rounds, newest first
+pub async fn fetch_feed_rounds(pool: &sqlx::PgPool) -> anyhow::Result<Vec<FeedRound>> {
+    let rows: Vec<FeedRoundRow> = sqlx::query_as(
+        "SELECT id, signature, share_size, COALESCE(cardinality(winners), 0), holders_number, \
+         EXTRACT(EPOCH FROM announce_at)::bigint FROM distributions WHERE announce_at <= CURRENT_TIMESTAMP \
+         ORDER BY id DESC LIMIT $1",
+    )
+    .bind(FEED_ROUNDS)
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch feed rounds")?;
+
+    Ok(rows
+        .into_iter()
+        .map(
+            |(id, signature, share_size, winners, holders_number, announced_at)| FeedRound {
+                id,
+                signature,
+                share_size: share_size as u64,
+                winners: winners as u64,
+                holders_number: holders_number as u64,
+                announced_at,
+            },
+        )
+        .collect())
+}
+
+/// UTC timestamp of the seconds since the epoch, e.g. `2024-03-01T09:00:00Z`
+pub fn rfc3339(timestamp: i64) -> String {
+    let (days, seconds) = (timestamp.div_euclid(86_400), timestamp.rem_euclid(86_400));
+    // civil date of the days since the epoch, by Howard Hinnant's `civil_from_days`
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::feed::{escape_xml, rfc3339, FeedRound, FeedSnapshot, Progress, FEED_CACHE_CONTROL, FEED_MAX_AGE};
+    use solana_sdk::pubkey::Pubkey;
+
+    fn snapshot() -> FeedSnapshot {
+        FeedSnapshot {
+            distributor_state: Pubkey::default(),
+            decimals: 9,
+            rounds: vec![FeedRound {
+                id: 7,
+                signature: "sig".to_string(),
+                share_size: 331_000_000_000,
+                winners: 9,
+                holders_number: 120,
+                announced_at: 1_709_283_600,
+            }],
+            progress: Progress {
+                round: 8,
+                vault_balance: 1_655_000_000_000,
+                threshold: 3_310_000_000_000,
+                paused: false,
+            },
+            generated_at: 1_709_290_000,
+        }
+    }
+
+    #[test]
+    fn should_format_utc_timestamps() {
+        assert_eq!("1970-01-01T00:00:00Z", rfc3339(0));
+        assert_eq!("2024-03-01T09:00:00Z", rfc3339(1_709_283_600));
+        assert_eq!("2024-02-29T23:59:59Z", rfc3339(1_709_251_199));
+        assert_eq!("1969-12-31T23:59:59Z", rfc3339(-1));
+    }
+
+    #[test]
+    fn should_let_clients_cache_as_long_as_the_progress() {
+        assert_eq!(
+            format!("public, max-age={}", FEED_MAX_AGE.as_secs()),
+            FEED_CACHE_CONTROL
+        );
+    }
+
+    #[test]
+    fn should_cap_progress() {
+        let mut progress = snapshot().progress;
+        assert_eq!(50, progress.percent());
+        progress.vault_balance = progress.threshold * 2;
+        assert_eq!(100, progress.percent());
+    }
+
+    #[test]
+    fn should_render_json_feed() {
+        let feed =
+            serde_json::to_value(snapshot().to_json_feed(|signature| Some(format!("https://tx/{signature}")))).unwrap();
+        assert_eq!("https://jsonfeed.org/version/1.1", feed["version"]);
+        assert_eq!("Round 8 is 50% funded: 1655 of 3310", feed["description"]);
+        assert_eq!("Distribution #7: 9 winners got 331 each", feed["items"][0]["title"]);
+        assert_eq!("https://tx/sig", feed["items"][0]["external_url"]);
+        assert_eq!("2024-03-01T09:00:00Z", feed["items"][0]["date_published"]);
+        assert_eq!(50, feed["_distributor"]["percent"]);
+        assert_eq!(1_655_000_000_000u64, feed["_distributor"]["vault_balance"]);
+    }
+
+    #[test]
+    fn should_render_atom_feed() {
+        let atom = snapshot().to_atom(|_| Some("https://tx?a=1&b=2".to_string()));
+        assert!(atom.contains("<updated>2024-03-01T09:00:00Z</updated>"));
+        assert!(atom.contains("<link rel=\"related\" href=\"https://tx?a=1&amp;b=2\"/>"));
+        assert!(atom.contains("<summary>9 winners were drawn from 120 holders and received 2979 in total</summary>"));
+        assert_eq!("&lt;a href=&quot;x&quot;&gt; &amp;", escape_xml("<a href=\"x\"> &"));
+    }
+}
@@ -0,0 +1,217 @@
+//! Public history of the paid rounds and their winners. A round is public once its report is announced, the
+//! winners of the rounds paid in privacy mode are truncated and can't be looked up by their wallet.
+
+use crate::report::display_address;
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+
+/// Rounds of a `/rounds` page if the `limit` isn't passed
+pub const DEFAULT_PAGE_SIZE: u32 = 50;
+pub const MAX_PAGE_SIZE: u32 = 200;
+/// Rounds listed by `/winners/:wallet`, newest first
+const MAX_WINNER_ROUNDS: i64 = 100;
+
+/// Confirmed rounds of the distributor whose report is announced, `d.private` is set if the round was paid in privacy
+/// mode
+const PUBLIC_ROUNDS: &str = "FROM rounds r JOIN distributions d ON d.signature = r.signature \
+                             WHERE r.distributor_state = $1 AND r.status = 'confirmed' \
+                             AND d.announce_at <= CURRENT_TIMESTAMP";
+
+/// `/rounds` page of the rounds older than the round id `before`, newest first
+#[derive(Debug, Default, Deserialize)]
+pub struct RoundsQuery {
+    pub before: Option<i64>,
+    pub limit: Option<u32>,
+}
+
+impl RoundsQuery {
+    pub fn page_size(&self) -> u32 {
+        self.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HistoryRound {
+    /// Id of the round in the audit trail
+    pub id: i64,
+    pub round: u64,
+    /// Signature of the last transaction of the round
+    pub signature: String,
+    pub winners_number: u64,
+    pub triggered_at: i64,
+    pub announced_at: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HistoryWinner {
+    /// Truncated if the round was paid in privacy mode
+    pub wallet: String,
+    pub amount: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HistoryRoundDetails {
+    #[serde(flatten)]
+    pub round: HistoryRound,
+    /// In the winners order
+    pub winners: Vec<HistoryWinner>,
+}
+
+/// Round the wallet won, `amount` is the total of its shares in the round
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WinnerRound {
+    pub id: i64,
+    pub round: u64,
+    pub signature: String,
+    pub amount: u64,
+    pub announced_at: i64,
+}
+
+/// rounds.id, round, signature, winners count, triggered_at, announce_at
+type HistoryRoundRow = (i64, i64, String, i64, i64, i64);
+
+fn history_round((id, round, signature, winners_number, triggered_at, announced_at): HistoryRoundRow) -> HistoryRound {
+    HistoryRound {
+        id,
+        round: round as u64,
+        signature,
+        winners_number: winners_number as u64,
+        triggered_at,
+        announced_at,
+    }
+}
+
+/// Public rounds of the distributor, newest first
+pub async fn fetch_history_rounds(
+    pool: &sqlx::PgPool,
+    distributor_state: &Pubkey,
+    query: &RoundsQuery,
+) -> anyhow::Result<Vec<HistoryRound>> {
+    let rows: Vec<HistoryRoundRow> = sqlx::query_as(&format!(
+        "SELECT r.id, r.round, r.signature, (SELECT count(*) FROM winners w WHERE w.round_id = r.id), \
+         EXTRACT(EPOCH FROM r.triggered_at)::bigint, EXTRACT(EPOCH FROM d.announce_at)::bigint {PUBLIC_ROUNDS} \
+         AND ($2::bigint IS NULL OR r.id < $2) ORDER BY r.id DESC LIMIT $3"
+    ))
+    .bind(distributor_state.to_string())
+    .bind(query.before)
+    .bind(query.page_size() as i64)
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch rounds")?;
+
+    Ok(rows.into_iter().map(history_round).collect())
+}
+
+/// Public round of the distributor with its winners, `None` if it isn't public
+pub async fn fetch_history_round(
+    pool: &sqlx::PgPool,
+    distributor_state: &Pubkey,
+    id: i64,
+) -> anyhow::Result<Option<HistoryRoundDetails>> {
+    let row: Option<(i64, i64, String, i64, i64, i64, bool)> = sqlx::query_as(&format!(
+        "SELECT r.id, r.round, r.signature, (SELECT count(*) FROM winners w WHERE w.round_id = r.id), \
+         EXTRACT(EPOCH FROM r.triggered_at)::bigint, EXTRACT(EPOCH FROM d.announce_at)::bigint, \
+         d.private {PUBLIC_ROUNDS} AND r.id = $2"
+    ))
+    .bind(distributor_state.to_string())
+    .bind(id)
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch round")?;
+    let Some((id, round, signature, winners_number, triggered_at, announced_at, private)) = row else {
+        return Ok(None);
+    };
+
+    let winners: Vec<(String, i64)> =
+        sqlx::query_as("SELECT wallet, amount FROM winners WHERE round_id = $1 ORDER BY position")
+            .bind(id)
+            .fetch_all(pool)
+            .await
+            .context("Failed to fetch round winners")?;
+    let winners = winners
+        .into_iter()
+        .map(|(wallet, amount)| {
+            let wallet: Pubkey = wallet.parse().context("Invalid winner wallet")?;
+            Ok(HistoryWinner {
+                wallet: display_address(&wallet, private),
+                amount: amount as u64,
+            })
+        })
+        .collect::<anyhow::Result<_>>()?;
+
+    Ok(Some(HistoryRoundDetails {
+        round: history_round((id, round, signature, winners_number, triggered_at, announced_at)),
+        winners,
+    }))
+}
+
+/// Public rounds of the distributor the wallet won, newest first. The rounds paid in privacy mode aren't listed
+pub async fn fetch_winner_rounds(
+    pool: &sqlx::PgPool,
+    distributor_state: &Pubkey,
+    wallet: &Pubkey,
+) -> anyhow::Result<Vec<WinnerRound>> {
+    let rows: Vec<(i64, i64, String, i64, i64)> = sqlx::query_as(&format!(
+        "SELECT r.id, r.round, r.signature, \
+         (SELECT sum(w.amount)::bigint FROM winners w WHERE w.round_id = r.id AND w.wallet = $2), \
+         EXTRACT(EPOCH FROM d.announce_at)::bigint {PUBLIC_ROUNDS} AND NOT d.private \
+         AND EXISTS (SELECT 1 FROM winners w WHERE w.round_id = r.id AND w.wallet = $2) \
+         ORDER BY r.id DESC LIMIT $3"
+    ))
+    .bind(distributor_state.to_string())
+    .bind(wallet.to_string())
+    .bind(MAX_WINNER_ROUNDS)
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch winner rounds")?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(id, round, signature, amount, announced_at)| WinnerRound {
+            id,
+            round: round as u64,
+            signature,
+            amount: amount as u64,
+            announced_at,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::history::{HistoryRound, HistoryRoundDetails, HistoryWinner, RoundsQuery, MAX_PAGE_SIZE};
+
+    #[test]
+    fn should_clamp_page_size() {
+        assert_eq!(50, RoundsQuery::default().page_size());
+        let query = |limit| RoundsQuery {
+            before: None,
+            limit: Some(limit),
+        };
+        assert_eq!(1, query(0).page_size());
+        assert_eq!(10, query(10).page_size());
+        assert_eq!(MAX_PAGE_SIZE, query(u32::MAX).page_size());
+    }
+
+    #[test]
+    fn should_flatten_round_details() {
+        let details = serde_json::to_value(HistoryRoundDetails {
+            round: HistoryRound {
+                id: 7,
+                round: 3,
+                signature: "sig".to_string(),
+                winners_number: 1,
+                triggered_at: 1,
+                announced_at: 2,
+            },
+            winners: vec![HistoryWinner {
+                wallet: "De49…G28i".to_string(),
+                amount: 100,
+            }],
+        })
+        .unwrap();
+        assert_eq!(7, details["id"]);
+        assert_eq!(100, details["winners"][0]["amount"]);
+    }
+}
@@ -0,0 +1,315 @@
+use anyhow::Context;
+use serde::Serialize;
+use std::{
+    fmt::Write,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Stages of a round in the order they are completed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Stage {
+    /// The webhook or another trigger which started the round was received
+    WebhookReceived,
+    /// Entrants of the round are known
+    SnapshotDone,
+    /// Winners are drawn, replaced if disqualified and the transactions are built
+    DrawDone,
+    TxSent,
+    TxConfirmed,
+}
+
+impl Stage {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Stage::WebhookReceived => "webhook_received",
+            Stage::SnapshotDone => "snapshot_done",
+            Stage::DrawDone => "draw_done",
+            Stage::TxSent => "tx_sent",
+            Stage::TxConfirmed => "tx_confirmed",
+        }
+    }
+}
+
+/// Time it took to complete the stage since the previous one
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct StageLatency {
+    pub stage: Stage,
+    #[serde(rename = "latency_ms", serialize_with = "serialize_millis")]
+    pub latency: Duration,
+}
+
+/// Wall clock timestamps of the stages of a single round
+#[derive(Debug, Clone)]
+pub struct RoundTimeline {
+    stages: Vec<(Stage, SystemTime)>,
+}
+
+impl RoundTimeline {
+    pub fn new(received_at: SystemTime) -> Self {
+        Self {
+            stages: vec![(Stage::WebhookReceived, received_at)],
+        }
+    }
+
+    pub fn received_at(&self) -> SystemTime {
+        self.stages[0].1
+    }
+
+    pub fn record(&mut self, stage: Stage) {
+        self.record_at(stage, SystemTime::now());
+    }
+
+    pub fn record_at(&mut self, stage: Stage, at: SystemTime) {
+        self.stages.push((stage, at));
+    }
+
+    pub fn timestamp(&self, stage: Stage) -> Option<SystemTime> {
+        self.stages
+            .iter()
+            .find(|(recorded, _)| *recorded == stage)
+            .map(|(_, at)| *at)
+    }
+
+    pub fn latencies(&self) -> Vec<StageLatency> {
+        self.stages
+            .windows(2)
+            .map(|stages| StageLatency {
+                stage: stages[1].0,
+                latency: stages[1].1.duration_since(stages[0].1).unwrap_or_default(),
+            })
+            .collect()
+    }
+
+    /// Time from the trigger to the confirmation, `None` until the round is confirmed
+    pub fn end_to_end(&self) -> Option<Duration> {
+        let confirmed_at = self.timestamp(Stage::TxConfirmed)?;
+        Some(confirmed_at.duration_since(self.received_at()).unwrap_or_default())
+    }
+}
+
+/// Latencies of the last confirmed round and the SLO breaches since the start, rendered for Prometheus
+#[derive(Debug, Default)]
+pub struct LatencyMetrics {
+    rounds: u64,
+    slo_breaches: u64,
+    last_round: Vec<StageLatency>,
+    last_end_to_end: Option<Duration>,
+}
+
+impl LatencyMetrics {
+    /// Records a confirmed round, returns whether it exceeded the `slo`
+    pub fn observe(&mut self, timeline: &RoundTimeline, slo: Duration) -> bool {
+        let end_to_end = timeline.end_to_end();
+        let breached = end_to_end.is_some_and(|end_to_end| end_to_end > slo);
+        self.rounds += 1;
+        self.slo_breaches += breached as u64;
+        self.last_round = timeline.latencies();
+        self.last_end_to_end = end_to_end;
+        breached
+    }
+
+    pub fn render(&self, slo: Duration) -> String {
+        let mut metrics = String::new();
+        let _ = writeln!(
+            metrics,
+            "# HELP distributor_round_stage_latency_seconds Time the stage of the last round took since the previous \
+             stage\n# TYPE distributor_round_stage_latency_seconds gauge"
+        );
+        for StageLatency { stage, latency } in &self.last_round {
+            let _ = writeln!(
+                metrics,
+                "distributor_round_stage_latency_seconds{{stage=\"{}\"}} {}",
+                stage.as_str(),
+                latency.as_secs_f64()
+            );
+        }
+        let _ = writeln!(
+            metrics,
+            "# HELP distributor_round_end_to_end_seconds Time from the trigger to the confirmation of the last \
+             round\n# TYPE distributor_round_end_to_end_seconds gauge"
+        );
+        if let Some(end_to_end) = self.last_end_to_end {
+            let _ = writeln!(
+                metrics,
+                "distributor_round_end_to_end_seconds {}",
+                end_to_end.as_secs_f64()
+            );
+        }
+        let _ = writeln!(
+            metrics,
+            "# HELP distributor_round_slo_seconds End-to-end latency promised for a round\n\
+             # TYPE distributor_round_slo_seconds gauge\n\
+             distributor_round_slo_seconds {}\n\
+             # HELP distributor_rounds_total Rounds confirmed since the start\n\
+             # TYPE distributor_rounds_total counter\n\
+             distributor_rounds_total {}\n\
+             # HELP distributor_round_slo_breaches_total Rounds which exceeded the SLO since the start\n\
+             # TYPE distributor_round_slo_breaches_total counter\n\
+             distributor_round_slo_breaches_total {}",
+            slo.as_secs_f64(),
+            self.rounds,
+            self.slo_breaches
+        );
+        metrics
+    }
+}
+
+/// Stage latencies of a stored round
+#[derive(Debug, Serialize)]
+pub struct RoundLatency {
+    pub stages: Vec<StageLatency>,
+    #[serde(rename = "end_to_end_ms", serialize_with = "serialize_optional_millis")]
+    pub end_to_end: Option<Duration>,
+    #[serde(rename = "slo_ms", serialize_with = "serialize_millis")]
+    pub slo: Duration,
+    pub within_slo: Option<bool>,
+}
+
+/// Stages in the order of their columns in the `distributions` table
+const STAGES: [Stage; 5] = [
+    Stage::WebhookReceived,
+    Stage::SnapshotDone,
+    Stage::DrawDone,
+    Stage::TxSent,
+    Stage::TxConfirmed,
+];
+
+pub async fn store_timeline(pool: &sqlx::PgPool, id: i64, timeline: &RoundTimeline) -> anyhow::Result<()> {
+    let mut query = sqlx::query(
+        "UPDATE distributions SET webhook_received_at = to_timestamp($2), snapshot_done_at = to_timestamp($3), \
+         draw_done_at = to_timestamp($4), tx_sent_at = to_timestamp($5), tx_confirmed_at = to_timestamp($6) \
+         WHERE id = $1",
+    )
+    .bind(id);
+    for stage in STAGES {
+        query = query.bind(timeline.timestamp(stage).map(unix_seconds));
+    }
+    query.execute(pool).await.context("Failed to store round timeline")?;
+    Ok(())
+}
+
+/// Latencies of an announced round, `None` if it doesn't exist or wasn't announced yet
+pub async fn fetch_round_latency(pool: &sqlx::PgPool, id: i64, slo: Duration) -> anyhow::Result<Option<RoundLatency>> {
+    let timestamps: Option<Vec<Option<f64>>> = sqlx::query_scalar(
+        "SELECT ARRAY[EXTRACT(EPOCH FROM webhook_received_at), EXTRACT(EPOCH FROM snapshot_done_at), \
+         EXTRACT(EPOCH FROM draw_done_at), EXTRACT(EPOCH FROM tx_sent_at), \
+         EXTRACT(EPOCH FROM tx_confirmed_at)]::float8[] FROM distributions \
+         WHERE id = $1 AND announce_at <= CURRENT_TIMESTAMP",
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await
+    .context("Failed to fetch round timeline")?;
+    let Some(timestamps) = timestamps else {
+        return Ok(None);
+    };
+
+    let mut stages = STAGES.into_iter().zip(timestamps);
+    let Some((_, Some(received_at))) = stages.next() else {
+        // rounds distributed before the stages were tracked
+        return Ok(Some(RoundLatency {
+            stages: Vec::new(),
+            end_to_end: None,
+            slo,
+            within_slo: None,
+        }));
+    };
+    let mut timeline = RoundTimeline::new(from_unix_seconds(received_at));
+    for (stage, timestamp) in stages {
+        if let Some(timestamp) = timestamp {
+            timeline.record_at(stage, from_unix_seconds(timestamp));
+        }
+    }
+
+    let end_to_end = timeline.end_to_end();
+    Ok(Some(RoundLatency {
+        stages: timeline.latencies(),
+        end_to_end,
+        slo,
+        within_slo: end_to_end.map(|end_to_end| end_to_end <= slo),
+    }))
+}
+
+fn unix_seconds(at: SystemTime) -> f64 {
+    at.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs_f64()
+}
+
+fn from_unix_seconds(seconds: f64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs_f64(seconds.max(0.0))
+}
+
+fn serialize_millis<S: serde::Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u128(duration.as_millis())
+}
+
+fn serialize_optional_millis<S: serde::Serializer>(
+    duration: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match duration {
+        Some(duration) => serialize_millis(duration, serializer),
+        None => serializer.serialize_none(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::latency::{LatencyMetrics, RoundTimeline, Stage, StageLatency};
+    use std::time::{Duration, UNIX_EPOCH};
+
+    fn timeline(confirmed_after: u64) -> RoundTimeline {
+        let received_at = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let mut timeline = RoundTimeline::new(received_at);
+        timeline.record_at(Stage::SnapshotDone, received_at + Duration::from_secs(2));
+        timeline.record_at(Stage::DrawDone, received_at + Duration::from_secs(5));
+        timeline.record_at(Stage::TxSent, received_at + Duration::from_secs(6));
+        timeline.record_at(Stage::TxConfirmed, received_at + Duration::from_secs(confirmed_after));
+        timeline
+    }
+
+    #[test]
+    fn should_compute_stage_latencies() {
+        let timeline = timeline(10);
+        assert_eq!(
+            vec![
+                StageLatency {
+                    stage: Stage::SnapshotDone,
+                    latency: Duration::from_secs(2)
+                },
+                StageLatency {
+                    stage: Stage::DrawDone,
+                    latency: Duration::from_secs(3)
+                },
+                StageLatency {
+                    stage: Stage::TxSent,
+                    latency: Duration::from_secs(1)
+                },
+                StageLatency {
+                    stage: Stage::TxConfirmed,
+                    latency: Duration::from_secs(4)
+                },
+            ],
+            timeline.latencies()
+        );
+        assert_eq!(Some(Duration::from_secs(10)), timeline.end_to_end());
+
+        let unconfirmed = RoundTimeline::new(UNIX_EPOCH);
+        assert!(unconfirmed.latencies().is_empty());
+        assert_eq!(None, unconfirmed.end_to_end());
+    }
+
+    #[test]
+    fn should_count_slo_breaches() {
+        let slo = Duration::from_secs(60);
+        let mut metrics = LatencyMetrics::default();
+        assert!(!metrics.observe(&timeline(10), slo));
+        assert!(metrics.observe(&timeline(61), slo));
+
+        let rendered = metrics.render(slo);
+        assert!(rendered.contains("distributor_round_stage_latency_seconds{stage=\"tx_confirmed\"} 55"));
+        assert!(rendered.contains("distributor_round_end_to_end_seconds 61"));
+        assert!(rendered.contains("distributor_rounds_total 2"));
+        assert!(rendered.contains("distributor_round_slo_breaches_total 1"));
+    }
+}
@@ -1,6 +1,33 @@
+pub mod alerting;
+pub mod analytics;
 pub mod any_keypair;
+pub mod audit;
+pub mod claims;
+pub mod confirmation;
+pub mod dry_run;
+pub mod features;
+pub mod feed;
+pub mod history;
+pub mod latency;
+pub mod lookup_table;
+pub mod notifications;
+pub mod pipeline;
 pub mod priority_fee;
+pub mod program_error;
+pub mod relayer;
+pub mod report;
+pub mod retention;
+pub mod round;
+pub mod runtime_config;
+pub mod self_check;
 pub mod service;
 pub mod settings;
+pub mod shadow_draw;
+pub mod telemetry;
+pub mod ticket;
 pub mod token_holder;
 pub mod transaction_status;
+pub mod validation;
+pub mod vault_monitor;
+pub mod webhook_registration;
+pub mod what_if;
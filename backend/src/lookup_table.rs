@@ -0,0 +1,285 @@
+use crate::service::AppState;
+use anyhow::{ensure, Context};
+use solana_sdk::{
+    address_lookup_table::{
+        self,
+        state::{AddressLookupTable, LOOKUP_TABLE_MAX_ADDRESSES},
+    },
+    address_lookup_table_account::AddressLookupTableAccount,
+    commitment_config::CommitmentConfig,
+    instruction::Instruction,
+    pubkey::Pubkey,
+    signature::Signer,
+    transaction::Transaction,
+};
+use std::{collections::BTreeSet, time::Duration};
+use tokio::sync::Mutex;
+
+/// Addresses added by one `extend_lookup_table`, so the extension fits a legacy transaction
+const EXTEND_ADDRESSES: usize = 20;
+/// Interval the slot is polled at until the extended addresses can be looked up
+const SLOT_POLL_INTERVAL: Duration = Duration::from_millis(400);
+
+/// Address Lookup Table of the round transactions owned by the payer, created on the first round and extended by
+/// the accounts of every round. A full table is deactivated and replaced by a new one, it is closed by a later
+/// round once its deactivation cooled down and its rent goes back to the payer
+pub struct LookupTable {
+    address: Mutex<Option<Pubkey>>,
+}
+
+impl LookupTable {
+    pub fn new(address: Option<Pubkey>) -> Self {
+        Self {
+            address: Mutex::new(address),
+        }
+    }
+
+    /// Table holding every account of the instructions which doesn't sign them, ready to compile them to a v0
+    /// message. The distributor state and the vault stay static keys of the transactions, the webhook reads the
+    /// vault balance by them
+    pub async fn prepare(&self, state: &AppState, ixns: &[Instruction]) -> anyhow::Result<AddressLookupTableAccount> {
+        let mut address = self.address.lock().await;
+        if let Err(err) = close_deactivated(state).await {
+            tracing::warn!(%err, "Failed to close deactivated lookup tables");
+        }
+        let mut accounts = lookup_accounts(ixns);
+        accounts.remove(&state.distributor_state_pubkey);
+        accounts.remove(&state.distributor_state.vault);
+        ensure!(
+            accounts.len() <= LOOKUP_TABLE_MAX_ADDRESSES,
+            "Round has more than {LOOKUP_TABLE_MAX_ADDRESSES} accounts"
+        );
+
+        let (table, mut addresses) = match *address {
+            Some(table) => (table, fetch_addresses(state, &table).await?),
+            None => (create(state).await?, Vec::new()),
+        };
+        let mut missing = accounts
+            .iter()
+            .filter(|account| !addresses.contains(account))
+            .copied()
+            .collect::<Vec<_>>();
+        let table = if addresses.len() + missing.len() > LOOKUP_TABLE_MAX_ADDRESSES {
+            tracing::info!(%table, "Lookup table is full, replacing it");
+            send(
+                state,
+                address_lookup_table::instruction::deactivate_lookup_table(table, state.payer.pubkey()),
+            )
+            .await
+            .context("Failed to deactivate lookup table")?;
+            save_deactivated(&state.pool, &state.distributor_state_pubkey, &table).await?;
+            addresses.clear();
+            missing = accounts.into_iter().collect();
+            create(state).await?
+        } else {
+            table
+        };
+        if address.replace(table) != Some(table) {
+            save(&state.pool, &state.distributor_state_pubkey, &table).await?;
+        }
+
+        if !missing.is_empty() {
+            for chunk in missing.chunks(EXTEND_ADDRESSES) {
+                send(
+                    state,
+                    address_lookup_table::instruction::extend_lookup_table(
+                        table,
+                        state.payer.pubkey(),
+                        Some(state.payer.pubkey()),
+                        chunk.to_vec(),
+                    ),
+                )
+                .await
+                .context("Failed to extend lookup table")?;
+            }
+            tracing::info!(%table, extended = missing.len(), "Lookup table extended");
+            wait_next_slot(state).await?;
+            addresses.extend(missing);
+        }
+
+        Ok(AddressLookupTableAccount { key: table, addresses })
+    }
+
+    /// Current table as it is on-chain, for the previews of a round which mustn't send anything. The accounts it
+    /// doesn't hold yet stay static keys of the transactions, so a preview may be larger than the round it shows.
+    /// `None` before the first round creates the table
+    pub async fn current(&self, state: &AppState) -> anyhow::Result<Option<AddressLookupTableAccount>> {
+        let Some(table) = *self.address.lock().await else {
+            return Ok(None);
+        };
+        let addresses = fetch_addresses(state, &table).await?;
+        Ok(Some(AddressLookupTableAccount { key: table, addresses }))
+    }
+}
+
+/// Closes the tables deactivated by the earlier rounds, a table still cooling down is left for the next round
+async fn close_deactivated(state: &AppState) -> anyhow::Result<()> {
+    let rpc_client = state.program.async_rpc();
+    for table in fetch_deactivated(&state.pool, &state.distributor_state_pubkey).await? {
+        let exists = rpc_client
+            .get_account_with_commitment(&table, CommitmentConfig::confirmed())
+            .await
+            .context("Failed to fetch deactivated lookup table")?
+            .value
+            .is_some();
+        if exists {
+            let close = address_lookup_table::instruction::close_lookup_table(
+                table,
+                state.payer.pubkey(),
+                state.payer.pubkey(),
+            );
+            if let Err(err) = send(state, close).await {
+                tracing::debug!(%table, %err, "Deactivated lookup table can't be closed yet");
+                continue;
+            }
+            tracing::info!(%table, "Deactivated lookup table closed");
+        }
+        sqlx::query("DELETE FROM deactivated_lookup_tables WHERE address = $1")
+            .bind(table.to_string())
+            .execute(&state.pool)
+            .await
+            .context("Failed to delete closed lookup table")?;
+    }
+    Ok(())
+}
+
+/// Accounts of the instructions which can be looked up: all but the signers and the invoked programs
+pub fn lookup_accounts(ixns: &[Instruction]) -> BTreeSet<Pubkey> {
+    let signers = ixns
+        .iter()
+        .flat_map(|ix| &ix.accounts)
+        .filter(|meta| meta.is_signer)
+        .map(|meta| meta.pubkey)
+        .collect::<BTreeSet<_>>();
+    let programs = ixns.iter().map(|ix| ix.program_id).collect::<BTreeSet<_>>();
+    ixns.iter()
+        .flat_map(|ix| &ix.accounts)
+        .map(|meta| meta.pubkey)
+        .filter(|account| !signers.contains(account) && !programs.contains(account))
+        .collect()
+}
+
+async fn create(state: &AppState) -> anyhow::Result<Pubkey> {
+    let recent_slot = state
+        .program
+        .async_rpc()
+        .get_slot_with_commitment(CommitmentConfig::finalized())
+        .await
+        .context("Failed to get slot")?;
+    let (ix, table) =
+        address_lookup_table::instruction::create_lookup_table(state.payer.pubkey(), state.payer.pubkey(), recent_slot);
+    send(state, ix).await.context("Failed to create lookup table")?;
+    tracing::info!(%table, "Lookup table created");
+    Ok(table)
+}
+
+async fn fetch_addresses(state: &AppState, table: &Pubkey) -> anyhow::Result<Vec<Pubkey>> {
+    let data = state
+        .program
+        .async_rpc()
+        .get_account_data(table)
+        .await
+        .context("Failed to fetch lookup table")?;
+    let table = AddressLookupTable::deserialize(&data).context("Invalid lookup table")?;
+    Ok(table.addresses.to_vec())
+}
+
+async fn send(state: &AppState, ix: Instruction) -> anyhow::Result<()> {
+    let rpc_client = state.program.async_rpc();
+    let blockhash = rpc_client
+        .get_latest_blockhash()
+        .await
+        .context("Failed to get latest blockhash")?;
+    let tx = Transaction::new_signed_with_payer(&[ix], Some(&state.payer.pubkey()), &[&state.payer], blockhash);
+    rpc_client.send_and_confirm_transaction(&tx).await?;
+    Ok(())
+}
+
+/// Addresses added to a table can be looked up from the next slot on
+async fn wait_next_slot(state: &AppState) -> anyhow::Result<()> {
+    let rpc_client = state.program.async_rpc();
+    let slot = rpc_client.get_slot().await.context("Failed to get slot")?;
+    while rpc_client.get_slot().await.context("Failed to get slot")? <= slot {
+        tokio::time::sleep(SLOT_POLL_INTERVAL).await;
+    }
+    Ok(())
+}
+
+/// Lookup table of the distributor saved by a previous run
+pub async fn fetch_lookup_table(pool: &sqlx::PgPool, distributor_state: &Pubkey) -> anyhow::Result<Option<Pubkey>> {
+    let address: Option<String> = sqlx::query_scalar("SELECT address FROM lookup_tables WHERE distributor_state = $1")
+        .bind(distributor_state.to_string())
+        .fetch_optional(pool)
+        .await
+        .context("Failed to fetch lookup table")?;
+    address
+        .map(|address| address.parse().context("Invalid lookup table address"))
+        .transpose()
+}
+
+async fn fetch_deactivated(pool: &sqlx::PgPool, distributor_state: &Pubkey) -> anyhow::Result<Vec<Pubkey>> {
+    let addresses: Vec<String> =
+        sqlx::query_scalar("SELECT address FROM deactivated_lookup_tables WHERE distributor_state = $1")
+            .bind(distributor_state.to_string())
+            .fetch_all(pool)
+            .await
+            .context("Failed to fetch deactivated lookup tables")?;
+    addresses
+        .into_iter()
+        .map(|address| address.parse().context("Invalid lookup table address"))
+        .collect()
+}
+
+async fn save_deactivated(pool: &sqlx::PgPool, distributor_state: &Pubkey, table: &Pubkey) -> anyhow::Result<()> {
+    sqlx::query("INSERT INTO deactivated_lookup_tables (address, distributor_state) VALUES ($1, $2)")
+        .bind(table.to_string())
+        .bind(distributor_state.to_string())
+        .execute(pool)
+        .await
+        .context("Failed to save deactivated lookup table")?;
+    Ok(())
+}
+
+async fn save(pool: &sqlx::PgPool, distributor_state: &Pubkey, table: &Pubkey) -> anyhow::Result<()> {
+    sqlx::query(
+        "INSERT INTO lookup_tables (distributor_state, address) VALUES ($1, $2) \
+         ON CONFLICT (distributor_state) DO UPDATE SET address = EXCLUDED.address, updated_at = CURRENT_TIMESTAMP",
+    )
+    .bind(distributor_state.to_string())
+    .bind(table.to_string())
+    .execute(pool)
+    .await
+    .context("Failed to save lookup table")?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::lookup_table::lookup_accounts;
+    use solana_sdk::{
+        instruction::{AccountMeta, Instruction},
+        pubkey::Pubkey,
+    };
+    use std::collections::BTreeSet;
+
+    #[test]
+    fn should_look_up_all_but_signers_and_programs() {
+        let (payer, state, ata, program) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let ixns = [
+            Instruction::new_with_bytes(program, &[], vec![
+                AccountMeta::new(payer, true),
+                AccountMeta::new(state, false),
+                AccountMeta::new(ata, false),
+            ]),
+            Instruction::new_with_bytes(Pubkey::new_unique(), &[], vec![AccountMeta::new_readonly(
+                program, false,
+            )]),
+        ];
+        assert_eq!(BTreeSet::from([state, ata]), lookup_accounts(&ixns));
+    }
+}
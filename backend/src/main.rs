@@ -1,22 +1,49 @@
 use anchor_client::{Client as AnchorClient, Cluster};
 use anyhow::{anyhow, Context};
 use axum::{
-    extract::{rejection::JsonRejection, State},
-    http::StatusCode,
+    body::Bytes,
+    extract::{rejection::JsonRejection, DefaultBodyLimit, FromRef, Path, Query, State},
+    http::{header, StatusCode},
+    response::IntoResponse,
     routing::{get, post},
     Json, Router,
 };
 use backend::{
-    service::{ActorHandle, AppState},
+    alerting::{Alerter, ALERT_PROBE_INTERVAL},
+    analytics::{fetch_rounds, rounds_to_parquet, tickets_from_parquet, tickets_to_parquet, PARQUET_CONTENT_TYPE},
+    claims::{fetch_claims, ClaimEntry},
+    dry_run::DryRun,
+    features::Features,
+    feed::{Feed, ATOM_CONTENT_TYPE, FEED_CACHE_CONTROL, JSON_FEED_CONTENT_TYPE},
+    history::{
+        fetch_history_round, fetch_history_rounds, fetch_winner_rounds, HistoryRound, HistoryRoundDetails, RoundsQuery,
+        WinnerRound,
+    },
+    latency::{fetch_round_latency, RoundLatency},
+    lookup_table::{fetch_lookup_table, LookupTable},
+    notifications::Notifier,
+    pipeline::transfer_hook_accounts,
+    relayer::{ClaimRelayer, RelayMessage, SignedRelayMessage},
+    report::{fetch_report, ExplorerLinks, ReportFormat},
+    retention::{render_table_sizes, table_sizes},
+    runtime_config::RuntimeConfig,
+    self_check::{ReadinessProbe, SelfCheck, SelfCheckReport},
+    service::{fetch_distributor_state, ActorHandle, AppState, WorstCaseSimulation},
     settings::Settings,
+    shadow_draw::{fetch_divergence_stats, DivergenceStats},
+    telemetry::init_tracing,
+    ticket::{store_tickets, TicketEntry, TicketSnapshot},
     token_holder::HeliusClient,
     transaction_status::EncodedConfirmedTransactionWithStatusMeta,
+    webhook_registration::{SyncOutcome, WebhookRegistration},
+    what_if::{WhatIfParams, WhatIfReport},
 };
-use distributor::DistributorState;
 use jsonrpsee::http_client::HttpClientBuilder;
+use serde::{Deserialize, Serialize};
 use shuttle_secrets::SecretStore;
 use solana_sdk::{
     commitment_config::CommitmentConfig,
+    pubkey::Pubkey,
     signature::{Keypair, Signer},
 };
 
@@ -25,6 +52,42 @@ use tokio::sync::Mutex;
 use tower::ServiceBuilder;
 use tower_http::validate_request::ValidateRequestHeaderLayer;
 
+/// Largest snapshot file accepted by the import
+const MAX_SNAPSHOT_FILE_SIZE: usize = 64 * 1024 * 1024;
+
+#[derive(Clone, FromRef)]
+struct ApiState {
+    handle: ActorHandle,
+    pool: sqlx::PgPool,
+    readiness: Arc<ReadinessProbe>,
+    distributor_state: Pubkey,
+    relayer: Option<Arc<ClaimRelayer>>,
+    feed: Arc<Feed>,
+    webhook_registration: Option<Arc<WebhookRegistration>>,
+}
+
+#[derive(Deserialize)]
+struct TicketsRequest {
+    campaign: String,
+    entries: Vec<TicketEntry>,
+}
+
+#[derive(Deserialize)]
+struct SimulateQuery {
+    shares: u64,
+}
+
+#[derive(Serialize)]
+struct Health {
+    status: &'static str,
+}
+
+#[derive(Deserialize)]
+struct ReportQuery {
+    #[serde(default)]
+    format: ReportFormat,
+}
+
 #[tracing::instrument(skip_all)]
 async fn webhook_handle(
     State(handle): State<ActorHandle>,
@@ -42,6 +105,111 @@ async fn webhook_handle(
     Ok(())
 }
 
+#[tracing::instrument(skip_all, fields(campaign = %request.campaign, entries = request.entries.len()))]
+async fn tickets_handle(
+    State(pool): State<sqlx::PgPool>,
+    Json(request): Json<TicketsRequest>,
+) -> Result<Json<u64>, StatusCode> {
+    let stored = store_tickets(&pool, &request.campaign, &request.entries)
+        .await
+        .map_err(|err| {
+            tracing::warn!(%err, "Failed to store tickets");
+            StatusCode::BAD_REQUEST
+        })?;
+
+    Ok(Json(stored))
+}
+
+/// Tickets of the campaign as a Parquet file for the analytics outside of the production database
+#[tracing::instrument(skip(pool))]
+async fn export_tickets_handle(
+    State(pool): State<sqlx::PgPool>,
+    Path(campaign): Path<String>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let snapshot = TicketSnapshot::fetch(&pool, &campaign).await.map_err(|err| {
+        tracing::warn!(%err, "Failed to fetch tickets");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let file = tickets_to_parquet(snapshot.entries()).map_err(|err| {
+        tracing::warn!(%err, "Failed to export tickets");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(([(header::CONTENT_TYPE, PARQUET_CONTENT_TYPE)], file))
+}
+
+/// Adds the tickets of an externally computed snapshot in Parquet to the campaign, like `/tickets` does
+#[tracing::instrument(skip(pool, file), fields(size = file.len()))]
+async fn import_tickets_handle(
+    State(pool): State<sqlx::PgPool>,
+    Path(campaign): Path<String>,
+    file: Bytes,
+) -> Result<Json<u64>, StatusCode> {
+    let entries = tickets_from_parquet(file).map_err(|err| {
+        tracing::warn!(%err, "Failed to read snapshot");
+        StatusCode::BAD_REQUEST
+    })?;
+    let stored = store_tickets(&pool, &campaign, &entries).await.map_err(|err| {
+        tracing::warn!(%err, "Failed to store tickets");
+        StatusCode::BAD_REQUEST
+    })?;
+
+    Ok(Json(stored))
+}
+
+/// Every recorded round as a Parquet file
+#[tracing::instrument(skip_all)]
+async fn export_rounds_handle(State(pool): State<sqlx::PgPool>) -> Result<impl IntoResponse, StatusCode> {
+    let rounds = fetch_rounds(&pool).await.map_err(|err| {
+        tracing::warn!(%err, "Failed to fetch rounds");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let file = rounds_to_parquet(&rounds).map_err(|err| {
+        tracing::warn!(%err, "Failed to export rounds");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(([(header::CONTENT_TYPE, PARQUET_CONTENT_TYPE)], file))
+}
+
+#[tracing::instrument(skip(handle))]
+async fn simulate_handle(
+    State(handle): State<ActorHandle>,
+    Query(SimulateQuery { shares }): Query<SimulateQuery>,
+) -> Result<Json<WorstCaseSimulation>, StatusCode> {
+    let simulation = handle.simulate_worst_case(shares).await.map_err(|err| {
+        tracing::warn!(%err, "Failed to simulate worst case distribution");
+        StatusCode::BAD_REQUEST
+    })?;
+
+    Ok(Json(simulation))
+}
+
+/// Preview of the round which would be sent now, for a review before the threshold is reached
+#[tracing::instrument(skip_all)]
+async fn dry_run_handle(State(handle): State<ActorHandle>) -> Result<Json<DryRun>, StatusCode> {
+    let dry_run = handle.dry_run().await.map_err(|err| {
+        tracing::warn!(%err, "Failed to dry run the round");
+        StatusCode::BAD_REQUEST
+    })?;
+
+    Ok(Json(dry_run))
+}
+
+/// Eligible population and win probabilities of the latest ticket snapshot under hypothetical parameters
+#[tracing::instrument(skip(handle))]
+async fn what_if_handle(
+    State(handle): State<ActorHandle>,
+    Json(params): Json<WhatIfParams>,
+) -> Result<Json<WhatIfReport>, StatusCode> {
+    let report = handle.what_if(params).await.map_err(|err| {
+        tracing::warn!(%err, "Failed to compare the eligibility parameters");
+        StatusCode::BAD_REQUEST
+    })?;
+
+    Ok(Json(report))
+}
+
 #[tracing::instrument(skip_all)]
 async fn explicit_handle(State(handle): State<ActorHandle>) -> Result<(), StatusCode> {
     handle.handle_request(None);
@@ -49,6 +217,267 @@ async fn explicit_handle(State(handle): State<ActorHandle>) -> Result<(), Status
     Ok(())
 }
 
+#[tracing::instrument(skip(pool))]
+async fn report_handle(
+    State(pool): State<sqlx::PgPool>,
+    Path(id): Path<i64>,
+    Query(ReportQuery { format }): Query<ReportQuery>,
+) -> Result<impl IntoResponse, StatusCode> {
+    render_report(&pool, id, format, false).await
+}
+
+/// Reports of the rounds which aren't announced yet are available to admins only
+#[tracing::instrument(skip(pool))]
+async fn preview_report_handle(
+    State(pool): State<sqlx::PgPool>,
+    Path(id): Path<i64>,
+    Query(ReportQuery { format }): Query<ReportQuery>,
+) -> Result<impl IntoResponse, StatusCode> {
+    render_report(&pool, id, format, true).await
+}
+
+/// Registers the Helius webhook at once instead of waiting for the next periodic verification
+#[tracing::instrument(skip_all)]
+async fn sync_webhook_handle(
+    State(webhook_registration): State<Option<Arc<WebhookRegistration>>>,
+) -> Result<Json<SyncOutcome>, StatusCode> {
+    let webhook_registration = webhook_registration.ok_or(StatusCode::NOT_FOUND)?;
+    let outcome = webhook_registration.sync().await.map_err(|err| {
+        tracing::warn!(?err, "Failed to sync the Helius webhook");
+        StatusCode::BAD_GATEWAY
+    })?;
+    Ok(Json(outcome))
+}
+
+#[tracing::instrument(skip_all)]
+async fn feed_json_handle(
+    State(pool): State<sqlx::PgPool>,
+    State(feed): State<Arc<Feed>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let snapshot = feed.snapshot(&pool).await.map_err(|err| {
+        tracing::warn!(%err, "Failed to build the feed");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let body = serde_json::to_vec(&snapshot.to_json_feed(|signature| feed.transaction_link(signature)))
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, JSON_FEED_CONTENT_TYPE),
+            (header::CACHE_CONTROL, FEED_CACHE_CONTROL),
+        ],
+        body,
+    ))
+}
+
+#[tracing::instrument(skip_all)]
+async fn feed_atom_handle(
+    State(pool): State<sqlx::PgPool>,
+    State(feed): State<Arc<Feed>>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let snapshot = feed.snapshot(&pool).await.map_err(|err| {
+        tracing::warn!(%err, "Failed to build the feed");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let body = snapshot.to_atom(|signature| feed.transaction_link(signature));
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, ATOM_CONTENT_TYPE),
+            (header::CACHE_CONTROL, FEED_CACHE_CONTROL),
+        ],
+        body,
+    ))
+}
+
+async fn render_report(
+    pool: &sqlx::PgPool,
+    id: i64,
+    format: ReportFormat,
+    preview: bool,
+) -> Result<impl IntoResponse, StatusCode> {
+    let report = fetch_report(pool, id, format, preview).await.map_err(|err| {
+        tracing::warn!(%err, "Failed to fetch distribution report");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let report = report.ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(([(header::CONTENT_TYPE, format.content_type())], report))
+}
+
+#[tracing::instrument(skip(pool, handle))]
+async fn latency_handle(
+    State(pool): State<sqlx::PgPool>,
+    State(handle): State<ActorHandle>,
+    Path(id): Path<i64>,
+) -> Result<Json<RoundLatency>, StatusCode> {
+    let latency = fetch_round_latency(&pool, id, handle.latency_slo())
+        .await
+        .map_err(|err| {
+            tracing::warn!(%err, "Failed to fetch round latency");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let latency = latency.ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Json(latency))
+}
+
+/// Applies the runtime config from the database between the rounds, like SIGHUP does
+#[tracing::instrument(skip_all)]
+async fn reload_handle(State(handle): State<ActorHandle>) -> Result<Json<RuntimeConfig>, StatusCode> {
+    let config = handle.reload().await.map_err(|err| {
+        tracing::warn!(%err, "Failed to reload runtime config");
+        StatusCode::BAD_REQUEST
+    })?;
+
+    Ok(Json(config))
+}
+
+/// How the candidate draw strategy diverged from the paid draws it shadowed
+#[tracing::instrument(skip_all)]
+async fn shadow_draws_handle(
+    State(pool): State<sqlx::PgPool>,
+    State(distributor_state): State<Pubkey>,
+) -> Result<Json<Vec<DivergenceStats>>, StatusCode> {
+    let stats = fetch_divergence_stats(&pool, &distributor_state).await.map_err(|err| {
+        tracing::warn!(%err, "Failed to fetch shadow draws");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(Json(stats))
+}
+
+#[tracing::instrument(skip_all)]
+async fn metrics_handle(
+    State(handle): State<ActorHandle>,
+    State(pool): State<sqlx::PgPool>,
+) -> Result<impl IntoResponse, StatusCode> {
+    let table_sizes = table_sizes(&pool).await.map_err(|err| {
+        tracing::warn!(%err, "Failed to fetch table sizes");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok((
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        format!("{}{}", handle.latency_metrics(), render_table_sizes(&table_sizes)),
+    ))
+}
+
+/// Announced rounds of the distributor, newest first
+#[tracing::instrument(skip(pool, distributor_state))]
+async fn rounds_handle(
+    State(pool): State<sqlx::PgPool>,
+    State(distributor_state): State<Pubkey>,
+    Query(query): Query<RoundsQuery>,
+) -> Result<Json<Vec<HistoryRound>>, StatusCode> {
+    let rounds = fetch_history_rounds(&pool, &distributor_state, &query)
+        .await
+        .map_err(|err| {
+            tracing::warn!(%err, "Failed to fetch rounds");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Json(rounds))
+}
+
+#[tracing::instrument(skip(pool, distributor_state))]
+async fn round_handle(
+    State(pool): State<sqlx::PgPool>,
+    State(distributor_state): State<Pubkey>,
+    Path(id): Path<i64>,
+) -> Result<Json<HistoryRoundDetails>, StatusCode> {
+    let round = fetch_history_round(&pool, &distributor_state, id)
+        .await
+        .map_err(|err| {
+            tracing::warn!(%err, "Failed to fetch round");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let round = round.ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Json(round))
+}
+
+/// Announced rounds the wallet won, newest first
+#[tracing::instrument(skip(pool, distributor_state))]
+async fn winner_handle(
+    State(pool): State<sqlx::PgPool>,
+    State(distributor_state): State<Pubkey>,
+    Path(wallet): Path<String>,
+) -> Result<Json<Vec<WinnerRound>>, StatusCode> {
+    let wallet: Pubkey = wallet.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+    let rounds = fetch_winner_rounds(&pool, &distributor_state, &wallet)
+        .await
+        .map_err(|err| {
+            tracing::warn!(%err, "Failed to fetch winner rounds");
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Json(rounds))
+}
+
+#[tracing::instrument(skip(pool))]
+async fn claims_handle(
+    State(pool): State<sqlx::PgPool>,
+    Path(wallet): Path<String>,
+) -> Result<Json<Vec<ClaimEntry>>, StatusCode> {
+    let wallet: Pubkey = wallet.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+    let claims = fetch_claims(&pool, &wallet).await.map_err(|err| {
+        tracing::warn!(%err, "Failed to fetch claims");
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(Json(claims))
+}
+
+/// Claim transaction for the winner to sign, the relayer pays its fee
+#[tracing::instrument(skip(relayer))]
+async fn prepare_relay_handle(
+    State(relayer): State<Option<Arc<ClaimRelayer>>>,
+    Path((wallet, round)): Path<(String, u64)>,
+) -> Result<Json<RelayMessage>, StatusCode> {
+    let relayer = relayer.ok_or(StatusCode::NOT_FOUND)?;
+    let wallet: Pubkey = wallet.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+    let message = relayer.prepare(&wallet, round).await.map_err(|err| {
+        tracing::warn!(%err, "Failed to prepare relayed claim");
+        StatusCode::BAD_REQUEST
+    })?;
+
+    Ok(Json(message))
+}
+
+/// Submits the claim transaction signed by the winner, returns the transaction signature
+#[tracing::instrument(skip(relayer, signed))]
+async fn submit_relay_handle(
+    State(relayer): State<Option<Arc<ClaimRelayer>>>,
+    Path((wallet, round)): Path<(String, u64)>,
+    Json(signed): Json<SignedRelayMessage>,
+) -> Result<Json<String>, StatusCode> {
+    let relayer = relayer.ok_or(StatusCode::NOT_FOUND)?;
+    let wallet: Pubkey = wallet.parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+    let signature = relayer.submit(&wallet, round, &signed).await.map_err(|err| {
+        tracing::warn!(%err, "Failed to relay claim");
+        StatusCode::BAD_REQUEST
+    })?;
+
+    Ok(Json(signature.to_string()))
+}
+
+#[tracing::instrument(skip_all)]
+async fn readyz_handle(State(readiness): State<Arc<ReadinessProbe>>) -> (StatusCode, Json<SelfCheckReport>) {
+    let report = readiness.run().await;
+    let status = if report.ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+    (status, Json(report))
+}
+
+/// The process is up and serves requests, whether it can pay a round is up to `/readyz`
+async fn healthz_handle() -> Json<Health> {
+    Json(Health { status: "ok" })
+}
+
 #[shuttle_runtime::main]
 async fn axum(
     #[shuttle_secrets::Secrets] secret_store: SecretStore,
@@ -57,14 +486,45 @@ async fn axum(
     let Settings {
         solana_rpc_url,
         priority_fee_url,
+        priority_fee_config,
         payer: payer_keypair,
         distributor_authority: distributor_authority_keypair,
+        service_signer: service_signer_keypair,
         distributor_state: distributor_state_pubkey,
         program_id,
         auth_token,
         memo,
         marker_mint,
+        explorer_cluster,
+        excluded_wallets,
+        payer_balance_floor,
+        record_rounds,
+        ticket_campaign,
+        announcement_delay,
+        vrf_draw,
+        privacy_mode,
+        distribute_v2,
+        validate_winners,
+        pro_rata,
+        holders_refresh_interval,
+        vault_inspection_interval,
+        latency_slo,
+        max_send_attempts,
+        use_lookup_table,
+        nonce_account,
+        retention,
+        notification_channels,
+        notification_collapse_threshold,
+        shadow_draw_strategy,
+        shadow_draw_percentage,
+        relayer,
+        relayer_claims_per_hour,
+        alert_sinks,
+        webhook,
+        webhook_verify_interval,
+        otlp,
     } = Settings::try_from(&secret_store)?;
+    init_tracing(otlp.as_ref())?;
 
     let payer = payer_keypair.pubkey();
     let distributor_authority = distributor_authority_keypair.pubkey();
@@ -77,25 +537,12 @@ async fn axum(
     .program(program_id)
     .context("Failed setup anchor client program")?;
 
-    let distributor_state: DistributorState = program
-        .account(distributor_state_pubkey)
-        .await
-        .context("Failed to fetch distributor state")?;
-    if distributor_state.distributor_authority != distributor_authority {
-        return Err(anyhow!(
-            "Distributor authority mismatch: {} vs {}",
-            distributor_state.distributor_authority,
-            distributor_authority
-        )
-        .into());
-    }
-
     sqlx::migrate!()
         .run(&pool)
         .await
         .context("Failed to run database migrations")?;
 
-    let helius_client = HeliusClient::new(solana_rpc_url, marker_mint, pool)
+    let helius_client = HeliusClient::new(solana_rpc_url.clone(), marker_mint, pool.clone())
         .await
         .context("Failed to create Helius client")?;
 
@@ -103,26 +550,196 @@ async fn axum(
         .build(priority_fee_url)
         .context("Failed to build priority fee client")?;
 
+    let self_check = SelfCheck {
+        rpc_client: &program.async_rpc(),
+        program_id,
+        distributor_state: distributor_state_pubkey,
+        distributor_authority,
+        service_signer: service_signer_keypair.as_ref().map(Signer::pubkey),
+        payer,
+        payer_balance_floor,
+        helius_client: &helius_client,
+        priority_fee: &priority_fee,
+        priority_level: priority_fee_config.level,
+        pool: &pool,
+    }
+    .run()
+    .await;
+    tracing::info!("Self-check results:\n{self_check}");
+    if !self_check.ready {
+        let failed = self_check
+            .critical_failures()
+            .map(|check| check.name)
+            .collect::<Vec<_>>();
+        return Err(anyhow!("Self-check failed: {}", failed.join(", ")).into());
+    }
+
+    let distributor_state = fetch_distributor_state(&program.async_rpc(), &distributor_state_pubkey).await?;
+
+    let alerter = Arc::new(Alerter::new(
+        alert_sinks,
+        payer_balance_floor,
+        distributor_state_pubkey.to_string(),
+    )?);
+    tokio::spawn(Arc::clone(&alerter).watch(program.async_rpc(), payer, ALERT_PROBE_INTERVAL));
+    let readiness = Arc::new(ReadinessProbe {
+        rpc_client: program.async_rpc(),
+        distributor_state: distributor_state_pubkey,
+        payer,
+        payer_balance_floor,
+        pool: pool.clone(),
+    });
+
     let vault = distributor_state.vault;
+    let webhook_registration = webhook.map(|webhook| {
+        Arc::new(WebhookRegistration::new(webhook, &auth_token, &[
+            vault,
+            distributor_state_pubkey,
+        ]))
+    });
+    if let Some(webhook_registration) = &webhook_registration {
+        tokio::spawn(Arc::clone(webhook_registration).watch(webhook_verify_interval));
+    }
+    let mint_decimals = distributor_state.decimals;
+    // the rounds and the claims are paid by the token program of the mint
+    let token_program = program
+        .async_rpc()
+        .get_account(&distributor_state.mint)
+        .await
+        .context("Failed to fetch mint")?
+        .owner;
+    let transfer_hook_accounts = transfer_hook_accounts(
+        &program.async_rpc(),
+        &distributor_state.mint,
+        &distributor_state.vault,
+        &distributor_state_pubkey,
+        mint_decimals,
+    )
+    .await?;
+    let relayer = match relayer {
+        Some(relayer) => Some(Arc::new(ClaimRelayer::new(
+            solana_rpc_url,
+            relayer,
+            distributor_state_pubkey,
+            distributor_state.mint,
+            token_program,
+            pool.clone(),
+            relayer_claims_per_hour,
+        ))),
+        None => None,
+    };
 
+    let feed = Arc::new(Feed::new(
+        program.async_rpc(),
+        distributor_state_pubkey,
+        mint_decimals,
+        ExplorerLinks {
+            cluster: explorer_cluster.clone(),
+        },
+    ));
+
+    let lookup_table = if use_lookup_table {
+        Some(LookupTable::new(
+            fetch_lookup_table(&pool, &distributor_state_pubkey).await?,
+        ))
+    } else {
+        None
+    };
     let state = AppState {
         program,
         distributor_state,
         helius_client: Mutex::new(helius_client),
         payer: payer_keypair,
         distributor_authority: distributor_authority_keypair,
+        service_signer: service_signer_keypair,
         distributor_state_pubkey,
         priority_fee,
+        priority_fee_config,
         memo,
+        pool: pool.clone(),
+        mint_decimals,
+        token_program,
+        transfer_hook_accounts,
+        explorer: ExplorerLinks {
+            cluster: explorer_cluster,
+        },
+        excluded_wallets,
+        ticket_campaign,
+        announcement_delay,
+        features: Features {
+            record_rounds,
+            vrf_draw,
+            privacy_mode,
+            distribute_v2,
+            validate_winners,
+            pro_rata,
+        },
+        holders_refresh_interval,
+        vault_inspection_interval,
+        latency_slo,
+        max_send_attempts,
+        lookup_table,
+        nonce_account,
+        retention,
+        notifier: (!notification_channels.is_empty())
+            .then(|| Arc::new(Notifier::new(notification_channels, notification_collapse_threshold))),
+        shadow_draw_strategy,
+        shadow_draw_percentage,
+        alerter,
     };
 
     let handle = ActorHandle::new(state);
+    tokio::spawn({
+        let handle = handle.clone();
+        async move {
+            if let Err(err) = handle.reload_on_sighup().await {
+                tracing::warn!(%err, "Runtime config can't be reloaded by SIGHUP");
+            }
+        }
+    });
 
     let router = Router::new()
         .route("/", post(webhook_handle))
+        .route("/tickets", post(tickets_handle))
+        .route("/admin/simulate", get(simulate_handle))
+        .route("/admin/dry-run", get(dry_run_handle))
+        .route("/admin/what-if", post(what_if_handle))
+        .route("/admin/distributions/:id/report", get(preview_report_handle))
+        .route("/admin/metrics", get(metrics_handle))
+        .route("/admin/reload", post(reload_handle))
+        .route("/admin/shadow-draws", get(shadow_draws_handle))
+        .route("/admin/export/tickets/:campaign", get(export_tickets_handle))
+        .route(
+            "/admin/import/tickets/:campaign",
+            post(import_tickets_handle).layer(DefaultBodyLimit::max(MAX_SNAPSHOT_FILE_SIZE)),
+        )
+        .route("/admin/export/rounds", get(export_rounds_handle))
+        .route("/admin/webhook/sync", post(sync_webhook_handle))
         .layer(ServiceBuilder::new().layer(ValidateRequestHeaderLayer::bearer(&auth_token)))
         .route("/distibute", get(explicit_handle))
-        .with_state(handle);
+        .route("/distributions/:id/report", get(report_handle))
+        .route("/distributions/:id/latency", get(latency_handle))
+        .route("/feed.json", get(feed_json_handle))
+        .route("/feed.atom", get(feed_atom_handle))
+        .route("/rounds", get(rounds_handle))
+        .route("/rounds/:id", get(round_handle))
+        .route("/winners/:wallet", get(winner_handle))
+        .route("/claims/:wallet", get(claims_handle))
+        .route(
+            "/claims/:wallet/:round/relay",
+            get(prepare_relay_handle).post(submit_relay_handle),
+        )
+        .route("/healthz", get(healthz_handle))
+        .route("/readyz", get(readyz_handle))
+        .with_state(ApiState {
+            handle,
+            pool,
+            readiness,
+            distributor_state: distributor_state_pubkey,
+            relayer,
+            feed,
+            webhook_registration,
+        });
 
     tracing::info!(%payer, %distributor_authority,
         %distributor_state_pubkey,
@@ -0,0 +1,292 @@
+use crate::report::{display_address, ui_amount, RoundReport};
+use anyhow::{ensure, Context};
+use reqwest::{header::RETRY_AFTER, StatusCode};
+use serde_json::{json, Value};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+
+/// How many times a message is sent before the rate limited channel is given up
+const MAX_ATTEMPTS: usize = 5;
+/// Wait before a retry if the platform didn't tell how long
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(1);
+
+/// Where the rounds are announced
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Channel {
+    Discord { webhook_url: String },
+    Telegram { bot_token: String, chat_id: String },
+}
+
+impl Channel {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Channel::Discord { .. } => "discord",
+            Channel::Telegram { .. } => "telegram",
+        }
+    }
+
+    /// Longest message the platform accepts, in characters
+    pub fn max_message_len(&self) -> usize {
+        match self {
+            Channel::Discord { .. } => 2000,
+            Channel::Telegram { .. } => 4096,
+        }
+    }
+
+    /// Discord takes 5 webhook requests per 2 seconds, a Telegram bot 20 messages per minute to a group
+    fn rate_limit(&self) -> TokenBucket {
+        match self {
+            Channel::Discord { .. } => TokenBucket::new(5, 2.5),
+            Channel::Telegram { .. } => TokenBucket::new(20, 20.0 / 60.0),
+        }
+    }
+
+    fn request(&self, client: &reqwest::Client, text: &str) -> reqwest::RequestBuilder {
+        match self {
+            Channel::Discord { webhook_url } => client.post(webhook_url).json(&json!({
+                "content": text,
+                "allowed_mentions": { "parse": [] },
+            })),
+            Channel::Telegram { bot_token, chat_id } => client
+                .post(format!("https://api.telegram.org/bot{bot_token}/sendMessage"))
+                .json(&json!({
+                    "chat_id": chat_id,
+                    "text": text,
+                    "disable_web_page_preview": true,
+                })),
+        }
+    }
+}
+
+/// Schedules the messages of a channel within its rate limit
+#[derive(Debug)]
+pub struct TokenBucket {
+    capacity: f64,
+    tokens: f64,
+    per_second: f64,
+    refilled_at: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(capacity: u32, per_second: f64) -> Self {
+        Self {
+            capacity: capacity as f64,
+            tokens: capacity as f64,
+            per_second,
+            refilled_at: Instant::now(),
+        }
+    }
+
+    /// Takes a token at `now`, or tells how long to wait for the next one
+    pub fn try_take(&mut self, now: Instant) -> Result<(), Duration> {
+        let elapsed = now.saturating_duration_since(self.refilled_at).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_second).min(self.capacity);
+        self.refilled_at = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - self.tokens) / self.per_second))
+        }
+    }
+
+    async fn take(&mut self) {
+        while let Err(wait) = self.try_take(Instant::now()) {
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+/// Posts the announcements of the rounds to every configured channel
+pub struct Notifier {
+    client: reqwest::Client,
+    channels: Vec<(Channel, Mutex<TokenBucket>)>,
+    /// Rounds with more winners are announced by a summary instead of a line per winner
+    pub collapse_threshold: usize,
+}
+
+impl Notifier {
+    pub fn new(channels: Vec<Channel>, collapse_threshold: usize) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            channels: channels
+                .into_iter()
+                .map(|channel| {
+                    let rate_limit = Mutex::new(channel.rate_limit());
+                    (channel, rate_limit)
+                })
+                .collect(),
+            collapse_threshold,
+        }
+    }
+
+    /// Posts the lines batched into as few messages as the platforms allow, a failing channel doesn't stop the others
+    pub async fn announce(&self, lines: &[String]) {
+        for (channel, rate_limit) in &self.channels {
+            if let Err(err) = self.post(channel, rate_limit, lines).await {
+                tracing::warn!(%err, channel = channel.name(), "Failed to post the announcement");
+            }
+        }
+    }
+
+    async fn post(&self, channel: &Channel, rate_limit: &Mutex<TokenBucket>, lines: &[String]) -> anyhow::Result<()> {
+        let mut rate_limit = rate_limit.lock().await;
+        for message in batch_messages(lines, channel.max_message_len()) {
+            for attempt in 1.. {
+                rate_limit.take().await;
+                let response = channel
+                    .request(&self.client, &message)
+                    .send()
+                    .await
+                    .context("Failed to send the message")?;
+                if response.status() != StatusCode::TOO_MANY_REQUESTS {
+                    response.error_for_status().context("Message was rejected")?;
+                    break;
+                }
+                ensure!(attempt < MAX_ATTEMPTS, "Still rate limited after {attempt} attempts");
+
+                let header = response
+                    .headers()
+                    .get(RETRY_AFTER)
+                    .and_then(|value| value.to_str().ok())
+                    .map(str::to_owned);
+                let body = response.json::<Value>().await.unwrap_or_default();
+                let wait = retry_after(header.as_deref(), &body);
+                tracing::info!(channel = channel.name(), ?wait, "Rate limited, retrying the message");
+                tokio::time::sleep(wait).await;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Lines of the round announcement, a line per winner unless there are more than `collapse_threshold` of them
+pub fn announcement(report: &RoundReport, id: i64, private: bool, collapse_threshold: usize) -> Vec<String> {
+    let summary = format!(
+        "Round #{id}: {} winners got {} each, {} in total",
+        report.winners.len(),
+        ui_amount(report.share_size, report.decimals),
+        ui_amount(report.total_paid(), report.decimals)
+    );
+    if report.winners.len() > collapse_threshold {
+        return vec![summary];
+    }
+    std::iter::once(summary)
+        .chain(report.winners.iter().map(|winner| {
+            format!(
+                "{} won {}",
+                display_address(&winner.wallet, private),
+                ui_amount(winner.amount, report.decimals)
+            )
+        }))
+        .collect()
+}
+
+/// Lines joined into as few messages of at most `max_len` characters as possible, longer lines are cut
+pub fn batch_messages(lines: &[String], max_len: usize) -> Vec<String> {
+    let mut messages = Vec::new();
+    let mut message = String::new();
+    let mut message_len = 0;
+    for line in lines {
+        let line = line.chars().take(max_len).collect::<String>();
+        let line_len = line.chars().count();
+        if message_len > 0 && message_len + 1 + line_len > max_len {
+            messages.push(std::mem::take(&mut message));
+            message_len = 0;
+        }
+        if message_len > 0 {
+            message.push('\n');
+            message_len += 1;
+        }
+        message.push_str(&line);
+        message_len += line_len;
+    }
+    if message_len > 0 {
+        messages.push(message);
+    }
+    messages
+}
+
+/// Wait asked by a rate limited response: its `Retry-After` header, `retry_after` of a Discord body or
+/// `parameters.retry_after` of a Telegram one, all in seconds
+pub fn retry_after(header: Option<&str>, body: &Value) -> Duration {
+    header
+        .and_then(|header| header.trim().parse::<f64>().ok())
+        .or_else(|| body.get("retry_after").and_then(Value::as_f64))
+        .or_else(|| body.pointer("/parameters/retry_after").and_then(Value::as_f64))
+        .filter(|seconds| seconds.is_finite() && *seconds >= 0.0)
+        .map_or(DEFAULT_RETRY_AFTER, Duration::from_secs_f64)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        notifications::{announcement, batch_messages, retry_after, TokenBucket},
+        report::{CostBreakdown, Randomness, ReportWinner, RoundReport},
+    };
+    use serde_json::json;
+    use solana_sdk::{pubkey::Pubkey, signature::Signature};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn should_wait_for_the_refill_of_an_empty_bucket() {
+        let mut bucket = TokenBucket::new(2, 0.5);
+        let now = Instant::now();
+        assert_eq!(Ok(()), bucket.try_take(now));
+        assert_eq!(Ok(()), bucket.try_take(now));
+        assert_eq!(Err(Duration::from_secs(2)), bucket.try_take(now));
+        assert_eq!(Ok(()), bucket.try_take(now + Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn should_batch_lines_within_the_message_limit() {
+        let lines = ["aaaa", "bbb", "cc", "dddddddddddd"].map(str::to_string);
+        assert_eq!(vec!["aaaa\nbbb", "cc", "dddddddd"], batch_messages(&lines, 8));
+    }
+
+    #[test]
+    fn should_prefer_the_retry_after_header() {
+        assert_eq!(
+            Duration::from_secs(3),
+            retry_after(Some("3"), &json!({ "retry_after": 7.5 }))
+        );
+        assert_eq!(
+            Duration::from_secs_f64(7.5),
+            retry_after(None, &json!({ "retry_after": 7.5 }))
+        );
+        assert_eq!(
+            Duration::from_secs(12),
+            retry_after(None, &json!({ "ok": false, "parameters": { "retry_after": 12 } }))
+        );
+        assert_eq!(Duration::from_secs(1), retry_after(Some("soon"), &json!({})));
+    }
+
+    #[test]
+    fn should_collapse_the_winners_over_the_threshold() {
+        let report = RoundReport {
+            signature: Signature::default(),
+            decimals: 2,
+            share_size: 150,
+            burned: 0,
+            holders_number: 10,
+            randomness: Randomness::Seed([0; 32]),
+            winners: (0..3)
+                .map(|index| ReportWinner {
+                    index,
+                    wallet: Pubkey::new_unique(),
+                    amount: 150,
+                    ata_created: false,
+                })
+                .collect(),
+            replacements: Vec::new(),
+            costs: CostBreakdown::default(),
+        };
+
+        let lines = announcement(&report, 7, true, 3);
+        assert_eq!(4, lines.len());
+        assert_eq!("Round #7: 3 winners got 1.5 each, 4.5 in total", lines[0]);
+        assert!(lines[1].ends_with(" won 1.5"));
+
+        assert_eq!(1, announcement(&report, 7, true, 2).len());
+    }
+}
@@ -0,0 +1,1412 @@
+use crate::{
+    claims::{claim_entries, store_claims},
+    confirmation::fetch_durable_nonce,
+    features::Features,
+    program_error::ProgramFailure,
+    service::AppState,
+    token_holder::Winner,
+    validation::valid_winners,
+};
+use anchor_client::anchor_lang::{
+    prelude::{AccountMeta, Pubkey},
+    AnchorDeserialize,
+};
+use anyhow::{anyhow, ensure, Context};
+use async_trait::async_trait;
+use base64::{prelude::BASE64_STANDARD, Engine};
+use distributor::{DistributeArgs, DistributionMode, DistributionRound, DistributorState, RoundProgress, VaultConfig};
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::{
+    nonblocking::rpc_client::RpcClient,
+    rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig, RpcSimulateTransactionConfig},
+    rpc_filter::{Memcmp, RpcFilterType},
+};
+use solana_sdk::{
+    address_lookup_table_account::AddressLookupTableAccount,
+    commitment_config::CommitmentConfig,
+    compute_budget::ComputeBudgetInstruction,
+    hash::{hash, Hash},
+    instruction::Instruction,
+    message::{v0, Message, VersionedMessage},
+    program_pack::Pack,
+    signature::{Keypair, Signature, Signer},
+    system_instruction,
+    transaction::{Transaction, VersionedTransaction},
+};
+use spl_associated_token_account::{
+    get_associated_token_address, get_associated_token_address_with_program_id,
+    instruction::create_associated_token_account_idempotent,
+};
+use spl_token_2022::{
+    extension::{transfer_hook, StateWithExtensions},
+    offchain::resolve_extra_transfer_account_metas,
+    state::{Account as TokenAccount, AccountState, Mint},
+};
+use std::time::Duration;
+
+/// Seed of the ORAO VRF network configuration, the fee of a randomness request is paid to its treasury
+const VRF_NETWORK_STATE_SEED: &[u8] = b"orao-vrf-network-configuration";
+
+/// Transactions paying out a round, they are sent in order
+pub struct RoundTransactions {
+    pub transactions: Vec<VersionedTransaction>,
+    /// Token accounts of the winners in the winners order which the round creates if missing, empty if the
+    /// winners aren't paid by the round itself, are paid in native SOL, their shares are vested or may be too large
+    /// to be paid without an acceptance
+    pub token_accounts: Vec<Pubkey>,
+}
+
+/// Compute units a single transaction can request
+pub const MAX_COMPUTE_UNIT_LIMIT: u32 = 1_400_000;
+/// Compute unit limit of a transaction whose simulation failed
+const FALLBACK_COMPUTE_UNIT_LIMIT: u32 = 800_000;
+/// Safety margin over the simulated compute units, the accounts may change between the simulation and the landing
+const COMPUTE_UNIT_MARGIN_PERCENT: u64 = 20;
+
+/// Pays out the drawn winners the way the `DistributionMode` of the distributor requires
+#[async_trait]
+pub trait Distributor: Send + Sync {
+    /// Transactions paying the `winners`. A `preview` round is only simulated, it is built without sending
+    /// anything ahead of it
+    async fn build(
+        &self,
+        state: &AppState,
+        distributor_state: &DistributorState,
+        winners: &[Winner],
+        compute_unit_price: u64,
+        preview: bool,
+    ) -> anyhow::Result<RoundTransactions>;
+
+    /// Amount every winner is paid in the winners order, the share size unless the round splits its shares
+    async fn amounts(
+        &self,
+        _state: &AppState,
+        distributor_state: &DistributorState,
+        winners: &[Winner],
+    ) -> anyhow::Result<Vec<u64>> {
+        Ok(vec![distributor_state.share_size; winners.len()])
+    }
+
+    /// Makes the confirmed round available to the winners
+    async fn publish(
+        &self,
+        _state: &AppState,
+        _distributor_state: &DistributorState,
+        _winners: &[Winner],
+    ) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    /// Number of the winners paid so far of a round which was started on-chain but isn't completed, `None` if no
+    /// round is open. The open round is resumed with its recorded winners even if the vault balance is below the
+    /// threshold now
+    async fn round_in_progress(
+        &self,
+        _state: &AppState,
+        _distributor_state: &DistributorState,
+    ) -> anyhow::Result<Option<u64>> {
+        Ok(None)
+    }
+}
+
+pub fn pipeline(mode: DistributionMode, features: Features) -> Box<dyn Distributor> {
+    match mode {
+        DistributionMode::SingleTx | DistributionMode::Dividend => Box::new(SingleTxDistributor { features }),
+        DistributionMode::Chunked => Box::new(ChunkedDistributor { features }),
+        DistributionMode::Claim => Box::new(ClaimDistributor { features }),
+    }
+}
+
+/// All winners are paid by one `distribute` transaction, a v0 one if the backend keeps a lookup table of the
+/// round accounts. The dividend rounds pay the largest holders by one `distribute_dividend` transaction
+pub struct SingleTxDistributor {
+    features: Features,
+}
+
+#[async_trait]
+impl Distributor for SingleTxDistributor {
+    async fn build(
+        &self,
+        state: &AppState,
+        distributor_state: &DistributorState,
+        winners: &[Winner],
+        compute_unit_price: u64,
+        preview: bool,
+    ) -> anyhow::Result<RoundTransactions> {
+        let payees = payees(state, distributor_state, winners).await?;
+        let collection_nfts = collection_nfts(state, distributor_state, winners).await?;
+        let remaining_accounts = winners
+            .iter()
+            .zip(payees)
+            .zip(collection_nfts)
+            .flat_map(|((winner, payee), nft)| winner_accounts(state, distributor_state, &winner.wallet, payee, nft))
+            .collect::<Vec<_>>();
+
+        let (distributor_authority, service_signer) = round_signers(state, distributor_state)?;
+
+        let request = state
+            .program
+            .request()
+            .instruction(spl_memo::build_memo(state.memo.as_bytes(), &[]))
+            .accounts(distributor::accounts::Distribute {
+                payer: state.payer.pubkey(),
+                distributor_authority,
+                service_signer,
+                distributor_state: state.distributor_state_pubkey,
+                mint: distributor_state.mint,
+                vault: distributor_state.vault,
+                treasury: distributor_state.remainder_policy.treasury(),
+                round: records_round(&self.features, distributor_state).then(|| {
+                    distributor::client::distribution_round_address(
+                        &state.distributor_state_pubkey,
+                        distributor_state.round,
+                    )
+                }),
+                round_progress: distributor::client::round_progress_address(
+                    &state.distributor_state_pubkey,
+                    distributor_state.round,
+                ),
+                draw: vrf_draw(&self.features, state, distributor_state),
+                native_unwrap: native_unwrap(state, distributor_state),
+                blocklist: blocklist(state, distributor_state),
+                previous_round: previous_round(state, distributor_state),
+                vesting_escrow: vesting_escrow(state, distributor_state),
+                fee_account: distributor_state.fee_account(&state.token_program),
+                fee_vault: fee_vault(state, distributor_state),
+                trigger_bonus_account: distributor_state.trigger_bonus_account(&state.token_program),
+                committed_draw: committed_draw(state, distributor_state),
+                holder_snapshot: holder_snapshot(state, distributor_state),
+                system_program: solana_sdk::system_program::ID,
+                token_program: state.token_program,
+                associated_token_program: spl_associated_token_account::ID,
+                event_authority: event_authority(),
+                program: distributor::ID,
+            })
+            .accounts(remaining_accounts)
+            .accounts(state.transfer_hook_accounts.clone());
+        // the request isn't `Send`, it can't outlive the statement
+        let ixns = if distributor_state.distribution_mode == DistributionMode::Dividend {
+            request
+                .args(distributor::instruction::DistributeDividend {
+                    expected_round: distributor_state.round,
+                })
+                .instructions()
+        } else if self.features.distribute_v2 {
+            request
+                .args(distributor::instruction::DistributeV2 {
+                    args: DistributeArgs {
+                        expected_round: distributor_state.round,
+                        pro_rata: self.features.pro_rata,
+                        ..Default::default()
+                    },
+                })
+                .instructions()
+        } else {
+            request
+                .args(distributor::instruction::Distribute { notes: vec![] })
+                .instructions()
+        }
+        .context("Failed to create distribute instructions")?;
+        let ixns = with_trigger_bonus_account(state, distributor_state, ixns);
+        let lookup_tables = match &state.lookup_table {
+            Some(lookup_table) if preview => lookup_table.current(state).await?.into_iter().collect(),
+            Some(lookup_table) => vec![lookup_table.prepare(state, &ixns).await?],
+            None => Vec::new(),
+        };
+
+        Ok(RoundTransactions {
+            transactions: vec![
+                sign_transaction(
+                    state,
+                    &ixns,
+                    compute_unit_price,
+                    &lookup_tables,
+                    state.nonce_account.as_ref(),
+                )
+                .await?,
+            ],
+            token_accounts: token_accounts(state, distributor_state, winners),
+        })
+    }
+
+    /// The pro-rata rounds split the paid shares by the marker balances of the winners the way `distribute_v2` does,
+    /// the dividend rounds split the vault balance by them the way `distribute_dividend` does
+    async fn amounts(
+        &self,
+        state: &AppState,
+        distributor_state: &DistributorState,
+        winners: &[Winner],
+    ) -> anyhow::Result<Vec<u64>> {
+        let dividend = distributor_state.distribution_mode == DistributionMode::Dividend;
+        let pro_rata = self.features.distribute_v2 && self.features.pro_rata;
+        if !dividend && !pro_rata {
+            return Ok(vec![distributor_state.share_size; winners.len()]);
+        }
+        let marker_accounts = winners
+            .iter()
+            .map(|winner| get_associated_token_address(&winner.wallet, &distributor_state.marker_mint))
+            .collect::<Vec<_>>();
+        let balances = fetch_token_balances(state, &marker_accounts).await?;
+        let (amounts, _) = if dividend {
+            let vault_amount = fetch_token_balances(state, &[distributor_state.vault]).await?[0];
+            distributor_state
+                .split_dividend(vault_amount, &balances)
+                .context("Failed to split the vault balance by the marker balances")?
+        } else {
+            distributor_state
+                .split_weighted(&balances)
+                .context("Failed to split the shares by the marker balances")?
+        };
+        Ok(amounts)
+    }
+}
+
+/// Winners are paid by several transactions of one round: `begin_round`, a `distribute_chunk` per
+/// `CHUNK_WINNERS` winners and `finalize_round`. A round which is already in progress is resumed from the first
+/// unpaid share, the winners of the paid shares are skipped
+pub struct ChunkedDistributor {
+    features: Features,
+}
+
+impl ChunkedDistributor {
+    /// Winners of a chunk without the marker token accounts, the same transaction size as `distribute` has
+    const CHUNK_WINNERS: usize = 8;
+    /// Winners of a chunk with the marker token accounts
+    const CHUNK_WINNERS_WITH_MARKER: usize = 5;
+    /// Winners of a chunk with the NFT token and metadata accounts of the marker collection
+    const CHUNK_WINNERS_WITH_COLLECTION: usize = 3;
+
+    fn chunk_winners(distributor_state: &DistributorState) -> usize {
+        if distributor_state.marker_collection.is_some() {
+            Self::CHUNK_WINNERS_WITH_COLLECTION
+        } else if distributor_state.required_marker_balance().is_some() {
+            Self::CHUNK_WINNERS_WITH_MARKER
+        } else {
+            Self::CHUNK_WINNERS
+        }
+    }
+
+    async fn fetch_progress(
+        state: &AppState,
+        distributor_state: &DistributorState,
+    ) -> anyhow::Result<Option<RoundProgress>> {
+        let address =
+            distributor::client::round_progress_address(&state.distributor_state_pubkey, distributor_state.round);
+        let Some(account) = state
+            .program
+            .async_rpc()
+            .get_account_with_commitment(&address, CommitmentConfig::confirmed())
+            .await
+            .context("Failed to fetch round progress")?
+            .value
+        else {
+            return Ok(None);
+        };
+        let progress = distributor::client::round_progress(&address, &account.owner, &account.data)
+            .context("Invalid round progress account")?;
+        Ok(Some(progress))
+    }
+}
+
+#[async_trait]
+impl Distributor for ChunkedDistributor {
+    async fn build(
+        &self,
+        state: &AppState,
+        distributor_state: &DistributorState,
+        winners: &[Winner],
+        compute_unit_price: u64,
+        _preview: bool,
+    ) -> anyhow::Result<RoundTransactions> {
+        let progress = Self::fetch_progress(state, distributor_state).await?;
+        let (distributor_authority, service_signer) = round_signers(state, distributor_state)?;
+        let round_progress =
+            distributor::client::round_progress_address(&state.distributor_state_pubkey, distributor_state.round);
+        let round = records_round(&self.features, distributor_state).then(|| {
+            distributor::client::distribution_round_address(&state.distributor_state_pubkey, distributor_state.round)
+        });
+
+        let mut transactions = Vec::new();
+        let paid = match &progress {
+            Some(progress) => {
+                ensure!(
+                    progress.paid_shares() == winners.len() as u64,
+                    "Round in progress has {} winners, {} were drawn",
+                    progress.paid_shares(),
+                    winners.len()
+                );
+                tracing::info!(paid = %progress.paid, "Resuming the round in progress");
+                progress.paid as usize
+            },
+            None => {
+                let ixns = state
+                    .program
+                    .request()
+                    .instruction(spl_memo::build_memo(state.memo.as_bytes(), &[]))
+                    .accounts(distributor::accounts::BeginRound {
+                        payer: state.payer.pubkey(),
+                        distributor_authority,
+                        service_signer,
+                        distributor_state: state.distributor_state_pubkey,
+                        vault: distributor_state.vault,
+                        round_progress,
+                        round,
+                        fee_vault: fee_vault(state, distributor_state),
+                        system_program: solana_sdk::system_program::ID,
+                    })
+                    .args(distributor::instruction::BeginRound {})
+                    .instructions()
+                    .context("Failed to create begin round instructions")?;
+                transactions.push(sign_transaction(state, &ixns, compute_unit_price, &[], None).await?);
+                0
+            },
+        };
+
+        let chunk_winners = Self::chunk_winners(distributor_state);
+        let payees = payees(state, distributor_state, &winners[paid..]).await?;
+        let collection_nfts = collection_nfts(state, distributor_state, &winners[paid..]).await?;
+        for (((chunk, payees), nfts), first_share) in winners[paid..]
+            .chunks(chunk_winners)
+            .zip(payees.chunks(chunk_winners))
+            .zip(collection_nfts.chunks(chunk_winners))
+            .zip((paid..).step_by(chunk_winners))
+        {
+            let remaining_accounts = chunk
+                .iter()
+                .zip(payees)
+                .zip(nfts)
+                .flat_map(|((winner, payee), nft)| {
+                    winner_accounts(state, distributor_state, &winner.wallet, *payee, *nft)
+                })
+                .collect::<Vec<_>>();
+            let ixns = state
+                .program
+                .request()
+                .accounts(distributor::accounts::DistributeChunk {
+                    payer: state.payer.pubkey(),
+                    distributor_authority,
+                    service_signer,
+                    distributor_state: state.distributor_state_pubkey,
+                    mint: distributor_state.mint,
+                    vault: distributor_state.vault,
+                    round_progress,
+                    round,
+                    native_unwrap: native_unwrap(state, distributor_state),
+                    blocklist: blocklist(state, distributor_state),
+                    previous_round: previous_round(state, distributor_state),
+                    vesting_escrow: vesting_escrow(state, distributor_state),
+                    system_program: solana_sdk::system_program::ID,
+                    token_program: state.token_program,
+                    associated_token_program: spl_associated_token_account::ID,
+                    event_authority: event_authority(),
+                    program: distributor::ID,
+                })
+                .accounts(remaining_accounts)
+                .accounts(state.transfer_hook_accounts.clone())
+                .args(distributor::instruction::DistributeChunk {
+                    first_share: first_share as u64,
+                    notes: vec![],
+                })
+                .instructions()
+                .context("Failed to create distribute chunk instructions")?;
+            transactions.push(sign_transaction(state, &ixns, compute_unit_price, &[], None).await?);
+        }
+
+        let ixns = state
+            .program
+            .request()
+            .accounts(distributor::accounts::FinalizeRound {
+                payer: progress.map_or(state.payer.pubkey(), |progress| progress.payer),
+                distributor_authority,
+                service_signer,
+                distributor_state: state.distributor_state_pubkey,
+                mint: distributor_state.mint,
+                vault: distributor_state.vault,
+                treasury: distributor_state.remainder_policy.treasury(),
+                round_progress,
+                round,
+                draw: vrf_draw(&self.features, state, distributor_state),
+                fee_account: distributor_state.fee_account(&state.token_program),
+                trigger_bonus_account: distributor_state.trigger_bonus_account(&state.token_program),
+                token_program: state.token_program,
+                event_authority: event_authority(),
+                program: distributor::ID,
+            })
+            .accounts(state.transfer_hook_accounts.clone())
+            .args(distributor::instruction::FinalizeRound {})
+            .instructions()
+            .context("Failed to create finalize round instructions")?;
+        let ixns = with_trigger_bonus_account(state, distributor_state, ixns);
+        transactions.push(sign_transaction(state, &ixns, compute_unit_price, &[], None).await?);
+
+        Ok(RoundTransactions {
+            transactions,
+            token_accounts: token_accounts(state, distributor_state, winners),
+        })
+    }
+
+    async fn round_in_progress(
+        &self,
+        state: &AppState,
+        distributor_state: &DistributorState,
+    ) -> anyhow::Result<Option<u64>> {
+        Ok(Self::fetch_progress(state, distributor_state)
+            .await?
+            .map(|progress| progress.paid))
+    }
+}
+
+/// The winners shares are escrowed by `distribute_claims` and the winners claim them with the published proofs
+pub struct ClaimDistributor {
+    features: Features,
+}
+
+impl ClaimDistributor {
+    /// Attempts to store the claims of a confirmed round before its proofs are given up
+    const MAX_STORE_ATTEMPTS: u32 = 5;
+    /// Delay before the first retry, every further one waits longer
+    const STORE_RETRY_DELAY: Duration = Duration::from_secs(2);
+}
+
+#[async_trait]
+impl Distributor for ClaimDistributor {
+    async fn build(
+        &self,
+        state: &AppState,
+        distributor_state: &DistributorState,
+        winners: &[Winner],
+        compute_unit_price: u64,
+        _preview: bool,
+    ) -> anyhow::Result<RoundTransactions> {
+        let (merkle_root, _) = claim_entries(
+            &state.distributor_state_pubkey,
+            distributor_state.round,
+            winners,
+            distributor_state.share_size,
+        );
+        let claim_round =
+            distributor::client::claim_round_address(&state.distributor_state_pubkey, distributor_state.round);
+        let (distributor_authority, service_signer) = round_signers(state, distributor_state)?;
+
+        let ixns = state
+            .program
+            .request()
+            .instruction(spl_memo::build_memo(state.memo.as_bytes(), &[]))
+            .accounts(distributor::accounts::DistributeClaims {
+                payer: state.payer.pubkey(),
+                distributor_authority,
+                service_signer,
+                distributor_state: state.distributor_state_pubkey,
+                mint: distributor_state.mint,
+                vault: distributor_state.vault,
+                treasury: distributor_state.remainder_policy.treasury(),
+                claim_round,
+                escrow: distributor::client::escrow_address(&claim_round),
+                round: records_round(&self.features, distributor_state).then(|| {
+                    distributor::client::distribution_round_address(
+                        &state.distributor_state_pubkey,
+                        distributor_state.round,
+                    )
+                }),
+                round_progress: distributor::client::round_progress_address(
+                    &state.distributor_state_pubkey,
+                    distributor_state.round,
+                ),
+                draw: vrf_draw(&self.features, state, distributor_state),
+                fee_account: distributor_state.fee_account(&state.token_program),
+                fee_vault: fee_vault(state, distributor_state),
+                trigger_bonus_account: distributor_state.trigger_bonus_account(&state.token_program),
+                system_program: solana_sdk::system_program::ID,
+                token_program: state.token_program,
+                event_authority: event_authority(),
+                program: distributor::ID,
+            })
+            .accounts(state.transfer_hook_accounts.clone())
+            .args(distributor::instruction::DistributeClaims { merkle_root })
+            .instructions()
+            .context("Failed to create distribute claims instructions")?;
+        let ixns = with_trigger_bonus_account(state, distributor_state, ixns);
+
+        Ok(RoundTransactions {
+            transactions: vec![sign_transaction(state, &ixns, compute_unit_price, &[], None).await?],
+            token_accounts: vec![],
+        })
+    }
+
+    async fn publish(
+        &self,
+        state: &AppState,
+        distributor_state: &DistributorState,
+        winners: &[Winner],
+    ) -> anyhow::Result<()> {
+        // `distributor_state` is fetched before the round, so its round is the one committed
+        let (_, entries) = claim_entries(
+            &state.distributor_state_pubkey,
+            distributor_state.round,
+            winners,
+            distributor_state.share_size,
+        );
+        // the round is committed on-chain already, the winners can't claim without the proofs
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            match store_claims(&state.pool, &entries).await {
+                Ok(()) => return Ok(()),
+                Err(err) if attempt < Self::MAX_STORE_ATTEMPTS => {
+                    tracing::warn!(%err, attempt, "Failed to store the claims, retrying");
+                    tokio::time::sleep(Self::STORE_RETRY_DELAY * attempt).await;
+                },
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+/// Whether each of the winners passes the checks of `distribute` for the amount it is paid, in the winners order. The
+/// `validate_winners` view is simulated with the remaining accounts of the winners in chunks of `distribute_chunk`,
+/// it can't be sent
+pub async fn validate_winners(
+    state: &AppState,
+    distributor_state: &DistributorState,
+    winners: &[Winner],
+    amounts: &[u64],
+) -> anyhow::Result<Vec<bool>> {
+    ensure!(
+        winners.len() == amounts.len(),
+        "{} winners are paid {} amounts",
+        winners.len(),
+        amounts.len()
+    );
+    let chunk_winners = ChunkedDistributor::chunk_winners(distributor_state);
+    let config = RpcSimulateTransactionConfig {
+        sig_verify: false,
+        replace_recent_blockhash: true,
+        ..Default::default()
+    };
+    let rpc_client = state.program.async_rpc();
+    let payees = payees(state, distributor_state, winners).await?;
+    let collection_nfts = collection_nfts(state, distributor_state, winners).await?;
+
+    let mut valid = Vec::with_capacity(winners.len());
+    for (((chunk, payees), nfts), amounts) in winners
+        .chunks(chunk_winners)
+        .zip(payees.chunks(chunk_winners))
+        .zip(collection_nfts.chunks(chunk_winners))
+        .zip(amounts.chunks(chunk_winners))
+    {
+        // the view writes nothing, the payout accounts are read-only
+        let remaining_accounts = chunk
+            .iter()
+            .zip(payees)
+            .zip(nfts)
+            .flat_map(|((winner, payee), nft)| winner_accounts(state, distributor_state, &winner.wallet, *payee, *nft))
+            .map(|meta| AccountMeta::new_readonly(meta.pubkey, false))
+            .collect::<Vec<_>>();
+        let ixns = state
+            .program
+            .request()
+            .accounts(distributor::accounts::ValidateWinners {
+                distributor_state: state.distributor_state_pubkey,
+                mint: distributor_state.mint,
+                blocklist: blocklist(state, distributor_state),
+                previous_round: previous_round(state, distributor_state),
+                token_program: state.token_program,
+            })
+            .accounts(remaining_accounts)
+            .args(distributor::instruction::ValidateWinners {
+                amounts: Some(amounts.to_vec()),
+            })
+            .instructions()
+            .context("Failed to create validate winners instructions")?;
+        let tx = Transaction::new_with_payer(&ixns, Some(&state.payer.pubkey()));
+
+        let simulation = rpc_client
+            .simulate_transaction_with_config(&tx, config.clone())
+            .await
+            .context("Failed to simulate validate winners")?
+            .value;
+        if let Some(err) = simulation.err {
+            let failure = ProgramFailure::new(&err, simulation.logs.as_deref().unwrap_or_default());
+            return Err(anyhow!(failure).context("Validate winners simulation failed"));
+        }
+        let return_data = simulation
+            .return_data
+            .context("Validate winners simulation returned no data")?;
+        let data = BASE64_STANDARD
+            .decode(return_data.data.0)
+            .context("Invalid validate winners return data")?;
+        let bitmap = Vec::<u8>::deserialize(&mut data.as_slice()).context("Invalid validate winners bitmap")?;
+        valid.extend(valid_winners(&bitmap, chunk.len()));
+    }
+    Ok(valid)
+}
+
+/// Extra payout mints of the distributor, see `distribute_extra_mint`
+pub async fn fetch_vault_configs(state: &AppState) -> anyhow::Result<Vec<(Pubkey, VaultConfig)>> {
+    let vault_configs = state
+        .program
+        .accounts::<VaultConfig>(vec![RpcFilterType::Memcmp(Memcmp::new_base58_encoded(
+            8,
+            state.distributor_state_pubkey.as_ref(),
+        ))])
+        .await
+        .context("Failed to fetch vault configs")?;
+    for (address, vault_config) in &vault_configs {
+        ensure!(
+            *address == distributor::client::vault_config_address(&state.distributor_state_pubkey, &vault_config.mint),
+            "Invalid vault config account {address}"
+        );
+    }
+    Ok(vault_configs)
+}
+
+/// Extra payout mint config at `address`, to continue with after paying a part of its rounds
+pub async fn fetch_vault_config(state: &AppState, address: &Pubkey) -> anyhow::Result<VaultConfig> {
+    state
+        .program
+        .account::<VaultConfig>(*address)
+        .await
+        .context("Failed to fetch vault config")
+}
+
+/// `distribute_extra_mint` transactions paying the extra mint to the recorded winners of the round, starting at the
+/// first winner the vault didn't pay yet
+pub async fn extra_mint_transactions(
+    state: &AppState,
+    distributor_state: &DistributorState,
+    vault_config_address: Pubkey,
+    vault_config: &VaultConfig,
+    round: &DistributionRound,
+    compute_unit_price: u64,
+) -> anyhow::Result<Vec<VersionedTransaction>> {
+    let paid = match vault_config.last_round {
+        Some(last_round) if last_round == round.round => vault_config.paid as usize,
+        _ => 0,
+    };
+    let round_address = distributor::client::distribution_round_address(&state.distributor_state_pubkey, round.round);
+    let (distributor_authority, service_signer) = round_signers(state, distributor_state)?;
+    let rpc_client = state.program.async_rpc();
+    let mint = rpc_client
+        .get_account(&vault_config.mint)
+        .await
+        .context("Failed to fetch extra mint")?;
+    let decimals = StateWithExtensions::<Mint>::unpack(&mint.data)
+        .context("Invalid extra mint")?
+        .base
+        .decimals;
+    let hook_accounts = transfer_hook_accounts(
+        &rpc_client,
+        &vault_config.mint,
+        &vault_config.vault,
+        &state.distributor_state_pubkey,
+        decimals,
+    )
+    .await?;
+
+    let mut transactions = Vec::new();
+    for (chunk, first_payout) in round.payouts[paid.min(round.payouts.len())..]
+        .chunks(ChunkedDistributor::CHUNK_WINNERS)
+        .zip((paid..).step_by(ChunkedDistributor::CHUNK_WINNERS))
+    {
+        let remaining_accounts = chunk
+            .iter()
+            .flat_map(|payout| {
+                [
+                    AccountMeta::new_readonly(payout.wallet, false),
+                    AccountMeta::new(
+                        get_associated_token_address_with_program_id(&payout.wallet, &vault_config.mint, &mint.owner),
+                        false,
+                    ),
+                ]
+            })
+            .collect::<Vec<_>>();
+        let ixns = state
+            .program
+            .request()
+            .accounts(distributor::accounts::DistributeExtraMint {
+                payer: state.payer.pubkey(),
+                distributor_authority,
+                service_signer,
+                distributor_state: state.distributor_state_pubkey,
+                vault_config: vault_config_address,
+                mint: vault_config.mint,
+                vault: vault_config.vault,
+                round: round_address,
+                system_program: solana_sdk::system_program::ID,
+                token_program: mint.owner,
+                associated_token_program: spl_associated_token_account::ID,
+                event_authority: event_authority(),
+                program: distributor::ID,
+            })
+            .accounts(remaining_accounts)
+            .accounts(hook_accounts.clone())
+            .args(distributor::instruction::DistributeExtraMint {
+                first_payout: first_payout as u64,
+            })
+            .instructions()
+            .context("Failed to create distribute extra mint instructions")?;
+        transactions.push(sign_transaction(state, &ixns, compute_unit_price, &[], None).await?);
+    }
+    Ok(transactions)
+}
+
+/// Requests the VRF randomness of `seed` from ORAO and binds the current round to it by `request_draw` in the same
+/// transaction. `entrants` is the holders count the winner indices are drawn from
+pub async fn request_draw_transaction(
+    state: &AppState,
+    distributor_state: &DistributorState,
+    seed: [u8; 32],
+    entrants: u64,
+    compute_unit_price: u64,
+) -> anyhow::Result<VersionedTransaction> {
+    let network_state = Pubkey::find_program_address(&[VRF_NETWORK_STATE_SEED], &distributor::vrf::ID).0;
+    let data = state
+        .program
+        .async_rpc()
+        .get_account_data(&network_state)
+        .await
+        .context("Failed to fetch VRF network state")?;
+    // discriminator, authority and treasury of the network configuration
+    let treasury = data
+        .get(40..72)
+        .and_then(|treasury| Pubkey::try_from(treasury).ok())
+        .context("Invalid VRF network state")?;
+    let randomness = Pubkey::find_program_address(&[distributor::vrf::RANDOMNESS_SEED, &seed], &distributor::vrf::ID).0;
+    let (distributor_authority, service_signer) = round_signers(state, distributor_state)?;
+    let request = Instruction {
+        program_id: distributor::vrf::ID,
+        accounts: vec![
+            AccountMeta::new(state.payer.pubkey(), true),
+            AccountMeta::new(network_state, false),
+            AccountMeta::new(treasury, false),
+            AccountMeta::new(randomness, false),
+            AccountMeta::new_readonly(solana_sdk::system_program::ID, false),
+        ],
+        data: [&hash(b"global:request").to_bytes()[..8], &seed[..]].concat(),
+    };
+
+    let ixns = state
+        .program
+        .request()
+        .instruction(request)
+        .accounts(distributor::accounts::RequestDraw {
+            payer: state.payer.pubkey(),
+            distributor_authority,
+            service_signer,
+            distributor_state: state.distributor_state_pubkey,
+            randomness,
+            holder_snapshot: distributor_state.requires_vrf_draw.then(|| {
+                distributor::client::holder_snapshot_address(&state.distributor_state_pubkey, distributor_state.round)
+            }),
+            draw: distributor::client::draw_address(&state.distributor_state_pubkey, distributor_state.round),
+            system_program: solana_sdk::system_program::ID,
+        })
+        .args(distributor::instruction::RequestDraw { seed, entrants })
+        .instructions()
+        .context("Failed to create request draw instructions")?;
+    sign_transaction(state, &ixns, compute_unit_price, &[], None).await
+}
+
+/// Copies the fulfilled VRF randomness to the draw of the round
+pub async fn reveal_draw_transaction(
+    state: &AppState,
+    draw: Pubkey,
+    randomness: Pubkey,
+    compute_unit_price: u64,
+) -> anyhow::Result<VersionedTransaction> {
+    let ixns = state
+        .program
+        .request()
+        .accounts(distributor::accounts::RevealDraw { draw, randomness })
+        .args(distributor::instruction::RevealDraw {})
+        .instructions()
+        .context("Failed to create reveal draw instructions")?;
+    sign_transaction(state, &ixns, compute_unit_price, &[], None).await
+}
+
+/// Account the share of a winner is paid to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Payee {
+    /// Associated token account of the winner
+    TokenAccount,
+    /// New vesting account of the winner, its share is vested or too large to be paid without its acceptance
+    Vesting,
+    /// New escrowed share of the winner whose token account is frozen, it is claimed later
+    Escrow,
+}
+
+/// Payees of the winners in the winners order, the escrow of the distributor takes precedence like in `distribute`
+async fn payees(
+    state: &AppState,
+    distributor_state: &DistributorState,
+    winners: &[Winner],
+) -> anyhow::Result<Vec<Payee>> {
+    let acceptances = large_payout_acceptances(state, distributor_state, winners).await?;
+    let frozen = frozen_token_accounts(state, distributor_state, winners).await?;
+    Ok(acceptances
+        .into_iter()
+        .zip(frozen)
+        .map(|(accepts, frozen)| {
+            if frozen && distributor_state.escrow_claim_period.is_some() {
+                Payee::Escrow
+            } else if distributor_state.vesting_duration.is_some() || !accepts {
+                Payee::Vesting
+            } else {
+                Payee::TokenAccount
+            }
+        })
+        .collect())
+}
+
+/// Whether the token account of every winner is frozen, checked only while the distributor escrows such shares
+async fn frozen_token_accounts(
+    state: &AppState,
+    distributor_state: &DistributorState,
+    winners: &[Winner],
+) -> anyhow::Result<Vec<bool>> {
+    if distributor_state.escrow_claim_period.is_none() || distributor_state.is_native() {
+        return Ok(vec![false; winners.len()]);
+    }
+    let rpc_client = state.program.async_rpc();
+    let mut frozen = Vec::with_capacity(winners.len());
+    // the RPC returns up to 100 accounts at once
+    for winners in winners.chunks(100) {
+        let addresses = winners
+            .iter()
+            .map(|winner| {
+                get_associated_token_address_with_program_id(
+                    &winner.wallet,
+                    &distributor_state.mint,
+                    &state.token_program,
+                )
+            })
+            .collect::<Vec<_>>();
+        let accounts = rpc_client
+            .get_multiple_accounts(&addresses)
+            .await
+            .context("Failed to fetch winners token accounts")?;
+        frozen.extend(accounts.iter().map(|account| {
+            account
+                .as_ref()
+                .and_then(|account| StateWithExtensions::<TokenAccount>::unpack(&account.data).ok())
+                .is_some_and(|token_account| token_account.base.state == AccountState::Frozen)
+        }));
+    }
+    Ok(frozen)
+}
+
+/// Payout accounts of a winner by its payee, followed by the accounts of its `collection_nft`
+fn winner_accounts(
+    state: &AppState,
+    distributor_state: &DistributorState,
+    wallet: &Pubkey,
+    payee: Payee,
+    collection_nft: Option<CollectionNft>,
+) -> Vec<AccountMeta> {
+    let marker_account = distributor_state.required_marker_balance().map(|_| {
+        AccountMeta::new_readonly(
+            get_associated_token_address(wallet, &distributor_state.marker_mint),
+            false,
+        )
+    });
+    // native shares are credited to the wallet itself
+    let payout_accounts = if distributor_state.is_native() {
+        vec![AccountMeta::new(*wallet, false)]
+    } else {
+        let payout_account = match payee {
+            Payee::TokenAccount => {
+                get_associated_token_address_with_program_id(wallet, &distributor_state.mint, &state.token_program)
+            },
+            Payee::Vesting => {
+                distributor::client::vesting_address(&state.distributor_state_pubkey, distributor_state.round, wallet)
+            },
+            Payee::Escrow => distributor::client::escrowed_share_address(
+                &state.distributor_state_pubkey,
+                distributor_state.round,
+                wallet,
+            ),
+        };
+        vec![
+            AccountMeta::new_readonly(*wallet, false),
+            AccountMeta::new(payout_account, false),
+        ]
+    };
+    let payout_acceptance = distributor_state
+        .large_payout_threshold
+        .map(|_| AccountMeta::new_readonly(distributor::client::payout_acceptance_address(wallet), false));
+    let collection_accounts = collection_nft.into_iter().flat_map(|nft| {
+        [
+            AccountMeta::new_readonly(nft.token_account, false),
+            AccountMeta::new_readonly(nft.metadata, false),
+        ]
+    });
+    payout_accounts
+        .into_iter()
+        .chain(marker_account)
+        .chain(collection_accounts)
+        .chain(payout_acceptance)
+        .collect()
+}
+
+/// NFT of the marker collection held by a winner, its token account and metadata account prove the membership
+#[derive(Debug, Clone, Copy, Default)]
+struct CollectionNft {
+    token_account: Pubkey,
+    metadata: Pubkey,
+}
+
+/// NFTs of the marker collection held by the winners in the winners order, `None` for every winner if the
+/// distributor has no collection. A winner holding none gets the default accounts, the program rejects them like it
+/// rejects a winner which isn't a collection holder
+async fn collection_nfts(
+    state: &AppState,
+    distributor_state: &DistributorState,
+    winners: &[Winner],
+) -> anyhow::Result<Vec<Option<CollectionNft>>> {
+    let Some(collection) = distributor_state.marker_collection else {
+        return Ok(vec![None; winners.len()]);
+    };
+    let rpc_client = state.program.async_rpc();
+    let mut nfts = Vec::with_capacity(winners.len());
+    for winner in winners {
+        let config = RpcProgramAccountsConfig {
+            filters: Some(vec![
+                RpcFilterType::DataSize(spl_token::state::Account::LEN as u64),
+                // owner of the token account
+                RpcFilterType::Memcmp(Memcmp::new_base58_encoded(32, winner.wallet.as_ref())),
+            ]),
+            account_config: RpcAccountInfoConfig {
+                encoding: Some(UiAccountEncoding::Base64),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let held = rpc_client
+            .get_program_accounts_with_config(&spl_token::ID, config)
+            .await
+            .context("Failed to fetch winner token accounts")?
+            .into_iter()
+            .filter_map(|(address, account)| {
+                let token_account = spl_token::state::Account::unpack(&account.data).ok()?;
+                (token_account.amount > 0).then_some((address, token_account.mint))
+            })
+            .collect::<Vec<_>>();
+
+        let mut nft = CollectionNft::default();
+        // the RPC returns up to 100 accounts at once
+        for held in held.chunks(100) {
+            let metadata_addresses = held
+                .iter()
+                .map(|(_, mint)| distributor::metadata::metadata_address(mint))
+                .collect::<Vec<_>>();
+            let metadata_accounts = rpc_client
+                .get_multiple_accounts(&metadata_addresses)
+                .await
+                .context("Failed to fetch NFT metadata accounts")?;
+            let member =
+                held.iter()
+                    .zip(metadata_addresses)
+                    .zip(metadata_accounts)
+                    .find(|(((_, mint), _), account)| {
+                        account.as_ref().is_some_and(|account| {
+                            account.owner == distributor::metadata::ID
+                                && distributor::metadata::verified_collection(&account.data, mint) == Some(collection)
+                        })
+                    });
+            if let Some((((token_account, _), metadata), _)) = member {
+                nft = CollectionNft {
+                    token_account: *token_account,
+                    metadata,
+                };
+                break;
+            }
+        }
+        nfts.push(Some(nft));
+    }
+    Ok(nfts)
+}
+
+/// Whether the shares of the round exceed the large payout threshold
+fn large_shares(distributor_state: &DistributorState) -> bool {
+    distributor_state
+        .large_payout_threshold
+        .is_some_and(|threshold| distributor_state.share_size > threshold)
+}
+
+/// Whether the winners accepted large payouts in the winners order, everyone does if the shares aren't large
+async fn large_payout_acceptances(
+    state: &AppState,
+    distributor_state: &DistributorState,
+    winners: &[Winner],
+) -> anyhow::Result<Vec<bool>> {
+    if !large_shares(distributor_state) {
+        return Ok(vec![true; winners.len()]);
+    }
+    let rpc_client = state.program.async_rpc();
+    let mut acceptances = Vec::with_capacity(winners.len());
+    // the RPC returns up to 100 accounts at once
+    for winners in winners.chunks(100) {
+        let addresses = winners
+            .iter()
+            .map(|winner| distributor::client::payout_acceptance_address(&winner.wallet))
+            .collect::<Vec<_>>();
+        let accounts = rpc_client
+            .get_multiple_accounts(&addresses)
+            .await
+            .context("Failed to fetch payout acceptances")?;
+        acceptances.extend(accounts.iter().map(Option::is_some));
+    }
+    Ok(acceptances)
+}
+
+/// Balances of the token accounts of either token program in their order, zero for the missing ones
+async fn fetch_token_balances(state: &AppState, token_accounts: &[Pubkey]) -> anyhow::Result<Vec<u64>> {
+    let rpc_client = state.program.async_rpc();
+    let mut balances = Vec::with_capacity(token_accounts.len());
+    // the RPC returns up to 100 accounts at once
+    for token_accounts in token_accounts.chunks(100) {
+        let accounts = rpc_client
+            .get_multiple_accounts(token_accounts)
+            .await
+            .context("Failed to fetch token accounts")?;
+        for account in accounts {
+            let balance = match account {
+                Some(account) => {
+                    StateWithExtensions::<TokenAccount>::unpack(&account.data)
+                        .context("Invalid token account")?
+                        .base
+                        .amount
+                },
+                None => 0,
+            };
+            balances.push(balance);
+        }
+    }
+    Ok(balances)
+}
+
+/// Prepends the creation of the token account of the depositor who triggered the round to `ixns` while a bonus is
+/// due, the program requires the account to pay the bonus. The payer funds the account if it's missing
+fn with_trigger_bonus_account(
+    state: &AppState,
+    distributor_state: &DistributorState,
+    ixns: Vec<Instruction>,
+) -> Vec<Instruction> {
+    let create = distributor_state
+        .trigger_bonus
+        .and(distributor_state.last_depositor)
+        .map(|depositor| {
+            create_associated_token_account_idempotent(
+                &state.payer.pubkey(),
+                &depositor,
+                &distributor_state.mint,
+                &state.token_program,
+            )
+        });
+    create.into_iter().chain(ixns).collect()
+}
+
+fn token_accounts(state: &AppState, distributor_state: &DistributorState, winners: &[Winner]) -> Vec<Pubkey> {
+    if distributor_state.is_native() || distributor_state.vesting_duration.is_some() || large_shares(distributor_state)
+    {
+        return Vec::new();
+    }
+    winners
+        .iter()
+        .map(|winner| {
+            get_associated_token_address_with_program_id(&winner.wallet, &distributor_state.mint, &state.token_program)
+        })
+        .collect()
+}
+
+/// Extra accounts of the Token-2022 transfer hook of `mint`, passed after the winners of the round transactions:
+/// the hook program, its extra account metas account and the extra accounts they list. Empty if the mint has no
+/// hook. The extra accounts are resolved for a transfer out of `vault`, a hook whose extra accounts depend on the
+/// destination of a transfer isn't supported
+pub async fn transfer_hook_accounts(
+    rpc_client: &RpcClient,
+    mint: &Pubkey,
+    vault: &Pubkey,
+    authority: &Pubkey,
+    decimals: u8,
+) -> anyhow::Result<Vec<AccountMeta>> {
+    let mint_account = rpc_client.get_account(mint).await.context("Failed to fetch mint")?;
+    if mint_account.owner != spl_token_2022::ID {
+        return Ok(Vec::new());
+    }
+    let mint_state = StateWithExtensions::<Mint>::unpack(&mint_account.data).context("Invalid mint")?;
+    let Some(hook_program) = transfer_hook::get_program_id(&mint_state) else {
+        return Ok(Vec::new());
+    };
+    let mut transfer = spl_token_2022::instruction::transfer_checked(
+        &spl_token_2022::ID,
+        vault,
+        mint,
+        vault,
+        authority,
+        &[],
+        0,
+        decimals,
+    )
+    .context("Failed to create transfer instruction")?;
+    let base_accounts = transfer.accounts.len();
+    resolve_extra_transfer_account_metas(
+        &mut transfer,
+        |address| async move {
+            let account = rpc_client
+                .get_account_with_commitment(&address, CommitmentConfig::confirmed())
+                .await?
+                .value;
+            Ok(account.map(|account| account.data))
+        },
+        mint,
+    )
+    .await
+    .map_err(|err| anyhow!("Failed to resolve transfer hook accounts: {err}"))?;
+    // the program finds the hook accounts of a chunk by the hook program, it goes first
+    let extra_accounts = transfer
+        .accounts
+        .split_off(base_accounts)
+        .into_iter()
+        .filter(|meta| meta.pubkey != hook_program)
+        .map(|meta| AccountMeta {
+            is_signer: false,
+            ..meta
+        });
+    Ok(std::iter::once(AccountMeta::new_readonly(hook_program, false))
+        .chain(extra_accounts)
+        .collect())
+}
+
+/// Draw of the current round committing its holders count, rounds without a VRF draw have none. `distribute` accepts
+/// only its verified winners while the distributor requires one
+fn vrf_draw(features: &Features, state: &AppState, distributor_state: &DistributorState) -> Option<Pubkey> {
+    (features.vrf_draw || distributor_state.requires_vrf_draw)
+        .then(|| distributor::client::draw_address(&state.distributor_state_pubkey, distributor_state.round))
+}
+
+fn native_unwrap(state: &AppState, distributor_state: &DistributorState) -> Option<Pubkey> {
+    distributor_state
+        .is_native()
+        .then(|| distributor::client::native_unwrap_address(&state.distributor_state_pubkey))
+}
+
+/// Committed draw of the current round, its verified winners are the only ones `distribute` accepts
+fn committed_draw(state: &AppState, distributor_state: &DistributorState) -> Option<Pubkey> {
+    distributor_state
+        .requires_committed_draw
+        .then(|| distributor::client::committed_draw_address(&state.distributor_state_pubkey, distributor_state.round))
+}
+
+/// Holders snapshot committed for the current round, `distribute` is bound to it while the distributor requires one.
+/// The VRF draw of a distributor requiring one is drawn from it as well
+fn holder_snapshot(state: &AppState, distributor_state: &DistributorState) -> Option<Pubkey> {
+    (distributor_state.requires_holder_snapshot || distributor_state.requires_vrf_draw)
+        .then(|| distributor::client::holder_snapshot_address(&state.distributor_state_pubkey, distributor_state.round))
+}
+
+fn fee_vault(state: &AppState, distributor_state: &DistributorState) -> Option<Pubkey> {
+    (distributor_state.lamport_fee > 0).then(|| distributor::client::fee_vault_address(&state.distributor_state_pubkey))
+}
+
+fn vesting_escrow(state: &AppState, distributor_state: &DistributorState) -> Option<Pubkey> {
+    (distributor_state.vesting_duration.is_some()
+        || large_shares(distributor_state)
+        || distributor_state.escrow_claim_period.is_some())
+    .then(|| distributor::client::vesting_escrow_address(&state.distributor_state_pubkey))
+}
+
+fn blocklist(state: &AppState, distributor_state: &DistributorState) -> Option<Pubkey> {
+    (distributor_state.blocked_recipients > 0)
+        .then(|| distributor::client::blocklist_address(&state.distributor_state_pubkey))
+}
+
+/// Rounds are recorded if the feature is enabled, or if the next round checks the winners of this one
+fn records_round(features: &Features, distributor_state: &DistributorState) -> bool {
+    features.record_rounds || distributor_state.requires_round_record()
+}
+
+fn previous_round(state: &AppState, distributor_state: &DistributorState) -> Option<Pubkey> {
+    distributor_state
+        .no_repeat_winners_since
+        .filter(|since| distributor_state.round > *since)
+        .map(|_| {
+            distributor::client::distribution_round_address(
+                &state.distributor_state_pubkey,
+                distributor_state.round - 1,
+            )
+        })
+}
+
+/// Signers of the round instructions in the authority and the service signer slots, the service signer takes the
+/// authority slot in its crank mode
+fn round_signers(state: &AppState, distributor_state: &DistributorState) -> anyhow::Result<(Pubkey, Option<Pubkey>)> {
+    let Some(service_signer) = distributor_state.service_signer else {
+        return Ok((state.distributor_authority.pubkey(), None));
+    };
+    let keypair = state
+        .service_signer
+        .as_ref()
+        .context("Distributor requires a service signer, SERVICE_SIGNER_KEYPAIR isn't set")?;
+    ensure!(
+        keypair.pubkey() == service_signer.key,
+        "SERVICE_SIGNER_KEYPAIR {} isn't the service signer {} of the distributor",
+        keypair.pubkey(),
+        service_signer.key
+    );
+    if service_signer.crank {
+        Ok((service_signer.key, None))
+    } else {
+        Ok((state.distributor_authority.pubkey(), Some(service_signer.key)))
+    }
+}
+
+fn event_authority() -> Pubkey {
+    Pubkey::find_program_address(&[b"__event_authority"], &distributor::ID).0
+}
+
+/// Keypairs of the backend signing `message`. Only the keypairs the instructions require can sign, the service
+/// signer replaces the authority in its crank mode
+pub fn transaction_signers<'a>(state: &'a AppState, message: &VersionedMessage) -> Vec<&'a Keypair> {
+    let signer_keys = &message.static_account_keys()[..message.header().num_required_signatures as usize];
+    [Some(&state.distributor_authority), state.service_signer.as_ref()]
+        .into_iter()
+        .flatten()
+        .filter(|keypair| signer_keys.contains(&keypair.pubkey()))
+        .chain([&state.payer])
+        .collect()
+}
+
+/// Legacy message of the instructions, a v0 one looking the accounts up in `lookup_tables` if any are passed
+fn compile_message(
+    payer: &Pubkey,
+    ixns: &[Instruction],
+    lookup_tables: &[AddressLookupTableAccount],
+    blockhash: Hash,
+) -> anyhow::Result<VersionedMessage> {
+    if lookup_tables.is_empty() {
+        return Ok(VersionedMessage::Legacy(Message::new_with_blockhash(
+            ixns,
+            Some(payer),
+            &blockhash,
+        )));
+    }
+    v0::Message::try_compile(payer, ixns, lookup_tables, blockhash)
+        .map(VersionedMessage::V0)
+        .context("Failed to compile v0 message")
+}
+
+/// Compute unit limit for the consumed units of a simulation with `COMPUTE_UNIT_MARGIN_PERCENT` on top
+pub fn compute_unit_limit(units_consumed: u64) -> u32 {
+    let limit = units_consumed.saturating_mul(100 + COMPUTE_UNIT_MARGIN_PERCENT) / 100;
+    limit.min(MAX_COMPUTE_UNIT_LIMIT as u64) as u32
+}
+
+/// Compute unit limit of the instructions measured by a simulation, `FALLBACK_COMPUTE_UNIT_LIMIT` if it fails. The
+/// later transactions of a chunked round fail the simulation as they depend on the earlier ones
+async fn estimate_compute_unit_limit(
+    state: &AppState,
+    ixns: &[Instruction],
+    lookup_tables: &[AddressLookupTableAccount],
+) -> anyhow::Result<u32> {
+    let ixns = [ComputeBudgetInstruction::set_compute_unit_limit(MAX_COMPUTE_UNIT_LIMIT)]
+        .into_iter()
+        .chain(ixns.iter().cloned())
+        .collect::<Vec<_>>();
+    let message = compile_message(&state.payer.pubkey(), &ixns, lookup_tables, Hash::default())?;
+    let tx = VersionedTransaction {
+        signatures: vec![Signature::default(); message.header().num_required_signatures as usize],
+        message,
+    };
+    let config = RpcSimulateTransactionConfig {
+        sig_verify: false,
+        replace_recent_blockhash: true,
+        ..Default::default()
+    };
+    let limit = match state
+        .program
+        .async_rpc()
+        .simulate_transaction_with_config(&tx, config)
+        .await
+    {
+        Ok(response) => match (response.value.err, response.value.units_consumed) {
+            (None, Some(units_consumed)) => {
+                let limit = compute_unit_limit(units_consumed);
+                tracing::info!(units_consumed, limit, "Compute units of the transaction");
+                limit
+            },
+            (err, _) => {
+                tracing::info!(?err, "Simulation failed, the compute unit limit isn't estimated");
+                FALLBACK_COMPUTE_UNIT_LIMIT
+            },
+        },
+        Err(err) => {
+            tracing::warn!(
+                ?err,
+                "Failed to simulate the transaction, the compute unit limit isn't estimated"
+            );
+            FALLBACK_COMPUTE_UNIT_LIMIT
+        },
+    };
+    Ok(limit)
+}
+
+/// Signs the instructions by the payer and the signers they require. The compute unit limit measured by a
+/// simulation is prepended, along with a non-zero `compute_unit_price` as the priority fee of the transaction. The
+/// transaction is a v0 one looking its accounts up in `lookup_tables` if any are passed. A transaction signed against
+/// the durable nonce of `nonce_account` advances it by its first instruction instead of expiring with a recent
+/// blockhash, the payer is the nonce authority
+async fn sign_transaction(
+    state: &AppState,
+    ixns: &[Instruction],
+    compute_unit_price: u64,
+    lookup_tables: &[AddressLookupTableAccount],
+    nonce_account: Option<&Pubkey>,
+) -> anyhow::Result<VersionedTransaction> {
+    let rpc_client = state.program.async_rpc();
+    let (latest_hash, advance_nonce) = match nonce_account {
+        Some(nonce_account) => (
+            fetch_durable_nonce(&rpc_client, nonce_account).await?,
+            Some(system_instruction::advance_nonce_account(
+                nonce_account,
+                &state.payer.pubkey(),
+            )),
+        ),
+        None => (
+            rpc_client
+                .get_latest_blockhash()
+                .await
+                .context("Failed to get latest blockhash")?,
+            None,
+        ),
+    };
+
+    let compute_unit_limit = estimate_compute_unit_limit(state, ixns, lookup_tables).await?;
+    let ixns = advance_nonce
+        .into_iter()
+        .chain([ComputeBudgetInstruction::set_compute_unit_limit(compute_unit_limit)])
+        .chain((compute_unit_price > 0).then(|| ComputeBudgetInstruction::set_compute_unit_price(compute_unit_price)))
+        .chain(ixns.iter().cloned())
+        .collect::<Vec<_>>();
+    let message = compile_message(&state.payer.pubkey(), &ixns, lookup_tables, latest_hash)?;
+    let tx = VersionedTransaction::try_new(message.clone(), &transaction_signers(state, &message))
+        .context("Failed to sign transaction")?;
+
+    let tx_size = bincode::serialize(&tx).unwrap_or_default().len();
+    tracing::info!(%tx_size, "Round transaction size. Maximum possible is 1232 bytes.");
+
+    Ok(tx)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::pipeline::{compute_unit_limit, MAX_COMPUTE_UNIT_LIMIT};
+
+    #[test]
+    fn should_add_margin_to_simulated_units() {
+        assert_eq!(240_000, compute_unit_limit(200_000));
+        assert_eq!(0, compute_unit_limit(0));
+        assert_eq!(MAX_COMPUTE_UNIT_LIMIT, compute_unit_limit(1_300_000));
+        assert_eq!(MAX_COMPUTE_UNIT_LIMIT, compute_unit_limit(u64::MAX));
+    }
+}
@@ -1,7 +1,52 @@
+use anyhow::bail;
 use jsonrpsee::{http_client::HttpClient, proc_macros::rpc};
 use serde::{Deserialize, Serialize};
 use serde_with::{serde_as, DisplayFromStr};
 use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+
+/// Default `PRIORITY_FEE_MAX`, micro-lamports per compute unit
+pub const DEFAULT_MAX_PRIORITY_FEE: u64 = 1_000_000;
+
+/// Percentile of the recent priority fees the estimate is taken at
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PriorityLevel {
+    /// 0th percentile
+    Min,
+    /// 25th percentile
+    Low,
+    /// 50th percentile
+    #[default]
+    Medium,
+    /// 75th percentile
+    High,
+    /// 95th percentile
+    VeryHigh,
+    /// 100th percentile
+    UnsafeMax,
+}
+
+impl FromStr for PriorityLevel {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "min" => Ok(PriorityLevel::Min),
+            "low" => Ok(PriorityLevel::Low),
+            "medium" => Ok(PriorityLevel::Medium),
+            "high" => Ok(PriorityLevel::High),
+            "very_high" => Ok(PriorityLevel::VeryHigh),
+            "unsafe_max" => Ok(PriorityLevel::UnsafeMax),
+            _ => bail!("Unknown priority level {s}"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GetPriorityFeeEstimateOptions {
+    priority_level: PriorityLevel,
+}
 
 #[serde_as]
 #[derive(Debug, Serialize, Deserialize)]
@@ -9,6 +54,7 @@ use solana_sdk::pubkey::Pubkey;
 struct GetPriorityFeeEstimateRequest {
     #[serde_as(as = "Vec<DisplayFromStr>")]
     account_keys: Vec<Pubkey>, // estimate fee for a list of accounts
+    options: GetPriorityFeeEstimateOptions,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -26,11 +72,93 @@ trait PriorityFeeApi {
     ) -> Result<GetPriorityFeeEstimateResponse, ErrorObjectOwned>;
 }
 
-pub async fn fetch_recent_priority_fee(client: &HttpClient) -> anyhow::Result<u64> {
+pub async fn fetch_recent_priority_fee(client: &HttpClient, level: PriorityLevel) -> anyhow::Result<u64> {
     let GetPriorityFeeEstimateResponse { priority_fee_estimate } = client
         .get_priority_fee_estimate(GetPriorityFeeEstimateRequest {
             account_keys: vec![distributor::ID],
+            options: GetPriorityFeeEstimateOptions { priority_level: level },
         })
         .await?;
     Ok(priority_fee_estimate as u64)
 }
+
+/// Compute unit price of the round transactions, the estimate at `level` kept between `min` and `max`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PriorityFeeConfig {
+    pub level: PriorityLevel,
+    /// Micro-lamports per compute unit, also the price if the estimate fails
+    pub min: u64,
+    /// Micro-lamports per compute unit
+    pub max: u64,
+}
+
+impl Default for PriorityFeeConfig {
+    fn default() -> Self {
+        Self {
+            level: PriorityLevel::default(),
+            min: 0,
+            max: DEFAULT_MAX_PRIORITY_FEE,
+        }
+    }
+}
+
+impl PriorityFeeConfig {
+    pub fn clamp(&self, estimate: u64) -> u64 {
+        estimate.clamp(self.min, self.max)
+    }
+
+    /// Compute unit price of the next round, `min` if the priority fee RPC fails so the round is sent anyway
+    pub async fn compute_unit_price(&self, client: &HttpClient) -> u64 {
+        match fetch_recent_priority_fee(client, self.level).await {
+            Ok(estimate) => {
+                let price = self.clamp(estimate);
+                tracing::info!(estimate, price, "Compute unit price of the round");
+                price
+            },
+            Err(err) => {
+                tracing::warn!(
+                    ?err,
+                    price = self.min,
+                    "Priority fee estimate failed, falling back to the minimum"
+                );
+                self.min
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::priority_fee::{
+        GetPriorityFeeEstimateOptions, GetPriorityFeeEstimateRequest, PriorityFeeConfig, PriorityLevel,
+    };
+    use solana_sdk::pubkey::Pubkey;
+
+    #[test]
+    fn should_clamp_estimate() {
+        let config = PriorityFeeConfig {
+            level: PriorityLevel::High,
+            min: 1_000,
+            max: 50_000,
+        };
+        assert_eq!(1_000, config.clamp(0));
+        assert_eq!(20_000, config.clamp(20_000));
+        assert_eq!(50_000, config.clamp(u64::MAX));
+    }
+
+    #[test]
+    fn should_request_estimate_at_level() {
+        assert_eq!(PriorityLevel::VeryHigh, "very_high".parse().unwrap());
+        assert!("p75".parse::<PriorityLevel>().is_err());
+
+        let request = serde_json::to_value(GetPriorityFeeEstimateRequest {
+            account_keys: vec![Pubkey::default()],
+            options: GetPriorityFeeEstimateOptions {
+                priority_level: PriorityLevel::VeryHigh,
+            },
+        })
+        .unwrap();
+        assert_eq!("VeryHigh", request["options"]["priorityLevel"]);
+        assert_eq!(Pubkey::default().to_string(), request["accountKeys"][0]);
+    }
+}
@@ -0,0 +1,196 @@
+use anchor_client::anchor_lang::prelude::Pubkey;
+use distributor::error::DistributorError;
+use solana_client::{
+    client_error::{ClientError, ClientErrorKind},
+    rpc_request::{RpcError, RpcResponseErrorData},
+};
+use solana_sdk::{instruction::InstructionError, transaction::TransactionError};
+use std::{fmt, str::FromStr};
+
+/// Failure of a distribute transaction reported by the cluster
+#[derive(Debug, Clone, PartialEq)]
+pub enum ProgramFailure {
+    /// Vault balance dropped below the threshold since it was checked
+    ThresholdNotMet,
+    /// Cooldown since the previous round isn't over yet
+    IntervalNotElapsed,
+    /// One of the winners accounts was rejected or a winner doesn't hold enough marker tokens, the account is
+    /// known if the program logged it
+    InvalidRecipient {
+        account: Option<Pubkey>,
+    },
+    /// Any other distributor error
+    Distributor {
+        code: u32,
+        name: Option<String>,
+    },
+    Transaction(TransactionError),
+}
+
+/// What the actor should do about a failed transaction
+#[derive(Debug, Clone, PartialEq)]
+pub enum Remediation {
+    /// Replace the winner owning the account (or all of them if unknown) and try again
+    Redraw(Option<Pubkey>),
+    /// Try the whole round again later
+    Reschedule,
+    Abort,
+}
+
+impl ProgramFailure {
+    pub fn new(err: &TransactionError, logs: &[String]) -> Self {
+        let TransactionError::InstructionError(_, InstructionError::Custom(code)) = err else {
+            return Self::Transaction(err.clone());
+        };
+        let code = *code;
+
+        if code == u32::from(DistributorError::ThresholdNotMet) {
+            Self::ThresholdNotMet
+        } else if code == u32::from(DistributorError::IntervalNotElapsed) {
+            Self::IntervalNotElapsed
+        } else if code == u32::from(DistributorError::InvalidNativeRecipient) {
+            // the program logs the owner of the wallet, not the wallet itself
+            Self::InvalidRecipient { account: None }
+        } else if code == u32::from(DistributorError::RecipientBlocked) {
+            // blocked winners are disqualified before the round, the wallet was blocked since
+            Self::InvalidRecipient { account: None }
+        } else if code == u32::from(DistributorError::RepeatWinner) {
+            // winners of the previous round are disqualified before the round, it was confirmed since
+            Self::InvalidRecipient { account: None }
+        } else if code == u32::from(DistributorError::InvalidAssociatedTokenAccount)
+            || code == u32::from(DistributorError::MarkerBalanceTooLow)
+        {
+            Self::InvalidRecipient {
+                account: logged_left_pubkey(logs),
+            }
+        } else {
+            Self::Distributor {
+                code,
+                name: logged_error_name(logs),
+            }
+        }
+    }
+
+    /// Extracts the failure from a send/simulation error, `None` if the transaction didn't reach the program
+    pub fn from_client_error(err: &ClientError) -> Option<Self> {
+        match err.kind() {
+            ClientErrorKind::RpcError(RpcError::RpcResponseError {
+                data: RpcResponseErrorData::SendTransactionPreflightFailure(result),
+                ..
+            }) => result
+                .err
+                .as_ref()
+                .map(|err| Self::new(err, result.logs.as_deref().unwrap_or_default())),
+            ClientErrorKind::TransactionError(err) => Some(Self::new(err, &[])),
+            _ => None,
+        }
+    }
+
+    pub fn remediation(&self) -> Remediation {
+        match self {
+            ProgramFailure::ThresholdNotMet | ProgramFailure::IntervalNotElapsed => Remediation::Reschedule,
+            ProgramFailure::InvalidRecipient { account } => Remediation::Redraw(*account),
+            ProgramFailure::Transaction(TransactionError::BlockhashNotFound) => Remediation::Reschedule,
+            ProgramFailure::Distributor { .. } | ProgramFailure::Transaction(_) => Remediation::Abort,
+        }
+    }
+}
+
+impl fmt::Display for ProgramFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ProgramFailure::ThresholdNotMet => write!(f, "Vault balance is below the threshold"),
+            ProgramFailure::IntervalNotElapsed => write!(f, "Minimal interval since the previous round isn't over"),
+            ProgramFailure::InvalidRecipient { account: Some(account) } => {
+                write!(f, "Recipient account {account} was rejected")
+            },
+            ProgramFailure::InvalidRecipient { account: None } => write!(f, "Recipient account was rejected"),
+            ProgramFailure::Distributor { code, name: Some(name) } => write!(f, "Distributor error {name} ({code})"),
+            ProgramFailure::Distributor { code, name: None } => write!(f, "Distributor error {code}"),
+            ProgramFailure::Transaction(err) => write!(f, "Transaction failed: {err}"),
+        }
+    }
+}
+
+impl std::error::Error for ProgramFailure {}
+
+/// `require_keys_eq!` logs the compared keys as "Left:" / "Right:" lines, left is the account passed in
+fn logged_left_pubkey(logs: &[String]) -> Option<Pubkey> {
+    let position = logs.iter().position(|log| log == "Program log: Left:")?;
+    let key = logs.get(position + 1)?.strip_prefix("Program log: ")?;
+    Pubkey::from_str(key).ok()
+}
+
+fn logged_error_name(logs: &[String]) -> Option<String> {
+    logs.iter().find_map(|log| {
+        let (_, rest) = log.split_once("Error Code: ")?;
+        rest.split('.').next().map(ToString::to_string)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::program_error::{ProgramFailure, Remediation};
+    use distributor::error::DistributorError;
+    use solana_sdk::{instruction::InstructionError, pubkey, transaction::TransactionError};
+
+    fn custom(code: u32) -> TransactionError {
+        TransactionError::InstructionError(2, InstructionError::Custom(code))
+    }
+
+    #[test]
+    fn should_reschedule_when_threshold_not_met() {
+        let failure = ProgramFailure::new(&custom(DistributorError::ThresholdNotMet.into()), &[]);
+        assert_eq!(ProgramFailure::ThresholdNotMet, failure);
+        assert_eq!(Remediation::Reschedule, failure.remediation());
+    }
+
+    #[test]
+    fn should_reschedule_when_interval_not_elapsed() {
+        let failure = ProgramFailure::new(&custom(DistributorError::IntervalNotElapsed.into()), &[]);
+        assert_eq!(ProgramFailure::IntervalNotElapsed, failure);
+        assert_eq!(Remediation::Reschedule, failure.remediation());
+    }
+
+    #[test]
+    fn should_redraw_rejected_recipient() {
+        let logs = [
+            "Program log: AnchorError thrown in programs/distributor/src/lib.rs:95. Error Code: \
+             InvalidAssociatedTokenAccount. Error Number: 6003. Error Message: InvalidAssociatedTokenAccount.",
+            "Program log: Left:",
+            "Program log: 4wZ2E3St33iB5xu9R2Kf6NbMa5pkoeqVNe1SkcFVvoX5",
+            "Program log: Right:",
+            "Program log: EBHnjoKTCn4S27pYsfYesRbnVr3JmAHg6E5JEnrgAqCR",
+        ]
+        .map(String::from);
+        let failure = ProgramFailure::new(&custom(DistributorError::InvalidAssociatedTokenAccount.into()), &logs);
+        assert_eq!(
+            Remediation::Redraw(Some(pubkey!("4wZ2E3St33iB5xu9R2Kf6NbMa5pkoeqVNe1SkcFVvoX5"))),
+            failure.remediation()
+        );
+    }
+
+    #[test]
+    fn should_redraw_everyone_when_marker_balance_is_too_low() {
+        let failure = ProgramFailure::new(&custom(DistributorError::MarkerBalanceTooLow.into()), &[]);
+        assert_eq!(Remediation::Redraw(None), failure.remediation());
+    }
+
+    #[test]
+    fn should_abort_on_other_errors() {
+        let logs = ["Program log: AnchorError occurred. Error Code: MissingRemainingAccounts. Error Number: 6002."]
+            .map(String::from);
+        let failure = ProgramFailure::new(&custom(DistributorError::MissingRemainingAccounts.into()), &logs);
+        assert_eq!(
+            ProgramFailure::Distributor {
+                code: DistributorError::MissingRemainingAccounts.into(),
+                name: Some("MissingRemainingAccounts".to_string())
+            },
+            failure
+        );
+        assert_eq!(Remediation::Abort, failure.remediation());
+
+        let failure = ProgramFailure::new(&TransactionError::InsufficientFundsForFee, &[]);
+        assert_eq!(Remediation::Abort, failure.remediation());
+    }
+}
@@ -0,0 +1,310 @@
+use crate::{
+    claims::{fetch_claims, ClaimEntry},
+    service::unix_timestamp,
+    validation::fetch_blocked_wallets,
+};
+use anchor_client::anchor_lang::{InstructionData, ToAccountMetas};
+use anyhow::{bail, ensure, Context};
+use serde::{Deserialize, Serialize};
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{
+    commitment_config::CommitmentConfig,
+    instruction::Instruction,
+    message::Message,
+    pubkey::Pubkey,
+    signature::{Keypair, Signature, Signer},
+    transaction::Transaction,
+};
+use spl_associated_token_account::{
+    get_associated_token_address_with_program_id, instruction::create_associated_token_account_idempotent,
+};
+use std::{
+    collections::{HashMap, VecDeque},
+    time::{Duration, Instant},
+};
+
+/// Window of the relayed claims limit of a wallet
+const RATE_LIMIT_WINDOW: Duration = Duration::from_secs(60 * 60);
+
+/// Claim transaction prepared for the winner to sign, the relayer pays the fee, the token account rent and the growth
+/// of the round record
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RelayMessage {
+    /// Base58 of the serialized message
+    pub message: String,
+}
+
+/// Relay message signed by the winner
+#[derive(Debug, Clone, Deserialize)]
+pub struct SignedRelayMessage {
+    pub message: String,
+    /// Base58 signature of the winner over the message
+    pub signature: String,
+}
+
+/// Optional accounts the claim has to pass
+struct ClaimAccounts {
+    /// Blocklist of the distributor if any wallet is blocked
+    blocklist: Option<Pubkey>,
+    /// Record of the claim round if it was recorded, the claim adds the claimant to it
+    round: Option<Pubkey>,
+    /// Record of the round before while its winners can't win again
+    previous_round: Option<Pubkey>,
+}
+
+/// Builds, pays for and submits the `claim` transactions of the winners without SOL. The winner still signs the
+/// transaction as the claimant, the relayer only co-signs the exact claim it prepared
+pub struct ClaimRelayer {
+    rpc_client: RpcClient,
+    relayer: Keypair,
+    distributor_state: Pubkey,
+    mint: Pubkey,
+    /// Token program owning the mint, SPL Token or Token-2022
+    token_program: Pubkey,
+    pool: sqlx::PgPool,
+    limiter: std::sync::Mutex<RateLimiter>,
+}
+
+impl ClaimRelayer {
+    pub fn new(
+        solana_rpc_url: String,
+        relayer: Keypair,
+        distributor_state: Pubkey,
+        mint: Pubkey,
+        token_program: Pubkey,
+        pool: sqlx::PgPool,
+        claims_per_hour: u32,
+    ) -> Self {
+        Self {
+            rpc_client: RpcClient::new_with_commitment(solana_rpc_url, CommitmentConfig::confirmed()),
+            relayer,
+            distributor_state,
+            mint,
+            token_program,
+            pool,
+            limiter: std::sync::Mutex::new(RateLimiter::new(claims_per_hour, RATE_LIMIT_WINDOW)),
+        }
+    }
+
+    /// Claim transaction of the wallet for the round with the latest blockhash, for the wallet to sign
+    pub async fn prepare(&self, wallet: &Pubkey, round: u64) -> anyhow::Result<RelayMessage> {
+        let (entry, accounts) = self.claimable(wallet, round).await?;
+        let blockhash = self
+            .rpc_client
+            .get_latest_blockhash()
+            .await
+            .context("Failed to get latest blockhash")?;
+        let message = Message::new_with_blockhash(
+            &self.instructions(&entry, &accounts),
+            Some(&self.relayer.pubkey()),
+            &blockhash,
+        );
+
+        Ok(RelayMessage {
+            message: bs58::encode(message.serialize()).into_string(),
+        })
+    }
+
+    /// Co-signs and submits the claim transaction signed by the wallet, returns the signature of the transaction. Only
+    /// the messages signed by the wallet count against its limit, anyone else could exhaust it otherwise
+    pub async fn submit(&self, wallet: &Pubkey, round: u64, signed: &SignedRelayMessage) -> anyhow::Result<Signature> {
+        let message_bytes = bs58::decode(&signed.message)
+            .into_vec()
+            .context("Invalid relay message")?;
+        let message: Message = bincode::deserialize(&message_bytes).context("Invalid relay message")?;
+        let signature: Signature = signed.signature.parse().context("Invalid relay signature")?;
+        ensure!(
+            signature.verify(wallet.as_ref(), &message_bytes),
+            "Relay message isn't signed by {wallet}"
+        );
+        ensure!(
+            self.limiter
+                .lock()
+                .expect("Poisoned relay limiter")
+                .try_acquire(wallet, Instant::now()),
+            "Too many relayed claims of {wallet}"
+        );
+
+        // Only the claim prepared by `prepare` is paid for, whatever blockhash it was prepared with
+        let (entry, accounts) = self.claimable(wallet, round).await?;
+        let expected = Message::new_with_blockhash(
+            &self.instructions(&entry, &accounts),
+            Some(&self.relayer.pubkey()),
+            &message.recent_blockhash,
+        );
+        ensure!(message == expected, "Relay message isn't the claim of {wallet}");
+
+        let Some(position) = message.account_keys.iter().position(|key| key == wallet) else {
+            bail!("Relay message isn't signed by {wallet}");
+        };
+        let mut tx = Transaction::new_unsigned(message);
+        tx.try_partial_sign(&[&self.relayer], tx.message.recent_blockhash)
+            .context("Failed to sign relay transaction")?;
+        tx.signatures[position] = signature;
+
+        self.rpc_client
+            .send_and_confirm_transaction(&tx)
+            .await
+            .context("Failed to send relay transaction")
+    }
+
+    /// Claim of the wallet for the round which is still unclaimed and not expired, with the optional accounts the
+    /// claim has to pass
+    async fn claimable(&self, wallet: &Pubkey, round: u64) -> anyhow::Result<(ClaimEntry, ClaimAccounts)> {
+        let Some(entry) = fetch_claims(&self.pool, wallet)
+            .await?
+            .into_iter()
+            .find(|entry| entry.distributor_state == self.distributor_state && entry.round == round)
+        else {
+            bail!("{wallet} has no claim in round {round}");
+        };
+
+        let address = distributor::client::claim_round_address(&self.distributor_state, round);
+        let account = self
+            .rpc_client
+            .get_account(&address)
+            .await
+            .context("Failed to get claim round")?;
+        let claim_round =
+            distributor::client::claim_round(&address, &account.owner, &account.data).context("Invalid claim round")?;
+        ensure!(
+            !claim_round.is_claimed(entry.index),
+            "{wallet} already claimed round {round}"
+        );
+        ensure!(
+            claim_round.expires_at > unix_timestamp(),
+            "Claims of round {round} are expired"
+        );
+
+        let account = self
+            .rpc_client
+            .get_account(&self.distributor_state)
+            .await
+            .context("Failed to get distributor state")?;
+        let distributor_state =
+            distributor::client::distributor_state(&self.distributor_state, &account.owner, &account.data)
+                .context("Invalid distributor state")?;
+        let blocked = fetch_blocked_wallets(&self.rpc_client, &self.distributor_state, &distributor_state).await?;
+        ensure!(!blocked.contains(wallet), "{wallet} is blocked");
+        let blocklist = (distributor_state.blocked_recipients > 0)
+            .then(|| distributor::client::blocklist_address(&self.distributor_state));
+
+        let record = distributor::client::distribution_round_address(&self.distributor_state, round);
+        let recorded = self
+            .rpc_client
+            .get_account_with_commitment(&record, self.rpc_client.commitment())
+            .await
+            .context("Failed to get round record")?
+            .value
+            .is_some();
+        let previous_round = distributor_state
+            .no_repeat_winners_since
+            .is_some_and(|since| round > since)
+            .then(|| distributor::client::distribution_round_address(&self.distributor_state, round - 1));
+
+        let accounts = ClaimAccounts {
+            blocklist,
+            round: recorded.then_some(record),
+            previous_round,
+        };
+        Ok((entry, accounts))
+    }
+
+    /// Creates the token account of the winner if missing and claims into it
+    fn instructions(&self, entry: &ClaimEntry, accounts: &ClaimAccounts) -> Vec<Instruction> {
+        let claim_round = distributor::client::claim_round_address(&self.distributor_state, entry.round);
+        let token_account =
+            get_associated_token_address_with_program_id(&entry.wallet, &self.mint, &self.token_program);
+        vec![
+            create_associated_token_account_idempotent(
+                &self.relayer.pubkey(),
+                &entry.wallet,
+                &self.mint,
+                &self.token_program,
+            ),
+            Instruction {
+                program_id: distributor::ID,
+                accounts: distributor::accounts::Claim {
+                    claimant: entry.wallet,
+                    payer: self.relayer.pubkey(),
+                    claim_round,
+                    distributor_state: self.distributor_state,
+                    blocklist: accounts.blocklist,
+                    round: accounts.round,
+                    previous_round: accounts.previous_round,
+                    mint: self.mint,
+                    escrow: distributor::client::escrow_address(&claim_round),
+                    token_account,
+                    referral: None,
+                    system_program: solana_sdk::system_program::ID,
+                    token_program: self.token_program,
+                }
+                .to_account_metas(None),
+                data: distributor::instruction::Claim {
+                    index: entry.index,
+                    amount: entry.amount,
+                    proof: entry.proof.clone(),
+                }
+                .data(),
+            },
+        ]
+    }
+}
+
+/// Sliding window limit of the relayed claims of every wallet
+pub struct RateLimiter {
+    limit: u32,
+    window: Duration,
+    attempts: HashMap<Pubkey, VecDeque<Instant>>,
+}
+
+impl RateLimiter {
+    pub fn new(limit: u32, window: Duration) -> Self {
+        Self {
+            limit,
+            window,
+            attempts: HashMap::new(),
+        }
+    }
+
+    /// Records an attempt of the wallet unless it ran out of them in the window
+    pub fn try_acquire(&mut self, wallet: &Pubkey, now: Instant) -> bool {
+        let window = self.window;
+        self.attempts
+            .retain(|_, attempts| attempts.back().is_some_and(|last| now.duration_since(*last) < window));
+
+        let attempts = self.attempts.entry(*wallet).or_default();
+        while attempts
+            .front()
+            .is_some_and(|first| now.duration_since(*first) >= window)
+        {
+            attempts.pop_front();
+        }
+        if attempts.len() >= self.limit as usize {
+            return false;
+        }
+        attempts.push_back(now);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::relayer::RateLimiter;
+    use solana_sdk::pubkey::Pubkey;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn should_limit_relayed_claims_per_wallet() {
+        let mut limiter = RateLimiter::new(2, Duration::from_secs(60));
+        let wallet = Pubkey::new_unique();
+        let start = Instant::now();
+
+        assert!(limiter.try_acquire(&wallet, start));
+        assert!(limiter.try_acquire(&wallet, start + Duration::from_secs(10)));
+        assert!(!limiter.try_acquire(&wallet, start + Duration::from_secs(20)));
+        assert!(limiter.try_acquire(&Pubkey::new_unique(), start + Duration::from_secs(20)));
+        assert!(limiter.try_acquire(&wallet, start + Duration::from_secs(60)));
+        assert!(!limiter.try_acquire(&wallet, start + Duration::from_secs(65)));
+    }
+}
@@ -0,0 +1,467 @@
+use crate::validation::Replacement;
+use anyhow::Context;
+use serde::Deserialize;
+use solana_sdk::{native_token::lamports_to_sol, pubkey::Pubkey, signature::Signature};
+use std::time::Duration;
+
+const MARKDOWN_TEMPLATE: &str = include_str!("../templates/report.md");
+const HTML_TEMPLATE: &str = include_str!("../templates/report.html");
+
+/// Randomness the winners of a round were drawn with, shown in its report so anyone can reproduce the draw
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Randomness {
+    /// `rand::rngs::StdRng` seeded from the OS, the redraws continue its sequence
+    Seed([u8; 32]),
+    /// Revealed VRF randomness of the round, the winners are its `DrawIndices`
+    Vrf([u8; 64]),
+    /// Randomness of the committed draw of the round, the winners are its `DrawIndices`
+    Committed([u8; 64]),
+    /// Nothing is drawn by a dividend round, the holders with the largest marker balances are paid
+    Dividend,
+}
+
+impl Randomness {
+    pub fn describe(&self) -> String {
+        match self {
+            Randomness::Seed(seed) => format!("rand::rngs::StdRng seeded with 0x{}", hex(seed)),
+            Randomness::Vrf(randomness) => format!("ORAO VRF randomness 0x{}", hex(randomness)),
+            Randomness::Committed(randomness) => format!("committed draw randomness 0x{}", hex(randomness)),
+            Randomness::Dividend => "none, the largest marker holders are paid a dividend".to_string(),
+        }
+    }
+
+    /// Kind and bytes the randomness is stored by
+    pub fn to_parts(&self) -> (&'static str, Vec<u8>) {
+        match self {
+            Randomness::Seed(seed) => ("seed", seed.to_vec()),
+            Randomness::Vrf(randomness) => ("vrf", randomness.to_vec()),
+            Randomness::Committed(randomness) => ("committed", randomness.to_vec()),
+            Randomness::Dividend => ("dividend", vec![]),
+        }
+    }
+
+    pub fn from_parts(kind: &str, bytes: &[u8]) -> anyhow::Result<Self> {
+        match kind {
+            "seed" => Ok(Randomness::Seed(bytes.try_into().context("Invalid seed")?)),
+            "vrf" => Ok(Randomness::Vrf(bytes.try_into().context("Invalid VRF randomness")?)),
+            "committed" => Ok(Randomness::Committed(
+                bytes.try_into().context("Invalid committed randomness")?,
+            )),
+            "dividend" => Ok(Randomness::Dividend),
+            kind => anyhow::bail!("Unknown randomness {kind}"),
+        }
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[derive(Debug, Clone, Copy, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ReportFormat {
+    #[default]
+    Markdown,
+    Html,
+}
+
+impl ReportFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ReportFormat::Markdown => "text/markdown; charset=utf-8",
+            ReportFormat::Html => "text/html; charset=utf-8",
+        }
+    }
+}
+
+pub struct ExplorerLinks {
+    pub cluster: Option<String>,
+}
+
+impl ExplorerLinks {
+    pub fn transaction(&self, signature: &Signature) -> String {
+        self.with_cluster(format!("https://explorer.solana.com/tx/{signature}"))
+    }
+
+    pub fn address(&self, address: &Pubkey) -> String {
+        self.with_cluster(format!("https://explorer.solana.com/address/{address}"))
+    }
+
+    fn with_cluster(&self, url: String) -> String {
+        match &self.cluster {
+            Some(cluster) => format!("{url}?cluster={cluster}"),
+            None => url,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ReportWinner {
+    /// Position of the winner in the holders list the draw was made from
+    pub index: u64,
+    pub wallet: Pubkey,
+    pub amount: u64,
+    pub ata_created: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct CostBreakdown {
+    pub fee_lamports: u64,
+    pub rent_lamports: u64,
+}
+
+impl CostBreakdown {
+    pub fn total_lamports(&self) -> u64 {
+        self.fee_lamports + self.rent_lamports
+    }
+}
+
+#[derive(Debug)]
+pub struct RoundReport {
+    pub signature: Signature,
+    pub decimals: u8,
+    pub share_size: u64,
+    pub burned: u64,
+    pub holders_number: u64,
+    pub randomness: Randomness,
+    pub winners: Vec<ReportWinner>,
+    /// Drawn winners which were disqualified before the payout and their replacements
+    pub replacements: Vec<Replacement>,
+    pub costs: CostBreakdown,
+}
+
+impl RoundReport {
+    pub fn total_paid(&self) -> u64 {
+        self.winners.iter().map(|winner| winner.amount).sum()
+    }
+
+    /// `private` reports show truncated winner addresses without explorer links
+    pub fn render(&self, id: i64, format: ReportFormat, links: &ExplorerLinks, private: bool) -> String {
+        let (template, winners) = match format {
+            ReportFormat::Markdown => (MARKDOWN_TEMPLATE, self.markdown_winners(links, private)),
+            ReportFormat::Html => (HTML_TEMPLATE, self.html_winners(links, private)),
+        };
+
+        render_template(template, &[
+            ("id", id.to_string()),
+            ("share_count", self.winners.len().to_string()),
+            ("share_size", ui_amount(self.share_size, self.decimals)),
+            ("total_paid", ui_amount(self.total_paid(), self.decimals)),
+            ("burned", ui_amount(self.burned, self.decimals)),
+            ("winners", winners),
+            ("replacements", self.render_replacements(format, private)),
+            ("rng", self.randomness.describe()),
+            ("holders_number", self.holders_number.to_string()),
+            ("fee", lamports_to_sol(self.costs.fee_lamports).to_string()),
+            ("rent", lamports_to_sol(self.costs.rent_lamports).to_string()),
+            ("total_cost", lamports_to_sol(self.costs.total_lamports()).to_string()),
+            ("signature", self.signature.to_string()),
+            ("signature_url", links.transaction(&self.signature)),
+        ])
+    }
+
+    fn render_replacements(&self, format: ReportFormat, private: bool) -> String {
+        if self.replacements.is_empty() {
+            return String::new();
+        }
+        let lines = self.replacements.iter().map(|replacement| {
+            let (disqualified, reason, replacement) = (
+                display_address(&replacement.disqualified.wallet, private),
+                replacement.reason.as_str(),
+                display_address(&replacement.replacement.wallet, private),
+            );
+            match format {
+                ReportFormat::Markdown => format!("- `{disqualified}` ({reason}) replaced by `{replacement}`"),
+                ReportFormat::Html => {
+                    format!("  <li><code>{disqualified}</code> ({reason}) replaced by <code>{replacement}</code></li>")
+                },
+            }
+        });
+        match format {
+            ReportFormat::Markdown => format!("**Replaced winners**:\n{}\n", lines.collect::<Vec<_>>().join("\n")),
+            ReportFormat::Html => format!(
+                "<p><strong>Replaced winners</strong>:</p>\n<ul>\n{}\n</ul>\n",
+                lines.collect::<Vec<_>>().join("\n")
+            ),
+        }
+    }
+
+    fn markdown_winners(&self, links: &ExplorerLinks, private: bool) -> String {
+        self.winners
+            .iter()
+            .enumerate()
+            .map(|(position, winner)| {
+                let wallet = if private {
+                    truncate_address(&winner.wallet)
+                } else {
+                    format!("[{}]({})", winner.wallet, links.address(&winner.wallet))
+                };
+                format!(
+                    "| {} | {} | {} | {} | {} |",
+                    position + 1,
+                    winner.index,
+                    wallet,
+                    ui_amount(winner.amount, self.decimals),
+                    if winner.ata_created { "yes" } else { "no" }
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    fn html_winners(&self, links: &ExplorerLinks, private: bool) -> String {
+        self.winners
+            .iter()
+            .enumerate()
+            .map(|(position, winner)| {
+                let wallet = if private {
+                    truncate_address(&winner.wallet)
+                } else {
+                    format!("<a href=\"{}\">{}</a>", links.address(&winner.wallet), winner.wallet)
+                };
+                format!(
+                    "    <tr><td>{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+                    position + 1,
+                    winner.index,
+                    wallet,
+                    ui_amount(winner.amount, self.decimals),
+                    if winner.ata_created { "yes" } else { "no" }
+                )
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+/// Shortens the address to its first and last 4 characters, e.g. `De49…G28i`
+pub fn truncate_address(address: &Pubkey) -> String {
+    let address = address.to_string();
+    format!("{}…{}", &address[..4], &address[address.len() - 4..])
+}
+
+/// Full address, or the truncated one if `private`
+pub fn display_address(address: &Pubkey, private: bool) -> String {
+    if private {
+        truncate_address(address)
+    } else {
+        address.to_string()
+    }
+}
+
+/// Replaces every `{{key}}` placeholder of the template with its value.
+fn render_template(template: &str, values: &[(&str, String)]) -> String {
+    values.iter().fold(template.to_string(), |rendered, (key, value)| {
+        rendered.replace(&format!("{{{{{key}}}}}"), value)
+    })
+}
+
+/// Formats base units as a decimal token amount without trailing zeros.
+pub fn ui_amount(amount: u64, decimals: u8) -> String {
+    let unit = 10u128.pow(decimals as u32);
+    let (whole, fraction) = (amount as u128 / unit, amount as u128 % unit);
+    if fraction == 0 {
+        return whole.to_string();
+    }
+    let fraction = format!("{:0width$}", fraction, width = decimals as usize);
+    format!("{whole}.{}", fraction.trim_end_matches('0'))
+}
+
+/// Persists the round and its rendered reports, returning the distribution id. The reports are announced
+/// (publicly available) after `announcement_delay`, in `privacy_mode` the public ones truncate winner addresses.
+pub async fn store_report(
+    pool: &sqlx::PgPool,
+    report: &RoundReport,
+    links: &ExplorerLinks,
+    announcement_delay: Duration,
+    privacy_mode: bool,
+) -> anyhow::Result<i64> {
+    let id: i64 = sqlx::query_scalar(
+        "INSERT INTO distributions (signature, share_size, burned, holders_number, winners, fee_lamports, \
+         rent_lamports, announce_at, private) VALUES ($1, $2, $3, $4, $5, $6, $7, \
+         CURRENT_TIMESTAMP + $8 * interval '1 second', $9) RETURNING id",
+    )
+    .bind(report.signature.to_string())
+    .bind(report.share_size as i64)
+    .bind(report.burned as i64)
+    .bind(report.holders_number as i64)
+    .bind(
+        report
+            .winners
+            .iter()
+            .map(|winner| winner.wallet.to_string())
+            .collect::<Vec<_>>(),
+    )
+    .bind(report.costs.fee_lamports as i64)
+    .bind(report.costs.rent_lamports as i64)
+    .bind(announcement_delay.as_secs_f64())
+    .bind(privacy_mode)
+    .fetch_one(pool)
+    .await
+    .context("Failed to insert distribution")?;
+
+    sqlx::query(
+        "UPDATE distributions SET report_markdown = $2, report_html = $3, public_report_markdown = $4, \
+         public_report_html = $5 WHERE id = $1",
+    )
+    .bind(id)
+    .bind(report.render(id, ReportFormat::Markdown, links, false))
+    .bind(report.render(id, ReportFormat::Html, links, false))
+    .bind(privacy_mode.then(|| report.render(id, ReportFormat::Markdown, links, true)))
+    .bind(privacy_mode.then(|| report.render(id, ReportFormat::Html, links, true)))
+    .execute(pool)
+    .await
+    .context("Failed to store distribution report")?;
+
+    Ok(id)
+}
+
+/// Reports which aren't announced yet are returned only for a `preview`, which always has the full addresses
+pub async fn fetch_report(
+    pool: &sqlx::PgPool,
+    id: i64,
+    format: ReportFormat,
+    preview: bool,
+) -> anyhow::Result<Option<String>> {
+    let query = match format {
+        ReportFormat::Markdown => {
+            "SELECT CASE WHEN $2 THEN report_markdown ELSE COALESCE(public_report_markdown, report_markdown) END \
+             FROM distributions WHERE id = $1 AND ($2 OR announce_at <= CURRENT_TIMESTAMP)"
+        },
+        ReportFormat::Html => {
+            "SELECT CASE WHEN $2 THEN report_html ELSE COALESCE(public_report_html, report_html) END \
+             FROM distributions WHERE id = $1 AND ($2 OR announce_at <= CURRENT_TIMESTAMP)"
+        },
+    };
+    let report: Option<Option<String>> = sqlx::query_scalar(query)
+        .bind(id)
+        .bind(preview)
+        .fetch_optional(pool)
+        .await
+        .context("Failed to fetch distribution report")?;
+    Ok(report.flatten())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        report::{
+            truncate_address, ui_amount, CostBreakdown, ExplorerLinks, Randomness, ReportFormat, ReportWinner,
+            RoundReport,
+        },
+        token_holder::Winner,
+        validation::{Disqualification, Replacement},
+    };
+    use solana_sdk::{pubkey, signature::Signature};
+
+    fn report() -> RoundReport {
+        RoundReport {
+            signature: Signature::default(),
+            decimals: 9,
+            share_size: 331_000_000_000,
+            burned: 331_000_000_000,
+            holders_number: 1234,
+            randomness: Randomness::Seed([0xab; 32]),
+            winners: vec![
+                ReportWinner {
+                    index: 17,
+                    wallet: pubkey!("De49soBQoHpombVpexCsPEh7Fi5Pfh5fNbhKimhfG28i"),
+                    amount: 331_000_000_000,
+                    ata_created: true,
+                },
+                ReportWinner {
+                    index: 1001,
+                    wallet: pubkey!("EBHnjoKTCn4S27pYsfYesRbnVr3JmAHg6E5JEnrgAqCR"),
+                    amount: 331_000_000_000,
+                    ata_created: false,
+                },
+            ],
+            replacements: vec![Replacement {
+                disqualified: Winner {
+                    index: 3,
+                    wallet: pubkey!("4wZ2E3St33iB5xu9R2Kf6NbMa5pkoeqVNe1SkcFVvoX5"),
+                },
+                reason: Disqualification::Excluded,
+                replacement: Winner {
+                    index: 1001,
+                    wallet: pubkey!("EBHnjoKTCn4S27pYsfYesRbnVr3JmAHg6E5JEnrgAqCR"),
+                },
+            }],
+            costs: CostBreakdown {
+                fee_lamports: 5000,
+                rent_lamports: 2_039_280,
+            },
+        }
+    }
+
+    #[test]
+    fn should_format_ui_amount() {
+        assert_eq!("331", ui_amount(331_000_000_000, 9));
+        assert_eq!("0.5", ui_amount(500_000_000, 9));
+        assert_eq!("1.000000001", ui_amount(1_000_000_001, 9));
+        assert_eq!("42", ui_amount(42, 0));
+    }
+
+    #[test]
+    fn should_restore_stored_randomness() {
+        for randomness in [
+            Randomness::Seed([1; 32]),
+            Randomness::Vrf([2; 64]),
+            Randomness::Committed([3; 64]),
+            Randomness::Dividend,
+        ] {
+            let (kind, bytes) = randomness.to_parts();
+            assert_eq!(randomness, Randomness::from_parts(kind, &bytes).unwrap());
+        }
+        assert!(Randomness::from_parts("vrf", &[2; 32]).is_err());
+    }
+
+    #[test]
+    fn should_render_markdown_report() {
+        let links = ExplorerLinks {
+            cluster: Some("devnet".to_string()),
+        };
+        let markdown = report().render(7, ReportFormat::Markdown, &links, false);
+
+        assert!(markdown.starts_with("## Distribution #7"));
+        assert!(markdown.contains("2 winners received **331** tokens each (662 in total, 331 burned)"));
+        assert!(markdown.contains(
+            "| 1 | 17 | [De49soBQoHpombVpexCsPEh7Fi5Pfh5fNbhKimhfG28i](https://explorer.solana.com/address/\
+             De49soBQoHpombVpexCsPEh7Fi5Pfh5fNbhKimhfG28i?cluster=devnet) | 331 | yes |"
+        ));
+        assert!(markdown.contains("0.000005 SOL transaction fee + 0.00203928 SOL"));
+        assert!(markdown.contains(&format!("`rand::rngs::StdRng seeded with 0x{}`", "ab".repeat(32))));
+        assert!(markdown.contains(
+            "- `4wZ2E3St33iB5xu9R2Kf6NbMa5pkoeqVNe1SkcFVvoX5` (excluded) replaced by \
+             `EBHnjoKTCn4S27pYsfYesRbnVr3JmAHg6E5JEnrgAqCR`"
+        ));
+        assert!(!markdown.contains("{{"));
+    }
+
+    #[test]
+    fn should_render_html_report() {
+        let links = ExplorerLinks { cluster: None };
+        let html = report().render(7, ReportFormat::Html, &links, false);
+
+        assert!(html.contains("<td>1001</td>"));
+        assert!(html.contains("href=\"https://explorer.solana.com/tx/"));
+        assert!(!html.contains("{{"));
+    }
+
+    #[test]
+    fn should_truncate_addresses_in_private_report() {
+        assert_eq!(
+            "De49…G28i",
+            truncate_address(&pubkey!("De49soBQoHpombVpexCsPEh7Fi5Pfh5fNbhKimhfG28i"))
+        );
+
+        let links = ExplorerLinks { cluster: None };
+        for format in [ReportFormat::Markdown, ReportFormat::Html] {
+            let rendered = report().render(7, format, &links, true);
+            assert!(rendered.contains("De49…G28i"));
+            assert!(rendered.contains("4wZ2…voX5"));
+            assert!(!rendered.contains("De49soBQoHpombVpexCsPEh7Fi5Pfh5fNbhKimhfG28i"));
+            assert!(!rendered.contains("EBHnjoKTCn4S27pYsfYesRbnVr3JmAHg6E5JEnrgAqCR"));
+            assert!(!rendered.contains("/address/"));
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use anyhow::Context;
+use std::{fmt::Write, time::Duration};
+
+/// Rows deleted by a pruning pass
+#[derive(Debug, Default)]
+pub struct PruneReport {
+    pub claims: u64,
+}
+
+/// Deletes the rows older than `retention`. Rounds and their disqualifications are the history of the
+/// distributor and are always kept, claims are kept at least for the `claim_period` of the distributor, so a
+/// winner can still fetch the proof of a share which can be claimed
+pub async fn prune(pool: &sqlx::PgPool, retention: Duration, claim_period: Duration) -> anyhow::Result<PruneReport> {
+    let claims = sqlx::query("DELETE FROM claims WHERE created_at < CURRENT_TIMESTAMP - $1 * interval '1 second'")
+        .bind(retention.max(claim_period).as_secs_f64())
+        .execute(pool)
+        .await
+        .context("Failed to prune claims")?
+        .rows_affected();
+    Ok(PruneReport { claims })
+}
+
+/// Total size of every table of the backend including its indexes, in bytes
+pub async fn table_sizes(pool: &sqlx::PgPool) -> anyhow::Result<Vec<(String, i64)>> {
+    sqlx::query_as(
+        "SELECT relname::text, pg_total_relation_size(relid) FROM pg_catalog.pg_statio_user_tables ORDER BY relname",
+    )
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch table sizes")
+}
+
+/// Table sizes in the Prometheus text format
+pub fn render_table_sizes(sizes: &[(String, i64)]) -> String {
+    let mut metrics = String::from(
+        "# HELP distributor_table_size_bytes Size of the database table including its indexes\n\
+         # TYPE distributor_table_size_bytes gauge\n",
+    );
+    for (table, size) in sizes {
+        let _ = writeln!(metrics, "distributor_table_size_bytes{{table=\"{table}\"}} {size}");
+    }
+    metrics
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::retention::render_table_sizes;
+
+    #[test]
+    fn should_render_table_sizes() {
+        let rendered = render_table_sizes(&[("claims".to_string(), 8192), ("distributions".to_string(), 16384)]);
+        assert!(rendered.contains("# TYPE distributor_table_size_bytes gauge\n"));
+        assert!(rendered.contains("distributor_table_size_bytes{table=\"claims\"} 8192\n"));
+        assert!(rendered.contains("distributor_table_size_bytes{table=\"distributions\"} 16384\n"));
+    }
+}
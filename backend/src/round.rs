@@ -0,0 +1,241 @@
+use crate::{
+    features::Features,
+    latency::{store_timeline, RoundTimeline},
+    pipeline::{pipeline, Distributor, RoundTransactions},
+    report::{store_report, CostBreakdown, ExplorerLinks, Randomness, ReportWinner, RoundReport},
+    shadow_draw::{store_draw_diff, DrawDiff},
+    token_holder::Winner,
+    validation::{store_replacements, Replacement},
+};
+use anyhow::Context;
+use async_trait::async_trait;
+use distributor::DistributorState;
+use solana_sdk::{pubkey::Pubkey, signature::Signature};
+use std::time::{Duration, SystemTime};
+
+/// Whether a round of the campaign can be paid now, before the vault balance is considered
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Readiness {
+    Ready,
+    Paused,
+    /// The minimal interval since the previous round isn't over until `next_round_at`
+    TooEarly {
+        next_round_at: i64,
+    },
+}
+
+impl Readiness {
+    /// Readiness of the distributor, it is checked before anything else of the round is fetched
+    pub fn of(distributor_state: &DistributorState, now: i64) -> Self {
+        let next_round_at = distributor_state
+            .last_round_at
+            .saturating_add(distributor_state.min_interval_seconds);
+        readiness(distributor_state.paused, next_round_at, now)
+    }
+}
+
+/// Campaign a round is paid for: the distributor state and the features resolved when the round started
+pub struct Campaign<'a> {
+    pub distributor_state: &'a DistributorState,
+    pub features: Features,
+}
+
+impl Campaign<'_> {
+    /// Pays out the rounds the way the distribution mode requires
+    pub fn pipeline(&self) -> Box<dyn Distributor> {
+        pipeline(self.distributor_state.distribution_mode, self.features)
+    }
+
+    /// Part of the burned shares which is burned
+    pub fn burned(&self) -> anyhow::Result<u64> {
+        let terminal_amount = self
+            .distributor_state
+            .terminal_amount()
+            .context("Terminal amount overflows")?;
+        let (burned, ..) = self.distributor_state.remainder_policy.apply(terminal_amount);
+        Ok(burned)
+    }
+}
+
+fn readiness(paused: bool, next_round_at: i64, now: i64) -> Readiness {
+    if paused {
+        Readiness::Paused
+    } else if now < next_round_at {
+        Readiness::TooEarly { next_round_at }
+    } else {
+        Readiness::Ready
+    }
+}
+
+/// Stages of a round, `Round::pay` moves it forward until it is paid or rescheduled
+pub enum RoundStage {
+    /// The round is due, its winners aren't drawn yet
+    Triggered,
+    /// The winners are drawn and the transactions paying them are built
+    Drawn {
+        draw: Draw,
+        transactions: RoundTransactions,
+    },
+    /// The transactions paying the winners are confirmed
+    Paid { draw: Draw, payout: Payout },
+    /// The round is left to a later run: its draw isn't revealed yet or its transactions may still land
+    Rescheduled,
+}
+
+/// Transitions of a round between its stages, they are carried out by the service
+#[async_trait]
+pub trait RoundTransitions: Sync {
+    /// `Triggered` to `Drawn`, or to `Rescheduled` if the winners can't be drawn yet
+    async fn draw(&self, round: &mut Round<'_>) -> anyhow::Result<RoundStage>;
+
+    /// `Drawn` to `Paid`, or to `Rescheduled` if the transactions may land later
+    async fn send(
+        &self,
+        round: &mut Round<'_>,
+        draw: Draw,
+        transactions: RoundTransactions,
+    ) -> anyhow::Result<RoundStage>;
+
+    /// Triggers the round again after a delay, the delay counts against the latency since `received_at`
+    fn reschedule(&self, received_at: SystemTime);
+}
+
+/// Round of a campaign from its trigger until its winners are paid
+pub struct Round<'a> {
+    pub campaign: &'a Campaign<'a>,
+    pub pipeline: &'a dyn Distributor,
+    pub compute_unit_price: u64,
+    pub timeline: RoundTimeline,
+    /// Audit trail the drawn winners are recorded in, `None` if the trigger of the round wasn't recorded
+    pub audit_id: Option<i64>,
+    /// A preview round is only drawn and built, nothing is sent or persisted ahead of it
+    pub preview: bool,
+}
+
+impl Round<'_> {
+    /// Moves the round through its stages, `None` if it is rescheduled
+    pub async fn pay(&mut self, transitions: &impl RoundTransitions) -> anyhow::Result<Option<(Draw, Payout)>> {
+        let mut stage = RoundStage::Triggered;
+        loop {
+            stage = match stage {
+                RoundStage::Triggered => transitions.draw(self).await?,
+                RoundStage::Drawn { draw, transactions } => transitions.send(self, draw, transactions).await?,
+                RoundStage::Paid { draw, payout } => return Ok(Some((draw, payout))),
+                RoundStage::Rescheduled => {
+                    transitions.reschedule(self.timeline.received_at());
+                    return Ok(None);
+                },
+            };
+        }
+    }
+}
+
+/// Winners of a round once the disqualified ones are replaced
+pub struct Draw {
+    pub holders_number: u64,
+    /// Randomness the winners were drawn with
+    pub randomness: Randomness,
+    pub winners: Vec<Winner>,
+    /// Amount every winner is paid in the winners order
+    pub amounts: Vec<u64>,
+    /// Drawn winners which were disqualified and their replacements
+    pub replacements: Vec<Replacement>,
+    /// Candidate strategy drawn in the shadow of the paid draw
+    pub shadow: Option<DrawDiff>,
+}
+
+/// Confirmed transactions of a round and what they cost the payer
+pub struct Payout {
+    /// Signature of the last transaction of the round
+    pub signature: Signature,
+    pub fee_lamports: u64,
+    pub ata_rent: u64,
+    /// Whether the round created the token account of the winner, in the winners order
+    pub atas_created: Vec<bool>,
+}
+
+impl Draw {
+    /// Report of the round the winners were paid by
+    pub fn into_report(
+        self,
+        campaign: &Campaign,
+        payout: Payout,
+        decimals: u8,
+    ) -> anyhow::Result<(RoundReport, Option<DrawDiff>)> {
+        let share_size = campaign.distributor_state.share_size;
+        let winners = self
+            .winners
+            .into_iter()
+            .zip(self.amounts)
+            .zip(payout.atas_created)
+            .map(|((winner, amount), ata_created)| ReportWinner {
+                index: winner.index,
+                wallet: winner.wallet,
+                amount,
+                ata_created,
+            })
+            .collect::<Vec<_>>();
+        let atas_created = winners.iter().filter(|winner| winner.ata_created).count() as u64;
+        let report = RoundReport {
+            signature: payout.signature,
+            decimals,
+            share_size,
+            burned: campaign.burned()?,
+            holders_number: self.holders_number,
+            randomness: self.randomness,
+            winners,
+            replacements: self.replacements,
+            costs: CostBreakdown {
+                fee_lamports: payout.fee_lamports,
+                rent_lamports: payout.ata_rent * atas_created,
+            },
+        };
+        Ok((report, self.shadow))
+    }
+}
+
+/// Stores the paid rounds of a campaign
+pub struct RoundRepository<'a> {
+    pub pool: &'a sqlx::PgPool,
+    pub distributor_state: &'a Pubkey,
+    pub explorer: &'a ExplorerLinks,
+}
+
+impl RoundRepository<'_> {
+    /// Stores the report and everything recorded along the round, returns the id of the round. Only a failure to
+    /// store the report fails, the rest is logged
+    pub async fn store(
+        &self,
+        report: &RoundReport,
+        timeline: &RoundTimeline,
+        shadow: Option<&DrawDiff>,
+        announcement_delay: Duration,
+        private: bool,
+    ) -> anyhow::Result<i64> {
+        let id = store_report(self.pool, report, self.explorer, announcement_delay, private).await?;
+        if let Err(err) = store_replacements(self.pool, id, &report.replacements).await {
+            tracing::warn!(%err, "Failed to store replaced winners");
+        }
+        if let Err(err) = store_timeline(self.pool, id, timeline).await {
+            tracing::warn!(%err, "Failed to store round timeline");
+        }
+        if let Some(shadow) = shadow {
+            if let Err(err) = store_draw_diff(self.pool, self.distributor_state, id, shadow).await {
+                tracing::warn!(%err, "Failed to store shadow draw");
+            }
+        }
+        Ok(id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::round::{readiness, Readiness};
+
+    #[test]
+    fn should_wait_for_the_interval_unless_paused() {
+        assert_eq!(Readiness::Paused, readiness(true, 0, 100));
+        assert_eq!(Readiness::TooEarly { next_round_at: 150 }, readiness(false, 150, 100));
+        assert_eq!(Readiness::Ready, readiness(false, 100, 100));
+    }
+}
@@ -0,0 +1,132 @@
+use crate::shadow_draw::DrawStrategy;
+use anyhow::{ensure, Context};
+use serde::Serialize;
+use serde_with::{serde_as, DisplayFromStr};
+use solana_sdk::pubkey::Pubkey;
+use std::{collections::HashSet, time::Duration};
+
+/// Settings which can be changed without a restart. The secrets are the defaults, rows of the `runtime_config`
+/// table override them once the config is reloaded. The actor applies a reload between the rounds only
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RuntimeConfig {
+    #[serde_as(as = "HashSet<DisplayFromStr>")]
+    pub excluded_wallets: HashSet<Pubkey>,
+    /// Campaign the winners are drawn from, token holders are drawn if not set
+    pub ticket_campaign: Option<String>,
+    #[serde(rename = "announcement_delay_secs", serialize_with = "serialize_secs")]
+    pub announcement_delay: Duration,
+    /// Candidate strategy drawn next to the paid draw of the holders, nothing is shadowed if not set
+    pub shadow_draw_strategy: Option<DrawStrategy>,
+    /// Percentage of the rounds the candidate strategy is drawn for
+    pub shadow_draw_percentage: u8,
+}
+
+impl RuntimeConfig {
+    /// Applies the overrides by setting name, the values are formatted as the secrets of the same name. Unknown
+    /// names are skipped, an invalid value rejects the whole reload
+    pub fn with_overrides(mut self, overrides: impl IntoIterator<Item = (String, String)>) -> anyhow::Result<Self> {
+        for (name, value) in overrides {
+            match name.as_str() {
+                "EXCLUDED_WALLETS" => {
+                    self.excluded_wallets = value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|wallet| !wallet.is_empty())
+                        .map(|wallet| wallet.parse())
+                        .collect::<Result<_, _>>()
+                        .context("Can't deserialize EXCLUDED_WALLETS")?;
+                },
+                "TICKET_CAMPAIGN" => self.ticket_campaign = Some(value).filter(|campaign| !campaign.is_empty()),
+                "ANNOUNCEMENT_DELAY_SECS" => {
+                    self.announcement_delay =
+                        Duration::from_secs(value.parse().context("Can't deserialize ANNOUNCEMENT_DELAY_SECS")?);
+                },
+                "SHADOW_DRAW_STRATEGY" => {
+                    self.shadow_draw_strategy = Some(value)
+                        .filter(|strategy| !strategy.is_empty())
+                        .map(|strategy| strategy.parse())
+                        .transpose()
+                        .context("Can't deserialize SHADOW_DRAW_STRATEGY")?;
+                },
+                "SHADOW_DRAW_PERCENTAGE" => {
+                    let percentage = value.parse().context("Can't deserialize SHADOW_DRAW_PERCENTAGE")?;
+                    ensure!(percentage <= 100, "SHADOW_DRAW_PERCENTAGE can't exceed 100");
+                    self.shadow_draw_percentage = percentage;
+                },
+                _ => tracing::debug!(%name, "Unknown runtime setting"),
+            }
+        }
+        Ok(self)
+    }
+}
+
+/// Runtime config of the campaign with its overrides from the database applied to `defaults`
+pub async fn load_runtime_config(
+    pool: &sqlx::PgPool,
+    distributor_state: &Pubkey,
+    defaults: RuntimeConfig,
+) -> anyhow::Result<RuntimeConfig> {
+    let overrides: Vec<(String, String)> =
+        sqlx::query_as("SELECT name, value FROM runtime_config WHERE distributor_state = $1")
+            .bind(distributor_state.to_string())
+            .fetch_all(pool)
+            .await
+            .context("Failed to fetch runtime config")?;
+    defaults.with_overrides(overrides)
+}
+
+fn serialize_secs<S: serde::Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(duration.as_secs())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{runtime_config::RuntimeConfig, shadow_draw::DrawStrategy};
+    use solana_sdk::pubkey::Pubkey;
+    use std::{collections::HashSet, time::Duration};
+
+    fn defaults() -> RuntimeConfig {
+        RuntimeConfig {
+            excluded_wallets: HashSet::new(),
+            ticket_campaign: Some("spring".to_string()),
+            announcement_delay: Duration::ZERO,
+            shadow_draw_strategy: None,
+            shadow_draw_percentage: 100,
+        }
+    }
+
+    #[test]
+    fn should_override_runtime_settings() {
+        let wallet = Pubkey::new_unique();
+        let config = defaults()
+            .with_overrides([
+                ("EXCLUDED_WALLETS".to_string(), format!(" {wallet}, ")),
+                ("TICKET_CAMPAIGN".to_string(), String::new()),
+                ("ANNOUNCEMENT_DELAY_SECS".to_string(), "600".to_string()),
+                ("SHADOW_DRAW_STRATEGY".to_string(), "distinct".to_string()),
+                ("SHADOW_DRAW_PERCENTAGE".to_string(), "25".to_string()),
+                ("PAYER_KEYPAIR".to_string(), "secret".to_string()),
+            ])
+            .unwrap();
+
+        assert_eq!(
+            RuntimeConfig {
+                excluded_wallets: HashSet::from([wallet]),
+                ticket_campaign: None,
+                announcement_delay: Duration::from_secs(600),
+                shadow_draw_strategy: Some(DrawStrategy::Distinct),
+                shadow_draw_percentage: 25,
+            },
+            config
+        );
+    }
+
+    #[test]
+    fn should_reject_invalid_overrides() {
+        let config = defaults().with_overrides([("ANNOUNCEMENT_DELAY_SECS".to_string(), "soon".to_string())]);
+        assert!(config.is_err());
+        let config = defaults().with_overrides([("SHADOW_DRAW_PERCENTAGE".to_string(), "150".to_string())]);
+        assert!(config.is_err());
+    }
+}
@@ -0,0 +1,345 @@
+use crate::{
+    priority_fee::{fetch_recent_priority_fee, PriorityLevel},
+    service::fetch_distributor_state,
+    token_holder::HeliusClient,
+};
+use anyhow::{anyhow, ensure, Context};
+use distributor::DistributorState;
+use jsonrpsee::http_client::HttpClient;
+use serde::Serialize;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{native_token::lamports_to_sol, program_pack::Pack, pubkey::Pubkey};
+use spl_token::state::Account as TokenAccount;
+use std::{collections::HashSet, fmt, future::Future, time::Duration};
+
+/// Check of the readiness probe which doesn't answer in time fails, so the probe answers before the load balancer
+/// gives up on it
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct CheckResult {
+    pub name: &'static str,
+    /// Startup fails if a critical check doesn't pass
+    pub critical: bool,
+    pub passed: bool,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SelfCheckReport {
+    pub ready: bool,
+    pub checks: Vec<CheckResult>,
+}
+
+impl SelfCheckReport {
+    pub fn new(checks: Vec<CheckResult>) -> Self {
+        let ready = checks.iter().all(|check| check.passed || !check.critical);
+        Self { ready, checks }
+    }
+
+    pub fn critical_failures(&self) -> impl Iterator<Item = &CheckResult> {
+        self.checks.iter().filter(|check| check.critical && !check.passed)
+    }
+}
+
+impl fmt::Display for SelfCheckReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for check in &self.checks {
+            let status = match (check.passed, check.critical) {
+                (true, _) => "ok",
+                (false, true) => "FAILED",
+                (false, false) => "warning",
+            };
+            writeln!(f, "[{status}] {}: {}", check.name, check.message)?;
+        }
+        Ok(())
+    }
+}
+
+/// Everything the backend depends on, checked once on boot so misconfigurations are reported before the first round
+pub struct SelfCheck<'a> {
+    pub rpc_client: &'a RpcClient,
+    pub program_id: Pubkey,
+    pub distributor_state: Pubkey,
+    pub distributor_authority: Pubkey,
+    pub service_signer: Option<Pubkey>,
+    pub payer: Pubkey,
+    pub payer_balance_floor: u64,
+    pub helius_client: &'a HeliusClient,
+    pub priority_fee: &'a HttpClient,
+    pub priority_level: PriorityLevel,
+    pub pool: &'a sqlx::PgPool,
+}
+
+impl SelfCheck<'_> {
+    pub async fn run(&self) -> SelfCheckReport {
+        let mut checks = vec![check("program", true, self.check_program()).await];
+
+        let (distributor_state, vault) = match self.check_distributor_state().await {
+            Ok(distributor_state) => (
+                Ok(format!("Distributor state {} is valid", self.distributor_state)),
+                self.check_vault(&distributor_state).await,
+            ),
+            Err(err) => (Err(err), Err(anyhow!("Skipped, distributor state is invalid"))),
+        };
+        checks.push(result("distributor_state", true, distributor_state));
+        checks.push(result("vault", true, vault));
+
+        checks.push(check("payer_balance", true, self.check_payer_balance()).await);
+        checks.push(check("helius", true, self.check_helius()).await);
+        checks.push(check("priority_fee", false, self.check_priority_fee()).await);
+        checks.push(check("migrations", true, self.check_migrations()).await);
+
+        SelfCheckReport::new(checks)
+    }
+
+    async fn check_program(&self) -> anyhow::Result<String> {
+        ensure!(
+            self.program_id == distributor::ID,
+            "PROGRAM_ID {} doesn't match the program id {} the backend is built with",
+            self.program_id,
+            distributor::ID
+        );
+        let account = self
+            .rpc_client
+            .get_account(&self.program_id)
+            .await
+            .with_context(|| format!("Program {} not found, check SOLANA_RPC_URL cluster", self.program_id))?;
+        ensure!(
+            account.executable,
+            "Account {} is not an executable program",
+            self.program_id
+        );
+        Ok(format!("Program {} is deployed", self.program_id))
+    }
+
+    async fn check_distributor_state(&self) -> anyhow::Result<DistributorState> {
+        let distributor_state = fetch_distributor_state(self.rpc_client, &self.distributor_state)
+            .await
+            .context("Check DISTRIBUTOR_STATE")?;
+        ensure!(
+            distributor_state.distributor_authority == self.distributor_authority,
+            "Distributor authority mismatch: {} vs {}, check DISTRIBUTOR_AUTHORITY_KEYPAIR",
+            distributor_state.distributor_authority,
+            self.distributor_authority
+        );
+        if let Some(service_signer) = distributor_state.service_signer {
+            ensure!(
+                self.service_signer == Some(service_signer.key),
+                "Distributor requires the service signer {}, check SERVICE_SIGNER_KEYPAIR",
+                service_signer.key
+            );
+        }
+        Ok(distributor_state)
+    }
+
+    async fn check_vault(&self, distributor_state: &DistributorState) -> anyhow::Result<String> {
+        let account = self
+            .rpc_client
+            .get_account(&distributor_state.vault)
+            .await
+            .with_context(|| format!("Vault {} not found", distributor_state.vault))?;
+        ensure!(
+            account.owner == spl_token::ID,
+            "Vault {} is owned by {} instead of the token program",
+            distributor_state.vault,
+            account.owner
+        );
+        let vault = TokenAccount::unpack(&account.data).context("Failed to unpack vault account")?;
+        ensure!(
+            vault.mint == distributor_state.mint,
+            "Vault mint {} doesn't match distributor mint {}",
+            vault.mint,
+            distributor_state.mint
+        );
+        ensure!(
+            vault.owner == self.distributor_state,
+            "Vault owner {} isn't the distributor state",
+            vault.owner
+        );
+        ensure!(
+            !vault.is_frozen(),
+            "Vault {} is frozen by the mint freeze authority",
+            distributor_state.vault
+        );
+        Ok(format!(
+            "Vault {} holds {} base units",
+            distributor_state.vault, vault.amount
+        ))
+    }
+
+    async fn check_payer_balance(&self) -> anyhow::Result<String> {
+        check_payer_balance(self.rpc_client, &self.payer, self.payer_balance_floor).await
+    }
+
+    async fn check_helius(&self) -> anyhow::Result<String> {
+        self.helius_client
+            .ping()
+            .await
+            .context("Helius API rejected the request, check the api-key in SOLANA_RPC_URL")?;
+        Ok("Helius API is reachable".to_string())
+    }
+
+    async fn check_priority_fee(&self) -> anyhow::Result<String> {
+        let fee = fetch_recent_priority_fee(self.priority_fee, self.priority_level)
+            .await
+            .context("Priority fee estimate failed, check PRIORITY_FEE_URL")?;
+        Ok(format!("Recent priority fee is {fee} micro-lamports"))
+    }
+
+    async fn check_migrations(&self) -> anyhow::Result<String> {
+        let applied: HashSet<i64> = sqlx::query_scalar("SELECT version FROM _sqlx_migrations WHERE success")
+            .fetch_all(self.pool)
+            .await
+            .context("Failed to fetch applied migrations")?
+            .into_iter()
+            .collect();
+        let pending: Vec<_> = sqlx::migrate!()
+            .iter()
+            .filter(|migration| !migration.migration_type.is_down_migration())
+            .filter(|migration| !applied.contains(&migration.version))
+            .map(|migration| migration.description.to_string())
+            .collect();
+        ensure!(pending.is_empty(), "Migrations are not applied: {}", pending.join(", "));
+        Ok(format!("{} migrations are applied", applied.len()))
+    }
+}
+
+/// What the backend needs to pay the next round, checked on every `/readyz` request. Unlike the boot self-check it
+/// only covers what can break while the backend runs
+pub struct ReadinessProbe {
+    pub rpc_client: RpcClient,
+    pub distributor_state: Pubkey,
+    pub payer: Pubkey,
+    pub payer_balance_floor: u64,
+    pub pool: sqlx::PgPool,
+}
+
+impl ReadinessProbe {
+    pub async fn run(&self) -> SelfCheckReport {
+        let (rpc, database, distributor_state, payer_balance) = tokio::join!(
+            probe("rpc", PROBE_TIMEOUT, self.check_rpc()),
+            probe("database", PROBE_TIMEOUT, self.check_database()),
+            probe("distributor_state", PROBE_TIMEOUT, self.check_distributor_state()),
+            probe(
+                "payer_balance",
+                PROBE_TIMEOUT,
+                check_payer_balance(&self.rpc_client, &self.payer, self.payer_balance_floor)
+            ),
+        );
+        SelfCheckReport::new(vec![rpc, database, distributor_state, payer_balance])
+    }
+
+    async fn check_rpc(&self) -> anyhow::Result<String> {
+        let slot = self.rpc_client.get_slot().await.context("RPC is unreachable")?;
+        Ok(format!("RPC is at slot {slot}"))
+    }
+
+    async fn check_database(&self) -> anyhow::Result<String> {
+        sqlx::query("SELECT 1")
+            .execute(&self.pool)
+            .await
+            .context("Database is unreachable")?;
+        Ok("Database is reachable".to_string())
+    }
+
+    async fn check_distributor_state(&self) -> anyhow::Result<String> {
+        let distributor_state = fetch_distributor_state(&self.rpc_client, &self.distributor_state).await?;
+        Ok(format!(
+            "Distributor state {} is at round {}",
+            self.distributor_state, distributor_state.round
+        ))
+    }
+}
+
+async fn check_payer_balance(rpc_client: &RpcClient, payer: &Pubkey, floor: u64) -> anyhow::Result<String> {
+    let balance = rpc_client
+        .get_balance(payer)
+        .await
+        .context("Failed to fetch payer balance")?;
+    ensure!(
+        balance >= floor,
+        "Payer {} balance {} SOL is below {} SOL, top it up or lower PAYER_BALANCE_FLOOR",
+        payer,
+        lamports_to_sol(balance),
+        lamports_to_sol(floor)
+    );
+    Ok(format!("Payer {} balance is {} SOL", payer, lamports_to_sol(balance)))
+}
+
+/// Critical check which fails if it doesn't answer within `timeout`
+async fn probe(
+    name: &'static str,
+    timeout: Duration,
+    check: impl Future<Output = anyhow::Result<String>>,
+) -> CheckResult {
+    let result = tokio::time::timeout(timeout, check)
+        .await
+        .unwrap_or_else(|_| Err(anyhow!("Timed out after {}ms", timeout.as_millis())));
+    self::result(name, true, result)
+}
+
+async fn check(name: &'static str, critical: bool, check: impl Future<Output = anyhow::Result<String>>) -> CheckResult {
+    result(name, critical, check.await)
+}
+
+fn result(name: &'static str, critical: bool, result: anyhow::Result<String>) -> CheckResult {
+    let (passed, message) = match result {
+        Ok(message) => (true, message),
+        Err(err) => (false, format!("{err:#}")),
+    };
+    CheckResult {
+        name,
+        critical,
+        passed,
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::self_check::{probe, result, SelfCheckReport};
+    use anyhow::anyhow;
+    use std::time::Duration;
+
+    #[test]
+    fn should_be_ready_with_failed_optional_checks() {
+        let report = SelfCheckReport::new(vec![
+            result("program", true, Ok("Program is deployed".to_string())),
+            result("priority_fee", false, Err(anyhow!("unreachable"))),
+        ]);
+        assert!(report.ready);
+        assert_eq!(0, report.critical_failures().count());
+    }
+
+    #[test]
+    fn should_not_be_ready_with_failed_critical_checks() {
+        let report = SelfCheckReport::new(vec![
+            result("program", true, Ok("Program is deployed".to_string())),
+            result(
+                "payer_balance",
+                true,
+                Err(anyhow!("Payer balance is too low").context("Check PAYER_KEYPAIR")),
+            ),
+        ]);
+        assert!(!report.ready);
+        assert_eq!(
+            "[ok] program: Program is deployed\n[FAILED] payer_balance: Check PAYER_KEYPAIR: Payer balance is too low\n",
+            report.to_string()
+        );
+    }
+
+    #[tokio::test]
+    async fn should_fail_probe_which_times_out() {
+        let check = probe("rpc", Duration::from_millis(10), std::future::pending()).await;
+        assert!(check.critical);
+        assert!(!check.passed);
+        assert_eq!("Timed out after 10ms", check.message);
+
+        let check = probe("rpc", Duration::from_millis(10), async {
+            Ok("RPC is at slot 1".to_string())
+        })
+        .await;
+        assert!(check.passed);
+    }
+}
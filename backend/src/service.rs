@@ -1,30 +1,72 @@
 use crate::{
-    priority_fee::fetch_recent_priority_fee, token_holder::HeliusClient,
+    alerting::{Alerter, Signal},
+    audit::RoundAudit,
+    confirmation::{durable_nonce_account, record_transaction, send_and_track, PendingRound, PendingRounds, TxOutcome},
+    dry_run::{preview_winners, DryRun, DryRunTotals, TransactionSimulation},
+    features::{resolve_features, Features},
+    latency::{LatencyMetrics, RoundTimeline, Stage},
+    lookup_table::LookupTable,
+    notifications::{announcement, Notifier},
+    pipeline::{
+        extra_mint_transactions, fetch_vault_config, fetch_vault_configs, pipeline, request_draw_transaction,
+        reveal_draw_transaction, transaction_signers, validate_winners, RoundTransactions, MAX_COMPUTE_UNIT_LIMIT,
+    },
+    priority_fee::PriorityFeeConfig,
+    program_error::{ProgramFailure, Remediation},
+    report::{ExplorerLinks, Randomness, RoundReport},
+    retention::{prune, PruneReport},
+    round::{Campaign, Draw, Payout, Readiness, Round, RoundRepository, RoundStage, RoundTransitions},
+    runtime_config::{load_runtime_config, RuntimeConfig},
+    shadow_draw::{in_canary, repeats, DrawDiff, DrawStrategy},
+    ticket::{TicketEntry, TicketSnapshot},
+    token_holder::{HeliusClient, Winner},
     transaction_status::EncodedConfirmedTransactionWithStatusMeta,
+    validation::{
+        disqualification, fetch_blocked_wallets, fetch_flagged_wallets, fetch_previous_winners, Disqualification,
+        Replacement,
+    },
+    vault_monitor::{fetch_vault, VaultAnomaly},
+    what_if::{what_if, Weighting, WhatIfParams, WhatIfReport},
 };
 use anchor_client::{
     anchor_lang::prelude::{AccountMeta, Pubkey},
     Program,
 };
-use anyhow::{anyhow, bail, Context};
-use distributor::DistributorState;
+use anyhow::{anyhow, bail, ensure, Context};
+use async_trait::async_trait;
+use distributor::{draw::DrawIndices, DistributionMode, DistributionRound, DistributorState, VaultConfig};
+use itertools::Itertools;
 use jsonrpsee::http_client::HttpClient;
+use rand::{
+    rngs::{OsRng, StdRng},
+    Rng, SeedableRng,
+};
+use serde::Serialize;
+use solana_client::{nonblocking::rpc_client::RpcClient, rpc_config::RpcSimulateTransactionConfig};
 use solana_sdk::{
-    compute_budget::ComputeBudgetInstruction,
+    commitment_config::CommitmentConfig,
+    message::VersionedMessage,
+    packet::PACKET_DATA_SIZE,
     program_pack::Pack,
-    signature::{Keypair, Signer},
-    transaction::Transaction,
+    signature::{Keypair, Signature, Signer},
+    transaction::{TransactionError, VersionedTransaction},
 };
 use solana_transaction_status::{
     option_serializer::OptionSerializer, EncodedTransaction, UiMessage, UiRawMessage, UiTransaction,
 };
-use spl_associated_token_account::get_associated_token_address;
+use spl_associated_token_account::get_associated_token_address_with_program_id;
 use spl_token::state::Account as TokenAccount;
-use std::{str::FromStr, sync::Arc};
+use std::{
+    collections::{hash_map::Entry, HashMap, HashSet},
+    str::FromStr,
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
 use tokio::sync::{
     mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
-    Mutex,
+    oneshot, Mutex, MutexGuard,
 };
+use tracing::Instrument;
 
 pub struct AppState {
     pub program: Program<Arc<Keypair>>,
@@ -32,127 +74,1606 @@ pub struct AppState {
     pub distributor_state: DistributorState,
     pub helius_client: Mutex<HeliusClient>,
     pub priority_fee: HttpClient,
+    /// Level and caps of the compute unit price of the round transactions
+    pub priority_fee_config: PriorityFeeConfig,
     pub payer: Keypair,
     pub distributor_authority: Keypair,
+    /// Backend identity signing the rounds of a distributor which requires it
+    pub service_signer: Option<Keypair>,
     pub memo: String,
+    pub pool: sqlx::PgPool,
+    pub mint_decimals: u8,
+    /// Token program of the mint, the legacy one or Token-2022
+    pub token_program: Pubkey,
+    /// Transfer hook accounts of a Token-2022 mint passed to the round transactions, see `transfer_hook_accounts`
+    pub transfer_hook_accounts: Vec<AccountMeta>,
+    pub explorer: ExplorerLinks,
+    pub excluded_wallets: HashSet<Pubkey>,
+    /// Draw the winners from the tickets of the campaign instead of the token holders
+    pub ticket_campaign: Option<String>,
+    pub announcement_delay: Duration,
+    /// Features of the campaign unless the `features` table overrides them
+    pub features: Features,
+    /// Refresh the holders number in the background between the rounds, disabled if not set
+    pub holders_refresh_interval: Option<Duration>,
+    /// Inspect the vault for tampering in the background between the rounds, disabled if not set
+    pub vault_inspection_interval: Option<Duration>,
+    /// Promised time from the trigger of a round to its confirmation, slower rounds are alerted
+    pub latency_slo: Duration,
+    /// Sends of a round transaction, each expired one is signed again with a fresh blockhash
+    pub max_send_attempts: u32,
+    /// Lookup table of the accounts of the single transaction rounds, legacy transactions are sent if not set
+    pub lookup_table: Option<LookupTable>,
+    /// Durable nonce account the single transaction rounds are signed against, a recent blockhash if not set
+    pub nonce_account: Option<Pubkey>,
+    /// Prune the database rows older than this in the background, disabled if not set
+    pub retention: Option<Duration>,
+    /// Announces the rounds once their reports are public, disabled if not set
+    pub notifier: Option<Arc<Notifier>>,
+    /// Candidate strategy drawn in the shadow of the paid draws of the holders, disabled if not set
+    pub shadow_draw_strategy: Option<DrawStrategy>,
+    /// Percentage of the rounds the candidate strategy is drawn for
+    pub shadow_draw_percentage: u8,
+    pub alerter: Arc<Alerter>,
+}
+
+/// Rng of a draw seeded from the OS, the seed is reported so the draw can be reproduced. Redraws continue the same
+/// sequence
+struct SeededRng {
+    seed: [u8; 32],
+    rng: std::sync::Mutex<StdRng>,
+}
+
+impl SeededRng {
+    fn new() -> Self {
+        let seed = OsRng.gen();
+        Self {
+            seed,
+            rng: std::sync::Mutex::new(StdRng::from_seed(seed)),
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, StdRng> {
+        self.rng.lock().expect("Draw rng is poisoned")
+    }
+}
+
+/// Where the winners of a round are drawn from
+enum Entrants<'a> {
+    Holders {
+        helius_client: MutexGuard<'a, HeliusClient>,
+        rng: SeededRng,
+    },
+    /// Holders at the indices derived from the VRF randomness of the round, redraws continue the same sequence
+    VerifiableHolders {
+        helius_client: MutexGuard<'a, HeliusClient>,
+        randomness: [u8; 64],
+        indices: std::sync::Mutex<Box<dyn Iterator<Item = u64> + Send>>,
+    },
+    Tickets {
+        snapshot: TicketSnapshot,
+        rng: SeededRng,
+    },
+    /// Winners verified on-chain, of the committed draw or of the VRF draw of a distributor requiring one. They
+    /// can't be replaced
+    Committed {
+        entrants: u64,
+        randomness: Randomness,
+        winners: std::sync::Mutex<Vec<Winner>>,
+    },
+    /// Holders from the largest marker balance down, a dividend round pays the first ones and replaces them by the
+    /// next ones
+    Dividend {
+        entrants: u64,
+        holders: std::sync::Mutex<Vec<Winner>>,
+    },
+}
+
+impl Entrants<'_> {
+    fn number(&self) -> u64 {
+        match self {
+            Entrants::Holders { helius_client, .. } | Entrants::VerifiableHolders { helius_client, .. } => {
+                helius_client.holders_number()
+            },
+            Entrants::Tickets { snapshot, .. } => snapshot.entries_number(),
+            Entrants::Committed { entrants, .. } | Entrants::Dividend { entrants, .. } => *entrants,
+        }
+    }
+
+    /// Randomness the winners are drawn with
+    fn randomness(&self) -> Randomness {
+        match self {
+            Entrants::Holders { rng, .. } | Entrants::Tickets { rng, .. } => Randomness::Seed(rng.seed),
+            Entrants::VerifiableHolders { randomness, .. } => Randomness::Vrf(*randomness),
+            Entrants::Committed { randomness, .. } => *randomness,
+            Entrants::Dividend { .. } => Randomness::Dividend,
+        }
+    }
+
+    async fn draw_winners(&self, n: u64) -> anyhow::Result<Vec<Winner>> {
+        match self {
+            Entrants::Holders { helius_client, rng } => {
+                let indices = helius_client.draw_indices(&mut *rng.lock(), n);
+                helius_client.winners_at(indices).await
+            },
+            Entrants::VerifiableHolders {
+                helius_client, indices, ..
+            } => {
+                let indices = indices
+                    .lock()
+                    .expect("Draw indices are poisoned")
+                    .by_ref()
+                    .take(n as usize)
+                    .collect::<Vec<_>>();
+                ensure!(indices.len() as u64 == n, "Not enough entrants to draw {n} winners");
+                let holders_number = helius_client.holders_number();
+                if let Some(index) = indices.iter().find(|index| **index >= holders_number) {
+                    bail!("Drawn index {index} is out of the {holders_number} holders");
+                }
+                helius_client.winners_at(indices).await
+            },
+            Entrants::Tickets { snapshot, rng } => snapshot.draw_winners(&mut *rng.lock(), n),
+            Entrants::Committed { winners, .. } => {
+                let mut winners = winners.lock().expect("Committed winners are poisoned");
+                ensure!(
+                    winners.len() as u64 >= n,
+                    "Winners of the committed draw can't be redrawn"
+                );
+                Ok(winners.drain(..n as usize).collect())
+            },
+            Entrants::Dividend { holders, .. } => {
+                let mut holders = holders.lock().expect("Dividend holders are poisoned");
+                ensure!(holders.len() as u64 >= n, "Not enough holders to pay {n} dividends");
+                Ok(holders.drain(..n as usize).collect())
+            },
+        }
+    }
 }
 
+/// How many times the winners can be redrawn before the round is given up
+const MAX_DISTRIBUTE_ATTEMPTS: usize = 3;
+/// How many times disqualified winners are replaced before the round is given up
+const MAX_REPLACEMENT_ROUNDS: usize = 5;
+const RESCHEDULE_DELAY: Duration = Duration::from_secs(30);
+/// How often the database is pruned if the retention is set
+const PRUNING_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
 struct Actor {
     receiver: UnboundedReceiver<ActorMessage>,
+    sender: UnboundedSender<ActorMessage>,
     state: AppState,
+    /// Anomalies found by the last vault inspection, they are alerted only when they change
+    vault_anomalies: Vec<VaultAnomaly>,
+    latency_metrics: Arc<std::sync::Mutex<LatencyMetrics>>,
+    /// Runtime config of the settings, reloads apply the database overrides to it
+    runtime_defaults: RuntimeConfig,
+}
+
+enum ActorMessage {
+    Distribute {
+        tx: Option<Box<EncodedConfirmedTransactionWithStatusMeta>>,
+        /// Start of the round for the latency tracking, rescheduled rounds keep the time of the original trigger
+        received_at: SystemTime,
+        /// Span of the trigger the round is traced under, rescheduled rounds stay in the trace of the original one
+        span: tracing::Span,
+    },
+    SimulateWorstCase {
+        shares: u64,
+        reply: oneshot::Sender<anyhow::Result<WorstCaseSimulation>>,
+    },
+    DryRun {
+        reply: oneshot::Sender<anyhow::Result<DryRun>>,
+    },
+    WhatIf {
+        params: WhatIfParams,
+        reply: oneshot::Sender<anyhow::Result<WhatIfReport>>,
+    },
+    /// Refresh the holders number between the rounds, so the draw only has to confirm it
+    RefreshHolders,
+    /// Check the vault for tampering between the rounds
+    InspectVault,
+    /// Delete the database rows past the retention
+    Prune,
+    /// Apply the runtime config from the database, queued behind the round in progress so it applies between the
+    /// rounds. SIGHUP doesn't wait for the reply
+    Reload {
+        reply: Option<oneshot::Sender<anyhow::Result<RuntimeConfig>>>,
+    },
 }
 
-struct ActorMessage(Option<EncodedConfirmedTransactionWithStatusMeta>);
+/// Size and compute units of the largest distribute transaction for the share count
+#[derive(Debug, Serialize)]
+pub struct WorstCaseSimulation {
+    pub shares: u64,
+    pub tx_size: usize,
+    pub max_tx_size: usize,
+    pub compute_units: Option<u64>,
+    /// Simulation runs against the current on-chain state, so it fails early (and consumes less units) if the
+    /// share count or the vault balance differ
+    pub simulation_error: Option<String>,
+    /// Whether the transaction fits the packet and the compute unit limit, a failed simulation doesn't prove it
+    pub fits: bool,
+}
 
 impl Actor {
-    pub fn new(receiver: UnboundedReceiver<ActorMessage>, state: AppState) -> Self {
-        Self { receiver, state }
+    pub fn new(
+        receiver: UnboundedReceiver<ActorMessage>,
+        sender: UnboundedSender<ActorMessage>,
+        state: AppState,
+        latency_metrics: Arc<std::sync::Mutex<LatencyMetrics>>,
+    ) -> Self {
+        let runtime_defaults = RuntimeConfig {
+            excluded_wallets: state.excluded_wallets.clone(),
+            ticket_campaign: state.ticket_campaign.clone(),
+            announcement_delay: state.announcement_delay,
+            shadow_draw_strategy: state.shadow_draw_strategy,
+            shadow_draw_percentage: state.shadow_draw_percentage,
+        };
+        Self {
+            receiver,
+            sender,
+            state,
+            vault_anomalies: Vec::new(),
+            latency_metrics,
+            runtime_defaults,
+        }
     }
 
-    pub async fn handle_message(&self, _: Option<EncodedConfirmedTransactionWithStatusMeta>) -> anyhow::Result<()> {
+    pub async fn handle_message(
+        &self,
+        _: Option<EncodedConfirmedTransactionWithStatusMeta>,
+        received_at: SystemTime,
+    ) -> anyhow::Result<()> {
         let rpc_client = self.state.program.async_rpc();
 
-        let data = rpc_client
-            .get_account_data(&self.state.distributor_state.vault)
-            .await
-            .context("Failed to fetch vault balance")?;
+        // The config can be changed by the authority at any time, so always use the latest one. The vault can be
+        // migrated too
+        let distributor_state = fetch_distributor_state(&rpc_client, &self.state.distributor_state_pubkey).await?;
 
-        let vault_account = TokenAccount::unpack(&data).context("Failed to unpack vault account")?;
+        // Rounds are halted until the vault is healthy again, the vault monitor resumes them
+        let (vault_account, anomalies) =
+            fetch_vault(&rpc_client, &self.state.distributor_state_pubkey, &distributor_state).await?;
+        let Some(vault_account) = vault_account.filter(|_| anomalies.is_empty()) else {
+            bail!(
+                "Distribution is halted, vault {} is unhealthy: {}",
+                distributor_state.vault,
+                anomalies.iter().join(", ")
+            );
+        };
 
-        self.distribute_tokens(vault_account.amount)
-            .await
-            .context("Failed to distribute tokens")?;
+        self.distribute_tokens(
+            &distributor_state,
+            vault_account.amount,
+            RoundTimeline::new(received_at),
+        )
+        .await
+        .context("Failed to distribute tokens")?;
 
         Ok(())
     }
 
-    #[tracing::instrument(skip(self))]
-    async fn distribute_tokens(&self, vault_balance: u64) -> anyhow::Result<()> {
-        let threshold = self.state.distributor_state.share_size * self.state.distributor_state.number_of_shares;
+    /// Deletes the rows past the retention, the claims are kept for the claim period the distributor has now, the
+    /// authority may have extended it since the startup
+    async fn prune(&self, retention: Duration) -> anyhow::Result<PruneReport> {
+        let rpc_client = self.state.program.async_rpc();
+        let distributor_state = fetch_distributor_state(&rpc_client, &self.state.distributor_state_pubkey).await?;
+        let claim_period = Duration::from_secs(distributor_state.claim_period.max(0) as u64);
+        prune(&self.state.pool, retention, claim_period).await
+    }
+
+    /// Alerts once the vault becomes unhealthy and resumes the rounds once it recovers
+    async fn inspect_vault(&mut self) -> anyhow::Result<()> {
+        let rpc_client = self.state.program.async_rpc();
+        let distributor_state = fetch_distributor_state(&rpc_client, &self.state.distributor_state_pubkey).await?;
+        let (_, anomalies) = fetch_vault(&rpc_client, &self.state.distributor_state_pubkey, &distributor_state).await?;
+        if anomalies == self.vault_anomalies {
+            return Ok(());
+        }
+
+        if anomalies.is_empty() {
+            tracing::info!(vault = %distributor_state.vault, "Vault is healthy again, resuming distribution");
+            let _ = self.sender.send(ActorMessage::Distribute {
+                tx: None,
+                received_at: SystemTime::now(),
+                span: tracing::Span::current(),
+            });
+        } else {
+            tracing::error!(
+                vault = %distributor_state.vault,
+                anomalies = %anomalies.iter().join(", "),
+                "ALERT: vault is unhealthy, distribution is halted"
+            );
+        }
+        self.vault_anomalies = anomalies;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self, distributor_state, timeline))]
+    async fn distribute_tokens(
+        &self,
+        distributor_state: &DistributorState,
+        vault_balance: u64,
+        timeline: RoundTimeline,
+    ) -> anyhow::Result<()> {
+        match Readiness::of(distributor_state, unix_timestamp()) {
+            Readiness::Ready => {},
+            Readiness::Paused => {
+                tracing::info!("Distributor is paused");
+                return Ok(());
+            },
+            Readiness::TooEarly { next_round_at } => {
+                tracing::info!(%next_round_at, "Minimal interval since the previous round isn't over");
+                // the interval is deliberate, it doesn't count against the latency
+                self.reschedule(None);
+                return Ok(());
+            },
+        }
+        let features = resolve_features(
+            &self.state.pool,
+            &self.state.distributor_state_pubkey,
+            self.state.features,
+        )
+        .await?;
+        let campaign = Campaign {
+            distributor_state,
+            features,
+        };
+        if let Some(pending_withdraw) = &distributor_state.pending_withdraw {
+            tracing::warn!(
+                amount = pending_withdraw.amount,
+                destination = %pending_withdraw.destination,
+                unlocks_at = pending_withdraw.unlocks_at,
+                "ALERT: emergency withdraw from the vault is pending"
+            );
+        }
+
+        let pipeline = campaign.pipeline();
+        tracing::info!(mode = ?distributor_state.distribution_mode, ?features, "Distribution mode");
+
+        let threshold = distributor_state.threshold().context("Threshold overflows")?;
         if vault_balance >= threshold {
             tracing::info!(%threshold, "Threshold reached, distributing");
+        } else if pipeline
+            .round_in_progress(&self.state, distributor_state)
+            .await?
+            .is_some()
+        {
+            tracing::info!(%threshold, "Round is in progress, resuming");
         } else {
             tracing::info!(%threshold, "Threshold isn't reached");
             return Ok(());
         }
 
+        let compute_unit_price = self
+            .state
+            .priority_fee_config
+            .compute_unit_price(&self.state.priority_fee)
+            .await;
+        let audit_id = match self.round_audit().begin(distributor_state.round).await {
+            Ok(id) => Some(id),
+            Err(err) => {
+                tracing::warn!(%err, "Failed to record the round trigger");
+                None
+            },
+        };
+        let mut round = Round {
+            campaign: &campaign,
+            pipeline: pipeline.as_ref(),
+            compute_unit_price,
+            timeline,
+            audit_id,
+            preview: false,
+        };
+        let paid = round.pay(self).await;
+        if let Some(id) = audit_id {
+            if let Err(err) = self.round_audit().finish(id, &paid).await {
+                tracing::warn!(%err, "Failed to record the round status");
+            }
+        }
+        let Some((draw, payout)) = paid? else {
+            return Ok(());
+        };
+        let timeline = round.timeline;
+        self.observe_latency(&timeline);
+
+        if let Err(err) = pipeline.publish(&self.state, distributor_state, &draw.winners).await {
+            tracing::error!(%err, round = distributor_state.round, "ALERT: confirmed round isn't published");
+            self.state.alerter.observe(Signal::RoundFailed {
+                error: format!("Round {} is paid but not published: {err:#}", distributor_state.round),
+            });
+        }
+        if let Err(err) = self.pay_extra_mints(distributor_state, compute_unit_price).await {
+            tracing::error!(%err, "Failed to pay the extra mints of the round");
+        }
+
+        let (report, shadow) = draw.into_report(&campaign, payout, self.state.mint_decimals)?;
+        self.record_round(&campaign, &report, &timeline, shadow.as_ref()).await;
+
+        Ok(())
+    }
+
+    fn round_audit(&self) -> RoundAudit<'_> {
+        RoundAudit {
+            pool: &self.state.pool,
+            distributor_state: &self.state.distributor_state_pubkey,
+        }
+    }
+
+    fn pending_rounds(&self) -> PendingRounds<'_> {
+        PendingRounds {
+            pool: &self.state.pool,
+            distributor_state: &self.state.distributor_state_pubkey,
+        }
+    }
+
+    /// Pending transaction of the round `round` which can still land. A stale one is forgotten: its nonce moved, so
+    /// it either landed while the service was down or can't land anymore
+    async fn pending_round(&self, round: u64) -> anyhow::Result<Option<PendingRound>> {
+        let pending_rounds = self.pending_rounds();
+        let Some(pending) = pending_rounds.fetch().await? else {
+            return Ok(None);
+        };
+        let rpc_client = self.state.program.async_rpc();
+        if pending.round == round && pending.is_valid(&rpc_client).await? {
+            return Ok(Some(pending));
+        }
+        if pending.round < round {
+            tracing::warn!(
+                pending = pending.round,
+                round,
+                "Pending transaction of an earlier round landed while the service was down, its report isn't stored"
+            );
+        }
+        pending_rounds.delete().await?;
+        Ok(None)
+    }
+
+    /// Draws the winners of the round, replaces the disqualified ones and builds the transactions paying them,
+    /// redrawing the winners the simulation rejects. The round is `Drawn` unless it is `Rescheduled`
+    #[tracing::instrument(skip_all)]
+    async fn draw_round(&self, round: &mut Round<'_>) -> anyhow::Result<RoundStage> {
+        let (campaign, pipeline) = (round.campaign, round.pipeline);
+        let distributor_state = campaign.distributor_state;
+        let Some(entrants) = self
+            .entrants(distributor_state, &campaign.features, round.preview)
+            .await?
+        else {
+            tracing::info!("Draw of the round isn't revealed yet");
+            return Ok(RoundStage::Rescheduled);
+        };
+        round.timeline.record(Stage::SnapshotDone);
+        let holders_number = entrants.number();
+        // the winners of an open round are kept, only the unpaid ones can be redrawn
+        let paid = pipeline.round_in_progress(&self.state, distributor_state).await?;
+        let resumed = paid.is_some();
+        let mut winners = match paid {
+            Some(paid) => {
+                let winners = self
+                    .round_audit()
+                    .recorded_winners(distributor_state.round)
+                    .await?
+                    .context("Winners of the round in progress aren't recorded")?;
+                tracing::info!(?winners, %paid, "Winners of the round in progress are resumed");
+                winners
+            },
+            None => {
+                let winners = entrants
+                    .draw_winners(distributor_state.paid_shares())
+                    .await
+                    .context("Failed to draw winners")?;
+                tracing::info!(?winners, "Winners has been selected");
+                winners
+            },
+        };
+        let paid = paid.unwrap_or(0) as usize;
+        let shadow = self.shadow_draw(&entrants, &winners);
+
+        let rpc_client = self.state.program.async_rpc();
+        let mut replacements = Vec::new();
+        let mut attempt = 0;
+        let transactions = loop {
+            attempt += 1;
+            if !resumed {
+                self.replace_disqualified(campaign, &entrants, &mut winners, &mut replacements)
+                    .await?;
+            }
+            let transactions = pipeline
+                .build(
+                    &self.state,
+                    distributor_state,
+                    &winners,
+                    round.compute_unit_price,
+                    round.preview,
+                )
+                .await?;
+
+            // the later transactions of the round depend on the earlier ones, they are simulated before being sent
+            let Some(failure) = simulation_failure(&rpc_client, &transactions.transactions[0]).await? else {
+                break transactions;
+            };
+
+            tracing::warn!(%failure, %attempt, "Distribute transaction simulation failed");
+            match failure.remediation() {
+                Remediation::Redraw(_) if attempt >= MAX_DISTRIBUTE_ATTEMPTS => return Err(failure.into()),
+                Remediation::Redraw(account) => {
+                    // Replace the unpaid winner owning the rejected account or every unpaid one if it is unknown
+                    let rejected = winners
+                        .iter()
+                        .enumerate()
+                        .skip(paid)
+                        .filter(|(position, winner)| match account {
+                            Some(key) => {
+                                key == winner.wallet || transactions.token_accounts.get(*position) == Some(&key)
+                            },
+                            None => true,
+                        })
+                        .map(|(position, _)| position)
+                        .collect::<Vec<_>>();
+                    if rejected.is_empty() {
+                        return Err(failure.into());
+                    }
+                    let replacements = entrants
+                        .draw_winners(rejected.len() as u64)
+                        .await
+                        .context("Failed to redraw winners")?;
+                    for (position, replacement) in rejected.into_iter().zip(replacements) {
+                        tracing::info!(rejected = %winners[position].wallet, replacement = %replacement.wallet, "Winner redrawn");
+                        winners[position] = replacement;
+                    }
+                },
+                Remediation::Reschedule => return Ok(RoundStage::Rescheduled),
+                Remediation::Abort => return Err(failure.into()),
+            }
+        };
+        round.timeline.record(Stage::DrawDone);
+
+        let amounts = pipeline.amounts(&self.state, distributor_state, &winners).await?;
+        let draw = Draw {
+            holders_number,
+            randomness: entrants.randomness(),
+            winners,
+            amounts,
+            replacements,
+            shadow,
+        };
+        Ok(RoundStage::Drawn { draw, transactions })
+    }
+
+    /// Sends the transactions of the round in order and prices them, each is tracked until it is confirmed and its
+    /// final status is recorded. `None` if the round has to be rescheduled
+    #[tracing::instrument(skip_all, fields(transactions = round.transactions.len()))]
+    async fn send_round(
+        &self,
+        draw: &Draw,
+        round: &RoundTransactions,
+        distributor_round: u64,
+        timeline: &mut RoundTimeline,
+    ) -> anyhow::Result<Option<Payout>> {
+        let rpc_client = self.state.program.async_rpc();
+        let atas_created = if round.token_accounts.is_empty() {
+            vec![false; draw.winners.len()]
+        } else {
+            rpc_client
+                .get_multiple_accounts(&round.token_accounts)
+                .await
+                .context("Failed to fetch winners token accounts")?
+                .iter()
+                .map(Option::is_none)
+                .collect()
+        };
+
+        let mut fee_lamports = 0;
+        for tx in &round.transactions {
+            fee_lamports += fetch_fee(&rpc_client, &tx.message).await?;
+        }
+        let ata_rent = rpc_client
+            .get_minimum_balance_for_rent_exemption(TokenAccount::LEN)
+            .await
+            .context("Failed to get token account rent")?;
+
+        timeline.record(Stage::TxSent);
+        let mut signature = Signature::default();
+        for (position, tx) in round.transactions.iter().enumerate() {
+            // the first transaction was simulated by the draw, the later ones only once the earlier ones landed
+            let simulated = match position {
+                0 => None,
+                _ => simulation_failure(&rpc_client, tx).await?,
+            };
+            if let Some(failure) = &simulated {
+                tracing::warn!(%failure, %position, "Distribute transaction simulation failed");
+            }
+            let signers = transaction_signers(&self.state, &tx.message);
+            let failure = match simulated {
+                Some(failure) => failure,
+                None => match send_and_track(&rpc_client, tx.clone(), &signers, self.state.max_send_attempts).await {
+                    Ok(confirmation) => {
+                        if let Err(err) = record_transaction(
+                            &self.state.pool,
+                            &self.state.distributor_state_pubkey,
+                            distributor_round,
+                            &confirmation,
+                        )
+                        .await
+                        {
+                            tracing::error!(?err, "Failed to record the round transaction");
+                        }
+                        match confirmation.outcome {
+                            TxOutcome::Confirmed => {
+                                signature = confirmation.signature;
+                                tracing::info!(%signature, attempts = confirmation.attempts, "Distribute transaction confirmed");
+                                continue;
+                            },
+                            TxOutcome::Failed(err) => ProgramFailure::new(&err, &[]),
+                            // never landed, the round can be sent again
+                            TxOutcome::Expired => ProgramFailure::Transaction(TransactionError::BlockhashNotFound),
+                        }
+                    },
+                    Err(err) => match ProgramFailure::from_client_error(&err) {
+                        Some(failure) => failure,
+                        None => return Err(err).context("Failed to send transaction"),
+                    },
+                },
+            };
+            if failure.remediation() == Remediation::Reschedule {
+                tracing::warn!(%failure, "Distribute transaction failed, rescheduling");
+                return Ok(None);
+            }
+            return Err(failure.into());
+        }
+        timeline.record(Stage::TxConfirmed);
+
+        Ok(Some(Payout {
+            signature,
+            fee_lamports,
+            ata_rent,
+            atas_created,
+        }))
+    }
+
+    /// Stores the paid round and announces it once its report is public
+    async fn record_round(
+        &self,
+        campaign: &Campaign<'_>,
+        report: &RoundReport,
+        timeline: &RoundTimeline,
+        shadow: Option<&DrawDiff>,
+    ) {
+        let repository = RoundRepository {
+            pool: &self.state.pool,
+            distributor_state: &self.state.distributor_state_pubkey,
+            explorer: &self.state.explorer,
+        };
+        let private = campaign.features.privacy_mode;
+        let id = match repository
+            .store(report, timeline, shadow, self.state.announcement_delay, private)
+            .await
+        {
+            Ok(id) => id,
+            Err(err) => {
+                tracing::warn!(%err, "Failed to store distribution report");
+                return;
+            },
+        };
+        tracing::info!(%id, "Distribution report stored");
+        if let Some(notifier) = &self.state.notifier {
+            let lines = announcement(report, id, private, notifier.collapse_threshold);
+            let notifier = notifier.clone();
+            let delay = self.state.announcement_delay;
+            tokio::spawn(async move {
+                tokio::time::sleep(delay).await;
+                notifier.announce(&lines).await;
+            });
+        }
+    }
+
+    /// Draws the winners by the candidate strategy for the canary share of the rounds and logs how they differ from
+    /// the paid draw. Only the plain draw of the holders is shadowed, the verifiable draws and the tickets aren't
+    /// comparable with it
+    fn shadow_draw(&self, entrants: &Entrants<'_>, winners: &[Winner]) -> Option<DrawDiff> {
+        let strategy = self.state.shadow_draw_strategy?;
+        if !matches!(entrants, Entrants::Holders { .. }) {
+            return None;
+        }
+        let mut rng = rand::thread_rng();
+        if !in_canary(&mut rng, self.state.shadow_draw_percentage) {
+            return None;
+        }
+        let diff = DrawDiff {
+            strategy,
+            entrants: entrants.number(),
+            current: winners.iter().map(|winner| winner.index).collect(),
+            candidate: strategy.draw(&mut rng, entrants.number(), winners.len() as u64),
+        };
+        tracing::info!(
+            strategy = strategy.as_str(),
+            common_winners = diff.common_winners(),
+            current_repeats = repeats(&diff.current),
+            candidate_repeats = repeats(&diff.candidate),
+            candidate = ?diff.candidate,
+            "Shadow draw"
+        );
+        Some(diff)
+    }
+
+    /// Records the latencies of the confirmed round and alerts if it took longer than the SLO
+    fn observe_latency(&self, timeline: &RoundTimeline) {
+        let breached = self
+            .latency_metrics
+            .lock()
+            .expect("Latency metrics are poisoned")
+            .observe(timeline, self.state.latency_slo);
+        let end_to_end = timeline.end_to_end().unwrap_or_default();
+        let stages = timeline
+            .latencies()
+            .iter()
+            .map(|latency| format!("{} {}ms", latency.stage.as_str(), latency.latency.as_millis()))
+            .join(", ");
+        if breached {
+            tracing::error!(
+                end_to_end_ms = %end_to_end.as_millis(),
+                slo_ms = %self.state.latency_slo.as_millis(),
+                %stages,
+                "ALERT: round exceeded the latency SLO"
+            );
+        } else {
+            tracing::info!(end_to_end_ms = %end_to_end.as_millis(), %stages, "Round latency");
+        }
+    }
+
+    /// Pays every extra payout mint to the winners recorded for the completed `round`. A vault which paid a part of
+    /// an earlier round completes it first, as the program doesn't let it go back once it moves on. The vaults which
+    /// can't pay all the winners of a round are skipped
+    async fn pay_extra_mints(
+        &self,
+        distributor_state: &DistributorState,
+        compute_unit_price: u64,
+    ) -> anyhow::Result<()> {
+        let round = distributor_state.round;
+        let vault_configs = fetch_vault_configs(&self.state).await?;
+        if vault_configs.is_empty() {
+            return Ok(());
+        }
+
+        let record = self.fetch_round_record(round).await?.with_context(|| {
+            format!("Round {round} isn't recorded, extra mints are paid to the recorded winners only")
+        })?;
+        for (vault_config_address, mut vault_config) in vault_configs {
+            let unfinished = match vault_config.last_round.filter(|last_round| *last_round < round) {
+                Some(last_round) => self
+                    .fetch_round_record(last_round)
+                    .await?
+                    .filter(|last_record| vault_config.paid < last_record.payouts.len() as u64),
+                None => None,
+            };
+            if let Some(last_record) = unfinished {
+                if !self
+                    .pay_extra_mint(
+                        distributor_state,
+                        vault_config_address,
+                        &vault_config,
+                        &last_record,
+                        compute_unit_price,
+                    )
+                    .await?
+                {
+                    continue;
+                }
+                vault_config = fetch_vault_config(&self.state, &vault_config_address).await?;
+            }
+            self.pay_extra_mint(
+                distributor_state,
+                vault_config_address,
+                &vault_config,
+                &record,
+                compute_unit_price,
+            )
+            .await?;
+        }
+        Ok(())
+    }
+
+    /// Record of the completed `round`, `None` if it isn't recorded
+    async fn fetch_round_record(&self, round: u64) -> anyhow::Result<Option<DistributionRound>> {
+        let address = distributor::client::distribution_round_address(&self.state.distributor_state_pubkey, round);
+        let Some(account) = self
+            .state
+            .program
+            .async_rpc()
+            .get_account_with_commitment(&address, CommitmentConfig::confirmed())
+            .await
+            .context("Failed to fetch round record")?
+            .value
+        else {
+            return Ok(None);
+        };
+        let record = distributor::client::distribution_round(&address, &account.owner, &account.data)
+            .context("Invalid round record")?;
+        Ok(Some(record))
+    }
+
+    /// Pays the extra mint to the winners of the recorded round it didn't pay yet. `false` if the vault can't pay
+    /// all of them, nothing is paid then
+    async fn pay_extra_mint(
+        &self,
+        distributor_state: &DistributorState,
+        vault_config_address: Pubkey,
+        vault_config: &VaultConfig,
+        record: &DistributionRound,
+        compute_unit_price: u64,
+    ) -> anyhow::Result<bool> {
+        let rpc_client = self.state.program.async_rpc();
+        let data = rpc_client
+            .get_account_data(&vault_config.vault)
+            .await
+            .context("Failed to fetch extra vault")?;
+        let vault = TokenAccount::unpack(&data).context("Failed to unpack extra vault")?;
+        let paid = match vault_config.last_round {
+            Some(last_round) if last_round == record.round => vault_config.paid,
+            _ => 0,
+        };
+        let unpaid = (record.payouts.len() as u64).saturating_sub(paid);
+        let required = vault_config.share_size.saturating_mul(unpaid);
+        if vault.amount < required {
+            tracing::warn!(mint = %vault_config.mint, round = record.round, balance = %vault.amount, %required, "Extra vault can't pay the round");
+            return Ok(false);
+        }
+
+        for tx in extra_mint_transactions(
+            &self.state,
+            distributor_state,
+            vault_config_address,
+            vault_config,
+            record,
+            compute_unit_price,
+        )
+        .await?
+        {
+            let signature = rpc_client
+                .send_and_confirm_transaction(&tx)
+                .await
+                .context("Failed to send distribute extra mint transaction")?;
+            tracing::info!(mint = %vault_config.mint, round = record.round, %signature, "Distribute extra mint transaction confirmed");
+        }
+        Ok(true)
+    }
+
+    /// Snapshot of the entrants of the round, token holders or the tickets of the campaign, the holders by their
+    /// balances in the dividend mode. `None` if the holders are drawn by VRF and the randomness of the round isn't
+    /// revealed yet, it is requested and revealed by the runs of the round, or if the winners of the committed draw,
+    /// or of the VRF draw of a distributor requiring one, aren't verified yet. A `preview` sends nothing, the VRF
+    /// draw is only used once a round revealed it
+    #[tracing::instrument(skip_all)]
+    async fn entrants(
+        &self,
+        distributor_state: &DistributorState,
+        features: &Features,
+        preview: bool,
+    ) -> anyhow::Result<Option<Entrants<'_>>> {
+        if distributor_state.requires_committed_draw {
+            return self.committed_entrants(distributor_state).await;
+        }
+
+        if distributor_state.distribution_mode == DistributionMode::Dividend {
+            let mut helius_client = self.state.helius_client.lock().await;
+            helius_client
+                .update_token_holders_number()
+                .await
+                .context("Failed to update token holders number")?;
+            let holders = helius_client
+                .holders_by_balance()
+                .await
+                .context("Failed to fetch token holders balances")?;
+            tracing::info!(holders = %holders.len(), "Fetched token holders balances");
+            return Ok(Some(Entrants::Dividend {
+                entrants: holders.len() as u64,
+                holders: std::sync::Mutex::new(holders),
+            }));
+        }
+
+        if let Some(campaign) = &self.state.ticket_campaign {
+            let snapshot = TicketSnapshot::fetch(&self.state.pool, campaign).await?;
+            tracing::info!(%campaign, tickets = %snapshot.entries_number(), "Fetched campaign tickets");
+            return Ok(Some(Entrants::Tickets {
+                snapshot,
+                rng: SeededRng::new(),
+            }));
+        }
+
         let mut helius_client = self.state.helius_client.lock().await;
         helius_client
             .update_token_holders_number()
             .await
             .context("Failed to update token holders number")?;
-
         tracing::info!(holders = %helius_client.holders_number(), "Updated token holders number");
 
-        let winners = helius_client
-            .draw_winners(self.state.distributor_state.number_of_shares - 1)
+        if !features.vrf_draw && !distributor_state.requires_vrf_draw {
+            return Ok(Some(Entrants::Holders {
+                helius_client,
+                rng: SeededRng::new(),
+            }));
+        }
+
+        let address = distributor::client::draw_address(&self.state.distributor_state_pubkey, distributor_state.round);
+        let Some(account) = self
+            .state
+            .program
+            .async_rpc()
+            .get_account_with_commitment(&address, CommitmentConfig::confirmed())
             .await
-            .context("Failed to draw winners")?;
-        drop(helius_client);
-        tracing::info!(?winners, "Winners has been selected");
+            .context("Failed to fetch draw")?
+            .value
+        else {
+            if preview {
+                return Ok(None);
+            }
+            // the draw of a distributor requiring one is drawn from the holders snapshot committed by the authority
+            let entrants = if distributor_state.requires_vrf_draw {
+                match self.committed_holders_count(distributor_state).await? {
+                    Some(holders_count) => holders_count,
+                    None => return Ok(None),
+                }
+            } else {
+                helius_client.holders_number()
+            };
+            self.request_draw(distributor_state, entrants).await?;
+            return Ok(None);
+        };
+        let draw =
+            distributor::client::draw(&address, &account.owner, &account.data).context("Invalid draw account")?;
+        let randomness = match draw.value {
+            Some(randomness) => randomness,
+            None if preview => return Ok(None),
+            None => match self.reveal_draw(address, &draw).await? {
+                Some(randomness) => randomness,
+                None => return Ok(None),
+            },
+        };
 
-        let remaining_accounts = winners
-            .into_iter()
-            .flat_map(|winner| {
-                let ata = get_associated_token_address(&winner, &self.state.distributor_state.mint);
-                [AccountMeta::new_readonly(winner, false), AccountMeta::new(ata, false)]
-            })
-            .collect::<Vec<_>>();
+        if distributor_state.requires_vrf_draw {
+            // the winners are verified against the holders snapshot by the authority outside of the backend
+            if (draw.winners.len() as u64) < draw.winners_number {
+                return Ok(None);
+            }
+            let winners = DrawIndices::new(randomness, draw.entrants)
+                .zip(draw.winners)
+                .map(|(index, wallet)| Winner { index, wallet })
+                .collect();
+            return Ok(Some(Entrants::Committed {
+                entrants: draw.entrants,
+                randomness: Randomness::Vrf(randomness),
+                winners: std::sync::Mutex::new(winners),
+            }));
+        }
+
+        let holders_number = helius_client.holders_number();
+        if draw.entrants != holders_number {
+            tracing::warn!(entrants = %draw.entrants, %holders_number, "Holders number changed since the draw was requested");
+        }
+        let indices = DrawIndices::new(randomness, draw.entrants);
+        Ok(Some(Entrants::VerifiableHolders {
+            helius_client,
+            randomness,
+            indices: std::sync::Mutex::new(Box::new(indices)),
+        }))
+    }
 
+    /// Requests the VRF randomness of the round, a later run reveals the draw once the request is fulfilled
+    async fn request_draw(&self, distributor_state: &DistributorState, entrants: u64) -> anyhow::Result<()> {
+        let compute_unit_price = self
+            .state
+            .priority_fee_config
+            .compute_unit_price(&self.state.priority_fee)
+            .await;
+        let seed = OsRng.gen::<[u8; 32]>();
+        let tx = request_draw_transaction(&self.state, distributor_state, seed, entrants, compute_unit_price).await?;
+        let signature = self
+            .state
+            .program
+            .async_rpc()
+            .send_and_confirm_transaction(&tx)
+            .await
+            .context("Failed to send request draw transaction")?;
+        tracing::info!(%signature, %entrants, "Requested the VRF randomness of the round");
+        Ok(())
+    }
+
+    /// Holders count of the snapshot committed for the round, `None` until the authority commits it
+    async fn committed_holders_count(&self, distributor_state: &DistributorState) -> anyhow::Result<Option<u64>> {
+        let address =
+            distributor::client::holder_snapshot_address(&self.state.distributor_state_pubkey, distributor_state.round);
+        let Some(account) = self
+            .state
+            .program
+            .async_rpc()
+            .get_account_with_commitment(&address, CommitmentConfig::confirmed())
+            .await
+            .context("Failed to fetch holder snapshot")?
+            .value
+        else {
+            return Ok(None);
+        };
+        let holder_snapshot = distributor::client::holder_snapshot(&address, &account.owner, &account.data)
+            .context("Invalid holder snapshot account")?;
+        Ok(Some(holder_snapshot.holders_count))
+    }
+
+    /// Reveals the draw once its VRF request is fulfilled, `None` while the request is pending
+    async fn reveal_draw(&self, address: Pubkey, draw: &distributor::Draw) -> anyhow::Result<Option<[u8; 64]>> {
         let rpc_client = self.state.program.async_rpc();
-        let latest_hash = rpc_client
-            .get_latest_blockhash()
+        let data = rpc_client
+            .get_account_data(&draw.randomness)
+            .await
+            .context("Failed to fetch VRF randomness")?;
+        // the randomness stays zeroed until the request is fulfilled
+        let Some(randomness) = data
+            .get(distributor::vrf::RANDOMNESS_RANGE)
+            .and_then(|randomness| <[u8; 64]>::try_from(randomness).ok())
+            .filter(|randomness| *randomness != [0; 64])
+        else {
+            return Ok(None);
+        };
+
+        let compute_unit_price = self
+            .state
+            .priority_fee_config
+            .compute_unit_price(&self.state.priority_fee)
+            .await;
+        let tx = reveal_draw_transaction(&self.state, address, draw.randomness, compute_unit_price).await?;
+        let signature = rpc_client
+            .send_and_confirm_transaction(&tx)
             .await
-            .context("Failed to get latest blockhash")?;
+            .context("Failed to send reveal draw transaction")?;
+        tracing::info!(%signature, "Revealed the VRF draw of the round");
+        Ok(Some(randomness))
+    }
 
-        let ixns = self
+    /// Verified winners of the committed draw of the round, the draw is committed, revealed and verified by the
+    /// authority outside of the backend
+    async fn committed_entrants(&self, distributor_state: &DistributorState) -> anyhow::Result<Option<Entrants<'_>>> {
+        let address =
+            distributor::client::committed_draw_address(&self.state.distributor_state_pubkey, distributor_state.round);
+        let Some(account) = self
             .state
             .program
-            .request()
-            .instruction(ComputeBudgetInstruction::set_compute_unit_limit(800_000))
-            .instruction(spl_memo::build_memo(self.state.memo.as_bytes(), &[]))
-            .accounts(distributor::accounts::Distribute {
-                payer: self.state.payer.pubkey(),
-                distributor_authority: self.state.distributor_authority.pubkey(),
-                distributor_state: self.state.distributor_state_pubkey,
-                mint: self.state.distributor_state.mint,
-                vault: self.state.distributor_state.vault,
-                system_program: solana_sdk::system_program::ID,
-                token_program: spl_token::ID,
-                associated_token_program: spl_associated_token_account::ID,
-            })
-            .accounts(remaining_accounts)
-            .args(distributor::instruction::Distribute)
-            .instructions()
-            .context("Failed to create distribute instructions")?;
-
-        let tx = Transaction::new_signed_with_payer(
-            &ixns,
-            Some(&self.state.payer.pubkey()),
-            &[&self.state.payer, &self.state.distributor_authority],
-            latest_hash,
+            .async_rpc()
+            .get_account_with_commitment(&address, CommitmentConfig::confirmed())
+            .await
+            .context("Failed to fetch committed draw")?
+            .value
+        else {
+            return Ok(None);
+        };
+        let committed_draw = distributor::client::committed_draw(&address, &account.owner, &account.data)
+            .context("Invalid committed draw account")?;
+        let Some(randomness) = committed_draw.randomness() else {
+            return Ok(None);
+        };
+        if (committed_draw.winners.len() as u64) < committed_draw.winners_number {
+            return Ok(None);
+        }
+
+        let winners = DrawIndices::new(randomness, committed_draw.entrants)
+            .zip(committed_draw.winners)
+            .map(|(index, wallet)| Winner { index, wallet })
+            .collect();
+        Ok(Some(Entrants::Committed {
+            entrants: committed_draw.entrants,
+            randomness: Randomness::Committed(randomness),
+            winners: std::sync::Mutex::new(winners),
+        }))
+    }
+
+    /// Replaces winners which can't receive a share by new ones drawn from the same holders snapshot. Once the
+    /// local checks pass, the winners are checked by the simulated `validate_winners` if the feature is enabled
+    async fn replace_disqualified(
+        &self,
+        campaign: &Campaign<'_>,
+        entrants: &Entrants<'_>,
+        winners: &mut [Winner],
+        replacements: &mut Vec<Replacement>,
+    ) -> anyhow::Result<()> {
+        let distributor_state = campaign.distributor_state;
+        // the claim rounds don't pay the winners accounts
+        let validates =
+            campaign.features.validate_winners && distributor_state.distribution_mode != DistributionMode::Claim;
+        let rpc_client = self.state.program.async_rpc();
+        let blocked =
+            fetch_blocked_wallets(&rpc_client, &self.state.distributor_state_pubkey, distributor_state).await?;
+        let previous_winners =
+            fetch_previous_winners(&rpc_client, &self.state.distributor_state_pubkey, distributor_state).await?;
+        for _ in 0..MAX_REPLACEMENT_ROUNDS {
+            let wallets = winners.iter().map(|winner| winner.wallet).collect::<Vec<_>>();
+            // native shares are paid to the wallets themselves
+            let payout_accounts = if distributor_state.is_native() {
+                wallets.clone()
+            } else {
+                wallets
+                    .iter()
+                    .map(|wallet| {
+                        get_associated_token_address_with_program_id(
+                            wallet,
+                            &distributor_state.mint,
+                            &self.state.token_program,
+                        )
+                    })
+                    .collect()
+            };
+            let token_accounts = rpc_client
+                .get_multiple_accounts(&payout_accounts)
+                .await
+                .context("Failed to fetch winners token accounts")?;
+            let flagged = fetch_flagged_wallets(&self.state.pool, &wallets).await?;
+
+            let mut seen = HashSet::new();
+            // the shares of the frozen token accounts are escrowed while the distributor has an escrow
+            let escrows = distributor_state.escrow_claim_period.is_some();
+            let mut disqualified = winners
+                .iter()
+                .zip(&token_accounts)
+                .enumerate()
+                .filter_map(|(position, (winner, token_account))| {
+                    disqualification(
+                        winner,
+                        token_account.as_ref().filter(|_| !escrows),
+                        &mut seen,
+                        &self.state.excluded_wallets,
+                        &flagged,
+                        &blocked,
+                        &previous_winners,
+                    )
+                    .map(|reason| (position, reason))
+                })
+                .collect::<Vec<_>>();
+            if disqualified.is_empty() && validates {
+                let amounts = campaign
+                    .pipeline()
+                    .amounts(&self.state, distributor_state, winners)
+                    .await?;
+                disqualified = validate_winners(&self.state, distributor_state, winners, &amounts)
+                    .await?
+                    .into_iter()
+                    .enumerate()
+                    .filter(|(_, valid)| !valid)
+                    .map(|(position, _)| (position, Disqualification::FailedValidation))
+                    .collect();
+            }
+            if disqualified.is_empty() {
+                return Ok(());
+            }
+
+            let redrawn = entrants
+                .draw_winners(disqualified.len() as u64)
+                .await
+                .context("Failed to redraw winners")?;
+            for ((position, reason), replacement) in disqualified.into_iter().zip(redrawn) {
+                tracing::info!(disqualified = %winners[position].wallet, reason = reason.as_str(), replacement = %replacement.wallet, "Winner replaced");
+                replacements.push(Replacement {
+                    disqualified: winners[position],
+                    reason,
+                    replacement,
+                });
+                winners[position] = replacement;
+            }
+        }
+        bail!("Failed to draw eligible winners in {MAX_REPLACEMENT_ROUNDS} rounds");
+    }
+
+    /// Builds the round for `shares` fresh wallets, so every winner ATA has to be created, and simulates its
+    /// largest transaction
+    #[tracing::instrument(skip(self))]
+    async fn simulate_worst_case(&self, shares: u64) -> anyhow::Result<WorstCaseSimulation> {
+        let rpc_client = self.state.program.async_rpc();
+        let mut distributor_state = fetch_distributor_state(&rpc_client, &self.state.distributor_state_pubkey).await?;
+        ensure!(
+            shares > distributor_state.burn_shares,
+            "There have to be more than {} shares",
+            distributor_state.burn_shares
         );
+        distributor_state.number_of_shares = shares;
+
+        let winners = (0..distributor_state.paid_shares())
+            .map(|index| Winner {
+                index,
+                wallet: Keypair::new().pubkey(),
+            })
+            .collect::<Vec<_>>();
+        let features = resolve_features(
+            &self.state.pool,
+            &self.state.distributor_state_pubkey,
+            self.state.features,
+        )
+        .await?;
+        // the priority fee instruction is the same size at any non-zero price
+        let round = pipeline(distributor_state.distribution_mode, features)
+            .build(
+                &self.state,
+                &distributor_state,
+                &winners,
+                self.state.priority_fee_config.max,
+                true,
+            )
+            .await?;
+        let tx = round
+            .transactions
+            .into_iter()
+            .max_by_key(|tx| bincode::serialized_size(tx).unwrap_or_default())
+            .context("Round has no transactions")?;
+        let tx_size = bincode::serialize(&tx)
+            .context("Failed to serialize transaction")?
+            .len();
 
-        let tx_size = bincode::serialize(&tx).unwrap_or_default().len();
-        tracing::info!(%tx_size, "Distribute transaction size. Maximum possible is 1232 bytes.");
+        let (compute_units, simulation_error) = if tx_size > PACKET_DATA_SIZE {
+            (None, Some("Transaction is too large to simulate".to_string()))
+        } else {
+            let config = RpcSimulateTransactionConfig {
+                sig_verify: false,
+                replace_recent_blockhash: true,
+                ..Default::default()
+            };
+            match rpc_client.simulate_transaction_with_config(&tx, config).await {
+                Ok(response) => (
+                    response.value.units_consumed,
+                    response.value.err.map(|err| {
+                        ProgramFailure::new(&err, response.value.logs.as_deref().unwrap_or_default()).to_string()
+                    }),
+                ),
+                Err(err) => (None, Some(err.to_string())),
+            }
+        };
 
-        let signature = rpc_client
-            .send_transaction(&tx)
+        // a failed simulation measured only the units consumed up to the failure
+        let fits = tx_size <= PACKET_DATA_SIZE
+            && simulation_error.is_none()
+            && compute_units.unwrap_or_default() <= MAX_COMPUTE_UNIT_LIMIT as u64;
+        Ok(WorstCaseSimulation {
+            shares,
+            tx_size,
+            max_tx_size: PACKET_DATA_SIZE,
+            compute_units,
+            simulation_error,
+            fits,
+        })
+    }
+
+    /// Replaces the runtime settings by the ones from the database, the state is left as is if they are invalid
+    async fn reload(&mut self) -> anyhow::Result<RuntimeConfig> {
+        let config = load_runtime_config(
+            &self.state.pool,
+            &self.state.distributor_state_pubkey,
+            self.runtime_defaults.clone(),
+        )
+        .await?;
+        self.state.excluded_wallets = config.excluded_wallets.clone();
+        self.state.ticket_campaign = config.ticket_campaign.clone();
+        self.state.announcement_delay = config.announcement_delay;
+        self.state.shadow_draw_strategy = config.shadow_draw_strategy;
+        self.state.shadow_draw_percentage = config.shadow_draw_percentage;
+        tracing::info!(?config, "Runtime config reloaded");
+        Ok(config)
+    }
+
+    /// Compares the eligible entrants and their win probabilities under `params` with the current ones. The entrants
+    /// of a ticket campaign weigh their tickets, the token holders weigh their marker balance and win equally
+    #[tracing::instrument(skip(self))]
+    async fn what_if(&self, params: WhatIfParams) -> anyhow::Result<WhatIfReport> {
+        let rpc_client = self.state.program.async_rpc();
+        let distributor_state = fetch_distributor_state(&rpc_client, &self.state.distributor_state_pubkey).await?;
+        let mut excluded =
+            fetch_blocked_wallets(&rpc_client, &self.state.distributor_state_pubkey, &distributor_state).await?;
+        excluded.extend(&self.state.excluded_wallets);
+
+        let (entries, current) = match &self.state.ticket_campaign {
+            Some(campaign) => {
+                let snapshot = TicketSnapshot::fetch(&self.state.pool, campaign).await?;
+                (snapshot.entries().to_vec(), WhatIfParams::default())
+            },
+            None => {
+                let mut helius_client = self.state.helius_client.lock().await;
+                helius_client
+                    .update_token_holders_number()
+                    .await
+                    .context("Failed to update token holders number")?;
+                let balances = helius_client
+                    .balances()
+                    .await
+                    .context("Failed to fetch token holders balances")?;
+                // a wallet holding several token accounts weighs their total balance
+                let mut entries: Vec<TicketEntry> = Vec::new();
+                let mut positions = HashMap::new();
+                for (winner, amount) in balances {
+                    match positions.entry(winner.wallet) {
+                        Entry::Occupied(position) => {
+                            let entry: &mut TicketEntry = &mut entries[*position.get()];
+                            entry.weight = entry.weight.saturating_add(amount);
+                        },
+                        Entry::Vacant(position) => {
+                            position.insert(entries.len());
+                            entries.push(TicketEntry {
+                                wallet: winner.wallet,
+                                weight: amount,
+                            });
+                        },
+                    }
+                }
+                let current = WhatIfParams {
+                    min_weight: distributor_state.min_marker_balance.unwrap_or(0),
+                    weighting: Weighting::Equal,
+                    ..Default::default()
+                };
+                (entries, current)
+            },
+        };
+
+        Ok(what_if(
+            &entries,
+            &excluded,
+            distributor_state.paid_shares(),
+            &current,
+            &params,
+        ))
+    }
+
+    /// Draws and builds the round the way `distribute_tokens` would right now and previews its payouts without
+    /// sending anything. A random draw is only a sample, the real round draws its own winners. A VRF draw isn't
+    /// requested or revealed by the preview, it can't be drawn until a round did
+    #[tracing::instrument(skip(self))]
+    async fn dry_run(&self) -> anyhow::Result<DryRun> {
+        let rpc_client = self.state.program.async_rpc();
+        let distributor_state = fetch_distributor_state(&rpc_client, &self.state.distributor_state_pubkey).await?;
+        let features = resolve_features(
+            &self.state.pool,
+            &self.state.distributor_state_pubkey,
+            self.state.features,
+        )
+        .await?;
+        let campaign = Campaign {
+            distributor_state: &distributor_state,
+            features,
+        };
+        let pipeline = campaign.pipeline();
+        let compute_unit_price = self
+            .state
+            .priority_fee_config
+            .compute_unit_price(&self.state.priority_fee)
+            .await;
+        let mut preview = Round {
+            campaign: &campaign,
+            pipeline: pipeline.as_ref(),
+            compute_unit_price,
+            timeline: RoundTimeline::new(SystemTime::now()),
+            audit_id: None,
+            preview: true,
+        };
+        let RoundStage::Drawn {
+            draw,
+            transactions: round,
+        } = self.draw(&mut preview).await?
+        else {
+            bail!("Round can't be drawn now, it would be rescheduled");
+        };
+        let (holders_number, winners, replacements) = (draw.holders_number, draw.winners, draw.replacements);
+        let sample_draw = matches!(draw.randomness, Randomness::Seed(_));
+
+        let mut simulations = Vec::with_capacity(round.transactions.len());
+        for tx in &round.transactions {
+            let config = RpcSimulateTransactionConfig {
+                sig_verify: false,
+                replace_recent_blockhash: true,
+                ..Default::default()
+            };
+            let simulation = match rpc_client.simulate_transaction_with_config(tx, config).await {
+                Ok(response) => TransactionSimulation {
+                    compute_units: response.value.units_consumed,
+                    error: response.value.err.map(|err| {
+                        ProgramFailure::new(&err, response.value.logs.as_deref().unwrap_or_default()).to_string()
+                    }),
+                },
+                Err(err) => TransactionSimulation {
+                    compute_units: None,
+                    error: Some(err.to_string()),
+                },
+            };
+            simulations.push(simulation);
+        }
+
+        // native shares are paid to the wallets themselves
+        let token_accounts = if round.token_accounts.is_empty() {
+            vec![true; winners.len()]
+        } else {
+            rpc_client
+                .get_multiple_accounts(&round.token_accounts)
+                .await
+                .context("Failed to fetch winners token accounts")?
+                .iter()
+                .map(Option::is_some)
+                .collect()
+        };
+        let mut fee_lamports = 0;
+        for tx in &round.transactions {
+            fee_lamports += fetch_fee(&rpc_client, &tx.message).await?;
+        }
+        let ata_rent = rpc_client
+            .get_minimum_balance_for_rent_exemption(TokenAccount::LEN)
             .await
-            .context("Failed to send transaction")?;
+            .context("Failed to get token account rent")?;
 
-        tracing::info!(%signature, "Distribute transaction sent");
+        let winners = preview_winners(
+            winners.iter().map(|winner| (winner.index, winner.wallet)),
+            &token_accounts,
+            distributor_state.share_size,
+            ata_rent,
+        );
+        Ok(DryRun {
+            round: distributor_state.round,
+            holders_number,
+            totals: DryRunTotals::new(&winners, fee_lamports, &distributor_state)?,
+            winners,
+            sample_draw,
+            replacements: replacements.len(),
+            simulations,
+        })
+    }
 
-        Ok(())
+    /// Triggers the distribution again after a delay. The retry keeps `received_at` of the round if it is set, so
+    /// the delay counts against the latency
+    fn reschedule(&self, received_at: Option<SystemTime>) {
+        let sender = self.sender.clone();
+        let span = tracing::Span::current();
+        tokio::spawn(async move {
+            tokio::time::sleep(RESCHEDULE_DELAY).await;
+            // the actor may be gone by now, nothing to reschedule then
+            let _ = sender.send(ActorMessage::Distribute {
+                tx: None,
+                received_at: received_at.unwrap_or_else(SystemTime::now),
+                span,
+            });
+        });
+    }
+}
+
+#[async_trait]
+impl RoundTransitions for Actor {
+    /// A single durable nonce transaction is persisted before it is sent, the round is paid by that very transaction
+    /// and its draw until the nonce moves. A preview round is drawn anew and persists nothing
+    async fn draw(&self, round: &mut Round<'_>) -> anyhow::Result<RoundStage> {
+        let distributor_round = round.campaign.distributor_state.round;
+        if !round.preview {
+            if let Some(pending) = self.pending_round(distributor_round).await? {
+                tracing::info!(round = pending.round, "Pending transaction of the round is sent again");
+                round.timeline.record(Stage::SnapshotDone);
+                round.timeline.record(Stage::DrawDone);
+                let draw = Draw {
+                    holders_number: pending.holders_number,
+                    randomness: pending.randomness,
+                    winners: pending.winners,
+                    amounts: pending.amounts,
+                    replacements: vec![],
+                    shadow: None,
+                };
+                let transactions = RoundTransactions {
+                    transactions: vec![pending.transaction],
+                    token_accounts: pending.token_accounts,
+                };
+                return Ok(RoundStage::Drawn { draw, transactions });
+            }
+        }
+
+        let stage = self.draw_round(round).await?;
+        if let RoundStage::Drawn { draw, transactions } = &stage {
+            if let [tx] = transactions.transactions.as_slice() {
+                if !round.preview && durable_nonce_account(&tx.message).is_some() {
+                    let pending = PendingRound {
+                        round: distributor_round,
+                        transaction: tx.clone(),
+                        holders_number: draw.holders_number,
+                        randomness: draw.randomness,
+                        winners: draw.winners.clone(),
+                        amounts: draw.amounts.clone(),
+                        token_accounts: transactions.token_accounts.clone(),
+                    };
+                    self.pending_rounds().save(&pending).await?;
+                }
+            }
+        }
+        Ok(stage)
+    }
+
+    /// The drawn winners are recorded in the audit trail of the round before they are paid
+    async fn send(
+        &self,
+        round: &mut Round<'_>,
+        draw: Draw,
+        transactions: RoundTransactions,
+    ) -> anyhow::Result<RoundStage> {
+        if let Some(id) = round.audit_id {
+            if let Err(err) = self.round_audit().store_winners(id, &draw.winners, &draw.amounts).await {
+                tracing::warn!(%err, "Failed to record the winners of the round");
+            }
+        }
+        let distributor_round = round.campaign.distributor_state.round;
+        let sent = self
+            .send_round(&draw, &transactions, distributor_round, &mut round.timeline)
+            .await;
+        // a rescheduled round keeps its transaction, the next run sends it again while its nonce is still valid
+        if !matches!(sent, Ok(None)) {
+            if let Err(err) = self.pending_rounds().delete().await {
+                tracing::warn!(%err, "Failed to delete the pending round");
+            }
+        }
+        Ok(match sent? {
+            Some(payout) => RoundStage::Paid { draw, payout },
+            None => RoundStage::Rescheduled,
+        })
+    }
+
+    fn reschedule(&self, received_at: SystemTime) {
+        Actor::reschedule(self, Some(received_at));
     }
 }
 
 async fn run_actor(mut actor: Actor) {
-    while let Some(ActorMessage(tx)) = actor.receiver.recv().await {
-        match actor.handle_message(tx).await {
-            Ok(_) => {},
-            Err(err) => {
-                tracing::warn!(%err, "Failed to handle message");
+    while let Some(message) = actor.receiver.recv().await {
+        match message {
+            ActorMessage::Distribute { tx, received_at, span } => {
+                let round = tracing::info_span!(parent: &span, "round");
+                match actor
+                    .handle_message(tx.map(|tx| *tx), received_at)
+                    .instrument(round)
+                    .await
+                {
+                    Ok(_) => actor.state.alerter.observe(Signal::RoundSucceeded),
+                    Err(err) => {
+                        tracing::warn!(%err, "Failed to handle message");
+                        actor.state.alerter.observe(Signal::RoundFailed {
+                            error: format!("{err:#}"),
+                        });
+                    },
+                }
+            },
+            ActorMessage::SimulateWorstCase { shares, reply } => {
+                // the requester may have given up waiting
+                let _ = reply.send(actor.simulate_worst_case(shares).await);
+            },
+            ActorMessage::DryRun { reply } => {
+                // the requester may have given up waiting
+                let _ = reply.send(actor.dry_run().await);
+            },
+            ActorMessage::WhatIf { params, reply } => {
+                // the requester may have given up waiting
+                let _ = reply.send(actor.what_if(params).await);
+            },
+            ActorMessage::RefreshHolders => {
+                let mut helius_client = actor.state.helius_client.lock().await;
+                match helius_client.update_token_holders_number().await {
+                    Ok(_) => {
+                        tracing::debug!(holders = %helius_client.holders_number(), "Refreshed token holders number")
+                    },
+                    Err(err) => tracing::warn!(%err, "Failed to refresh token holders number"),
+                }
+            },
+            ActorMessage::InspectVault => {
+                if let Err(err) = actor.inspect_vault().await {
+                    tracing::warn!(%err, "Failed to inspect vault");
+                }
+            },
+            ActorMessage::Prune => {
+                let Some(retention) = actor.state.retention else {
+                    continue;
+                };
+                match actor.prune(retention).await {
+                    Ok(report) => tracing::info!(claims = report.claims, "Pruned database"),
+                    Err(err) => tracing::warn!(%err, "Failed to prune database"),
+                }
+            },
+            ActorMessage::Reload { reply } => {
+                let config = actor.reload().await;
+                if let Err(err) = &config {
+                    tracing::warn!(%err, "Failed to reload runtime config");
+                }
+                if let Some(reply) = reply {
+                    // the requester may have given up waiting
+                    let _ = reply.send(config);
+                }
             },
         }
     }
@@ -161,19 +1682,153 @@ async fn run_actor(mut actor: Actor) {
 #[derive(Clone)]
 pub struct ActorHandle {
     sender: UnboundedSender<ActorMessage>,
+    latency_metrics: Arc<std::sync::Mutex<LatencyMetrics>>,
+    latency_slo: Duration,
 }
 
 impl ActorHandle {
     pub fn new(state: AppState) -> Self {
         let (sender, receiver) = unbounded_channel();
-        let actor = Actor::new(receiver, state);
+        let holders_refresh_interval = state.holders_refresh_interval;
+        let vault_inspection_interval = state.vault_inspection_interval;
+        let retention = state.retention;
+        let latency_slo = state.latency_slo;
+        let latency_metrics = Arc::default();
+        let actor = Actor::new(receiver, sender.clone(), state, Arc::clone(&latency_metrics));
         tokio::spawn(run_actor(actor));
-        Self { sender }
+        if let Some(period) = holders_refresh_interval {
+            tokio::spawn(tick(sender.clone(), period, || ActorMessage::RefreshHolders));
+        }
+        if let Some(period) = vault_inspection_interval {
+            tokio::spawn(tick(sender.clone(), period, || ActorMessage::InspectVault));
+        }
+        if retention.is_some() {
+            tokio::spawn(tick(sender.clone(), PRUNING_INTERVAL, || ActorMessage::Prune));
+        }
+        Self {
+            sender,
+            latency_metrics,
+            latency_slo,
+        }
     }
 
     pub fn handle_request(&self, tx: Option<EncodedConfirmedTransactionWithStatusMeta>) {
-        self.sender.send(ActorMessage(tx)).expect("Actor is dead");
+        self.sender
+            .send(ActorMessage::Distribute {
+                tx: tx.map(Box::new),
+                received_at: SystemTime::now(),
+                span: tracing::Span::current(),
+            })
+            .expect("Actor is dead");
     }
+
+    /// Latency metrics of the rounds in the Prometheus text format
+    pub fn latency_metrics(&self) -> String {
+        self.latency_metrics
+            .lock()
+            .expect("Latency metrics are poisoned")
+            .render(self.latency_slo)
+    }
+
+    pub fn latency_slo(&self) -> Duration {
+        self.latency_slo
+    }
+
+    pub async fn simulate_worst_case(&self, shares: u64) -> anyhow::Result<WorstCaseSimulation> {
+        let (reply, response) = oneshot::channel();
+        self.sender
+            .send(ActorMessage::SimulateWorstCase { shares, reply })
+            .expect("Actor is dead");
+        response.await.context("Actor dropped the simulation")?
+    }
+
+    /// Reloads the runtime config once the round in progress is over, returns the applied config
+    pub async fn reload(&self) -> anyhow::Result<RuntimeConfig> {
+        let (reply, response) = oneshot::channel();
+        self.sender
+            .send(ActorMessage::Reload { reply: Some(reply) })
+            .expect("Actor is dead");
+        response.await.context("Actor dropped the reload")?
+    }
+
+    /// Reloads the runtime config on every SIGHUP while the actor is alive
+    pub async fn reload_on_sighup(self) -> anyhow::Result<()> {
+        let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+            .context("Failed to listen to SIGHUP")?;
+        while hangup.recv().await.is_some() {
+            tracing::info!("SIGHUP received, reloading runtime config");
+            if self.sender.send(ActorMessage::Reload { reply: None }).is_err() {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    pub async fn dry_run(&self) -> anyhow::Result<DryRun> {
+        let (reply, response) = oneshot::channel();
+        self.sender.send(ActorMessage::DryRun { reply }).expect("Actor is dead");
+        response.await.context("Actor dropped the dry run")?
+    }
+
+    pub async fn what_if(&self, params: WhatIfParams) -> anyhow::Result<WhatIfReport> {
+        let (reply, response) = oneshot::channel();
+        self.sender
+            .send(ActorMessage::WhatIf { params, reply })
+            .expect("Actor is dead");
+        response.await.context("Actor dropped the what-if")?
+    }
+}
+
+/// Sends the message to the actor every `period` while it is alive, the messages queue behind the rounds
+async fn tick(sender: UnboundedSender<ActorMessage>, period: Duration, message: fn() -> ActorMessage) {
+    let mut interval = tokio::time::interval(period);
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        if sender.send(message()).is_err() {
+            break;
+        }
+    }
+}
+
+pub(crate) fn unix_timestamp() -> i64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs() as i64)
+}
+
+/// Fee of the message, legacy or v0
+async fn fetch_fee(rpc_client: &RpcClient, message: &VersionedMessage) -> anyhow::Result<u64> {
+    match message {
+        VersionedMessage::Legacy(message) => rpc_client.get_fee_for_message(message).await,
+        VersionedMessage::V0(message) => rpc_client.get_fee_for_message(message).await,
+    }
+    .context("Failed to get transaction fee")
+}
+
+/// Failure the transaction is simulated with, `None` if the simulation succeeds
+async fn simulation_failure(
+    rpc_client: &RpcClient,
+    tx: &VersionedTransaction,
+) -> anyhow::Result<Option<ProgramFailure>> {
+    let simulation = rpc_client
+        .simulate_transaction(tx)
+        .await
+        .context("Failed to simulate transaction")?
+        .value;
+    Ok(simulation
+        .err
+        .map(|err| ProgramFailure::new(&err, simulation.logs.as_deref().unwrap_or_default())))
+}
+
+/// Fetches the distributor state rejecting accounts which are corrupted or don't belong to the program
+pub async fn fetch_distributor_state(rpc_client: &RpcClient, address: &Pubkey) -> anyhow::Result<DistributorState> {
+    let account = rpc_client
+        .get_account(address)
+        .await
+        .context("Failed to fetch distributor state")?;
+    distributor::client::distributor_state(address, &account.owner, &account.data)
+        .context("Invalid distributor state account")
 }
 
 fn extract_vault_balance(vault: &Pubkey, tx: &EncodedConfirmedTransactionWithStatusMeta) -> anyhow::Result<u64> {
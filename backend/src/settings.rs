@@ -1,19 +1,116 @@
-use crate::any_keypair::AnyKeypair;
-use anyhow::{bail, Context};
+use crate::{
+    alerting::{AlertSink, Severity},
+    any_keypair::AnyKeypair,
+    notifications::Channel,
+    priority_fee::{PriorityFeeConfig, DEFAULT_MAX_PRIORITY_FEE},
+    shadow_draw::DrawStrategy,
+    telemetry::{parse_headers, OtlpConfig},
+    webhook_registration::WebhookConfig,
+};
+use anyhow::{bail, ensure, Context};
 use shuttle_secrets::SecretStore;
 use solana_sdk::{pubkey::Pubkey, signature::Keypair};
+use std::{collections::HashSet, time::Duration};
+
+/// Default `PAYER_BALANCE_FLOOR`, 0.05 SOL
+const DEFAULT_PAYER_BALANCE_FLOOR: u64 = 50_000_000;
+/// Default `VAULT_INSPECTION_INTERVAL_SECS`
+const DEFAULT_VAULT_INSPECTION_INTERVAL: Duration = Duration::from_secs(60);
+/// Default `RETENTION_DAYS`
+const DEFAULT_RETENTION_DAYS: u64 = 90;
+/// Default `ROUND_LATENCY_SLO_SECS`, holders are promised a payout within minutes of the threshold
+const DEFAULT_LATENCY_SLO: Duration = Duration::from_secs(5 * 60);
+/// Default `NOTIFICATION_COLLAPSE_THRESHOLD`
+const DEFAULT_NOTIFICATION_COLLAPSE_THRESHOLD: usize = 20;
+/// Default `RELAYER_CLAIMS_PER_HOUR`
+const DEFAULT_RELAYER_CLAIMS_PER_HOUR: u32 = 3;
+/// Default `MAX_SEND_ATTEMPTS`
+const DEFAULT_MAX_SEND_ATTEMPTS: u32 = 3;
+/// Default `WEBHOOK_VERIFY_INTERVAL_SECS`
+const DEFAULT_WEBHOOK_VERIFY_INTERVAL: Duration = Duration::from_secs(60 * 60);
 
 pub struct Settings {
     pub solana_rpc_url: String,
     pub priority_fee_url: String,
+    /// Priority fee level and caps of the round transactions
+    pub priority_fee_config: PriorityFeeConfig,
     pub payer: Keypair,
     pub distributor_authority: Keypair,
+    /// Signs `distribute` along with the authority, or in its place in the crank mode, if the distributor has a
+    /// service signer
+    pub service_signer: Option<Keypair>,
 
     pub distributor_state: Pubkey,
     pub program_id: Pubkey,
     pub marker_mint: Pubkey,
     pub auth_token: String,
     pub memo: String,
+    /// Cluster name appended to explorer links in reports, e.g. `devnet`
+    pub explorer_cluster: Option<String>,
+    /// Wallets which never receive a share, e.g. exchange and team wallets
+    pub excluded_wallets: HashSet<Pubkey>,
+    /// Startup fails if the payer has less lamports than this
+    pub payer_balance_floor: u64,
+    /// Record every round on-chain, the payer covers the rent of the records. Default of the `record_rounds` feature
+    pub record_rounds: bool,
+    /// Campaign of the `tickets` table the winners are drawn from, token holders are used if not set
+    pub ticket_campaign: Option<String>,
+    /// Reports of a round are public only after this delay since the confirmation, admins can preview them earlier
+    pub announcement_delay: Duration,
+    /// Draw the holders by the VRF randomness revealed for the round instead of a local RNG. Default of the
+    /// `vrf_draw` feature
+    pub vrf_draw: bool,
+    /// Public reports of the campaign show truncated winner addresses, the authenticated API keeps the full ones.
+    /// Default of the `privacy_mode` feature
+    pub privacy_mode: bool,
+    /// Pay the single transaction rounds by `distribute_v2`, which rejects a retry landing after its round. Default of
+    /// the `distribute_v2` feature, disabled until the distributor program is upgraded
+    pub distribute_v2: bool,
+    /// Simulate `validate_winners` and redraw the winners it rejects before the round is sent. Default of the
+    /// `validate_winners` feature, disabled until the distributor program is upgraded
+    pub validate_winners: bool,
+    /// Pay the `distribute_v2` rounds pro-rata to the marker balances of the winners. Default of the `pro_rata`
+    /// feature
+    pub pro_rata: bool,
+    /// Holders number is refreshed in the background at this interval, so the draw only confirms it. Disabled if
+    /// not set
+    pub holders_refresh_interval: Option<Duration>,
+    /// Vault is inspected for freezes, delegates and closure at this interval, zero disables the inspection
+    pub vault_inspection_interval: Option<Duration>,
+    /// Rounds which take longer from the trigger to the confirmation are alerted
+    pub latency_slo: Duration,
+    /// Sends of a round transaction, each expired one is signed again with a fresh blockhash
+    pub max_send_attempts: u32,
+    /// Keep an Address Lookup Table of the round accounts and pay the single transaction rounds by v0 transactions,
+    /// so more winners fit a transaction
+    pub use_lookup_table: bool,
+    /// Sign the single transaction rounds against the durable nonce of this account, whose authority is the payer,
+    /// so a round prepared during an RPC outage can still be sent later
+    pub nonce_account: Option<Pubkey>,
+    /// Rows older than this are pruned from the database in the background, zero days disables the pruning
+    pub retention: Option<Duration>,
+    /// Rounds are announced to these channels once their reports are public
+    pub notification_channels: Vec<Channel>,
+    /// Announcements of rounds with more winners list no winners
+    pub notification_collapse_threshold: usize,
+    /// Candidate strategy drawn in the shadow of the paid draws of the holders, the diffs are stored for review
+    pub shadow_draw_strategy: Option<DrawStrategy>,
+    /// Percentage of the rounds the candidate is drawn for, all of them by default
+    pub shadow_draw_percentage: u8,
+    /// Pays the fees of the claims relayed for the winners without SOL, kept apart from the payer of the rounds.
+    /// The relayer is disabled if not set
+    pub relayer: Option<Keypair>,
+    /// Claims a wallet can relay in an hour, attempts with invalid signatures count too
+    pub relayer_claims_per_hour: u32,
+    /// Alerts are delivered to these sinks if they are at least of the severity of the sink
+    pub alert_sinks: Vec<(AlertSink, Severity)>,
+    /// Helius webhook of the vault and the distributor state is registered and kept pointed at the backend. The
+    /// webhook is managed manually if not set
+    pub webhook: Option<WebhookConfig>,
+    /// The registered webhook is verified and repaired at this interval
+    pub webhook_verify_interval: Duration,
+    /// Spans are exported to this OTLP collector as distributed traces, only logged if not set
+    pub otlp: Option<OtlpConfig>,
 }
 
 impl TryFrom<&SecretStore> for Settings {
@@ -32,6 +129,30 @@ impl TryFrom<&SecretStore> for Settings {
         let Some(priority_fee_url) = secret_store.get("PRIORITY_FEE_URL") else {
             bail!("PRIORITY_FEE_URL not found in secret store");
         };
+        let priority_fee_config = PriorityFeeConfig {
+            level: secret_store
+                .get("PRIORITY_FEE_LEVEL")
+                .map(|secret| secret.parse())
+                .transpose()
+                .context("Can't deserialize PRIORITY_FEE_LEVEL")?
+                .unwrap_or_default(),
+            min: secret_store
+                .get("PRIORITY_FEE_MIN")
+                .map(|secret| secret.parse())
+                .transpose()
+                .context("Can't deserialize PRIORITY_FEE_MIN")?
+                .unwrap_or_default(),
+            max: secret_store
+                .get("PRIORITY_FEE_MAX")
+                .map(|secret| secret.parse())
+                .transpose()
+                .context("Can't deserialize PRIORITY_FEE_MAX")?
+                .unwrap_or(DEFAULT_MAX_PRIORITY_FEE),
+        };
+        ensure!(
+            priority_fee_config.min <= priority_fee_config.max,
+            "PRIORITY_FEE_MIN can't exceed PRIORITY_FEE_MAX"
+        );
         let Some(AnyKeypair(payer)) = secret_store
             .get("PAYER_KEYPAIR")
             .map(|secret| secret.parse())
@@ -48,6 +169,12 @@ impl TryFrom<&SecretStore> for Settings {
         else {
             bail!("DISTRIBUTOR_AUTHORITY_KEYPAIR not found in secret store")
         };
+        let service_signer = secret_store
+            .get("SERVICE_SIGNER_KEYPAIR")
+            .map(|secret| secret.parse())
+            .transpose()
+            .context("Can't deserialize SERVICE_SIGNER_KEYPAIR")?
+            .map(|AnyKeypair(keypair)| keypair);
         let Some(distributor_state) = secret_store
             .get("DISTRIBUTOR_STATE")
             .map(|secret| secret.parse())
@@ -73,16 +200,268 @@ impl TryFrom<&SecretStore> for Settings {
             bail!("MARKER_MINT not found in secret store")
         };
 
+        let explorer_cluster = secret_store.get("EXPLORER_CLUSTER");
+        let excluded_wallets = secret_store
+            .get("EXCLUDED_WALLETS")
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|wallet| !wallet.is_empty())
+            .map(|wallet| wallet.parse())
+            .collect::<Result<_, _>>()
+            .context("Can't deserialize EXCLUDED_WALLETS")?;
+        let payer_balance_floor = secret_store
+            .get("PAYER_BALANCE_FLOOR")
+            .map(|secret| secret.parse())
+            .transpose()
+            .context("Can't deserialize PAYER_BALANCE_FLOOR")?
+            .unwrap_or(DEFAULT_PAYER_BALANCE_FLOOR);
+        let record_rounds = secret_store
+            .get("RECORD_ROUNDS")
+            .map(|secret| secret.parse())
+            .transpose()
+            .context("Can't deserialize RECORD_ROUNDS")?
+            .unwrap_or(true);
+        let ticket_campaign = secret_store.get("TICKET_CAMPAIGN");
+        let announcement_delay = secret_store
+            .get("ANNOUNCEMENT_DELAY_SECS")
+            .map(|secret| secret.parse())
+            .transpose()
+            .context("Can't deserialize ANNOUNCEMENT_DELAY_SECS")?
+            .map(Duration::from_secs)
+            .unwrap_or_default();
+        let vrf_draw = secret_store
+            .get("VRF_DRAW")
+            .map(|secret| secret.parse())
+            .transpose()
+            .context("Can't deserialize VRF_DRAW")?
+            .unwrap_or_default();
+        let privacy_mode = secret_store
+            .get("PRIVACY_MODE")
+            .map(|secret| secret.parse())
+            .transpose()
+            .context("Can't deserialize PRIVACY_MODE")?
+            .unwrap_or_default();
+        let distribute_v2 = secret_store
+            .get("DISTRIBUTE_V2")
+            .map(|secret| secret.parse())
+            .transpose()
+            .context("Can't deserialize DISTRIBUTE_V2")?
+            .unwrap_or_default();
+        let validate_winners = secret_store
+            .get("VALIDATE_WINNERS")
+            .map(|secret| secret.parse())
+            .transpose()
+            .context("Can't deserialize VALIDATE_WINNERS")?
+            .unwrap_or_default();
+        let pro_rata = secret_store
+            .get("PRO_RATA")
+            .map(|secret| secret.parse())
+            .transpose()
+            .context("Can't deserialize PRO_RATA")?
+            .unwrap_or_default();
+        let holders_refresh_interval = secret_store
+            .get("HOLDERS_REFRESH_INTERVAL_SECS")
+            .map(|secret| secret.parse())
+            .transpose()
+            .context("Can't deserialize HOLDERS_REFRESH_INTERVAL_SECS")?
+            .map(Duration::from_secs)
+            .filter(|interval| !interval.is_zero());
+        let vault_inspection_interval = secret_store
+            .get("VAULT_INSPECTION_INTERVAL_SECS")
+            .map(|secret| secret.parse())
+            .transpose()
+            .context("Can't deserialize VAULT_INSPECTION_INTERVAL_SECS")?
+            .map(Duration::from_secs)
+            .or(Some(DEFAULT_VAULT_INSPECTION_INTERVAL))
+            .filter(|interval| !interval.is_zero());
+        let latency_slo = secret_store
+            .get("ROUND_LATENCY_SLO_SECS")
+            .map(|secret| secret.parse())
+            .transpose()
+            .context("Can't deserialize ROUND_LATENCY_SLO_SECS")?
+            .map_or(DEFAULT_LATENCY_SLO, Duration::from_secs);
+        let max_send_attempts = secret_store
+            .get("MAX_SEND_ATTEMPTS")
+            .map(|secret| secret.parse())
+            .transpose()
+            .context("Can't deserialize MAX_SEND_ATTEMPTS")?
+            .unwrap_or(DEFAULT_MAX_SEND_ATTEMPTS);
+        ensure!(max_send_attempts > 0, "MAX_SEND_ATTEMPTS can't be zero");
+        let use_lookup_table = secret_store
+            .get("USE_LOOKUP_TABLE")
+            .map(|secret| secret.parse())
+            .transpose()
+            .context("Can't deserialize USE_LOOKUP_TABLE")?
+            .unwrap_or_default();
+        let nonce_account = secret_store
+            .get("NONCE_ACCOUNT")
+            .map(|secret| secret.parse())
+            .transpose()
+            .context("Can't deserialize NONCE_ACCOUNT")?;
+        let retention = secret_store
+            .get("RETENTION_DAYS")
+            .map(|secret| secret.parse::<u64>())
+            .transpose()
+            .context("Can't deserialize RETENTION_DAYS")?
+            .map_or(Some(DEFAULT_RETENTION_DAYS), |days| (days > 0).then_some(days))
+            .map(|days| Duration::from_secs(days * 24 * 60 * 60));
+        let discord = secret_store
+            .get("DISCORD_WEBHOOK_URL")
+            .map(|webhook_url| Channel::Discord { webhook_url });
+        let telegram = match (
+            secret_store.get("TELEGRAM_BOT_TOKEN"),
+            secret_store.get("TELEGRAM_CHAT_ID"),
+        ) {
+            (Some(bot_token), Some(chat_id)) => Some(Channel::Telegram { bot_token, chat_id }),
+            (None, None) => None,
+            _ => bail!("TELEGRAM_BOT_TOKEN and TELEGRAM_CHAT_ID have to be set together"),
+        };
+        let notification_channels = discord.into_iter().chain(telegram).collect();
+        let notification_collapse_threshold = secret_store
+            .get("NOTIFICATION_COLLAPSE_THRESHOLD")
+            .map(|secret| secret.parse())
+            .transpose()
+            .context("Can't deserialize NOTIFICATION_COLLAPSE_THRESHOLD")?
+            .unwrap_or(DEFAULT_NOTIFICATION_COLLAPSE_THRESHOLD);
+        let shadow_draw_strategy = secret_store
+            .get("SHADOW_DRAW_STRATEGY")
+            .map(|secret| secret.parse())
+            .transpose()
+            .context("Can't deserialize SHADOW_DRAW_STRATEGY")?;
+        let shadow_draw_percentage = secret_store
+            .get("SHADOW_DRAW_PERCENTAGE")
+            .map(|secret| secret.parse())
+            .transpose()
+            .context("Can't deserialize SHADOW_DRAW_PERCENTAGE")?
+            .unwrap_or(100);
+        ensure!(shadow_draw_percentage <= 100, "SHADOW_DRAW_PERCENTAGE can't exceed 100");
+        let relayer = secret_store
+            .get("RELAYER_KEYPAIR")
+            .map(|secret| secret.parse())
+            .transpose()
+            .context("Can't deserialize RELAYER_KEYPAIR")?
+            .map(|AnyKeypair(keypair)| keypair);
+        let relayer_claims_per_hour = secret_store
+            .get("RELAYER_CLAIMS_PER_HOUR")
+            .map(|secret| secret.parse())
+            .transpose()
+            .context("Can't deserialize RELAYER_CLAIMS_PER_HOUR")?
+            .unwrap_or(DEFAULT_RELAYER_CLAIMS_PER_HOUR);
+        let pagerduty = secret_store
+            .get("ALERT_PAGERDUTY_ROUTING_KEY")
+            .map(|routing_key| AlertSink::PagerDuty { routing_key });
+        let pagerduty_severity = secret_store
+            .get("ALERT_PAGERDUTY_SEVERITY")
+            .map(|secret| secret.parse())
+            .transpose()
+            .context("Can't deserialize ALERT_PAGERDUTY_SEVERITY")?
+            .unwrap_or(Severity::Critical);
+        let discord = secret_store
+            .get("ALERT_DISCORD_WEBHOOK_URL")
+            .map(|webhook_url| AlertSink::Discord { webhook_url });
+        let discord_severity = secret_store
+            .get("ALERT_DISCORD_SEVERITY")
+            .map(|secret| secret.parse())
+            .transpose()
+            .context("Can't deserialize ALERT_DISCORD_SEVERITY")?
+            .unwrap_or(Severity::Warning);
+        let email = match (
+            secret_store.get("ALERT_SMTP_URL"),
+            secret_store.get("ALERT_EMAIL_FROM"),
+            secret_store.get("ALERT_EMAIL_TO"),
+        ) {
+            (Some(smtp_url), Some(from), Some(to)) => Some(AlertSink::Email {
+                smtp_url,
+                from: from.parse().context("Can't deserialize ALERT_EMAIL_FROM")?,
+                to: to.parse().context("Can't deserialize ALERT_EMAIL_TO")?,
+            }),
+            (None, None, None) => None,
+            _ => bail!("ALERT_SMTP_URL, ALERT_EMAIL_FROM and ALERT_EMAIL_TO have to be set together"),
+        };
+        let email_severity = secret_store
+            .get("ALERT_EMAIL_SEVERITY")
+            .map(|secret| secret.parse())
+            .transpose()
+            .context("Can't deserialize ALERT_EMAIL_SEVERITY")?
+            .unwrap_or(Severity::Warning);
+        let alert_sinks = [
+            pagerduty.map(|sink| (sink, pagerduty_severity)),
+            discord.map(|sink| (sink, discord_severity)),
+            email.map(|sink| (sink, email_severity)),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+
+        let webhook = match (secret_store.get("HELIUS_API_KEY"), secret_store.get("WEBHOOK_URL")) {
+            (Some(api_key), Some(webhook_url)) => Some(WebhookConfig { api_key, webhook_url }),
+            (None, None) => None,
+            _ => bail!("HELIUS_API_KEY and WEBHOOK_URL have to be set together"),
+        };
+        let webhook_verify_interval = secret_store
+            .get("WEBHOOK_VERIFY_INTERVAL_SECS")
+            .map(|secret| secret.parse())
+            .transpose()
+            .context("Can't deserialize WEBHOOK_VERIFY_INTERVAL_SECS")?
+            .map_or(DEFAULT_WEBHOOK_VERIFY_INTERVAL, Duration::from_secs);
+        ensure!(
+            !webhook_verify_interval.is_zero(),
+            "WEBHOOK_VERIFY_INTERVAL_SECS can't be zero"
+        );
+        let otlp = secret_store
+            .get("OTLP_ENDPOINT")
+            .map(|endpoint| {
+                let headers = secret_store
+                    .get("OTLP_HEADERS")
+                    .map(|headers| parse_headers(&headers))
+                    .transpose()
+                    .context("Can't deserialize OTLP_HEADERS")?
+                    .unwrap_or_default();
+                anyhow::Ok(OtlpConfig { endpoint, headers })
+            })
+            .transpose()?;
+
         Ok(Self {
             solana_rpc_url,
             priority_fee_url,
+            priority_fee_config,
             payer,
             distributor_authority,
+            service_signer,
             distributor_state,
             program_id,
             auth_token,
             memo,
             marker_mint,
+            explorer_cluster,
+            excluded_wallets,
+            payer_balance_floor,
+            record_rounds,
+            ticket_campaign,
+            announcement_delay,
+            vrf_draw,
+            privacy_mode,
+            distribute_v2,
+            validate_winners,
+            pro_rata,
+            holders_refresh_interval,
+            vault_inspection_interval,
+            latency_slo,
+            max_send_attempts,
+            use_lookup_table,
+            nonce_account,
+            retention,
+            notification_channels,
+            notification_collapse_threshold,
+            shadow_draw_strategy,
+            shadow_draw_percentage,
+            relayer,
+            relayer_claims_per_hour,
+            alert_sinks,
+            webhook,
+            webhook_verify_interval,
+            otlp,
         })
     }
 }
@@ -0,0 +1,264 @@
+use anyhow::{bail, Context};
+use rand::{
+    distributions::{Distribution, Uniform},
+    Rng,
+};
+use serde::Serialize;
+use solana_sdk::pubkey::Pubkey;
+use std::{collections::HashSet, str::FromStr};
+
+/// Buckets of the entrants list the winner positions are counted in
+const POSITION_BUCKETS: usize = 10;
+
+/// How the winner indices are drawn from the entrants list
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DrawStrategy {
+    /// Uniformly with replacement, the way the holders are drawn for the payouts
+    Uniform,
+    /// Uniformly without replacement, an entrant wins at most once per round
+    Distinct,
+}
+
+impl DrawStrategy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DrawStrategy::Uniform => "uniform",
+            DrawStrategy::Distinct => "distinct",
+        }
+    }
+
+    /// `n` indices of `entrants`, fewer if the strategy can't draw that many
+    pub fn draw(&self, rng: &mut impl Rng, entrants: u64, n: u64) -> Vec<u64> {
+        if entrants == 0 {
+            return Vec::new();
+        }
+        match self {
+            DrawStrategy::Uniform => Uniform::from(0..entrants).sample_iter(rng).take(n as usize).collect(),
+            DrawStrategy::Distinct => rand::seq::index::sample(rng, entrants as usize, n.min(entrants) as usize)
+                .into_iter()
+                .map(|index| index as u64)
+                .collect(),
+        }
+    }
+}
+
+impl FromStr for DrawStrategy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uniform" => Ok(DrawStrategy::Uniform),
+            "distinct" => Ok(DrawStrategy::Distinct),
+            _ => bail!("Unknown draw strategy {s}"),
+        }
+    }
+}
+
+/// Whether the round is in the `percentage` of the rounds the candidate strategy is drawn for
+pub fn in_canary(rng: &mut impl Rng, percentage: u8) -> bool {
+    rng.gen_range(0..100) < percentage
+}
+
+/// Paid draw of a round next to the draw of the candidate strategy from the same entrants
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DrawDiff {
+    pub strategy: DrawStrategy,
+    pub entrants: u64,
+    pub current: Vec<u64>,
+    pub candidate: Vec<u64>,
+}
+
+impl DrawDiff {
+    /// Entrants drawn by both strategies
+    pub fn common_winners(&self) -> usize {
+        let current = self.current.iter().collect::<HashSet<_>>();
+        self.candidate
+            .iter()
+            .collect::<HashSet<_>>()
+            .intersection(&current)
+            .count()
+    }
+}
+
+/// Shares of the draw which went to an entrant already drawn in the round
+pub fn repeats(indices: &[u64]) -> usize {
+    indices.len() - indices.iter().collect::<HashSet<_>>().len()
+}
+
+/// How the winner positions of a strategy spread over the entrants list
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PositionStats {
+    pub winners: u64,
+    pub repeats: u64,
+    /// Mean position relative to the number of entrants, close to 0.5 for a fair draw
+    pub mean_position: f64,
+    /// Winners per tenth of the entrants list
+    pub buckets: [u64; POSITION_BUCKETS],
+}
+
+impl PositionStats {
+    fn new() -> Self {
+        Self {
+            winners: 0,
+            repeats: 0,
+            mean_position: 0.0,
+            buckets: [0; POSITION_BUCKETS],
+        }
+    }
+
+    fn observe(&mut self, indices: &[u64], entrants: u64) {
+        if entrants == 0 {
+            return;
+        }
+        let mut position_sum = self.mean_position * self.winners as f64;
+        for index in indices {
+            let position = (*index as f64 + 0.5) / entrants as f64;
+            position_sum += position;
+            self.buckets[((position * POSITION_BUCKETS as f64) as usize).min(POSITION_BUCKETS - 1)] += 1;
+        }
+        self.winners += indices.len() as u64;
+        self.repeats += repeats(indices) as u64;
+        self.mean_position = if self.winners > 0 {
+            position_sum / self.winners as f64
+        } else {
+            0.0
+        };
+    }
+}
+
+/// Divergence of a candidate strategy from the paid draws over the rounds it shadowed
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DivergenceStats {
+    pub strategy: DrawStrategy,
+    pub rounds: u64,
+    /// Winners of the paid draws the candidate drew as well
+    pub common_winners: u64,
+    pub current: PositionStats,
+    pub candidate: PositionStats,
+}
+
+impl DivergenceStats {
+    /// Statistics per candidate strategy
+    pub fn from_diffs(diffs: &[DrawDiff]) -> Vec<Self> {
+        let mut stats: Vec<DivergenceStats> = Vec::new();
+        for diff in diffs {
+            let position = match stats.iter().position(|stats| stats.strategy == diff.strategy) {
+                Some(position) => position,
+                None => {
+                    stats.push(Self {
+                        strategy: diff.strategy,
+                        rounds: 0,
+                        common_winners: 0,
+                        current: PositionStats::new(),
+                        candidate: PositionStats::new(),
+                    });
+                    stats.len() - 1
+                },
+            };
+            let stats = &mut stats[position];
+            stats.rounds += 1;
+            stats.common_winners += diff.common_winners() as u64;
+            stats.current.observe(&diff.current, diff.entrants);
+            stats.candidate.observe(&diff.candidate, diff.entrants);
+        }
+        stats
+    }
+}
+
+pub async fn store_draw_diff(
+    pool: &sqlx::PgPool,
+    distributor_state: &Pubkey,
+    distribution_id: i64,
+    diff: &DrawDiff,
+) -> anyhow::Result<()> {
+    sqlx::query(
+        "INSERT INTO shadow_draws (distribution_id, distributor_state, strategy, entrants, current_indices, \
+         candidate_indices, common_winners) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+    )
+    .bind(distribution_id)
+    .bind(distributor_state.to_string())
+    .bind(diff.strategy.as_str())
+    .bind(diff.entrants as i64)
+    .bind(diff.current.iter().map(|index| *index as i64).collect::<Vec<_>>())
+    .bind(diff.candidate.iter().map(|index| *index as i64).collect::<Vec<_>>())
+    .bind(diff.common_winners() as i32)
+    .execute(pool)
+    .await
+    .context("Failed to store shadow draw")?;
+    Ok(())
+}
+
+/// Divergence statistics of the shadow draws of the campaign
+pub async fn fetch_divergence_stats(
+    pool: &sqlx::PgPool,
+    distributor_state: &Pubkey,
+) -> anyhow::Result<Vec<DivergenceStats>> {
+    let rows: Vec<(String, i64, Vec<i64>, Vec<i64>)> = sqlx::query_as(
+        "SELECT strategy, entrants, current_indices, candidate_indices FROM shadow_draws \
+         WHERE distributor_state = $1 ORDER BY id",
+    )
+    .bind(distributor_state.to_string())
+    .fetch_all(pool)
+    .await
+    .context("Failed to fetch shadow draws")?;
+    let diffs = rows
+        .into_iter()
+        .map(|(strategy, entrants, current, candidate)| {
+            Ok(DrawDiff {
+                strategy: strategy.parse()?,
+                entrants: entrants as u64,
+                current: current.into_iter().map(|index| index as u64).collect(),
+                candidate: candidate.into_iter().map(|index| index as u64).collect(),
+            })
+        })
+        .collect::<anyhow::Result<Vec<_>>>()?;
+    Ok(DivergenceStats::from_diffs(&diffs))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::shadow_draw::{repeats, DivergenceStats, DrawDiff, DrawStrategy};
+    use rand::{rngs::StdRng, SeedableRng};
+
+    #[test]
+    fn should_draw_distinct_indices() {
+        let mut rng = StdRng::seed_from_u64(7);
+        let indices = DrawStrategy::Distinct.draw(&mut rng, 5, 8);
+        assert_eq!(5, indices.len());
+        assert_eq!(0, repeats(&indices));
+        assert!(indices.iter().all(|index| *index < 5));
+
+        assert_eq!(8, DrawStrategy::Uniform.draw(&mut rng, 5, 8).len());
+        assert!(DrawStrategy::Uniform.draw(&mut rng, 0, 8).is_empty());
+    }
+
+    #[test]
+    fn should_aggregate_divergence_per_strategy() {
+        let diffs = [
+            DrawDiff {
+                strategy: DrawStrategy::Distinct,
+                entrants: 10,
+                current: vec![0, 0, 9],
+                candidate: vec![0, 4, 9],
+            },
+            DrawDiff {
+                strategy: DrawStrategy::Distinct,
+                entrants: 10,
+                current: vec![5],
+                candidate: vec![3],
+            },
+        ];
+        let stats = DivergenceStats::from_diffs(&diffs);
+
+        assert_eq!(1, stats.len());
+        let stats = &stats[0];
+        assert_eq!(2, stats.rounds);
+        assert_eq!(2, stats.common_winners);
+        assert_eq!(4, stats.current.winners);
+        assert_eq!(1, stats.current.repeats);
+        assert_eq!(0, stats.candidate.repeats);
+        assert_eq!([2, 0, 0, 0, 0, 1, 0, 0, 0, 1], stats.current.buckets);
+        assert!((stats.candidate.mean_position - 0.45).abs() < 1e-9);
+    }
+}
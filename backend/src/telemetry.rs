@@ -0,0 +1,104 @@
+//! Logs of the backend and, if an OTLP endpoint is configured, export of its spans as distributed traces.
+
+use anyhow::{anyhow, Context};
+use opentelemetry::KeyValue;
+use opentelemetry_otlp::WithExportConfig;
+use opentelemetry_sdk::{runtime, trace, Resource};
+use tonic::metadata::{Ascii, MetadataKey, MetadataMap, MetadataValue};
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+
+/// `service.name` of the exported traces
+const SERVICE_NAME: &str = "distributor-backend";
+/// Filter of the logs and the exported spans if `RUST_LOG` isn't set
+const DEFAULT_FILTER: &str = "info";
+
+/// OTLP gRPC collector the spans are exported to, e.g. Grafana Tempo or Honeycomb
+#[derive(Debug, Clone, PartialEq)]
+pub struct OtlpConfig {
+    /// `http://tempo:4317`, `https://api.honeycomb.io:443`
+    pub endpoint: String,
+    /// Sent along with every export, e.g. the `x-honeycomb-team` API key
+    pub headers: Vec<(String, String)>,
+}
+
+/// Headers in the `OTEL_EXPORTER_OTLP_HEADERS` format, `key1=value1,key2=value2`
+pub fn parse_headers(headers: &str) -> anyhow::Result<Vec<(String, String)>> {
+    headers
+        .split(',')
+        .map(str::trim)
+        .filter(|header| !header.is_empty())
+        .map(|header| {
+            let (key, value) = header
+                .split_once('=')
+                .ok_or_else(|| anyhow!("Header {header} isn't key=value"))?;
+            Ok((key.trim().to_string(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+fn metadata(headers: &[(String, String)]) -> anyhow::Result<MetadataMap> {
+    let mut metadata = MetadataMap::new();
+    for (key, value) in headers {
+        let key: MetadataKey<Ascii> = key.parse().with_context(|| format!("Invalid header name {key}"))?;
+        let value: MetadataValue<Ascii> = value
+            .parse()
+            .with_context(|| format!("Invalid value of header {key}"))?;
+        metadata.insert(key, value);
+    }
+    Ok(metadata)
+}
+
+/// Installs the global subscriber logging to stdout, which also exports the spans to the OTLP collector if `otlp`
+/// is set. The spans are exported in batches in the background
+pub fn init_tracing(otlp: Option<&OtlpConfig>) -> anyhow::Result<()> {
+    let otlp_layer = otlp
+        .map(|otlp| {
+            let exporter = opentelemetry_otlp::new_exporter()
+                .tonic()
+                .with_endpoint(&otlp.endpoint)
+                .with_metadata(metadata(&otlp.headers)?);
+            let tracer = opentelemetry_otlp::new_pipeline()
+                .tracing()
+                .with_exporter(exporter)
+                .with_trace_config(
+                    trace::config().with_resource(Resource::new([KeyValue::new("service.name", SERVICE_NAME)])),
+                )
+                .install_batch(runtime::Tokio)
+                .context("Failed to install the OTLP exporter")?;
+            anyhow::Ok(tracing_opentelemetry::layer().with_tracer(tracer))
+        })
+        .transpose()?;
+
+    tracing_subscriber::registry()
+        .with(EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER)))
+        .with(tracing_subscriber::fmt::layer().without_time())
+        .with(otlp_layer)
+        .try_init()
+        .context("Failed to install the tracing subscriber")?;
+    if let Some(otlp) = otlp {
+        tracing::info!(endpoint = %otlp.endpoint, "Exporting traces");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::telemetry::{metadata, parse_headers};
+
+    #[test]
+    fn should_parse_otlp_headers() {
+        let headers = parse_headers("x-honeycomb-team=key, x-honeycomb-dataset = distributor,").unwrap();
+        assert_eq!(
+            vec![
+                ("x-honeycomb-team".to_string(), "key".to_string()),
+                ("x-honeycomb-dataset".to_string(), "distributor".to_string()),
+            ],
+            headers
+        );
+        assert_eq!(2, metadata(&headers).unwrap().len());
+
+        assert!(parse_headers("").unwrap().is_empty());
+        assert!(parse_headers("x-honeycomb-team").is_err());
+        assert!(metadata(&[("bad header".to_string(), "key".to_string())]).is_err());
+    }
+}
@@ -0,0 +1,172 @@
+use crate::token_holder::Winner;
+use anyhow::{ensure, Context};
+use rand::{
+    distributions::{Distribution, WeightedIndex},
+    Rng,
+};
+use serde::Deserialize;
+use serde_with::{serde_as, DisplayFromStr};
+use solana_sdk::pubkey::Pubkey;
+use std::collections::{hash_map::Entry, HashMap};
+
+#[serde_as]
+#[derive(Debug, Clone, Deserialize)]
+pub struct TicketEntry {
+    #[serde_as(as = "DisplayFromStr")]
+    pub wallet: Pubkey,
+    /// Number of tickets the entry is worth
+    pub weight: u64,
+}
+
+/// Adds the entries to the campaign, weights of repeated wallets are summed up
+pub async fn store_tickets(pool: &sqlx::PgPool, campaign: &str, entries: &[TicketEntry]) -> anyhow::Result<u64> {
+    ensure!(!campaign.is_empty(), "Campaign can't be empty");
+    ensure!(
+        entries
+            .iter()
+            .all(|entry| entry.weight > 0 && entry.weight <= i64::MAX as u64),
+        "Ticket weight has to be positive"
+    );
+
+    // a row can be updated only once by a statement, the repeated wallets have to be summed up before
+    let entries = merge_entries(entries)?;
+    let result = sqlx::query(
+        "INSERT INTO tickets (campaign, wallet, weight) SELECT $1, * FROM UNNEST($2::varchar[], $3::bigint[]) \
+         ON CONFLICT (campaign, wallet) DO UPDATE SET weight = tickets.weight + EXCLUDED.weight, \
+         updated_at = CURRENT_TIMESTAMP",
+    )
+    .bind(campaign)
+    .bind(entries.iter().map(|entry| entry.wallet.to_string()).collect::<Vec<_>>())
+    .bind(entries.iter().map(|entry| entry.weight as i64).collect::<Vec<_>>())
+    .execute(pool)
+    .await
+    .context("Failed to store tickets")?;
+    Ok(result.rows_affected())
+}
+
+/// Entries with the weights of the repeated wallets summed up, in the order the wallets first appear
+fn merge_entries(entries: &[TicketEntry]) -> anyhow::Result<Vec<TicketEntry>> {
+    let mut positions = HashMap::<Pubkey, usize>::new();
+    let mut merged = Vec::<TicketEntry>::with_capacity(entries.len());
+    for entry in entries {
+        match positions.entry(entry.wallet) {
+            Entry::Occupied(position) => {
+                let merged = &mut merged[*position.get()];
+                merged.weight = merged
+                    .weight
+                    .checked_add(entry.weight)
+                    .filter(|weight| *weight <= i64::MAX as u64)
+                    .with_context(|| format!("Ticket weight of {} overflows", entry.wallet))?;
+            },
+            Entry::Vacant(position) => {
+                position.insert(merged.len());
+                merged.push(entry.clone());
+            },
+        }
+    }
+    Ok(merged)
+}
+
+/// Tickets of a campaign at the moment the round started, every draw of the round uses the same snapshot
+pub struct TicketSnapshot {
+    entries: Vec<TicketEntry>,
+}
+
+impl TicketSnapshot {
+    pub fn new(entries: Vec<TicketEntry>) -> Self {
+        Self { entries }
+    }
+
+    pub async fn fetch(pool: &sqlx::PgPool, campaign: &str) -> anyhow::Result<Self> {
+        let rows: Vec<(String, i64)> =
+            sqlx::query_as("SELECT wallet, weight FROM tickets WHERE campaign = $1 ORDER BY id")
+                .bind(campaign)
+                .fetch_all(pool)
+                .await
+                .context("Failed to fetch tickets")?;
+        let entries = rows
+            .into_iter()
+            .map(|(wallet, weight)| {
+                Ok(TicketEntry {
+                    wallet: wallet.parse().context("Invalid ticket wallet")?,
+                    weight: weight as u64,
+                })
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self { entries })
+    }
+
+    pub fn entries_number(&self) -> u64 {
+        self.entries.len() as u64
+    }
+
+    pub fn entries(&self) -> &[TicketEntry] {
+        &self.entries
+    }
+
+    /// Samples `n` entries proportionally to their weights with `rng`, the index of a winner is the entry position
+    pub fn draw_winners(&self, rng: &mut impl Rng, n: u64) -> anyhow::Result<Vec<Winner>> {
+        let distr =
+            WeightedIndex::new(self.entries.iter().map(|entry| entry.weight)).context("Campaign has no tickets")?;
+        Ok(distr
+            .sample_iter(rng)
+            .take(n as usize)
+            .map(|index| Winner {
+                index: index as u64,
+                wallet: self.entries[index].wallet,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::ticket::{merge_entries, TicketEntry, TicketSnapshot};
+    use solana_sdk::pubkey;
+
+    #[test]
+    fn should_draw_weighted_tickets() {
+        let snapshot = TicketSnapshot::new(vec![
+            TicketEntry {
+                wallet: pubkey!("De49soBQoHpombVpexCsPEh7Fi5Pfh5fNbhKimhfG28i"),
+                weight: u64::MAX / 2,
+            },
+            TicketEntry {
+                wallet: pubkey!("EBHnjoKTCn4S27pYsfYesRbnVr3JmAHg6E5JEnrgAqCR"),
+                weight: 1,
+            },
+        ]);
+
+        let winners = snapshot.draw_winners(&mut rand::thread_rng(), 5).unwrap();
+        assert_eq!(5, winners.len());
+        for winner in winners {
+            assert_eq!(0, winner.index);
+            assert_eq!(pubkey!("De49soBQoHpombVpexCsPEh7Fi5Pfh5fNbhKimhfG28i"), winner.wallet);
+        }
+    }
+
+    #[test]
+    fn should_sum_up_repeated_wallets() {
+        let (first, second) = (
+            pubkey!("De49soBQoHpombVpexCsPEh7Fi5Pfh5fNbhKimhfG28i"),
+            pubkey!("EBHnjoKTCn4S27pYsfYesRbnVr3JmAHg6E5JEnrgAqCR"),
+        );
+        let entry = |wallet, weight| TicketEntry { wallet, weight };
+        let merged = merge_entries(&[entry(first, 2), entry(second, 1), entry(first, 3)]).unwrap();
+        assert_eq!(
+            vec![(first, 5), (second, 1)],
+            merged
+                .iter()
+                .map(|entry| (entry.wallet, entry.weight))
+                .collect::<Vec<_>>()
+        );
+        assert!(merge_entries(&[entry(first, i64::MAX as u64), entry(first, 1)]).is_err());
+    }
+
+    #[test]
+    fn should_fail_to_draw_from_empty_campaign() {
+        assert!(TicketSnapshot::new(vec![])
+            .draw_winners(&mut rand::thread_rng(), 1)
+            .is_err());
+    }
+}
@@ -4,17 +4,20 @@ use jsonrpsee::{
     http_client::{HttpClient, HttpClientBuilder},
     proc_macros::rpc,
 };
-use rand::distributions::{Distribution, Uniform};
+use rand::{
+    distributions::{Distribution, Uniform},
+    Rng,
+};
 use serde::Deserialize;
-use serde_with::{serde_as, DisplayFromStr, FromInto};
+use serde_with::{serde_as, DisplayFromStr};
 use solana_sdk::pubkey::Pubkey;
+use std::future::Future;
 
 #[serde_as]
 #[derive(Deserialize)]
 struct GetTokenAccountsResponse {
     total: u64,
-    #[serde_as(as = "Vec<FromInto<TokenAccount>>")]
-    token_accounts: Vec<Pubkey>,
+    token_accounts: Vec<TokenAccount>,
 }
 
 #[serde_as]
@@ -22,17 +25,11 @@ struct GetTokenAccountsResponse {
 struct TokenAccount {
     // address: Pubkey
     // mint: Pubkey,
-    // amount: u64,
     // delegated_amount: u64,
     // frozen: false,
     #[serde_as(as = "DisplayFromStr")]
     owner: Pubkey,
-}
-
-impl From<TokenAccount> for Pubkey {
-    fn from(account: TokenAccount) -> Self {
-        account.owner
-    }
+    amount: u64,
 }
 
 #[rpc(client)]
@@ -41,6 +38,13 @@ trait HeliusGetTokenAccounts {
     async fn get_token_accounts(&self, mint: &str, page: u64, limit: u64) -> RpcResult<GetTokenAccountsResponse>;
 }
 
+#[derive(Debug, Clone, Copy)]
+pub struct Winner {
+    /// Position of the holder in the token accounts list
+    pub index: u64,
+    pub wallet: Pubkey,
+}
+
 pub struct HeliusClient {
     client: HttpClient,
     mint: Pubkey,
@@ -80,33 +84,40 @@ impl HeliusClient {
         Ok(())
     }
 
+    /// Starts from the page of the known holders number, so an up to date number costs a single request
     pub async fn discover_token_holders_number(&self) -> anyhow::Result<u64> {
         let limit = 1000;
-
-        for page in (self.holders_number / limit + 1)..2000 {
-            let GetTokenAccountsResponse { total, .. } = self
-                .client
-                .get_token_accounts(&self.mint.to_string(), page, limit)
-                .await?;
-            if total < limit {
-                return Ok(limit * (page - 1) + total);
+        let mint = self.mint.to_string();
+        find_holders_number(self.holders_number / limit + 1, limit, |page| {
+            let mint = &mint;
+            async move {
+                let GetTokenAccountsResponse { total, .. } = self.client.get_token_accounts(mint, page, limit).await?;
+                Ok(total)
             }
-        }
-        bail!("There is more than 2000 pages of token accounts");
+        })
+        .await
     }
 
-    pub async fn draw_winners(&self, n: u64) -> anyhow::Result<Vec<Pubkey>> {
-        let winner_idx = {
-            let mut rng = rand::thread_rng();
-            let distr = Uniform::from(0..self.holders_number);
+    /// Cheapest authenticated request, fails if the API key is invalid
+    pub async fn ping(&self) -> anyhow::Result<()> {
+        self.client.get_token_accounts(&self.mint.to_string(), 1, 1).await?;
+        Ok(())
+    }
+
+    /// Positions of `n` holders drawn uniformly with `rng`, a seeded one makes the draw reproducible
+    pub fn draw_indices(&self, rng: &mut impl Rng, n: u64) -> Vec<u64> {
+        Uniform::from(0..self.holders_number)
+            .sample_iter(rng)
+            .take(n as usize)
+            .collect()
+    }
 
-            let mut winner_idx: Vec<_> = distr.sample_iter(&mut rng).take(n as usize).collect();
-            winner_idx.sort_unstable();
-            winner_idx
-        };
+    /// Holders at the positions of the token accounts list
+    pub async fn winners_at(&self, mut winner_idx: Vec<u64>) -> anyhow::Result<Vec<Winner>> {
+        winner_idx.sort_unstable();
 
         let limit = 1000;
-        let mut winners = Vec::with_capacity(n as usize);
+        let mut winners = Vec::with_capacity(winner_idx.len());
         let distribution: Vec<_> = winner_idx
             .into_iter()
             .group_by(|idx| *idx / limit + 1)
@@ -119,22 +130,148 @@ impl HeliusClient {
                 .client
                 .get_token_accounts(&self.mint.to_string(), page, limit)
                 .await?;
-            winners.extend(idxs.into_iter().map(|idx| token_accounts[(idx % limit) as usize]));
+            for idx in idxs {
+                let Some(token_account) = token_accounts.get((idx % limit) as usize) else {
+                    bail!("There is no token holder at {idx}");
+                };
+                winners.push(Winner {
+                    index: idx,
+                    wallet: token_account.owner,
+                });
+            }
         }
         Ok(winners)
     }
 
+    /// Every holder in the token accounts list order and its balance, every page of the token accounts is fetched
+    pub async fn balances(&self) -> anyhow::Result<Vec<(Winner, u64)>> {
+        let limit = 1000;
+        let mut holders = Vec::with_capacity(self.holders_number as usize);
+        for page in 1..=self.holders_number.div_ceil(limit) {
+            let GetTokenAccountsResponse { token_accounts, .. } = self
+                .client
+                .get_token_accounts(&self.mint.to_string(), page, limit)
+                .await?;
+            let first = (page - 1) * limit;
+            holders.extend(token_accounts.into_iter().enumerate().map(|(idx, token_account)| {
+                let winner = Winner {
+                    index: first + idx as u64,
+                    wallet: token_account.owner,
+                };
+                (winner, token_account.amount)
+            }));
+        }
+        Ok(holders)
+    }
+
+    /// Holders from the largest balance down, the earlier position in the token accounts list wins a tie
+    pub async fn holders_by_balance(&self) -> anyhow::Result<Vec<Winner>> {
+        let mut holders = self.balances().await?;
+        holders.sort_by(|(winner, amount), (other, other_amount)| {
+            other_amount.cmp(amount).then(winner.index.cmp(&other.index))
+        });
+        Ok(holders.into_iter().map(|(winner, _)| winner).collect())
+    }
+
     pub fn holders_number(&self) -> u64 {
         self.holders_number
     }
 }
 
+/// Number of token accounts given the number of accounts on a (1-based) page. Pages are probed exponentially
+/// away from `start_page` until the last page is bracketed, then it is found by bisection
+async fn find_holders_number<F, Fut>(start_page: u64, limit: u64, mut page_len: F) -> anyhow::Result<u64>
+where
+    F: FnMut(u64) -> Fut,
+    Fut: Future<Output = anyhow::Result<u64>>,
+{
+    const MAX_PAGES: u64 = 2000;
+
+    // `full` is the last known full page (0 if none), `last` the first known page which isn't full
+    let start_len = page_len(start_page).await?;
+    if start_len > 0 && start_len < limit {
+        // only the last page is partially filled
+        return Ok(limit * (start_page - 1) + start_len);
+    }
+    let (mut full, mut last, mut last_len) = if start_len == 0 {
+        let (mut last, mut last_len, mut step) = (start_page, start_len, 1);
+        loop {
+            let page = last.saturating_sub(step);
+            if page == 0 {
+                break (0, last, last_len);
+            }
+            let len = page_len(page).await?;
+            if len == limit {
+                break (page, last, last_len);
+            }
+            (last, last_len, step) = (page, len, step * 2);
+        }
+    } else {
+        let (mut full, mut step) = (start_page, 1);
+        loop {
+            let page = full + step;
+            if page > MAX_PAGES {
+                bail!("There is more than {MAX_PAGES} pages of token accounts");
+            }
+            let len = page_len(page).await?;
+            if len < limit {
+                break (full, page, len);
+            }
+            (full, step) = (page, step * 2);
+        }
+    };
+
+    while last - full > 1 {
+        let page = full + (last - full) / 2;
+        let len = page_len(page).await?;
+        if len < limit {
+            (last, last_len) = (page, len);
+        } else {
+            full = page;
+        }
+    }
+    Ok(limit * (last - 1) + last_len)
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::token_holder::HeliusClient;
+    use crate::token_holder::{find_holders_number, HeliusClient};
     use dotenvy::dotenv;
     use solana_sdk::pubkey;
     use sqlx::PgPool;
+    use std::cell::Cell;
+
+    /// Finds `holders` starting from `start_page`, returns the found number and the number of requests
+    async fn find(holders: u64, start_page: u64) -> (u64, u64) {
+        let requests = Cell::new(0);
+        let found = find_holders_number(start_page, 10, |page| {
+            requests.set(requests.get() + 1);
+            std::future::ready(Ok(holders.saturating_sub((page - 1) * 10).min(10)))
+        })
+        .await
+        .unwrap();
+        (found, requests.get())
+    }
+
+    #[tokio::test]
+    async fn should_find_holders_number_from_any_page() {
+        for holders in [0, 1, 10, 11, 99, 100, 101, 1234] {
+            for start_page in [1, 2, 5, 11, 60, 200] {
+                assert_eq!(
+                    holders,
+                    find(holders, start_page).await.0,
+                    "{holders} from {start_page}"
+                );
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn should_need_single_request_for_known_holders_number() {
+        assert_eq!((1234, 1), find(1234, 124).await);
+        // a large change is found in a logarithmic number of requests
+        assert!(find(1234, 1).await.1 <= 16);
+    }
 
     #[ignore]
     #[sqlx::test]
@@ -168,7 +305,9 @@ mod tests {
         .await?;
         client.update_token_holders_number().await?;
 
-        let winners = client.draw_winners(10).await?;
+        let winners = client
+            .winners_at(client.draw_indices(&mut rand::thread_rng(), 10))
+            .await?;
         println!("{:?}", winners);
 
         Ok(())
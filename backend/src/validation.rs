@@ -0,0 +1,342 @@
+use crate::token_holder::Winner;
+use anchor_client::anchor_lang::prelude::Pubkey;
+use anyhow::Context;
+use distributor::DistributorState;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{account::Account, program_pack::Pack};
+use spl_token::state::{Account as TokenAccount, AccountState};
+use std::collections::HashSet;
+
+/// Reason a drawn winner can't receive a share
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Disqualification {
+    /// The same wallet was drawn more than once
+    Duplicate,
+    /// The wallet is in the `EXCLUDED_WALLETS` setting
+    Excluded,
+    /// The wallet is flagged in the `wallet_flags` table
+    Flagged,
+    /// The wallet is on the on-chain blocklist, the program rejects the round paying it
+    Blocked,
+    /// The wallet won the previous round and the program rejects repeat winners
+    RepeatWinner,
+    /// The wallet is not an ed25519 public key, so nobody holds its private key
+    OffCurve,
+    /// Winner token account is frozen, transfer to it would fail the whole transaction
+    FrozenTokenAccount,
+    /// Wallet of a native share is owned by a program, lamports can't be credited to it
+    ProgramOwnedWallet,
+    /// The simulated `validate_winners` view rejects the payout accounts of the winner
+    FailedValidation,
+}
+
+impl Disqualification {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Disqualification::Duplicate => "duplicate",
+            Disqualification::Excluded => "excluded",
+            Disqualification::Flagged => "flagged",
+            Disqualification::Blocked => "blocked",
+            Disqualification::RepeatWinner => "repeat winner",
+            Disqualification::OffCurve => "off-curve",
+            Disqualification::FrozenTokenAccount => "frozen token account",
+            Disqualification::ProgramOwnedWallet => "program-owned wallet",
+            Disqualification::FailedValidation => "failed validation",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Replacement {
+    pub disqualified: Winner,
+    pub reason: Disqualification,
+    pub replacement: Winner,
+}
+
+/// Checks a single winner, `seen` collects the wallets checked so far to catch duplicates
+/// `token_account` is the account the share is paid to: the winner token account, or the wallet of a native share
+pub fn disqualification(
+    winner: &Winner,
+    token_account: Option<&Account>,
+    seen: &mut HashSet<Pubkey>,
+    excluded: &HashSet<Pubkey>,
+    flagged: &HashSet<Pubkey>,
+    blocked: &HashSet<Pubkey>,
+    previous_winners: &HashSet<Pubkey>,
+) -> Option<Disqualification> {
+    if !seen.insert(winner.wallet) {
+        return Some(Disqualification::Duplicate);
+    }
+    if excluded.contains(&winner.wallet) {
+        return Some(Disqualification::Excluded);
+    }
+    if flagged.contains(&winner.wallet) {
+        return Some(Disqualification::Flagged);
+    }
+    if blocked.contains(&winner.wallet) {
+        return Some(Disqualification::Blocked);
+    }
+    if previous_winners.contains(&winner.wallet) {
+        return Some(Disqualification::RepeatWinner);
+    }
+    if !winner.wallet.is_on_curve() {
+        return Some(Disqualification::OffCurve);
+    }
+    let frozen = token_account
+        .and_then(|account| TokenAccount::unpack(&account.data).ok())
+        .is_some_and(|account| account.state == AccountState::Frozen);
+    if frozen {
+        return Some(Disqualification::FrozenTokenAccount);
+    }
+    // native shares are paid to the wallet itself instead of a token account
+    let program_owned = token_account
+        .is_some_and(|account| account.owner != spl_token::ID && account.owner != solana_sdk::system_program::ID);
+    if program_owned {
+        return Some(Disqualification::ProgramOwnedWallet);
+    }
+    None
+}
+
+/// Whether each of the `winners` passes the checks of `distribute`, decoded from the bitmap `validate_winners`
+/// returns: bit `i % 8` of byte `i / 8` is set if the winner `i` passes
+pub fn valid_winners(bitmap: &[u8], winners: usize) -> Vec<bool> {
+    (0..winners)
+        .map(|index| bitmap.get(index / 8).is_some_and(|byte| byte & (1 << (index % 8)) != 0))
+        .collect()
+}
+
+pub async fn fetch_flagged_wallets(pool: &sqlx::PgPool, wallets: &[Pubkey]) -> anyhow::Result<HashSet<Pubkey>> {
+    let flagged: Vec<String> = sqlx::query_scalar("SELECT wallet FROM wallet_flags WHERE wallet = ANY($1)")
+        .bind(wallets.iter().map(ToString::to_string).collect::<Vec<_>>())
+        .fetch_all(pool)
+        .await
+        .context("Failed to fetch flagged wallets")?;
+    flagged
+        .into_iter()
+        .map(|wallet| wallet.parse().context("Invalid flagged wallet"))
+        .collect()
+}
+
+/// Wallets on the on-chain blocklist of the distributor
+pub async fn fetch_blocked_wallets(
+    rpc_client: &RpcClient,
+    distributor_state_pubkey: &Pubkey,
+    distributor_state: &DistributorState,
+) -> anyhow::Result<HashSet<Pubkey>> {
+    if distributor_state.blocked_recipients == 0 {
+        return Ok(HashSet::new());
+    }
+    let address = distributor::client::blocklist_address(distributor_state_pubkey);
+    let account = rpc_client
+        .get_account(&address)
+        .await
+        .context("Failed to fetch blocklist")?;
+    let blocklist =
+        distributor::client::blocklist(&address, &account.owner, &account.data).context("Invalid blocklist")?;
+    Ok(blocklist.wallets.into_iter().collect())
+}
+
+/// Winners of the previous round while the program rejects them, empty otherwise
+pub async fn fetch_previous_winners(
+    rpc_client: &RpcClient,
+    distributor_state_pubkey: &Pubkey,
+    distributor_state: &DistributorState,
+) -> anyhow::Result<HashSet<Pubkey>> {
+    let Some(since) = distributor_state.no_repeat_winners_since else {
+        return Ok(HashSet::new());
+    };
+    if distributor_state.round <= since {
+        return Ok(HashSet::new());
+    }
+    let address =
+        distributor::client::distribution_round_address(distributor_state_pubkey, distributor_state.round - 1);
+    let account = rpc_client
+        .get_account(&address)
+        .await
+        .context("Failed to fetch previous round record")?;
+    let record = distributor::client::distribution_round(&address, &account.owner, &account.data)
+        .context("Invalid previous round record")?;
+    Ok(record.payouts.into_iter().map(|payout| payout.wallet).collect())
+}
+
+pub async fn store_replacements(
+    pool: &sqlx::PgPool,
+    distribution_id: i64,
+    replacements: &[Replacement],
+) -> anyhow::Result<()> {
+    for replacement in replacements {
+        sqlx::query(
+            "INSERT INTO disqualifications (distribution_id, wallet, reason, replacement) VALUES ($1, $2, $3, $4)",
+        )
+        .bind(distribution_id)
+        .bind(replacement.disqualified.wallet.to_string())
+        .bind(replacement.reason.as_str())
+        .bind(replacement.replacement.wallet.to_string())
+        .execute(pool)
+        .await
+        .context("Failed to store disqualification")?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        token_holder::Winner,
+        validation::{disqualification, valid_winners, Disqualification},
+    };
+    use solana_sdk::{
+        account::Account,
+        program_pack::Pack,
+        pubkey,
+        pubkey::Pubkey,
+        signature::{Keypair, Signer},
+    };
+    use spl_token::state::{Account as TokenAccount, AccountState};
+    use std::collections::HashSet;
+
+    const WALLET: Pubkey = pubkey!("De49soBQoHpombVpexCsPEh7Fi5Pfh5fNbhKimhfG28i");
+
+    fn winner(wallet: Pubkey) -> Winner {
+        Winner { index: 0, wallet }
+    }
+
+    fn token_account(state: AccountState) -> Account {
+        let mut data = vec![0; TokenAccount::LEN];
+        TokenAccount::pack(
+            TokenAccount {
+                owner: WALLET,
+                state,
+                ..Default::default()
+            },
+            &mut data,
+        )
+        .unwrap();
+        Account {
+            data,
+            owner: spl_token::ID,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn should_accept_valid_winner() {
+        let mut seen = HashSet::new();
+        let account = token_account(AccountState::Initialized);
+        let check = disqualification(
+            &winner(WALLET),
+            Some(&account),
+            &mut seen,
+            &HashSet::new(),
+            &HashSet::new(),
+            &HashSet::new(),
+            &HashSet::new(),
+        );
+        assert_eq!(None, check);
+        let check = disqualification(
+            &winner(Keypair::new().pubkey()),
+            None,
+            &mut seen,
+            &HashSet::new(),
+            &HashSet::new(),
+            &HashSet::new(),
+            &HashSet::new(),
+        );
+        assert_eq!(None, check);
+    }
+
+    #[test]
+    fn should_disqualify_winners() {
+        let list = HashSet::from([WALLET]);
+        let empty = HashSet::new();
+
+        let mut seen = HashSet::from([WALLET]);
+        let check = disqualification(&winner(WALLET), None, &mut seen, &empty, &empty, &empty, &empty);
+        assert_eq!(Some(Disqualification::Duplicate), check);
+
+        let check = disqualification(
+            &winner(WALLET),
+            None,
+            &mut HashSet::new(),
+            &list,
+            &empty,
+            &empty,
+            &empty,
+        );
+        assert_eq!(Some(Disqualification::Excluded), check);
+
+        let check = disqualification(
+            &winner(WALLET),
+            None,
+            &mut HashSet::new(),
+            &empty,
+            &list,
+            &empty,
+            &empty,
+        );
+        assert_eq!(Some(Disqualification::Flagged), check);
+
+        let check = disqualification(
+            &winner(WALLET),
+            None,
+            &mut HashSet::new(),
+            &empty,
+            &empty,
+            &list,
+            &empty,
+        );
+        assert_eq!(Some(Disqualification::Blocked), check);
+
+        let check = disqualification(
+            &winner(WALLET),
+            None,
+            &mut HashSet::new(),
+            &empty,
+            &empty,
+            &empty,
+            &list,
+        );
+        assert_eq!(Some(Disqualification::RepeatWinner), check);
+
+        let pda = Pubkey::find_program_address(&[b"vault"], &distributor::ID).0;
+        let check = disqualification(&winner(pda), None, &mut HashSet::new(), &empty, &empty, &empty, &empty);
+        assert_eq!(Some(Disqualification::OffCurve), check);
+
+        let account = token_account(AccountState::Frozen);
+        let check = disqualification(
+            &winner(WALLET),
+            Some(&account),
+            &mut HashSet::new(),
+            &empty,
+            &empty,
+            &empty,
+            &empty,
+        );
+        assert_eq!(Some(Disqualification::FrozenTokenAccount), check);
+
+        let account = Account {
+            owner: distributor::ID,
+            ..Default::default()
+        };
+        let check = disqualification(
+            &winner(WALLET),
+            Some(&account),
+            &mut HashSet::new(),
+            &empty,
+            &empty,
+            &empty,
+            &empty,
+        );
+        assert_eq!(Some(Disqualification::ProgramOwnedWallet), check);
+    }
+
+    #[test]
+    fn should_decode_valid_winners_bitmap() {
+        assert_eq!(
+            vec![true, false, true, false, false, false, false, false, false, true],
+            valid_winners(&[0b0000_0101, 0b0000_0010], 10)
+        );
+        // a short bitmap rejects the winners it doesn't cover
+        assert_eq!(Some(&false), valid_winners(&[0b1111_1111], 9).last());
+    }
+}
@@ -0,0 +1,177 @@
+use anyhow::Context;
+use distributor::DistributorState;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_sdk::{account::Account, pubkey::Pubkey};
+use spl_token_2022::{
+    extension::StateWithExtensions,
+    state::{Account as TokenAccount, AccountState},
+};
+use std::fmt;
+
+/// State of the vault account which makes the program fail the round, or lets someone else move the funds
+#[derive(Debug, Clone, PartialEq)]
+pub enum VaultAnomaly {
+    Closed,
+    NotTokenAccount { owner: Pubkey },
+    Frozen,
+    WrongMint { mint: Pubkey },
+    WrongOwner { owner: Pubkey },
+    Delegated { delegate: Pubkey, amount: u64 },
+    CloseAuthority { close_authority: Pubkey },
+}
+
+impl fmt::Display for VaultAnomaly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VaultAnomaly::Closed => write!(f, "vault account is closed"),
+            VaultAnomaly::NotTokenAccount { owner } => write!(f, "vault account is owned by {owner}"),
+            VaultAnomaly::Frozen => write!(f, "vault is frozen by the mint freeze authority"),
+            VaultAnomaly::WrongMint { mint } => write!(f, "vault holds {mint} tokens"),
+            VaultAnomaly::WrongOwner { owner } => write!(f, "vault belongs to {owner}"),
+            VaultAnomaly::Delegated { delegate, amount } => {
+                write!(f, "vault delegates {amount} base units to {delegate}")
+            },
+            VaultAnomaly::CloseAuthority { close_authority } => {
+                write!(f, "vault can be closed by {close_authority}")
+            },
+        }
+    }
+}
+
+/// Everything wrong with the vault of the distributor, empty if it can be used for a round
+pub fn inspect_vault(account: Option<&Account>, mint: &Pubkey, distributor_state_pubkey: &Pubkey) -> Vec<VaultAnomaly> {
+    let Some(account) = account.filter(|account| account.lamports > 0) else {
+        return vec![VaultAnomaly::Closed];
+    };
+    if account.owner != spl_token::ID && account.owner != spl_token_2022::ID {
+        return vec![VaultAnomaly::NotTokenAccount { owner: account.owner }];
+    }
+    let Some(vault) = unpack_vault(account) else {
+        return vec![VaultAnomaly::Closed];
+    };
+
+    let mut anomalies = Vec::new();
+    if vault.state == AccountState::Frozen {
+        anomalies.push(VaultAnomaly::Frozen);
+    }
+    if vault.mint != *mint {
+        anomalies.push(VaultAnomaly::WrongMint { mint: vault.mint });
+    }
+    if vault.owner != *distributor_state_pubkey {
+        anomalies.push(VaultAnomaly::WrongOwner { owner: vault.owner });
+    }
+    if let Some(delegate) = Option::<Pubkey>::from(vault.delegate) {
+        anomalies.push(VaultAnomaly::Delegated {
+            delegate,
+            amount: vault.delegated_amount,
+        });
+    }
+    if let Some(close_authority) = Option::<Pubkey>::from(vault.close_authority) {
+        anomalies.push(VaultAnomaly::CloseAuthority { close_authority });
+    }
+    anomalies
+}
+
+/// Fetches the current vault of the distributor, it is healthy if there are no anomalies
+pub async fn fetch_vault(
+    rpc_client: &RpcClient,
+    distributor_state_pubkey: &Pubkey,
+    distributor_state: &DistributorState,
+) -> anyhow::Result<(Option<TokenAccount>, Vec<VaultAnomaly>)> {
+    let account = rpc_client
+        .get_account_with_commitment(&distributor_state.vault, rpc_client.commitment())
+        .await
+        .context("Failed to fetch vault")?
+        .value;
+    let anomalies = inspect_vault(account.as_ref(), &distributor_state.mint, distributor_state_pubkey);
+    let vault = account.as_ref().and_then(unpack_vault);
+    Ok((vault, anomalies))
+}
+
+/// Base state of the vault of either token program, a Token-2022 vault carries its extensions after it
+fn unpack_vault(account: &Account) -> Option<TokenAccount> {
+    StateWithExtensions::<TokenAccount>::unpack(&account.data)
+        .ok()
+        .map(|vault| vault.base)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::vault_monitor::{inspect_vault, VaultAnomaly};
+    use solana_sdk::{account::Account, program_option::COption, program_pack::Pack, pubkey::Pubkey};
+    use spl_token_2022::{
+        extension::{immutable_owner::ImmutableOwner, BaseStateWithExtensions, ExtensionType, StateWithExtensionsMut},
+        state::{Account as TokenAccount, AccountState},
+    };
+
+    fn vault_account(vault: TokenAccount) -> Account {
+        let mut data = vec![0; TokenAccount::LEN];
+        TokenAccount::pack(vault, &mut data).unwrap();
+        Account {
+            lamports: 2_039_280,
+            data,
+            owner: spl_token::ID,
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    /// Token-2022 vault with the immutable owner extension its ATA gets
+    fn vault_account_2022(vault: TokenAccount) -> Account {
+        let len = ExtensionType::try_calculate_account_len::<TokenAccount>(&[ExtensionType::ImmutableOwner]).unwrap();
+        let mut data = vec![0; len];
+        let mut state = StateWithExtensionsMut::<TokenAccount>::unpack_uninitialized(&mut data).unwrap();
+        state.base = vault;
+        state.pack_base();
+        state.init_account_type().unwrap();
+        state.init_extension::<ImmutableOwner>(true).unwrap();
+        assert!(state.get_extension::<ImmutableOwner>().is_ok());
+        Account {
+            lamports: 2_073_280,
+            data,
+            owner: spl_token_2022::ID,
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    #[test]
+    fn should_report_vault_anomalies() {
+        let distributor_state_pubkey = Pubkey::new_unique();
+        let mint = Pubkey::new_unique();
+        let healthy = TokenAccount {
+            mint,
+            owner: distributor_state_pubkey,
+            amount: 1000,
+            state: AccountState::Initialized,
+            ..Default::default()
+        };
+        let account = vault_account(healthy);
+        assert!(inspect_vault(Some(&account), &mint, &distributor_state_pubkey).is_empty());
+        let account = vault_account_2022(healthy);
+        assert!(inspect_vault(Some(&account), &mint, &distributor_state_pubkey).is_empty());
+
+        assert_eq!(
+            vec![VaultAnomaly::Closed],
+            inspect_vault(None, &mint, &distributor_state_pubkey)
+        );
+
+        let delegate = Pubkey::new_unique();
+        let tampered = TokenAccount {
+            state: AccountState::Frozen,
+            delegate: COption::Some(delegate),
+            delegated_amount: 500,
+            ..healthy
+        };
+        let account = vault_account(tampered);
+        assert_eq!(
+            vec![VaultAnomaly::Frozen, VaultAnomaly::Delegated { delegate, amount: 500 }],
+            inspect_vault(Some(&account), &mint, &distributor_state_pubkey)
+        );
+        let account = vault_account_2022(tampered);
+        assert_eq!(
+            vec![VaultAnomaly::Frozen, VaultAnomaly::Delegated { delegate, amount: 500 }],
+            inspect_vault(Some(&account), &mint, &distributor_state_pubkey)
+        );
+    }
+}
@@ -0,0 +1,215 @@
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use solana_sdk::pubkey::Pubkey;
+use std::{collections::BTreeSet, sync::Arc, time::Duration};
+
+const HELIUS_API_URL: &str = "https://api.helius.xyz/v0/webhooks";
+/// The `/` handler decodes the raw transactions
+const WEBHOOK_TYPE: &str = "raw";
+const TRANSACTION_TYPES: &[&str] = &["ANY"];
+
+/// Helius account the webhook of the backend is managed with
+#[derive(Debug, Clone)]
+pub struct WebhookConfig {
+    pub api_key: String,
+    /// Public URL of the `/` handler of the backend
+    pub webhook_url: String,
+}
+
+/// Webhook as the Helius webhook management API returns and takes it
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Webhook {
+    #[serde(rename = "webhookID", default, skip_serializing)]
+    pub webhook_id: String,
+    #[serde(rename = "webhookURL")]
+    pub webhook_url: String,
+    pub transaction_types: Vec<String>,
+    pub account_addresses: Vec<String>,
+    pub webhook_type: String,
+    #[serde(default)]
+    pub auth_header: Option<String>,
+}
+
+impl Webhook {
+    /// Whether the webhook delivers what `desired` does, the order of the lists doesn't matter
+    fn matches(&self, desired: &Webhook) -> bool {
+        let set = |values: &[String]| values.iter().cloned().collect::<BTreeSet<_>>();
+        self.webhook_url == desired.webhook_url
+            && self.webhook_type == desired.webhook_type
+            && self.auth_header == desired.auth_header
+            && set(&self.transaction_types) == set(&desired.transaction_types)
+            && set(&self.account_addresses) == set(&desired.account_addresses)
+    }
+}
+
+/// What has to be done to bring the registered webhooks to the desired one
+#[derive(Debug, Clone, PartialEq)]
+pub enum Plan {
+    Create,
+    Update { webhook_id: String },
+    Unchanged { webhook_id: String },
+}
+
+/// The webhook delivering to the URL of the backend is the one of the backend, a drifted one is updated in place
+pub fn plan(registered: &[Webhook], desired: &Webhook) -> Plan {
+    match registered
+        .iter()
+        .find(|webhook| webhook.webhook_url == desired.webhook_url)
+    {
+        Some(webhook) if webhook.matches(desired) => Plan::Unchanged {
+            webhook_id: webhook.webhook_id.clone(),
+        },
+        Some(webhook) => Plan::Update {
+            webhook_id: webhook.webhook_id.clone(),
+        },
+        None => Plan::Create,
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SyncOutcome {
+    pub webhook_id: String,
+    /// `created`, `updated` or `unchanged`
+    pub action: &'static str,
+}
+
+/// Keeps the Helius webhook of the vault and the distributor state pointed at the backend with its auth header,
+/// so the subscription doesn't drift from the deployment as a manually configured one does
+pub struct WebhookRegistration {
+    client: reqwest::Client,
+    api_key: String,
+    desired: Webhook,
+}
+
+impl WebhookRegistration {
+    pub fn new(config: WebhookConfig, auth_token: &str, addresses: &[Pubkey]) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            api_key: config.api_key,
+            desired: Webhook {
+                webhook_id: String::new(),
+                webhook_url: config.webhook_url,
+                transaction_types: TRANSACTION_TYPES.iter().map(ToString::to_string).collect(),
+                account_addresses: addresses.iter().map(ToString::to_string).collect(),
+                webhook_type: WEBHOOK_TYPE.to_string(),
+                auth_header: Some(format!("Bearer {auth_token}")),
+            },
+        }
+    }
+
+    pub async fn sync(&self) -> anyhow::Result<SyncOutcome> {
+        let registered: Vec<Webhook> = self
+            .client
+            .get(HELIUS_API_URL)
+            .query(&[("api-key", &self.api_key)])
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .context("Failed to list Helius webhooks")?
+            .json()
+            .await
+            .context("Failed to decode Helius webhooks")?;
+
+        let (request, action) = match plan(&registered, &self.desired) {
+            Plan::Unchanged { webhook_id } => {
+                return Ok(SyncOutcome {
+                    webhook_id,
+                    action: "unchanged",
+                })
+            },
+            Plan::Update { webhook_id } => {
+                tracing::warn!(%webhook_id, "Helius webhook drifted, updating");
+                (self.client.put(format!("{HELIUS_API_URL}/{webhook_id}")), "updated")
+            },
+            Plan::Create => (self.client.post(HELIUS_API_URL), "created"),
+        };
+        let webhook: Webhook = request
+            .query(&[("api-key", &self.api_key)])
+            .json(&self.desired)
+            .send()
+            .await
+            .and_then(reqwest::Response::error_for_status)
+            .context("Failed to register Helius webhook")?
+            .json()
+            .await
+            .context("Failed to decode Helius webhook")?;
+        tracing::info!(webhook_id = %webhook.webhook_id, action, "Helius webhook registered");
+
+        Ok(SyncOutcome {
+            webhook_id: webhook.webhook_id,
+            action,
+        })
+    }
+
+    /// Verifies the webhook at every `period`, the first check runs at once
+    pub async fn watch(self: Arc<Self>, period: Duration) {
+        let mut interval = tokio::time::interval(period);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            if let Err(err) = self.sync().await {
+                tracing::warn!(?err, "Failed to verify the Helius webhook");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::webhook_registration::{plan, Plan, Webhook};
+
+    fn webhook(webhook_id: &str, webhook_url: &str, account_addresses: &[&str]) -> Webhook {
+        Webhook {
+            webhook_id: webhook_id.to_string(),
+            webhook_url: webhook_url.to_string(),
+            transaction_types: vec!["ANY".to_string()],
+            account_addresses: account_addresses.iter().map(ToString::to_string).collect(),
+            webhook_type: "raw".to_string(),
+            auth_header: Some("Bearer token".to_string()),
+        }
+    }
+
+    #[test]
+    fn should_plan_webhook_changes() {
+        let desired = webhook("", "https://backend/", &["vault", "state"]);
+
+        assert_eq!(
+            Plan::Create,
+            plan(&[webhook("other", "https://other/", &["vault"])], &desired)
+        );
+        assert_eq!(
+            Plan::Unchanged {
+                webhook_id: "id".to_string()
+            },
+            plan(&[webhook("id", "https://backend/", &["state", "vault"])], &desired)
+        );
+        assert_eq!(
+            Plan::Update {
+                webhook_id: "id".to_string()
+            },
+            plan(&[webhook("id", "https://backend/", &["vault"])], &desired)
+        );
+
+        let mut unauthenticated = webhook("id", "https://backend/", &["vault", "state"]);
+        unauthenticated.auth_header = None;
+        assert_eq!(
+            Plan::Update {
+                webhook_id: "id".to_string()
+            },
+            plan(&[unauthenticated], &desired)
+        );
+    }
+
+    #[test]
+    fn should_decode_helius_webhook() {
+        let webhook: Webhook = serde_json::from_str(
+            r#"{"webhookID":"id","wallet":"w","webhookURL":"https://backend/","transactionTypes":["ANY"],
+            "accountAddresses":["vault"],"webhookType":"raw"}"#,
+        )
+        .unwrap();
+        assert_eq!("id", webhook.webhook_id);
+        assert_eq!(None, webhook.auth_header);
+        assert!(serde_json::to_value(&webhook).unwrap().get("webhookID").is_none());
+    }
+}
@@ -0,0 +1,227 @@
+use crate::ticket::TicketEntry;
+use serde::{Deserialize, Serialize};
+use serde_with::{serde_as, DisplayFromStr};
+use solana_sdk::pubkey::Pubkey;
+use std::collections::{HashMap, HashSet};
+
+/// Wallets with the largest changes of the win probability listed in the report
+const LISTED_CHANGES: usize = 20;
+
+/// How the weight of an eligible wallet, its tickets or its marker balance, counts in the draw
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Weighting {
+    /// Every ticket is a chance, the way the ticket rounds are drawn
+    #[default]
+    Tickets,
+    /// Every eligible wallet has the same chance, the way the holder rounds are drawn
+    Equal,
+    /// Chances grow with the square root of the tickets, damping the largest entries
+    Sqrt,
+}
+
+impl Weighting {
+    fn weight(&self, tickets: u64) -> f64 {
+        match self {
+            Weighting::Tickets => tickets as f64,
+            Weighting::Equal => 1.0,
+            Weighting::Sqrt => (tickets as f64).sqrt(),
+        }
+    }
+}
+
+/// Hypothetical eligibility parameters, compared against the current ones
+#[serde_as]
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct WhatIfParams {
+    /// Wallets with fewer tickets, or a smaller marker balance in a holder campaign, aren't eligible
+    #[serde(default)]
+    pub min_weight: u64,
+    /// Wallets excluded on top of the current exclusions
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    #[serde(default)]
+    pub exclude: Vec<Pubkey>,
+    /// Currently excluded wallets which become eligible
+    #[serde_as(as = "Vec<DisplayFromStr>")]
+    #[serde(default)]
+    pub include: Vec<Pubkey>,
+    #[serde(default)]
+    pub weighting: Weighting,
+}
+
+/// Eligible entrants and how the chances spread over them
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Population {
+    pub eligible: u64,
+    /// Probabilities are of winning at least one share of a round
+    pub max_win_probability: f64,
+    pub median_win_probability: f64,
+}
+
+impl Population {
+    fn new(probabilities: &HashMap<Pubkey, f64>) -> Self {
+        let mut sorted = probabilities.values().copied().collect::<Vec<_>>();
+        sorted.sort_by(f64::total_cmp);
+        Self {
+            eligible: sorted.len() as u64,
+            max_win_probability: sorted.last().copied().unwrap_or_default(),
+            median_win_probability: sorted.get(sorted.len() / 2).copied().unwrap_or_default(),
+        }
+    }
+}
+
+#[serde_as]
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ProbabilityChange {
+    #[serde_as(as = "DisplayFromStr")]
+    pub wallet: Pubkey,
+    pub current: f64,
+    pub hypothetical: f64,
+}
+
+/// How the hypothetical parameters would change the population of the latest snapshot
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct WhatIfReport {
+    /// Winners of a round the probabilities are computed for
+    pub winners: u64,
+    pub current: Population,
+    pub hypothetical: Population,
+    pub became_eligible: u64,
+    pub became_ineligible: u64,
+    /// Largest changes of the win probability first
+    pub changes: Vec<ProbabilityChange>,
+}
+
+/// Compares the `current` eligibility of `entries` with `params`, the `excluded` wallets are eligible only if
+/// `params` includes them. The entries are the tickets of a ticket campaign or the marker balances of the holders
+pub fn what_if(
+    entries: &[TicketEntry],
+    excluded: &HashSet<Pubkey>,
+    winners: u64,
+    current: &WhatIfParams,
+    params: &WhatIfParams,
+) -> WhatIfReport {
+    let eligible = |params: &WhatIfParams, entry: &TicketEntry| {
+        entry.weight >= params.min_weight
+            && !params.exclude.contains(&entry.wallet)
+            && (!excluded.contains(&entry.wallet) || params.include.contains(&entry.wallet))
+    };
+    let current = win_probabilities(entries, |entry| eligible(current, entry), current.weighting, winners);
+    let hypothetical = win_probabilities(entries, |entry| eligible(params, entry), params.weighting, winners);
+
+    let mut changes = entries
+        .iter()
+        .map(|entry| ProbabilityChange {
+            wallet: entry.wallet,
+            current: current.get(&entry.wallet).copied().unwrap_or_default(),
+            hypothetical: hypothetical.get(&entry.wallet).copied().unwrap_or_default(),
+        })
+        .filter(|change| change.current != change.hypothetical)
+        .collect::<Vec<_>>();
+    changes.sort_by(|a, b| {
+        (b.hypothetical - b.current)
+            .abs()
+            .total_cmp(&(a.hypothetical - a.current).abs())
+    });
+    changes.truncate(LISTED_CHANGES);
+
+    WhatIfReport {
+        winners,
+        current: Population::new(&current),
+        hypothetical: Population::new(&hypothetical),
+        became_eligible: hypothetical
+            .keys()
+            .filter(|wallet| !current.contains_key(wallet))
+            .count() as u64,
+        became_ineligible: current
+            .keys()
+            .filter(|wallet| !hypothetical.contains_key(wallet))
+            .count() as u64,
+        changes,
+    }
+}
+
+/// Probability of every eligible wallet to win at least once in `winners` draws with replacement, nobody can win if
+/// the eligible wallets weigh nothing
+fn win_probabilities(
+    entries: &[TicketEntry],
+    eligible: impl Fn(&TicketEntry) -> bool,
+    weighting: Weighting,
+    winners: u64,
+) -> HashMap<Pubkey, f64> {
+    let weights = entries
+        .iter()
+        .filter(|entry| eligible(entry))
+        .map(|entry| (entry.wallet, weighting.weight(entry.weight)))
+        .collect::<Vec<_>>();
+    let total = weights.iter().map(|(_, weight)| weight).sum::<f64>();
+    weights
+        .into_iter()
+        .map(|(wallet, weight)| {
+            if total == 0.0 {
+                return (wallet, 0.0);
+            }
+            let miss = 1.0 - weight / total;
+            (wallet, 1.0 - miss.powf(winners as f64))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ticket::TicketEntry,
+        what_if::{what_if, Weighting, WhatIfParams},
+    };
+    use solana_sdk::pubkey::Pubkey;
+    use std::collections::HashSet;
+
+    #[test]
+    fn should_compare_the_hypothetical_population() {
+        let entries = [1, 3, 4, 8]
+            .map(|weight| TicketEntry {
+                wallet: Pubkey::new_unique(),
+                weight,
+            })
+            .to_vec();
+        let excluded = HashSet::from([entries[3].wallet]);
+        let params = WhatIfParams {
+            min_weight: 2,
+            include: vec![entries[3].wallet],
+            weighting: Weighting::Equal,
+            ..Default::default()
+        };
+
+        let current = WhatIfParams::default();
+        let report = what_if(&entries, &excluded, 1, &current, &params);
+
+        assert_eq!(3, report.current.eligible);
+        assert!((report.current.max_win_probability - 0.5).abs() < 1e-9);
+        assert_eq!(3, report.hypothetical.eligible);
+        assert!((report.hypothetical.max_win_probability - 1.0 / 3.0).abs() < 1e-9);
+        assert_eq!(1, report.became_eligible);
+        assert_eq!(1, report.became_ineligible);
+        assert_eq!(entries[1].wallet, report.changes.last().unwrap().wallet);
+    }
+
+    #[test]
+    fn should_give_no_chance_when_nothing_weighs() {
+        let entries = [0, 0]
+            .map(|weight| TicketEntry {
+                wallet: Pubkey::new_unique(),
+                weight,
+            })
+            .to_vec();
+        let params = WhatIfParams {
+            weighting: Weighting::Equal,
+            ..Default::default()
+        };
+
+        let report = what_if(&entries, &HashSet::new(), 1, &WhatIfParams::default(), &params);
+
+        assert_eq!(2, report.current.eligible);
+        assert_eq!(0.0, report.current.max_win_probability);
+        assert!((report.hypothetical.max_win_probability - 0.5).abs() < 1e-9);
+        assert!(report.changes.iter().all(|change| !change.current.is_nan()));
+    }
+}
@@ -30,7 +30,7 @@ async function initDevnet(program: Program<Distributor>, payer: PublicKey) {
   let shareSize = (new BN(331)).mul(new BN(1_000_000_000));
   let numberOfShares = new BN(10);
 
-  let tx = await program.methods.initialize(shareSize, numberOfShares)
+  let tx = await program.methods.initialize({shareSize, numberOfShares, remainderPolicy: {burn: {}}, distributionMode: {singleTx: {}}, coAuthority: null, burnShares: new BN(1), name: "", uri: "", maxRounds: null, shareSizeDecimals: null})
     .accounts({
       payer,
       mint,
@@ -49,7 +49,7 @@ async function initMainnet(program: Program<Distributor>, payer: PublicKey) {
     let shareSize = (new BN(331)).mul(new BN(1_000_000_000));
     let numberOfShares = new BN(10);
 
-    let tx = await program.methods.initialize(shareSize, numberOfShares)
+    let tx = await program.methods.initialize({shareSize, numberOfShares, remainderPolicy: {burn: {}}, distributionMode: {singleTx: {}}, coAuthority: null, burnShares: new BN(1), name: "", uri: "", maxRounds: null, shareSizeDecimals: null})
         .accounts({
             payer,
             mint,
@@ -0,0 +1,490 @@
+//! Off-chain validation of the program accounts, cheap enough to run on every fetch.
+
+use std::future::Future;
+
+use anchor_lang::{error::ErrorCode, prelude::*, Discriminator};
+use anchor_spl::token_2022::spl_token_2022::{
+    self,
+    offchain::{resolve_extra_transfer_account_metas, AccountDataResult, AccountFetchError},
+};
+
+use crate::{
+    merkle, Blocklist, ClaimRound, CommittedDraw, DepositAllowance, DepositorRecord, DistributionRound,
+    DistributorMetadata, DistributorState, Draw, EscrowedShare, FeeVault, HolderSnapshot, PayoutAcceptance,
+    RoundProgress, VaultConfig, Vesting,
+};
+
+/// Deserializes account data checking the owner and the discriminator first.
+pub fn deserialize<T: AccountDeserialize + Discriminator>(owner: &Pubkey, data: &[u8]) -> Result<T> {
+    require_keys_eq!(*owner, crate::ID, ErrorCode::AccountOwnedByWrongProgram);
+    require!(
+        data.len() >= T::DISCRIMINATOR.len(),
+        ErrorCode::AccountDiscriminatorNotFound
+    );
+    require!(
+        data[..T::DISCRIMINATOR.len()] == T::DISCRIMINATOR,
+        ErrorCode::AccountDiscriminatorMismatch
+    );
+    T::try_deserialize(&mut &data[..])
+}
+
+/// Validates a fetched `DistributorState` account including the state invariants.
+pub fn distributor_state(address: &Pubkey, owner: &Pubkey, data: &[u8]) -> Result<DistributorState> {
+    let distributor_state: DistributorState = deserialize(owner, data)?;
+    distributor_state.validate()?;
+
+    let expected = Pubkey::create_program_address(
+        &[
+            distributor_state.mint.as_ref(),
+            distributor_state.marker_mint.as_ref(),
+            distributor_state.initial_share_size.to_le_bytes().as_ref(),
+            distributor_state.initial_number_of_shares.to_le_bytes().as_ref(),
+            &[distributor_state.distributor_state_bump],
+        ],
+        &crate::ID,
+    )
+    .map_err(|_| ErrorCode::ConstraintSeeds)?;
+    require_keys_eq!(*address, expected, ErrorCode::ConstraintSeeds);
+
+    let nonce = distributor_state.vault_nonce.to_le_bytes();
+    let bump = [distributor_state.vault_bump];
+    let vault_seeds: &[&[u8]] = if distributor_state.vault_nonce == 0 {
+        &[address.as_ref(), &bump]
+    } else {
+        &[DistributorState::VAULT_SEED_PREFIX, address.as_ref(), &nonce, &bump]
+    };
+    let vault = Pubkey::create_program_address(vault_seeds, &crate::ID).map_err(|_| ErrorCode::ConstraintSeeds)?;
+    require_keys_eq!(distributor_state.vault, vault, ErrorCode::ConstraintSeeds);
+
+    Ok(distributor_state)
+}
+
+/// Validates a fetched `DistributionRound` account, it has to be derived from its own distributor state and round.
+pub fn distribution_round(address: &Pubkey, owner: &Pubkey, data: &[u8]) -> Result<DistributionRound> {
+    let round: DistributionRound = deserialize(owner, data)?;
+
+    let expected = Pubkey::create_program_address(
+        &[round.distributor_state.as_ref(), round.round.to_le_bytes().as_ref(), &[
+            round.bump,
+        ]],
+        &crate::ID,
+    )
+    .map_err(|_| ErrorCode::ConstraintSeeds)?;
+    require_keys_eq!(*address, expected, ErrorCode::ConstraintSeeds);
+
+    Ok(round)
+}
+
+pub fn distribution_round_address(distributor_state: &Pubkey, round: u64) -> Pubkey {
+    Pubkey::find_program_address(&[distributor_state.as_ref(), round.to_le_bytes().as_ref()], &crate::ID).0
+}
+
+/// Validates a fetched `ClaimRound` account.
+pub fn claim_round(address: &Pubkey, owner: &Pubkey, data: &[u8]) -> Result<ClaimRound> {
+    let claim_round: ClaimRound = deserialize(owner, data)?;
+    require!(
+        claim_round.claimed.len() == ClaimRound::bitmap_len(claim_round.claimants),
+        ErrorCode::AccountDidNotDeserialize
+    );
+    require_gte!(
+        claim_round.total_amount,
+        claim_round.claimed_amount,
+        ErrorCode::AccountDidNotDeserialize
+    );
+
+    let expected = Pubkey::create_program_address(
+        &[
+            ClaimRound::SEED_PREFIX,
+            claim_round.distributor_state.as_ref(),
+            claim_round.round.to_le_bytes().as_ref(),
+            &[claim_round.bump],
+        ],
+        &crate::ID,
+    )
+    .map_err(|_| ErrorCode::ConstraintSeeds)?;
+    require_keys_eq!(*address, expected, ErrorCode::ConstraintSeeds);
+
+    Ok(claim_round)
+}
+
+pub fn claim_round_address(distributor_state: &Pubkey, round: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            ClaimRound::SEED_PREFIX,
+            distributor_state.as_ref(),
+            round.to_le_bytes().as_ref(),
+        ],
+        &crate::ID,
+    )
+    .0
+}
+
+pub fn escrow_address(claim_round: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[claim_round.as_ref()], &crate::ID).0
+}
+
+/// Validates a fetched `RoundProgress` account.
+pub fn round_progress(address: &Pubkey, owner: &Pubkey, data: &[u8]) -> Result<RoundProgress> {
+    let progress: RoundProgress = deserialize(owner, data)?;
+    require_gt!(
+        progress.number_of_shares,
+        progress.paid,
+        ErrorCode::AccountDidNotDeserialize
+    );
+
+    let expected = Pubkey::create_program_address(
+        &[
+            RoundProgress::SEED_PREFIX,
+            progress.distributor_state.as_ref(),
+            progress.round.to_le_bytes().as_ref(),
+            &[progress.bump],
+        ],
+        &crate::ID,
+    )
+    .map_err(|_| ErrorCode::ConstraintSeeds)?;
+    require_keys_eq!(*address, expected, ErrorCode::ConstraintSeeds);
+
+    Ok(progress)
+}
+
+pub fn round_progress_address(distributor_state: &Pubkey, round: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            RoundProgress::SEED_PREFIX,
+            distributor_state.as_ref(),
+            round.to_le_bytes().as_ref(),
+        ],
+        &crate::ID,
+    )
+    .0
+}
+
+/// Validates a fetched `VaultConfig` account.
+pub fn vault_config(address: &Pubkey, owner: &Pubkey, data: &[u8]) -> Result<VaultConfig> {
+    let vault_config: VaultConfig = deserialize(owner, data)?;
+
+    let expected = Pubkey::create_program_address(
+        &[
+            VaultConfig::SEED_PREFIX,
+            vault_config.distributor_state.as_ref(),
+            vault_config.mint.as_ref(),
+            &[vault_config.bump],
+        ],
+        &crate::ID,
+    )
+    .map_err(|_| ErrorCode::ConstraintSeeds)?;
+    require_keys_eq!(*address, expected, ErrorCode::ConstraintSeeds);
+
+    Ok(vault_config)
+}
+
+pub fn vault_config_address(distributor_state: &Pubkey, mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[VaultConfig::SEED_PREFIX, distributor_state.as_ref(), mint.as_ref()],
+        &crate::ID,
+    )
+    .0
+}
+
+/// Validates a fetched `Blocklist` account.
+pub fn blocklist(address: &Pubkey, owner: &Pubkey, data: &[u8]) -> Result<Blocklist> {
+    let blocklist: Blocklist = deserialize(owner, data)?;
+
+    let expected = Pubkey::create_program_address(
+        &[Blocklist::SEED_PREFIX, blocklist.distributor_state.as_ref(), &[
+            blocklist.bump,
+        ]],
+        &crate::ID,
+    )
+    .map_err(|_| ErrorCode::ConstraintSeeds)?;
+    require_keys_eq!(*address, expected, ErrorCode::ConstraintSeeds);
+
+    Ok(blocklist)
+}
+
+pub fn blocklist_address(distributor_state: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[Blocklist::SEED_PREFIX, distributor_state.as_ref()], &crate::ID).0
+}
+
+/// Validates a fetched `DistributorMetadata` account.
+pub fn distributor_metadata(address: &Pubkey, owner: &Pubkey, data: &[u8]) -> Result<DistributorMetadata> {
+    let distributor_metadata: DistributorMetadata = deserialize(owner, data)?;
+
+    let expected = Pubkey::create_program_address(
+        &[
+            DistributorMetadata::SEED_PREFIX,
+            distributor_metadata.distributor_state.as_ref(),
+            &[distributor_metadata.bump],
+        ],
+        &crate::ID,
+    )
+    .map_err(|_| ErrorCode::ConstraintSeeds)?;
+    require_keys_eq!(*address, expected, ErrorCode::ConstraintSeeds);
+
+    Ok(distributor_metadata)
+}
+
+pub fn distributor_metadata_address(distributor_state: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[DistributorMetadata::SEED_PREFIX, distributor_state.as_ref()],
+        &crate::ID,
+    )
+    .0
+}
+
+pub fn escrowed_share_address(distributor_state: &Pubkey, round: u64, wallet: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            EscrowedShare::SEED_PREFIX,
+            distributor_state.as_ref(),
+            round.to_le_bytes().as_ref(),
+            wallet.as_ref(),
+        ],
+        &crate::ID,
+    )
+    .0
+}
+
+pub fn fee_vault_address(distributor_state: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[FeeVault::SEED_PREFIX, distributor_state.as_ref()], &crate::ID).0
+}
+
+pub fn payout_acceptance_address(wallet: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[PayoutAcceptance::SEED_PREFIX, wallet.as_ref()], &crate::ID).0
+}
+
+pub fn vesting_escrow_address(distributor_state: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[crate::VESTING_ESCROW_SEED, distributor_state.as_ref()], &crate::ID).0
+}
+
+pub fn vesting_address(distributor_state: &Pubkey, round: u64, wallet: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            Vesting::SEED_PREFIX,
+            distributor_state.as_ref(),
+            round.to_le_bytes().as_ref(),
+            wallet.as_ref(),
+        ],
+        &crate::ID,
+    )
+    .0
+}
+
+pub fn native_unwrap_address(distributor_state: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[crate::NATIVE_UNWRAP_SEED, distributor_state.as_ref()], &crate::ID).0
+}
+
+pub fn depositor_record_address(distributor_state: &Pubkey, authority: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            DepositorRecord::SEED_PREFIX,
+            distributor_state.as_ref(),
+            authority.as_ref(),
+        ],
+        &crate::ID,
+    )
+    .0
+}
+
+pub fn deposit_allowance_address(distributor_state: &Pubkey, depositor: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            DepositAllowance::SEED_PREFIX,
+            distributor_state.as_ref(),
+            depositor.as_ref(),
+        ],
+        &crate::ID,
+    )
+    .0
+}
+
+/// Validates a fetched `Draw` account.
+pub fn draw(address: &Pubkey, owner: &Pubkey, data: &[u8]) -> Result<Draw> {
+    let draw: Draw = deserialize(owner, data)?;
+
+    let expected = Pubkey::create_program_address(
+        &[
+            Draw::SEED_PREFIX,
+            draw.distributor_state.as_ref(),
+            draw.round.to_le_bytes().as_ref(),
+            &[draw.bump],
+        ],
+        &crate::ID,
+    )
+    .map_err(|_| ErrorCode::ConstraintSeeds)?;
+    require_keys_eq!(*address, expected, ErrorCode::ConstraintSeeds);
+
+    Ok(draw)
+}
+
+pub fn draw_address(distributor_state: &Pubkey, round: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            Draw::SEED_PREFIX,
+            distributor_state.as_ref(),
+            round.to_le_bytes().as_ref(),
+        ],
+        &crate::ID,
+    )
+    .0
+}
+
+/// Validates a fetched `CommittedDraw` account.
+pub fn committed_draw(address: &Pubkey, owner: &Pubkey, data: &[u8]) -> Result<CommittedDraw> {
+    let committed_draw: CommittedDraw = deserialize(owner, data)?;
+
+    let expected = Pubkey::create_program_address(
+        &[
+            CommittedDraw::SEED_PREFIX,
+            committed_draw.distributor_state.as_ref(),
+            committed_draw.round.to_le_bytes().as_ref(),
+            &[committed_draw.bump],
+        ],
+        &crate::ID,
+    )
+    .map_err(|_| ErrorCode::ConstraintSeeds)?;
+    require_keys_eq!(*address, expected, ErrorCode::ConstraintSeeds);
+
+    Ok(committed_draw)
+}
+
+pub fn committed_draw_address(distributor_state: &Pubkey, round: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            CommittedDraw::SEED_PREFIX,
+            distributor_state.as_ref(),
+            round.to_le_bytes().as_ref(),
+        ],
+        &crate::ID,
+    )
+    .0
+}
+
+/// Validates a fetched `HolderSnapshot` account.
+pub fn holder_snapshot(address: &Pubkey, owner: &Pubkey, data: &[u8]) -> Result<HolderSnapshot> {
+    let holder_snapshot: HolderSnapshot = deserialize(owner, data)?;
+
+    let expected = Pubkey::create_program_address(
+        &[
+            HolderSnapshot::SEED_PREFIX,
+            holder_snapshot.distributor_state.as_ref(),
+            holder_snapshot.round.to_le_bytes().as_ref(),
+            &[holder_snapshot.bump],
+        ],
+        &crate::ID,
+    )
+    .map_err(|_| ErrorCode::ConstraintSeeds)?;
+    require_keys_eq!(*address, expected, ErrorCode::ConstraintSeeds);
+
+    Ok(holder_snapshot)
+}
+
+pub fn holder_snapshot_address(distributor_state: &Pubkey, round: u64) -> Pubkey {
+    Pubkey::find_program_address(
+        &[
+            HolderSnapshot::SEED_PREFIX,
+            distributor_state.as_ref(),
+            round.to_le_bytes().as_ref(),
+        ],
+        &crate::ID,
+    )
+    .0
+}
+
+/// Extra accounts of the Token-2022 transfer hook of `mint`, empty if the mint has no hook. They go after the
+/// winner accounts of `distribute` and are the remaining accounts of the other transferring instructions. All
+/// transfers of an instruction share these accounts, so the hook is resolved for one of them.
+pub async fn transfer_hook_accounts<F, Fut>(
+    fetch_account_data: F,
+    mint: &Pubkey,
+    source: &Pubkey,
+    destination: &Pubkey,
+    authority: &Pubkey,
+) -> std::result::Result<Vec<AccountMeta>, AccountFetchError>
+where
+    F: Fn(Pubkey) -> Fut,
+    Fut: Future<Output = AccountDataResult>,
+{
+    let mut instruction = spl_token_2022::instruction::transfer_checked(
+        &spl_token_2022::ID,
+        source,
+        mint,
+        destination,
+        authority,
+        &[],
+        0,
+        0,
+    )?;
+    let base_accounts = instruction.accounts.len();
+    resolve_extra_transfer_account_metas(&mut instruction, fetch_account_data, mint).await?;
+    Ok(instruction.accounts.split_off(base_accounts))
+}
+
+/// Merkle tree of the (index, wallet, amount) claims, or of the holders of a snapshot. The index of a leaf is its
+/// position in the list.
+pub struct MerkleTree {
+    /// Levels from the leaves up to the root
+    levels: Vec<Vec<[u8; 32]>>,
+}
+
+impl MerkleTree {
+    pub fn new(claims: &[(Pubkey, u64)]) -> Self {
+        Self::from_leaves(
+            claims
+                .iter()
+                .enumerate()
+                .map(|(index, (wallet, amount))| merkle::leaf(index as u64, wallet, *amount))
+                .collect(),
+        )
+    }
+
+    /// Tree of a holders snapshot committed by `commit_draw`
+    pub fn snapshot(holders: &[Pubkey]) -> Self {
+        Self::from_leaves(
+            holders
+                .iter()
+                .enumerate()
+                .map(|(index, wallet)| merkle::snapshot_leaf(index as u64, wallet))
+                .collect(),
+        )
+    }
+
+    fn from_leaves(leaves: Vec<[u8; 32]>) -> Self {
+        let mut levels = vec![leaves];
+        while levels.last().map_or(0, Vec::len) > 1 {
+            let level = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => merkle::node(left, right),
+                    // the odd node is promoted to the next level as is
+                    [single] => *single,
+                    _ => unreachable!(),
+                })
+                .collect();
+            levels.push(level);
+        }
+        Self { levels }
+    }
+
+    pub fn root(&self) -> [u8; 32] {
+        self.levels
+            .last()
+            .and_then(|level| level.first())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    pub fn proof(&self, index: usize) -> Vec<[u8; 32]> {
+        let mut proof = Vec::with_capacity(self.levels.len());
+        let mut index = index;
+        for level in &self.levels[..self.levels.len() - 1] {
+            if let Some(sibling) = level.get(index ^ 1) {
+                proof.push(*sibling);
+            }
+            index /= 2;
+        }
+        proof
+    }
+}
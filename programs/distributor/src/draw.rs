@@ -0,0 +1,76 @@
+//! Winner indices derived from the VRF randomness of a round, anyone can recompute them from the `Draw` account.
+
+use std::collections::BTreeSet;
+
+use anchor_lang::solana_program::hash::hashv;
+
+/// Distinct indices below `entrants` in the order they are drawn, replacements of rejected winners are the next
+/// indices of the same sequence
+pub struct DrawIndices {
+    randomness: [u8; 64],
+    entrants: u64,
+    counter: u64,
+    drawn: BTreeSet<u64>,
+}
+
+impl DrawIndices {
+    pub fn new(randomness: [u8; 64], entrants: u64) -> Self {
+        Self {
+            randomness,
+            entrants,
+            counter: 0,
+            drawn: BTreeSet::new(),
+        }
+    }
+}
+
+impl Iterator for DrawIndices {
+    type Item = u64;
+
+    fn next(&mut self) -> Option<u64> {
+        if self.drawn.len() as u64 >= self.entrants {
+            return None;
+        }
+        // values from the incomplete last range would favour the lower indices
+        let zone = u64::MAX - u64::MAX % self.entrants;
+        loop {
+            let hash = hashv(&[&self.randomness, &self.counter.to_le_bytes()]).to_bytes();
+            self.counter += 1;
+            let value = u64::from_le_bytes(hash[..8].try_into().unwrap());
+            if value >= zone {
+                continue;
+            }
+            let index = value % self.entrants;
+            if self.drawn.insert(index) {
+                return Some(index);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::DrawIndices;
+
+    #[test]
+    fn should_draw_every_entrant_once() {
+        let mut indices = DrawIndices::new([7; 64], 10).collect::<Vec<_>>();
+        assert_eq!(10, indices.len());
+        indices.sort_unstable();
+        assert_eq!((0..10).collect::<Vec<_>>(), indices);
+
+        assert_eq!(None, DrawIndices::new([7; 64], 0).next());
+    }
+
+    #[test]
+    fn should_draw_the_same_indices_from_the_same_randomness() {
+        let drawn = DrawIndices::new([1; 64], 1_000).take(5).collect::<Vec<_>>();
+        assert_eq!(drawn, DrawIndices::new([1; 64], 1_000).take(5).collect::<Vec<_>>());
+        assert_ne!(drawn, DrawIndices::new([2; 64], 1_000).take(5).collect::<Vec<_>>());
+        // the replacements continue the sequence
+        assert_eq!(
+            drawn[3..],
+            DrawIndices::new([1; 64], 1_000).skip(3).take(2).collect::<Vec<_>>()[..]
+        );
+    }
+}
@@ -6,4 +6,89 @@ pub enum DistributorError {
     ThresholdNotMet,
     MissingRemainingAccounts,
     InvalidAssociatedTokenAccount,
+    InvalidRemainderPolicy,
+    MissingTreasury,
+    Paused,
+    NotPendingAuthority,
+    InvalidProof,
+    AlreadyClaimed,
+    ClaimExpired,
+    ClaimsNotExpired,
+    NotesMismatch,
+    InsufficientVaultBalance,
+    InvalidTreasury,
+    DecimalsMismatch,
+    TooManyShares,
+    MarkerBalanceTooLow,
+    RandomnessNotFulfilled,
+    DrawAlreadyRevealed,
+    WrongDistributionMode,
+    ChunkOutOfOrder,
+    RoundNotComplete,
+    IntervalNotElapsed,
+    VaultFrozen,
+    NotNativeMint,
+    MissingNativeUnwrap,
+    InvalidNativeRecipient,
+    NoPendingWithdraw,
+    WithdrawTimelocked,
+    DepositTooSmall,
+    RecipientBlocked,
+    MissingBlocklist,
+    MissingRoundRecord,
+    MissingPreviousRound,
+    RepeatWinner,
+    MissingCoAuthority,
+    InvalidCoAuthority,
+    InvalidProtocolFee,
+    MissingFeeAccount,
+    InvalidFeeAccount,
+    UnauthorizedRound,
+    InvalidVestingDuration,
+    MissingVestingEscrow,
+    InvalidVestingAccount,
+    NothingVested,
+    DrawCommittedLate,
+    InvalidDrawSeed,
+    DrawComplete,
+    MissingCommittedDraw,
+    InvalidCommittedWinner,
+    NotPaused,
+    VaultNotEmpty,
+    MissingFeeVault,
+    NothingToCollect,
+    NotFeeCollector,
+    AlreadyMigrated,
+    InvalidCosigners,
+    MissingCosignatures,
+    VaultDelegated,
+    VaultCloseAuthoritySet,
+    MetadataTooLong,
+    NotDelegated,
+    RoundMismatch,
+    InsufficientRentBuffer,
+    ArithmeticOverflow,
+    DepositorNotAllowed,
+    MaxRoundsReached,
+    MissingHolderSnapshot,
+    SnapshotMismatch,
+    MarkerMintIsPayoutMint,
+    EmptyMarkerMint,
+    InvalidShareSizeDecimals,
+    DuplicateRecipient,
+    InvalidRoundingPolicy,
+    SelfRecipient,
+    ProRataWithoutMarker,
+    NotCollectionHolder,
+    ThresholdBelowPayout,
+    InvalidTriggerBonusAccount,
+    RoundInProgress,
+    InvalidRandomnessRequest,
+    ClaimRoundsOpen,
+    RentBufferNotEmpty,
+    VestingEscrowNotEmpty,
+    FeesNotCollected,
+    MissingTriggerBonusAccount,
+    MissingDraw,
+    InvalidDrawWinner,
 }
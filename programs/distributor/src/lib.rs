@@ -1,13 +1,34 @@
+#[cfg(feature = "client")]
+pub mod client;
+pub mod draw;
 pub mod error;
+pub mod merkle;
+pub mod metadata;
 
-use anchor_lang::{prelude::*, system_program};
+use anchor_lang::{
+    prelude::*,
+    solana_program::{hash::hash, program_pack::Pack},
+    system_program,
+};
 use anchor_spl::{
     associated_token::{self, get_associated_token_address_with_program_id, AssociatedToken, Create as CreateAta},
-    token_interface::{self, Burn, Mint, TokenAccount, TokenInterface, TransferChecked},
+    token::spl_token,
+    token_2022::{
+        self,
+        spl_token_2022::{
+            extension::{transfer_fee::TransferFeeConfig, transfer_hook, BaseStateWithExtensions, StateWithExtensions},
+            onchain::invoke_transfer_checked,
+            state::Mint as Token2022Mint,
+        },
+    },
+    token_interface::{
+        self, Burn, CloseAccount, InitializeAccount3, Mint, SyncNative, TokenAccount, TokenInterface, TransferChecked,
+    },
 };
-use itertools::Itertools;
 
+use draw::DrawIndices;
 use error::DistributorError;
+use std::cell::Cell;
 
 declare_id!("5YP6jdWGTNDUhLYMCfocbyfT4RN58QbhVdtYmBdL6Af1");
 
@@ -15,12 +36,57 @@ declare_id!("5YP6jdWGTNDUhLYMCfocbyfT4RN58QbhVdtYmBdL6Af1");
 pub mod distributor {
     use super::*;
 
-    pub fn initialize(ctx: Context<Initialize>, share_size: u64, number_of_shares: u64) -> Result<()> {
-        require_gt!(share_size, 0, DistributorError::InvalidParameters);
-        require_gt!(number_of_shares, 1, DistributorError::InvalidParameters);
+    pub fn initialize(ctx: Context<Initialize>, params: InitializeParams) -> Result<()> {
+        let InitializeParams {
+            share_size,
+            number_of_shares,
+            remainder_policy,
+            distribution_mode,
+            co_authority,
+            burn_shares,
+            name,
+            uri,
+            max_rounds,
+            share_size_decimals,
+        } = params;
+        require!(max_rounds != Some(0), DistributorError::InvalidParameters);
+        // a native share is paid to the winner itself, a token share takes the token account too
+        let accounts_per_winner = if ctx.accounts.mint.key() == spl_token::native_mint::ID {
+            1
+        } else {
+            2
+        };
+        DistributorState::validate_shares(
+            share_size,
+            number_of_shares,
+            burn_shares,
+            distribution_mode,
+            accounts_per_winner,
+        )?;
+        require_keys_neq!(
+            ctx.accounts.mint.key(),
+            ctx.accounts.marker_mint.key(),
+            DistributorError::MarkerMintIsPayoutMint
+        );
+        // nobody could ever hold the marker of an empty mint
+        require_gt!(ctx.accounts.marker_mint.supply, 0, DistributorError::EmptyMarkerMint);
+        if let Some(share_size_decimals) = share_size_decimals {
+            DistributorState::validate_share_size_decimals(
+                share_size,
+                ctx.accounts.mint.decimals,
+                share_size_decimals,
+            )?;
+        }
         require!(
-            share_size.checked_mul(number_of_shares).is_some(),
-            DistributorError::InvalidParameters
+            co_authority != Some(ctx.accounts.distributor_authority.key()),
+            DistributorError::InvalidCoAuthority
+        );
+        require!(remainder_policy.is_valid(), DistributorError::InvalidRemainderPolicy);
+        DistributorState::validate_metadata(&name, &uri)?;
+        // wrapped SOL can't be burned
+        require!(
+            ctx.accounts.mint.key() != spl_token::native_mint::ID || !remainder_policy.burns(),
+            DistributorError::InvalidRemainderPolicy
         );
 
         let distributor_state = &mut ctx.accounts.distributor_state;
@@ -30,253 +96,7246 @@ pub mod distributor {
         distributor_state.distributor_authority = ctx.accounts.distributor_authority.key();
         distributor_state.share_size = share_size;
         distributor_state.number_of_shares = number_of_shares;
+        distributor_state.initial_share_size = share_size;
+        distributor_state.initial_number_of_shares = number_of_shares;
+        distributor_state.burn_shares = burn_shares;
+        distributor_state.name = name;
+        distributor_state.uri = uri;
         distributor_state.distributor_state_bump = ctx.bumps.distributor_state;
         distributor_state.vault_bump = ctx.bumps.vault;
+        distributor_state.remainder_policy = remainder_policy;
+        distributor_state.decimals = ctx.accounts.mint.decimals;
+        distributor_state.claim_period = DEFAULT_CLAIM_PERIOD;
+        distributor_state.unclaimed_policy = UnclaimedPolicy::ReturnToVault;
+        distributor_state.distribution_mode = distribution_mode;
+        distributor_state.co_authority = co_authority;
+        distributor_state.max_rounds = max_rounds;
+        distributor_state.version = DistributorState::VERSION;
 
         Ok(())
     }
 
-    pub fn deposit(ctx: Context<Deposit>, amount: u64) -> Result<()> {
-        let decimals = ctx.accounts.mint.decimals;
-        token_interface::transfer_checked(ctx.accounts.into(), amount, decimals)
+    /// Brings a state created by an older version of the program to the current layout. The account grows to the
+    /// current size, the payer covers the rent of the growth, and the fields added since read as zeroes. A state of
+    /// the first layout is read through `LegacyDistributorState`, the fields it lacks get the values `initialize`
+    /// sets. The rent buffer stays on top of the rent, it doesn't pay for the growth
+    pub fn migrate_state(ctx: Context<MigrateState>) -> Result<()> {
+        let state_info = ctx.accounts.distributor_state.to_account_info();
+        let legacy = if state_info.data_len() == LegacyDistributorState::SPACE {
+            let data = state_info.try_borrow_data()?;
+            Some(LegacyDistributorState::deserialize(&mut &data[8..])?)
+        } else {
+            None
+        };
+        let space = 8 + DistributorState::INIT_SPACE;
+        if state_info.data_len() < space {
+            state_info.realloc(space, true)?;
+        }
+
+        let mut distributor_state = DistributorState::try_deserialize(&mut &state_info.try_borrow_data()?[..])?;
+        require_keys_eq!(
+            distributor_state.mint,
+            ctx.accounts.mint.key(),
+            DistributorError::InvalidParameters
+        );
+        if let Some(legacy) = legacy {
+            distributor_state.initial_share_size = legacy.share_size;
+            distributor_state.initial_number_of_shares = legacy.number_of_shares;
+            distributor_state.decimals = ctx.accounts.mint.decimals;
+            distributor_state.claim_period = DEFAULT_CLAIM_PERIOD;
+            distributor_state.unclaimed_policy = UnclaimedPolicy::ReturnToVault;
+        }
+        let required = Rent::get()?
+            .minimum_balance(space)
+            .checked_add(distributor_state.rent_buffer)
+            .ok_or(DistributorError::ArithmeticOverflow)?;
+        let top_up = required.saturating_sub(state_info.lamports());
+        if top_up > 0 {
+            system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: ctx.accounts.payer.to_account_info(),
+                        to: state_info.clone(),
+                    },
+                ),
+                top_up,
+            )?;
+        }
+        require_keys_eq!(
+            distributor_state.distributor_authority,
+            ctx.accounts.distributor_authority.key(),
+            DistributorError::InvalidParameters
+        );
+        let share_size = distributor_state.initial_share_size.to_le_bytes();
+        let number_of_shares = distributor_state.initial_number_of_shares.to_le_bytes();
+        let address = Pubkey::create_program_address(
+            &[
+                distributor_state.mint.as_ref(),
+                distributor_state.marker_mint.as_ref(),
+                share_size.as_ref(),
+                number_of_shares.as_ref(),
+                &[distributor_state.distributor_state_bump],
+            ],
+            ctx.program_id,
+        )
+        .map_err(|_| DistributorError::InvalidParameters)?;
+        require_keys_eq!(address, state_info.key(), DistributorError::InvalidParameters);
+        require_gt!(
+            DistributorState::VERSION,
+            distributor_state.version,
+            DistributorError::AlreadyMigrated
+        );
+
+        let from_version = distributor_state.version;
+        if from_version < 4 {
+            // the states before `burn_shares` burned a single share
+            distributor_state.burn_shares = 1;
+        }
+        distributor_state.version = DistributorState::VERSION;
+        distributor_state.validate()?;
+        distributor_state.try_serialize(&mut &mut state_info.try_borrow_mut_data()?[..])?;
+
+        emit!(StateMigratedEvent {
+            from_version,
+            to_version: DistributorState::VERSION,
+        });
+
+        Ok(())
     }
 
-    pub fn distribute<'c: 'info, 'info>(ctx: Context<'_, '_, 'c, 'info, Distribute<'info>>) -> Result<()> {
-        let vault_amount = ctx.accounts.vault.amount;
-        let threshold = ctx.accounts.distributor_state.threshold();
-        require_gte!(vault_amount, threshold, DistributorError::ThresholdNotMet);
+    pub fn update_config(ctx: Context<SensitiveConfig>, args: UpdateConfigArgs) -> Result<()> {
+        let distributor_state = &mut ctx.accounts.distributor_state;
+        let share_size = args.share_size.unwrap_or(distributor_state.share_size);
+        let number_of_shares = args.number_of_shares.unwrap_or(distributor_state.number_of_shares);
+        DistributorState::validate_shares(
+            share_size,
+            number_of_shares,
+            distributor_state.burn_shares,
+            distributor_state.distribution_mode,
+            accounts_per_winner(distributor_state),
+        )?;
 
-        let number_of_shares = ctx.accounts.distributor_state.number_of_shares;
-        let remaining_accounts = ctx.remaining_accounts;
-        // There is have to be (number_of_shares - 1) * 2 accounts - authority and token account
-        // for each share without last one
-        require_eq!(
-            remaining_accounts.len() as u64,
-            (number_of_shares - 1) * 2,
-            DistributorError::MissingRemainingAccounts
+        distributor_state.share_size = share_size;
+        distributor_state.number_of_shares = number_of_shares;
+        distributor_state.validate_threshold()?;
+        distributor_state.validate_protocol_fee()?;
+
+        if let Some(claim_period) = args.claim_period {
+            require_gt!(claim_period, 0, DistributorError::InvalidParameters);
+            distributor_state.claim_period = claim_period;
+        }
+        if let Some(unclaimed_policy) = args.unclaimed_policy {
+            distributor_state.unclaimed_policy = unclaimed_policy;
+        }
+        if let Some(min_interval_seconds) = args.min_interval_seconds {
+            require_gte!(min_interval_seconds, 0, DistributorError::InvalidParameters);
+            distributor_state.min_interval_seconds = min_interval_seconds;
+        }
+        if let Some(min_deposit) = args.min_deposit {
+            distributor_state.min_deposit = min_deposit;
+        }
+        if let Some(referral_bps) = args.referral_bps {
+            require_gte!(10_000, referral_bps, DistributorError::InvalidParameters);
+            distributor_state.referral_bps = referral_bps;
+        }
+
+        Ok(())
+    }
+
+    /// Labels the distributor for explorers and frontends, an empty string clears the field. The
+    /// `distributor_metadata` of the distributor, once opened, has to be passed to keep it in sync
+    pub fn set_metadata(ctx: Context<SetMetadata>, name: String, uri: String) -> Result<()> {
+        DistributorState::validate_metadata(&name, &uri)?;
+        let distributor_state = &mut ctx.accounts.distributor_state;
+        distributor_state.name = name;
+        distributor_state.uri = uri;
+        if let Some(distributor_metadata) = &mut ctx.accounts.distributor_metadata {
+            distributor_metadata.sync(distributor_state);
+        }
+        Ok(())
+    }
+
+    /// Creates the `DistributorMetadata` of the distributor, the campaign identity wallets and explorers render
+    /// like the metadata of a token. The name and the uri are set on the distributor as well
+    pub fn open_distributor_metadata(
+        ctx: Context<OpenDistributorMetadata>,
+        name: String,
+        symbol: String,
+        uri: String,
+    ) -> Result<()> {
+        DistributorMetadata::validate(&name, &symbol, &uri)?;
+        let distributor_state = &mut ctx.accounts.distributor_state;
+        distributor_state.name = name.clone();
+        distributor_state.uri = uri.clone();
+        ctx.accounts.distributor_metadata.set_inner(DistributorMetadata {
+            distributor_state: distributor_state.key(),
+            bump: ctx.bumps.distributor_metadata,
+            name,
+            symbol,
+            uri,
+        });
+        Ok(())
+    }
+
+    pub fn update_distributor_metadata(
+        ctx: Context<UpdateDistributorMetadata>,
+        name: String,
+        symbol: String,
+        uri: String,
+    ) -> Result<()> {
+        DistributorMetadata::validate(&name, &symbol, &uri)?;
+        let distributor_state = &mut ctx.accounts.distributor_state;
+        distributor_state.name = name;
+        distributor_state.uri = uri;
+        let distributor_metadata = &mut ctx.accounts.distributor_metadata;
+        distributor_metadata.symbol = symbol;
+        distributor_metadata.sync(distributor_state);
+        Ok(())
+    }
+
+    /// Removes the `DistributorMetadata`, the authority receives the rent
+    pub fn close_distributor_metadata(_ctx: Context<CloseDistributorMetadata>) -> Result<()> {
+        Ok(())
+    }
+
+    pub fn pause(ctx: Context<UpdateConfig>) -> Result<()> {
+        ctx.accounts.distributor_state.paused = true;
+        Ok(())
+    }
+
+    pub fn resume(ctx: Context<UpdateConfig>) -> Result<()> {
+        ctx.accounts.distributor_state.paused = false;
+        Ok(())
+    }
+
+    /// Finishes the distributor after `max_rounds` rounds, `None` lets it run indefinitely. A finished distributor
+    /// refuses new rounds and can only be emptied by `emergency_withdraw` and closed by `close_distributor`
+    pub fn set_max_rounds(ctx: Context<SensitiveConfig>, max_rounds: Option<u64>) -> Result<()> {
+        let distributor_state = &mut ctx.accounts.distributor_state;
+        if let Some(max_rounds) = max_rounds {
+            require_gte!(max_rounds, distributor_state.round, DistributorError::InvalidParameters);
+        }
+        distributor_state.max_rounds = max_rounds;
+        Ok(())
+    }
+
+    /// Triggers the rounds at `threshold` instead of the payout sum of the shares, the difference stays in the vault
+    /// as a float after every round. `None` triggers them at the payout sum
+    pub fn set_threshold(ctx: Context<SensitiveConfig>, threshold: Option<u64>) -> Result<()> {
+        let distributor_state = &mut ctx.accounts.distributor_state;
+        distributor_state.threshold = threshold;
+        distributor_state.validate_threshold()
+    }
+
+    /// Pays `trigger_bonus` from the vault to the depositor whose deposit pushed the vault over the threshold, along
+    /// with the round it triggered. `None` stops the bonus
+    pub fn set_trigger_bonus(ctx: Context<SensitiveConfig>, trigger_bonus: Option<u64>) -> Result<()> {
+        if let Some(trigger_bonus) = trigger_bonus {
+            require_gt!(trigger_bonus, 0, DistributorError::InvalidParameters);
+        }
+        ctx.accounts.distributor_state.trigger_bonus = trigger_bonus;
+        ctx.accounts.distributor_state.threshold()?;
+        Ok(())
+    }
+
+    pub fn nominate_authority(ctx: Context<SensitiveConfig>, new_authority: Pubkey) -> Result<()> {
+        ctx.accounts.distributor_state.pending_authority = Some(new_authority);
+        Ok(())
+    }
+
+    pub fn accept_authority(ctx: Context<AcceptAuthority>) -> Result<()> {
+        let distributor_state = &mut ctx.accounts.distributor_state;
+        require!(
+            distributor_state.co_authority != Some(ctx.accounts.pending_authority.key()),
+            DistributorError::InvalidCoAuthority
         );
+        distributor_state.distributor_authority = ctx.accounts.pending_authority.key();
+        distributor_state.pending_authority = None;
+        Ok(())
+    }
 
-        let mint = ctx.accounts.mint.key();
-        let mint_marker = ctx.accounts.distributor_state.marker_mint;
-        let share_size = ctx.accounts.distributor_state.share_size.to_le_bytes();
-        let number_of_shares = ctx.accounts.distributor_state.number_of_shares.to_le_bytes();
+    /// Sensitive instructions require the signature of `co_authority` as well, `None` lifts the requirement. The
+    /// co-authority has to sign the change while it is set
+    pub fn set_co_authority(ctx: Context<SensitiveConfig>, co_authority: Option<Pubkey>) -> Result<()> {
+        let distributor_state = &mut ctx.accounts.distributor_state;
+        require!(
+            co_authority != Some(distributor_state.distributor_authority),
+            DistributorError::InvalidCoAuthority
+        );
+        distributor_state.co_authority = co_authority;
+        Ok(())
+    }
+
+    /// Routes `fee_bps` of the payouts of every round to the associated token account of `fee_collector`, `None`
+    /// disables the fee. The fee is taken out of the burned shares and can't exceed them, the rounds paying their
+    /// own amounts have no burned shares and are charged on top of the payouts
+    pub fn set_protocol_fee(ctx: Context<SensitiveConfig>, fee_bps: u16, fee_collector: Option<Pubkey>) -> Result<()> {
+        let distributor_state = &mut ctx.accounts.distributor_state;
+        distributor_state.fee_bps = fee_bps;
+        distributor_state.fee_collector = fee_collector;
+        distributor_state.validate_protocol_fee()
+    }
+
+    pub fn set_remainder_policy(ctx: Context<UpdateConfig>, remainder_policy: RemainderPolicy) -> Result<()> {
+        require!(remainder_policy.is_valid(), DistributorError::InvalidRemainderPolicy);
+        require!(
+            !ctx.accounts.distributor_state.is_native() || !remainder_policy.burns(),
+            DistributorError::InvalidRemainderPolicy
+        );
+        ctx.accounts.distributor_state.remainder_policy = remainder_policy;
+        Ok(())
+    }
+
+    /// Rounding of the weighted splits of `distribute_v2`, wrapped SOL can't be burned so a native distributor can't
+    /// burn the remainder
+    pub fn set_rounding_policy(ctx: Context<UpdateConfig>, rounding_policy: RoundingPolicy) -> Result<()> {
+        require!(
+            !ctx.accounts.distributor_state.is_native() || rounding_policy != RoundingPolicy::FloorBurn,
+            DistributorError::InvalidRoundingPolicy
+        );
+        ctx.accounts.distributor_state.rounding_policy = rounding_policy;
+        Ok(())
+    }
+
+    /// Shares of winners whose token accounts are frozen are disposed of by `policy` instead of failing the whole
+    /// payout, `None` fails it. The treasury policy needs the treasury of the remainder policy and isn't available
+    /// to the chunked rounds and the extra mints, which don't pass it
+    pub fn set_frozen_recipient_policy(
+        ctx: Context<UpdateConfig>,
+        policy: Option<FrozenRecipientPolicy>,
+    ) -> Result<()> {
+        let distributor_state = &mut ctx.accounts.distributor_state;
+        require!(
+            policy != Some(FrozenRecipientPolicy::Treasury) || distributor_state.remainder_policy.treasury().is_some(),
+            DistributorError::MissingTreasury
+        );
+        require!(
+            !distributor_state.is_native() || policy != Some(FrozenRecipientPolicy::Burn),
+            DistributorError::InvalidRemainderPolicy
+        );
+        distributor_state.frozen_recipient_policy = policy;
+        Ok(())
+    }
+
+    /// Lets the winners be paid to any token account of the mint they own instead of their ATA only, e.g. to a
+    /// token account of a multisig or of a program
+    pub fn set_allow_non_ata_recipients(ctx: Context<UpdateConfig>, enabled: bool) -> Result<()> {
+        ctx.accounts.distributor_state.allow_non_ata_recipients = enabled;
+        Ok(())
+    }
+
+    /// Charges the payer `lamports` into the fee vault on every round, zero disables the fee. A single transaction
+    /// round is charged by its `distribute`, a chunked one by `begin_round` and a claim round by `distribute_claims`.
+    /// The fee vault has to be opened by `open_fee_vault` first and its lamports go to the fee collector
+    pub fn set_lamport_fee(ctx: Context<SensitiveConfig>, lamports: u64) -> Result<()> {
+        let distributor_state = &mut ctx.accounts.distributor_state;
+        require!(
+            lamports == 0 || distributor_state.fee_collector.is_some(),
+            DistributorError::InvalidProtocolFee
+        );
+        distributor_state.lamport_fee = lamports;
+        Ok(())
+    }
+
+    pub fn open_fee_vault(ctx: Context<OpenFeeVault>) -> Result<()> {
+        ctx.accounts.fee_vault.set_inner(FeeVault {
+            distributor_state: ctx.accounts.distributor_state.key(),
+            bump: ctx.bumps.fee_vault,
+            collected: 0,
+        });
+        Ok(())
+    }
+
+    /// Moves the lamport fees collected since the last call to `destination`, the fee vault keeps its rent
+    pub fn collect_fees(ctx: Context<CollectFees>) -> Result<()> {
+        let fee_vault = ctx.accounts.fee_vault.to_account_info();
+        let rent = Rent::get()?.minimum_balance(fee_vault.data_len());
+        let amount = fee_vault.lamports().saturating_sub(rent);
+        require_gt!(amount, 0, DistributorError::NothingToCollect);
+        fee_vault.sub_lamports(amount)?;
+        ctx.accounts.destination.add_lamports(amount)?;
+
+        emit!(FeesCollectedEvent {
+            destination: ctx.accounts.destination.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    /// Moves `amount` lamports of the authority to the rent buffer of the distributor. The payer of the payouts gets
+    /// back the rent of the winner token accounts it creates from the buffer, while it lasts
+    pub fn fund_rent_buffer(ctx: Context<RentBuffer>, amount: u64) -> Result<()> {
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.distributor_authority.to_account_info(),
+                    to: ctx.accounts.distributor_state.to_account_info(),
+                },
+            ),
+            amount,
+        )?;
+        let distributor_state = &mut ctx.accounts.distributor_state;
+        distributor_state.rent_buffer = distributor_state
+            .rent_buffer
+            .checked_add(amount)
+            .ok_or(DistributorError::InvalidParameters)?;
+        Ok(())
+    }
+
+    /// Returns `amount` of the rent buffer to the authority
+    pub fn withdraw_rent_buffer(ctx: Context<RentBuffer>, amount: u64) -> Result<()> {
+        let distributor_state = &mut ctx.accounts.distributor_state;
+        require_gte!(
+            distributor_state.rent_buffer,
+            amount,
+            DistributorError::InsufficientRentBuffer
+        );
+        distributor_state.rent_buffer -= amount;
+        distributor_state.sub_lamports(amount)?;
+        ctx.accounts.distributor_authority.add_lamports(amount)?;
+        Ok(())
+    }
+
+    /// Requires `distribute` and `distribute_weighted` to pay the verified winners of the committed draw of the
+    /// round, see `commit_draw`. The chunked and the claim rounds can't be started while it is required
+    pub fn set_requires_committed_draw(ctx: Context<SensitiveConfig>, enabled: bool) -> Result<()> {
+        ctx.accounts.distributor_state.requires_committed_draw = enabled;
+        Ok(())
+    }
+
+    /// Requires `distribute`, `distribute_weighted` and `distribute_v2` to pay the verified winners of the revealed
+    /// VRF draw of the round, see `verify_draw_winner`. The draw has to be requested over the holders snapshot
+    /// committed for the round. The chunked, the batched, the dividend and the claim rounds can't be started while
+    /// it is required
+    pub fn set_requires_vrf_draw(ctx: Context<SensitiveConfig>, enabled: bool) -> Result<()> {
+        ctx.accounts.distributor_state.requires_vrf_draw = enabled;
+        Ok(())
+    }
+
+    /// Requires `distribute`, `distribute_weighted` and `distribute_v2` to reference the holders snapshot committed
+    /// for the round by `commit_snapshot`. The chunked, the batched and the claim rounds can't be started while it
+    /// is required
+    pub fn set_requires_holder_snapshot(ctx: Context<SensitiveConfig>, enabled: bool) -> Result<()> {
+        ctx.accounts.distributor_state.requires_holder_snapshot = enabled;
+        Ok(())
+    }
+
+    /// Requires `threshold` of `keys` to co-sign the payouts of `distribute`, `distribute_weighted`,
+    /// `distribute_claims` and `begin_round` as signers among their remaining accounts, `None` leaves them to the
+    /// authority alone. The authority itself may be a multisig PDA signing by CPI
+    pub fn set_cosigners(ctx: Context<SensitiveConfig>, cosigners: Option<Cosigners>) -> Result<()> {
+        if let Some(cosigners) = &cosigners {
+            require!(cosigners.is_valid(), DistributorError::InvalidCosigners);
+        }
+        ctx.accounts.distributor_state.cosigners = cosigners;
+        Ok(())
+    }
+
+    /// Binds `distribute` to the identity of the backend, see `ServiceSigner`. `None` leaves it to the authority.
+    /// The service signer can be rotated independently of the authority
+    pub fn set_service_signer(ctx: Context<SensitiveConfig>, service_signer: Option<ServiceSigner>) -> Result<()> {
+        ctx.accounts.distributor_state.service_signer = service_signer;
+        Ok(())
+    }
+
+    /// Locks the shares of `distribute`, `distribute_weighted` and `distribute_chunk` in the vesting escrow and
+    /// releases them linearly over `duration` seconds by `claim_vested`, `None` pays them instantly. The escrow has
+    /// to be opened by `open_vesting_escrow` first. Native shares and extra mints are always paid instantly
+    pub fn set_vesting(ctx: Context<UpdateConfig>, duration: Option<i64>) -> Result<()> {
+        let distributor_state = &mut ctx.accounts.distributor_state;
+        if let Some(duration) = duration {
+            require_gt!(duration, 0, DistributorError::InvalidVestingDuration);
+            require!(!distributor_state.is_native(), DistributorError::InvalidVestingDuration);
+        }
+        distributor_state.vesting_duration = duration;
+        Ok(())
+    }
+
+    /// Shares above `threshold` are paid only to the winners who accepted large payouts by
+    /// `accept_large_payouts`, the shares of the others are locked in the vesting escrow for them to claim by
+    /// `claim_vested` at once. `None` pays every share. Extra mints aren't affected
+    pub fn set_large_payout_threshold(ctx: Context<UpdateConfig>, threshold: Option<u64>) -> Result<()> {
+        let distributor_state = &mut ctx.accounts.distributor_state;
+        if let Some(threshold) = threshold {
+            require_gt!(threshold, 0, DistributorError::InvalidParameters);
+            require!(!distributor_state.is_native(), DistributorError::InvalidParameters);
+        }
+        distributor_state.large_payout_threshold = threshold;
+        distributor_state.validate_winner_accounts()
+    }
+
+    /// Opts the wallet in to the shares above the large payout threshold of every distributor
+    pub fn accept_large_payouts(ctx: Context<AcceptLargePayouts>) -> Result<()> {
+        ctx.accounts.payout_acceptance.set_inner(PayoutAcceptance {
+            wallet: ctx.accounts.wallet.key(),
+            bump: ctx.bumps.payout_acceptance,
+        });
+        Ok(())
+    }
+
+    /// Opts the wallet out again, the wallet receives the rent
+    pub fn revoke_large_payouts(_ctx: Context<RevokeLargePayouts>) -> Result<()> {
+        Ok(())
+    }
+
+    /// Creates the token account holding the vested shares of all winners until they are claimed
+    pub fn open_vesting_escrow(_ctx: Context<OpenVestingEscrow>) -> Result<()> {
+        Ok(())
+    }
 
+    /// Releases the vested part of a locked share to a token account of the winner, the `Vesting` account is
+    /// closed to its payer once the whole share is released. The remaining accounts are the transfer hook accounts
+    pub fn claim_vested<'c: 'info, 'info>(ctx: Context<'_, '_, 'c, 'info, ClaimVested<'info>>) -> Result<()> {
+        let vesting = &mut ctx.accounts.vesting;
+        let amount = vesting.releasable(Clock::get()?.unix_timestamp);
+        require_gt!(amount, 0, DistributorError::NothingVested);
+        vesting.released += amount;
+
+        let distributor_state = &ctx.accounts.distributor_state;
+        let share_size = distributor_state.initial_share_size.to_le_bytes();
+        let number_of_shares = distributor_state.initial_number_of_shares.to_le_bytes();
         let seeds = [
-            mint.as_ref(),
-            mint_marker.as_ref(),
+            distributor_state.mint.as_ref(),
+            distributor_state.marker_mint.as_ref(),
             share_size.as_ref(),
             number_of_shares.as_ref(),
-            &[ctx.accounts.distributor_state.distributor_state_bump],
+            &[distributor_state.distributor_state_bump],
         ];
+        transfer_checked_with_hook(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.vesting_escrow.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    to: ctx.accounts.token_account.to_account_info(),
+                    authority: ctx.accounts.distributor_state.to_account_info(),
+                },
+                &[&seeds],
+            )
+            .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+            amount,
+            ctx.accounts.mint.decimals,
+        )?;
 
-        let token_program = ctx.accounts.token_program.key();
-        for (authority, token_account) in ctx.remaining_accounts.iter().tuples() {
-            require_keys_eq!(
-                *token_account.key,
-                get_associated_token_address_with_program_id(authority.key, &mint, &token_program),
-                DistributorError::InvalidAssociatedTokenAccount
-            );
+        if ctx.accounts.vesting.released == ctx.accounts.vesting.total {
+            ctx.accounts.vesting.close(ctx.accounts.payer.to_account_info())?;
+        }
+        Ok(())
+    }
 
-            // token account is not initialized
-            if token_account.owner == &system_program::ID && token_account.lamports() == 0 {
-                associated_token::create(CpiContext::new(
-                    ctx.accounts.associated_token_program.to_account_info(),
-                    CreateAta {
-                        payer: ctx.accounts.payer.to_account_info(),
-                        associated_token: token_account.to_account_info(),
-                        authority: authority.to_account_info(),
-                        mint: ctx.accounts.mint.to_account_info(),
-                        system_program: ctx.accounts.system_program.to_account_info(),
-                        token_program: ctx.accounts.token_program.to_account_info(),
-                    },
-                ))?;
-            }
-            let token_account = InterfaceAccount::<TokenAccount>::try_from(token_account)?;
-            require_keys_eq!(
-                token_account.mint,
-                ctx.accounts.mint.key(),
-                DistributorError::InvalidAssociatedTokenAccount
-            );
-            require_keys_eq!(
-                token_account.owner,
-                *authority.key,
-                DistributorError::InvalidAssociatedTokenAccount
-            );
+    /// Escrows the share of a winner whose token account can't receive it, e.g. an ATA a restrictive extension of
+    /// the mint doesn't let be created, instead of failing the round. The winner passes its `EscrowedShare`
+    /// account in place of the token account, the share is claimable by `claim_escrowed` for `claim_period`
+    /// seconds and is swept back to the vault by `sweep_expired` after that. The vesting escrow holds the shares,
+    /// `None` disables the escrow
+    pub fn set_escrow_claim_period(ctx: Context<UpdateConfig>, claim_period: Option<i64>) -> Result<()> {
+        let distributor_state = &mut ctx.accounts.distributor_state;
+        if let Some(claim_period) = claim_period {
+            require_gt!(claim_period, 0, DistributorError::InvalidParameters);
+            require!(!distributor_state.is_native(), DistributorError::InvalidParameters);
+        }
+        distributor_state.escrow_claim_period = claim_period;
+        Ok(())
+    }
+
+    /// Releases an escrowed share to a token account of the winner before it expires, the payer of the round gets
+    /// the rent of the `EscrowedShare` back. The remaining accounts are the transfer hook accounts
+    pub fn claim_escrowed<'c: 'info, 'info>(ctx: Context<'_, '_, 'c, 'info, ClaimEscrowed<'info>>) -> Result<()> {
+        require_gt!(
+            ctx.accounts.escrowed_share.expires_at,
+            Clock::get()?.unix_timestamp,
+            DistributorError::ClaimExpired
+        );
+        release_escrowed_share(
+            &ctx.accounts.distributor_state,
+            &ctx.accounts.mint,
+            &ctx.accounts.vesting_escrow,
+            ctx.accounts.token_account.to_account_info(),
+            &ctx.accounts.token_program,
+            ctx.remaining_accounts,
+            ctx.accounts.escrowed_share.amount,
+        )
+    }
+
+    /// Returns an expired escrowed share to the vault, anyone can sweep it. The payer of the round gets the rent of
+    /// the `EscrowedShare` back. The remaining accounts are the transfer hook accounts
+    pub fn sweep_expired<'c: 'info, 'info>(ctx: Context<'_, '_, 'c, 'info, SweepExpired<'info>>) -> Result<()> {
+        require_gte!(
+            Clock::get()?.unix_timestamp,
+            ctx.accounts.escrowed_share.expires_at,
+            DistributorError::ClaimsNotExpired
+        );
+        release_escrowed_share(
+            &ctx.accounts.distributor_state,
+            &ctx.accounts.mint,
+            &ctx.accounts.vesting_escrow,
+            ctx.accounts.vault.to_account_info(),
+            &ctx.accounts.token_program,
+            ctx.remaining_accounts,
+            ctx.accounts.escrowed_share.amount,
+        )
+    }
+
+    /// Requires the winners of `distribute` to hold at least `min_marker_balance` of the marker token, `None`
+    /// disables the check
+    pub fn set_min_marker_balance(ctx: Context<UpdateConfig>, min_marker_balance: Option<u64>) -> Result<()> {
+        let distributor_state = &mut ctx.accounts.distributor_state;
+        distributor_state.min_marker_balance = min_marker_balance;
+        distributor_state.validate_winner_accounts()
+    }
+
+    /// Requires the winners of `distribute` to own an NFT of the verified Metaplex `collection`, every winner
+    /// passes the token account holding the NFT and the metadata account of its mint. `None` disables the check
+    pub fn set_marker_collection(ctx: Context<UpdateConfig>, collection: Option<Pubkey>) -> Result<()> {
+        let distributor_state = &mut ctx.accounts.distributor_state;
+        distributor_state.marker_collection = collection;
+        distributor_state.validate_winner_accounts()
+    }
 
-            token_interface::transfer_checked(
+    /// First step of the recovery from a vault frozen by the freeze authority of the mint: creates an empty vault
+    /// derived with the next vault nonce. The distributor keeps using the current vault until `migrate_vault`
+    pub fn create_replacement_vault(ctx: Context<CreateReplacementVault>) -> Result<()> {
+        ctx.accounts.distributor_state.replacement_vault = Some(ctx.accounts.replacement_vault.key());
+        Ok(())
+    }
+
+    /// Moves the balance of the current vault to the replacement vault and switches the distributor to it. The
+    /// current vault has to be thawed first, unless `abandon_frozen` is set: then its balance stays where it is
+    pub fn migrate_vault<'c: 'info, 'info>(
+        ctx: Context<'_, '_, 'c, 'info, MigrateVault<'info>>,
+        abandon_frozen: bool,
+    ) -> Result<()> {
+        let vault = &ctx.accounts.vault;
+        let moved = if vault.is_frozen() {
+            require!(abandon_frozen, DistributorError::VaultFrozen);
+            0
+        } else {
+            vault.amount
+        };
+
+        if moved > 0 {
+            let mint = ctx.accounts.mint.key();
+            let mint_marker = ctx.accounts.distributor_state.marker_mint;
+            let share_size = ctx.accounts.distributor_state.initial_share_size.to_le_bytes();
+            let number_of_shares = ctx.accounts.distributor_state.initial_number_of_shares.to_le_bytes();
+            let seeds = [
+                mint.as_ref(),
+                mint_marker.as_ref(),
+                share_size.as_ref(),
+                number_of_shares.as_ref(),
+                &[ctx.accounts.distributor_state.distributor_state_bump],
+            ];
+            transfer_checked_with_hook(
                 CpiContext::new_with_signer(
                     ctx.accounts.token_program.to_account_info(),
                     TransferChecked {
                         from: ctx.accounts.vault.to_account_info(),
                         mint: ctx.accounts.mint.to_account_info(),
-                        to: token_account.to_account_info(),
+                        to: ctx.accounts.replacement_vault.to_account_info(),
                         authority: ctx.accounts.distributor_state.to_account_info(),
                     },
                     &[&seeds],
-                ),
-                ctx.accounts.distributor_state.share_size,
+                )
+                .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+                moved,
                 ctx.accounts.mint.decimals,
             )?;
         }
 
-        token_interface::burn(
-            CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                Burn {
-                    mint: ctx.accounts.mint.to_account_info(),
-                    from: ctx.accounts.vault.to_account_info(),
-                    authority: ctx.accounts.distributor_state.to_account_info(),
-                },
-                &[&seeds],
-            ),
-            ctx.accounts.distributor_state.share_size,
-        )
+        let distributor_state = &mut ctx.accounts.distributor_state;
+        let previous_vault = distributor_state.vault;
+        distributor_state.vault = ctx.accounts.replacement_vault.key();
+        distributor_state.vault_bump = ctx.bumps.replacement_vault;
+        distributor_state.vault_nonce += 1;
+        distributor_state.replacement_vault = None;
+
+        emit!(VaultMigratedEvent {
+            previous_vault,
+            vault: distributor_state.vault,
+            vault_nonce: distributor_state.vault_nonce,
+            moved,
+        });
+
+        Ok(())
+    }
+
+    /// First step of an emergency withdraw: records the withdraw in the state, so depositors can see it coming,
+    /// and `emergency_withdraw` can execute it once `EMERGENCY_WITHDRAW_TIMELOCK` has passed. Replaces a pending one
+    pub fn request_emergency_withdraw(ctx: Context<SensitiveConfig>, amount: u64, destination: Pubkey) -> Result<()> {
+        require_gt!(amount, 0, DistributorError::InvalidParameters);
+        let unlocks_at = Clock::get()?.unix_timestamp + EMERGENCY_WITHDRAW_TIMELOCK;
+        ctx.accounts.distributor_state.pending_withdraw = Some(PendingWithdraw {
+            amount,
+            destination,
+            unlocks_at,
+        });
+
+        emit!(EmergencyWithdrawRequestedEvent {
+            amount,
+            destination,
+            unlocks_at,
+        });
+
+        Ok(())
+    }
+
+    pub fn cancel_emergency_withdraw(ctx: Context<UpdateConfig>) -> Result<()> {
+        require!(
+            ctx.accounts.distributor_state.pending_withdraw.take().is_some(),
+            DistributorError::NoPendingWithdraw
+        );
+        Ok(())
+    }
+
+    /// Transfers the amount of the pending emergency withdraw from the vault to its destination after the timelock.
+    /// The remaining accounts are the transfer hook accounts of the mint
+    pub fn emergency_withdraw<'c: 'info, 'info>(
+        ctx: Context<'_, '_, 'c, 'info, EmergencyWithdraw<'info>>,
+    ) -> Result<()> {
+        let Some(pending_withdraw) = ctx.accounts.distributor_state.pending_withdraw else {
+            return err!(DistributorError::NoPendingWithdraw);
+        };
+        require_keys_eq!(
+            ctx.accounts.destination.key(),
+            pending_withdraw.destination,
+            DistributorError::InvalidParameters
+        );
+        require_gte!(
+            Clock::get()?.unix_timestamp,
+            pending_withdraw.unlocks_at,
+            DistributorError::WithdrawTimelocked
+        );
+        require_gte!(
+            ctx.accounts.vault.amount,
+            pending_withdraw.amount,
+            DistributorError::InsufficientVaultBalance
+        );
+
+        let mint = ctx.accounts.mint.key();
+        let mint_marker = ctx.accounts.distributor_state.marker_mint;
+        let share_size = ctx.accounts.distributor_state.initial_share_size.to_le_bytes();
+        let number_of_shares = ctx.accounts.distributor_state.initial_number_of_shares.to_le_bytes();
+        let seeds = [
+            mint.as_ref(),
+            mint_marker.as_ref(),
+            share_size.as_ref(),
+            number_of_shares.as_ref(),
+            &[ctx.accounts.distributor_state.distributor_state_bump],
+        ];
+        transfer_checked_with_hook(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.vault.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    to: ctx.accounts.destination.to_account_info(),
+                    authority: ctx.accounts.distributor_state.to_account_info(),
+                },
+                &[&seeds],
+            )
+            .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+            pending_withdraw.amount,
+            ctx.accounts.mint.decimals,
+        )?;
+        ctx.accounts.distributor_state.pending_withdraw = None;
+
+        emit!(EmergencyWithdrawEvent {
+            amount: pending_withdraw.amount,
+            destination: pending_withdraw.destination,
+        });
+
+        Ok(())
+    }
+
+    /// Closes the empty distributor. Everything it still owes has to be paid out first: the vault, the vesting
+    /// escrow, the claim rounds, the uncollected lamport fees and the rent buffer. The empty vesting escrow and fee
+    /// vault are closed along with the state, their rent goes to `rent_recipient`
+    pub fn close_distributor(ctx: Context<CloseDistributor>) -> Result<()> {
+        require_eq!(ctx.accounts.vault.amount, 0, DistributorError::VaultNotEmpty);
+        require_eq!(
+            ctx.accounts.distributor_state.open_claim_rounds,
+            0,
+            DistributorError::ClaimRoundsOpen
+        );
+        require_eq!(
+            ctx.accounts.distributor_state.rent_buffer,
+            0,
+            DistributorError::RentBufferNotEmpty
+        );
+
+        let distributor_state = &ctx.accounts.distributor_state;
+        let share_size = distributor_state.initial_share_size.to_le_bytes();
+        let number_of_shares = distributor_state.initial_number_of_shares.to_le_bytes();
+        let seeds = [
+            distributor_state.mint.as_ref(),
+            distributor_state.marker_mint.as_ref(),
+            share_size.as_ref(),
+            number_of_shares.as_ref(),
+            &[distributor_state.distributor_state_bump],
+        ];
+        token_interface::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: ctx.accounts.vault.to_account_info(),
+                destination: ctx.accounts.rent_recipient.to_account_info(),
+                authority: ctx.accounts.distributor_state.to_account_info(),
+            },
+            &[&seeds],
+        ))?;
+
+        let vesting_escrow = ctx.accounts.vesting_escrow.to_account_info();
+        if *vesting_escrow.owner == ctx.accounts.token_program.key() {
+            let amount = {
+                let data = vesting_escrow.try_borrow_data()?;
+                TokenAccount::try_deserialize(&mut &data[..])?.amount
+            };
+            require_eq!(amount, 0, DistributorError::VestingEscrowNotEmpty);
+            token_interface::close_account(CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                CloseAccount {
+                    account: vesting_escrow,
+                    destination: ctx.accounts.rent_recipient.to_account_info(),
+                    authority: ctx.accounts.distributor_state.to_account_info(),
+                },
+                &[&seeds],
+            ))?;
+        }
+
+        let fee_vault = ctx.accounts.fee_vault.to_account_info();
+        if *fee_vault.owner == crate::ID {
+            let rent = Rent::get()?.minimum_balance(fee_vault.data_len());
+            require_gte!(rent, fee_vault.lamports(), DistributorError::FeesNotCollected);
+            let lamports = fee_vault.lamports();
+            fee_vault.sub_lamports(lamports)?;
+            ctx.accounts.rent_recipient.add_lamports(lamports)?;
+            fee_vault.assign(&system_program::ID);
+            fee_vault.realloc(0, false)?;
+        }
+
+        emit!(DistributorClosedEvent {
+            rent_recipient: ctx.accounts.rent_recipient.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Rejects the winners of the previous round in `distribute`, `distribute_weighted` and `distribute_chunk`
+    /// from the next round on. The rounds have to be recorded while the rule is enabled, the previous round record
+    /// holds its winners
+    pub fn set_no_repeat_winners(ctx: Context<UpdateConfig>, enabled: bool) -> Result<()> {
+        let distributor_state = &mut ctx.accounts.distributor_state;
+        distributor_state.no_repeat_winners_since = enabled.then_some(distributor_state.round);
+        Ok(())
+    }
+
+    pub fn open_blocklist(ctx: Context<OpenBlocklist>) -> Result<()> {
+        ctx.accounts.blocklist.set_inner(Blocklist {
+            distributor_state: ctx.accounts.distributor_state.key(),
+            bump: ctx.bumps.blocklist,
+            wallets: Vec::new(),
+        });
+        Ok(())
+    }
+
+    /// Bars the wallet from receiving shares of `distribute`, `distribute_weighted` and `distribute_chunk`. The
+    /// payer covers the growth of the blocklist
+    pub fn add_to_blocklist(ctx: Context<AddToBlocklist>, wallet: Pubkey) -> Result<()> {
+        let wallets = &mut ctx.accounts.blocklist.wallets;
+        let Err(position) = wallets.binary_search(&wallet) else {
+            return err!(DistributorError::InvalidParameters);
+        };
+        wallets.insert(position, wallet);
+        ctx.accounts.distributor_state.blocked_recipients = wallets.len() as u32;
+        Ok(())
+    }
+
+    /// The payer receives the rent of the freed space
+    pub fn remove_from_blocklist(ctx: Context<RemoveFromBlocklist>, wallet: Pubkey) -> Result<()> {
+        let wallets = &mut ctx.accounts.blocklist.wallets;
+        let Ok(position) = wallets.binary_search(&wallet) else {
+            return err!(DistributorError::InvalidParameters);
+        };
+        wallets.remove(position);
+        ctx.accounts.distributor_state.blocked_recipients = wallets.len() as u32;
+        Ok(())
+    }
+
+    /// Adds a mint paid to the winners of every round next to the distributor mint, see `distribute_extra_mint`
+    pub fn add_payout_mint(ctx: Context<AddPayoutMint>, share_size: u64) -> Result<()> {
+        require_gt!(share_size, 0, DistributorError::InvalidParameters);
+        ctx.accounts.vault_config.set_inner(VaultConfig {
+            distributor_state: ctx.accounts.distributor_state.key(),
+            mint: ctx.accounts.mint.key(),
+            vault: ctx.accounts.vault.key(),
+            share_size,
+            last_round: None,
+            paid: 0,
+            bump: ctx.bumps.vault_config,
+        });
+        Ok(())
+    }
+
+    pub fn set_payout_share_size(ctx: Context<UpdateVaultConfig>, share_size: u64) -> Result<()> {
+        require_gt!(share_size, 0, DistributorError::InvalidParameters);
+        ctx.accounts.vault_config.share_size = share_size;
+        Ok(())
+    }
+
+    /// Pays `share_size` of an extra payout mint to the winners of a completed round, in the order of its
+    /// `DistributionRound` record starting at `first_payout`. Every call has to continue where the previous one of
+    /// the round stopped, so a round can be paid in several transactions but never twice. Any completed round can
+    /// be paid until it is fully paid, except for the rounds older than the one the mint paid last. The remaining
+    /// accounts are the authority and the token account of every winner, followed by the transfer hook accounts of
+    /// the mint starting with the hook program
+    pub fn distribute_extra_mint<'c: 'info, 'info>(
+        ctx: Context<'_, '_, 'c, 'info, DistributeExtraMint<'info>>,
+        first_payout: u64,
+    ) -> Result<()> {
+        let round = ctx.accounts.round.round;
+        let vault_config = &ctx.accounts.vault_config;
+        let paid = match vault_config.last_round {
+            Some(last_round) if last_round == round => vault_config.paid,
+            // only the last round is tracked, an older one could be paid twice
+            Some(last_round) => {
+                require_gt!(round, last_round, DistributorError::RoundMismatch);
+                0
+            },
+            None => 0,
+        };
+        require_eq!(first_payout, paid, DistributorError::ChunkOutOfOrder);
+
+        let payouts = &ctx.accounts.round.payouts;
+        let (winner_accounts, hook_accounts) = split_at_hook_accounts(&ctx.accounts.mint, ctx.remaining_accounts)?;
+        let winners = (winner_accounts.len() / 2).min(payouts.len().saturating_sub(first_payout as usize));
+        require_gt!(winners, 0, DistributorError::MissingRemainingAccounts);
+        let winner_accounts = &winner_accounts[..winners * 2];
+        for (accounts, payout) in winner_accounts.chunks_exact(2).zip(&payouts[first_payout as usize..]) {
+            require_keys_eq!(
+                *accounts[0].key,
+                payout.wallet,
+                DistributorError::InvalidAssociatedTokenAccount
+            );
+        }
+
+        let share_size = vault_config.share_size;
+        let total = share_size
+            .checked_mul(winners as u64)
+            .ok_or(DistributorError::InsufficientVaultBalance)?;
+        require_gte!(
+            ctx.accounts.vault.amount,
+            total,
+            DistributorError::InsufficientVaultBalance
+        );
+
+        let mint = ctx.accounts.distributor_state.mint;
+        let mint_marker = ctx.accounts.distributor_state.marker_mint;
+        let initial_share_size = ctx.accounts.distributor_state.initial_share_size.to_le_bytes();
+        let number_of_shares = ctx.accounts.distributor_state.initial_number_of_shares.to_le_bytes();
+        let seeds = [
+            mint.as_ref(),
+            mint_marker.as_ref(),
+            initial_share_size.as_ref(),
+            number_of_shares.as_ref(),
+            &[ctx.accounts.distributor_state.distributor_state_bump],
+        ];
+
+        let ata_rent = Cell::new(0);
+        let payout_accounts = PayoutAccounts {
+            payer: &ctx.accounts.payer,
+            distributor_state: &ctx.accounts.distributor_state,
+            mint: &ctx.accounts.mint,
+            vault: &ctx.accounts.vault,
+            system_program: &ctx.accounts.system_program,
+            token_program: &ctx.accounts.token_program,
+            associated_token_program: &ctx.accounts.associated_token_program,
+            native_unwrap: None,
+            native_unwrap_bump: 0,
+            // the treasury holds the distributed mint, not the extra one
+            treasury: None,
+            // the winners of the round were checked when its shares were paid
+            blocklist: None,
+            previous_round: None,
+            // extra mints are never vested
+            vesting_escrow: None,
+            ata_rent: &ata_rent,
+        };
+        let mut delivered = Vec::with_capacity(winners);
+        let mut fees = 0u64;
+        let mut skipped = 0u64;
+        for accounts in winner_accounts.chunks_exact(2) {
+            match pay_token_share(
+                &payout_accounts,
+                &accounts[0],
+                &accounts[1],
+                hook_accounts,
+                share_size,
+                &seeds,
+            )? {
+                Some(fee) => {
+                    fees += fee;
+                    delivered.push(share_size - fee);
+                },
+                None => {
+                    skipped += share_size;
+                    delivered.push(0);
+                },
+            }
+        }
+        fees += dispose_skipped_shares(&payout_accounts, skipped, hook_accounts, &seeds)?;
+        reimburse_ata_rent(&mut ctx.accounts.distributor_state, &ctx.accounts.payer, ata_rent.get())?;
+
+        let vault_config = &mut ctx.accounts.vault_config;
+        vault_config.last_round = Some(round);
+        vault_config.paid = first_payout + winners as u64;
+
+        emit_cpi!(ExtraMintDistributeEvent {
+            round,
+            mint: ctx.accounts.mint.key(),
+            first_payout,
+            winners: winner_accounts.iter().step_by(2).map(|account| account.key()).collect(),
+            share_size,
+            delivered,
+            fees,
+        });
+
+        Ok(())
+    }
+
+    /// Creates the accounting record of the depositor, `deposit` updates it when it is passed
+    pub fn open_depositor_record(ctx: Context<OpenDepositorRecord>) -> Result<()> {
+        ctx.accounts.depositor_record.set_inner(DepositorRecord {
+            distributor_state: ctx.accounts.distributor_state.key(),
+            authority: ctx.accounts.authority.key(),
+            total_deposited: 0,
+            deposits: 0,
+            last_deposit_slot: 0,
+            last_deposit_round: 0,
+            bump: ctx.bumps.depositor_record,
+        });
+        Ok(())
+    }
+
+    /// Restricts the deposits to the depositors allowed by `allow_depositor`
+    pub fn set_deposit_allowlist(ctx: Context<UpdateConfig>, enabled: bool) -> Result<()> {
+        ctx.accounts.distributor_state.deposit_allowlist = enabled;
+        Ok(())
+    }
+
+    /// Allows `depositor` to deposit while the allowlist is enabled, e.g. the PDA of a program routing fees to the
+    /// distributor
+    pub fn allow_depositor(ctx: Context<AllowDepositor>, depositor: Pubkey) -> Result<()> {
+        ctx.accounts.deposit_allowance.set_inner(DepositAllowance {
+            distributor_state: ctx.accounts.distributor_state.key(),
+            depositor,
+            bump: ctx.bumps.deposit_allowance,
+        });
+        Ok(())
+    }
+
+    /// Revokes the allowance of the depositor, the payer receives the rent
+    pub fn disallow_depositor(_ctx: Context<DisallowDepositor>) -> Result<()> {
+        Ok(())
+    }
+
+    pub fn deposit<'c: 'info, 'info>(ctx: Context<'_, '_, 'c, 'info, Deposit<'info>>, amount: u64) -> Result<()> {
+        ctx.accounts
+            .distributor_state
+            .check_depositor(ctx.accounts.deposit_allowance.as_ref())?;
+        require_gte!(
+            amount,
+            ctx.accounts.distributor_state.min_deposit,
+            DistributorError::DepositTooSmall
+        );
+        let decimals = ctx.accounts.mint.decimals;
+        let balance_before = ctx.accounts.vault.amount;
+        let cpi_ctx = CpiContext::from(&mut *ctx.accounts).with_remaining_accounts(ctx.remaining_accounts.to_vec());
+        transfer_checked_with_hook(cpi_ctx, amount, decimals)?;
+
+        if let Some(record) = &mut ctx.accounts.depositor_record {
+            record.record_deposit(amount, ctx.accounts.distributor_state.round)?;
+        }
+
+        ctx.accounts.vault.reload()?;
+        let depositor = ctx.accounts.authority.key();
+        ctx.accounts
+            .distributor_state
+            .record_trigger(depositor, balance_before, ctx.accounts.vault.amount)?;
+        emit_cpi!(DepositEvent {
+            depositor: ctx.accounts.authority.key(),
+            amount,
+            vault_balance: ctx.accounts.vault.amount,
+        });
+
+        Ok(())
+    }
+
+    /// `deposit` of a native mint distributor: wraps `lamports` of the authority into the vault, so the depositor
+    /// doesn't need a wrapped SOL account
+    pub fn deposit_native(ctx: Context<DepositNative>, lamports: u64) -> Result<()> {
+        ctx.accounts
+            .distributor_state
+            .check_depositor(ctx.accounts.deposit_allowance.as_ref())?;
+        require_gte!(
+            lamports,
+            ctx.accounts.distributor_state.min_deposit,
+            DistributorError::DepositTooSmall
+        );
+        let balance_before = ctx.accounts.vault.amount;
+        system_program::transfer(
+            CpiContext::new(
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.authority.to_account_info(),
+                    to: ctx.accounts.vault.to_account_info(),
+                },
+            ),
+            lamports,
+        )?;
+        token_interface::sync_native(CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            SyncNative {
+                account: ctx.accounts.vault.to_account_info(),
+            },
+        ))?;
+
+        if let Some(record) = &mut ctx.accounts.depositor_record {
+            record.record_deposit(lamports, ctx.accounts.distributor_state.round)?;
+        }
+
+        ctx.accounts.vault.reload()?;
+        let depositor = ctx.accounts.authority.key();
+        ctx.accounts
+            .distributor_state
+            .record_trigger(depositor, balance_before, ctx.accounts.vault.amount)?;
+        emit_cpi!(DepositEvent {
+            depositor: ctx.accounts.authority.key(),
+            amount: lamports,
+            vault_balance: ctx.accounts.vault.amount,
+        });
+
+        Ok(())
+    }
+
+    /// Pulls `amount` from a token account which approved the distributor state as its delegate, `None` pulls the
+    /// whole delegated amount. Anyone can crank the pull, so programs which can only approve a delegate can route
+    /// their revenue to the distributor. The deposit is credited to the owner of the token account
+    pub fn deposit_with_delegate<'c: 'info, 'info>(
+        ctx: Context<'_, '_, 'c, 'info, DepositWithDelegate<'info>>,
+        amount: Option<u64>,
+    ) -> Result<()> {
+        ctx.accounts
+            .distributor_state
+            .check_depositor(ctx.accounts.deposit_allowance.as_ref())?;
+        let amount = amount.unwrap_or(ctx.accounts.token_account.delegated_amount);
+        require_gt!(amount, 0, DistributorError::NotDelegated);
+        require_gte!(
+            ctx.accounts.token_account.delegated_amount,
+            amount,
+            DistributorError::NotDelegated
+        );
+        require_gte!(
+            amount,
+            ctx.accounts.distributor_state.min_deposit,
+            DistributorError::DepositTooSmall
+        );
+
+        let balance_before = ctx.accounts.vault.amount;
+        let mint = ctx.accounts.mint.key();
+        let mint_marker = ctx.accounts.distributor_state.marker_mint;
+        let share_size = ctx.accounts.distributor_state.initial_share_size.to_le_bytes();
+        let number_of_shares = ctx.accounts.distributor_state.initial_number_of_shares.to_le_bytes();
+        let seeds = [
+            mint.as_ref(),
+            mint_marker.as_ref(),
+            share_size.as_ref(),
+            number_of_shares.as_ref(),
+            &[ctx.accounts.distributor_state.distributor_state_bump],
+        ];
+        transfer_checked_with_hook(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.token_account.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    to: ctx.accounts.vault.to_account_info(),
+                    authority: ctx.accounts.distributor_state.to_account_info(),
+                },
+                &[&seeds],
+            )
+            .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+            amount,
+            ctx.accounts.mint.decimals,
+        )?;
+
+        if let Some(record) = &mut ctx.accounts.depositor_record {
+            record.record_deposit(amount, ctx.accounts.distributor_state.round)?;
+        }
+
+        ctx.accounts.vault.reload()?;
+        let depositor = ctx.accounts.token_account.owner;
+        ctx.accounts
+            .distributor_state
+            .record_trigger(depositor, balance_before, ctx.accounts.vault.amount)?;
+        emit_cpi!(DepositEvent {
+            depositor: ctx.accounts.token_account.owner,
+            amount,
+            vault_balance: ctx.accounts.vault.amount,
+        });
+
+        Ok(())
+    }
+
+    /// `notes` are optional caller data per winner (e.g. ticket ids), either empty or one per winner in the
+    /// remaining accounts order, they are only emitted in `DistributeEvent`. The transfer hook accounts of a
+    /// Token-2022 mint follow the winner accounts, see `client::transfer_hook_accounts`
+    pub fn distribute<'c: 'info, 'info>(
+        ctx: Context<'_, '_, 'c, 'info, Distribute<'info>>,
+        notes: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        distribute_shares(ctx, notes, None)
+    }
+
+    /// Tiered variant of `distribute`: every winner gets its own amount, `amounts` are in the remaining accounts
+    /// order. There is no terminal share, the amounts can use up to the whole vault balance
+    pub fn distribute_weighted<'c: 'info, 'info>(
+        ctx: Context<'_, '_, 'c, 'info, Distribute<'info>>,
+        amounts: Vec<u64>,
+        notes: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        distribute_amounts(ctx, amounts, notes, None, 0)
+    }
+
+    /// Versioned `distribute`: the arguments are a struct, so they can grow without breaking the clients of the
+    /// legacy `distribute` and `distribute_weighted`. A retry which lands after the round it was built for is
+    /// rejected by `expected_round`. `weights` split the paid shares of the round in their proportion, the
+    /// amounts are rounded by the rounding policy of the distributor. `pro_rata` splits them the same way by the
+    /// marker balances of the winners, read from their marker accounts
+    pub fn distribute_v2<'c: 'info, 'info>(
+        ctx: Context<'_, '_, 'c, 'info, Distribute<'info>>,
+        args: DistributeArgs,
+    ) -> Result<()> {
+        let DistributeArgs {
+            expected_round,
+            amounts,
+            notes,
+            snapshot_slot,
+            weights,
+            pro_rata,
+        } = args;
+        require_eq!(
+            ctx.accounts.distributor_state.round,
+            expected_round,
+            DistributorError::RoundMismatch
+        );
+        let weights = if pro_rata {
+            require!(
+                amounts.is_none() && weights.is_none(),
+                DistributorError::InvalidParameters
+            );
+            Some(marker_weights(&ctx.accounts.distributor_state, ctx.remaining_accounts)?)
+        } else {
+            weights
+        };
+        match (amounts, weights) {
+            (Some(_), Some(_)) => err!(DistributorError::InvalidParameters),
+            (Some(amounts), None) => distribute_amounts(ctx, amounts, notes, snapshot_slot, 0),
+            (None, Some(weights)) => {
+                let (amounts, rounding_remainder) = ctx.accounts.distributor_state.split_weighted(&weights)?;
+                distribute_amounts(ctx, amounts, notes, snapshot_slot, rounding_remainder)
+            },
+            (None, None) => distribute_shares(ctx, notes, snapshot_slot),
+        }
+    }
+
+    /// Round of the dividend mode: the vault balance is split over the holders passed in the remaining accounts by
+    /// their marker balances, read from their marker accounts, instead of paying equal shares to drawn winners. The
+    /// accounts are the ones of `distribute`, the number of the holders is the paid shares. There is no terminal
+    /// share, the whole balance is paid once the threshold is met
+    pub fn distribute_dividend<'c: 'info, 'info>(
+        ctx: Context<'_, '_, 'c, 'info, Distribute<'info>>,
+        expected_round: u64,
+    ) -> Result<()> {
+        let distributor_state = &ctx.accounts.distributor_state;
+        require_eq!(distributor_state.round, expected_round, DistributorError::RoundMismatch);
+        // the holders aren't drawn, there are no winners to commit
+        require!(
+            distributor_state.distribution_mode == DistributionMode::Dividend
+                && !distributor_state.requires_committed_draw
+                && !distributor_state.requires_vrf_draw,
+            DistributorError::WrongDistributionMode
+        );
+        let vault_amount = ctx.accounts.vault.amount;
+        require_gte!(
+            vault_amount,
+            distributor_state.threshold()?,
+            DistributorError::ThresholdNotMet
+        );
+        let weights = marker_weights(distributor_state, ctx.remaining_accounts)?;
+        let (amounts, rounding_remainder) = distributor_state.split_dividend(vault_amount, &weights)?;
+        distribute_amounts(ctx, amounts, vec![], None, rounding_remainder)
+    }
+
+    /// Read-only dry run of the winner checks of `distribute`: takes the same remaining accounts and returns a
+    /// bitmap, bit `i % 8` of byte `i / 8` is set if the winner `i` would be paid. `amounts` are the ones of
+    /// `distribute_weighted`, the share size is used for every winner if not passed. Meant to be simulated by the
+    /// backend before it sends the round, a missing blocklist or previous round record fails the whole call
+    pub fn validate_winners<'c: 'info, 'info>(
+        ctx: Context<'_, '_, 'c, 'info, ValidateWinners<'info>>,
+        amounts: Option<Vec<u64>>,
+    ) -> Result<Vec<u8>> {
+        let distributor_state = &ctx.accounts.distributor_state;
+        let amounts =
+            amounts.unwrap_or_else(|| vec![distributor_state.share_size; distributor_state.paid_shares() as usize]);
+        let accounts_per_winner = accounts_per_winner(distributor_state);
+        require_gte!(
+            ctx.remaining_accounts.len(),
+            amounts.len() * accounts_per_winner,
+            DistributorError::MissingRemainingAccounts
+        );
+        if ctx.accounts.blocklist.is_none() {
+            require_eq!(
+                distributor_state.blocked_recipients,
+                0,
+                DistributorError::MissingBlocklist
+            );
+        }
+        let no_repeat_winners = distributor_state
+            .no_repeat_winners_since
+            .is_some_and(|since| distributor_state.round > since);
+        require!(
+            !no_repeat_winners || ctx.accounts.previous_round.is_some(),
+            DistributorError::MissingPreviousRound
+        );
+
+        let mut bitmap = vec![0u8; amounts.len().div_ceil(8)];
+        let mut wallets = Vec::with_capacity(amounts.len());
+        for (index, (accounts, amount)) in ctx
+            .remaining_accounts
+            .chunks_exact(accounts_per_winner)
+            .zip(amounts)
+            .enumerate()
+        {
+            // only the first share of a wallet passes, `distribute` rejects the duplicates
+            let duplicate = wallets.contains(&accounts[0].key);
+            wallets.push(accounts[0].key);
+            if !duplicate && is_valid_winner(ctx.accounts, accounts, amount) {
+                bitmap[index / 8] |= 1 << (index % 8);
+            }
+        }
+
+        Ok(bitmap)
+    }
+
+    /// Pull alternative to `distribute`: the winners shares are moved to an escrow and only the Merkle root of
+    /// (index, winner, amount) leaves is committed, every winner claims the share with a proof
+    pub fn distribute_claims<'c: 'info, 'info>(
+        ctx: Context<'_, '_, 'c, 'info, DistributeClaims<'info>>,
+        merkle_root: [u8; 32],
+    ) -> Result<()> {
+        let vault_amount = ctx.accounts.vault.amount;
+        let threshold = ctx.accounts.distributor_state.threshold()?;
+        require_gte!(vault_amount, threshold, DistributorError::ThresholdNotMet);
+        let now = Clock::get()?.unix_timestamp;
+        ctx.accounts.distributor_state.check_interval(now)?;
+        ctx.accounts.distributor_state.check_rounds_left(1)?;
+        ctx.accounts.distributor_state.check_cosigners(ctx.remaining_accounts)?;
+        // the claimants are only committed by the root, they can't be bound to a holders snapshot or be checked
+        // against the winners of a committed or a VRF draw
+        require!(
+            !ctx.accounts.distributor_state.requires_holder_snapshot
+                && !ctx.accounts.distributor_state.requires_committed_draw
+                && !ctx.accounts.distributor_state.requires_vrf_draw,
+            DistributorError::WrongDistributionMode
+        );
+        require!(
+            !ctx.accounts.distributor_state.requires_round_record() || ctx.accounts.round.is_some(),
+            DistributorError::MissingRoundRecord
+        );
+
+        let mint = ctx.accounts.mint.key();
+        let mint_marker = ctx.accounts.distributor_state.marker_mint;
+        let share_size = ctx.accounts.distributor_state.initial_share_size.to_le_bytes();
+        let number_of_shares = ctx.accounts.distributor_state.initial_number_of_shares.to_le_bytes();
+
+        let seeds = [
+            mint.as_ref(),
+            mint_marker.as_ref(),
+            share_size.as_ref(),
+            number_of_shares.as_ref(),
+            &[ctx.accounts.distributor_state.distributor_state_bump],
+        ];
+
+        let claimants = ctx.accounts.distributor_state.paid_shares();
+        let total_amount = shares_amount(ctx.accounts.distributor_state.share_size, claimants)?;
+        transfer_checked_with_hook(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.vault.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    to: ctx.accounts.escrow.to_account_info(),
+                    authority: ctx.accounts.distributor_state.to_account_info(),
+                },
+                &[&seeds],
+            )
+            .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+            total_amount,
+            ctx.accounts.mint.decimals,
+        )?;
+
+        let protocol_fee = pay_protocol_fee(
+            &ctx.accounts.distributor_state,
+            ctx.accounts.distributor_state.protocol_fee()?,
+            &ctx.accounts.mint,
+            &ctx.accounts.vault,
+            ctx.accounts.fee_account.as_ref(),
+            &ctx.accounts.token_program,
+            ctx.remaining_accounts,
+            &seeds,
+        )?;
+        pay_lamport_fee(
+            &ctx.accounts.distributor_state,
+            &ctx.accounts.payer,
+            ctx.accounts.fee_vault.as_mut(),
+            &ctx.accounts.system_program,
+        )?;
+        let terminal_share = ctx
+            .accounts
+            .distributor_state
+            .terminal_amount()?
+            .checked_sub(protocol_fee)
+            .ok_or(DistributorError::InvalidProtocolFee)?;
+        let (burn, treasury, rollover) = dispose_terminal_share(
+            &ctx.accounts.distributor_state,
+            terminal_share,
+            &ctx.accounts.mint,
+            &ctx.accounts.vault,
+            ctx.accounts.treasury.as_ref(),
+            &ctx.accounts.token_program,
+            ctx.remaining_accounts,
+            &seeds,
+        )?;
+        pay_trigger_bonus(
+            &ctx.accounts.distributor_state,
+            &mut ctx.accounts.vault,
+            ctx.accounts.trigger_bonus_account.as_ref(),
+            &ctx.accounts.mint,
+            &ctx.accounts.token_program,
+            ctx.remaining_accounts,
+            &seeds,
+        )?;
+
+        let distributor_state = &mut ctx.accounts.distributor_state;
+        let round = distributor_state.round;
+        distributor_state.round += 1;
+        distributor_state.last_round_at = now;
+        distributor_state.last_depositor = None;
+        distributor_state.open_claim_rounds += 1;
+
+        let holders_count = drawn_entrants(ctx.accounts.draw.as_ref())?;
+        if let Some(round_account) = &mut ctx.accounts.round {
+            round_account.set_inner(DistributionRound {
+                distributor_state: distributor_state.key(),
+                round,
+                timestamp: now,
+                payouts: vec![],
+                burned: burn,
+                treasury,
+                rollover,
+                bump: ctx.bumps.round,
+                holders_count,
+                snapshot_root: None,
+            });
+        }
+
+        let expires_at = now
+            .checked_add(distributor_state.claim_period)
+            .ok_or(DistributorError::ArithmeticOverflow)?;
+        ctx.accounts.claim_round.set_inner(ClaimRound {
+            distributor_state: distributor_state.key(),
+            round,
+            merkle_root,
+            escrow: ctx.accounts.escrow.key(),
+            payer: ctx.accounts.payer.key(),
+            claimants,
+            total_amount,
+            claimed_amount: 0,
+            referral_bps: distributor_state.referral_bps,
+            referral_amount: 0,
+            expires_at,
+            bump: ctx.bumps.claim_round,
+            claimed: vec![0; ClaimRound::bitmap_len(claimants)],
+        });
+
+        emit_cpi!(ClaimsCommittedEvent {
+            round,
+            merkle_root,
+            claimants,
+            total_amount,
+            expires_at,
+            burned: burn,
+            treasury,
+            rollover,
+            holders_count,
+            protocol_fee,
+        });
+
+        Ok(())
+    }
+
+    /// Pays the claim to a token account of the claimant, a passed `referral` gets `referral_bps` of it instead.
+    /// A wallet blocked after the round was published can't claim its leaf, neither can a winner of the previous
+    /// round while the previous winners can't win again. The claimant is added to the payouts of the round record
+    pub fn claim<'c: 'info, 'info>(
+        ctx: Context<'_, '_, 'c, 'info, Claim<'info>>,
+        index: u64,
+        amount: u64,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        let claim_round = &mut ctx.accounts.claim_round;
+        require_gt!(
+            claim_round.expires_at,
+            Clock::get()?.unix_timestamp,
+            DistributorError::ClaimExpired
+        );
+        require_gt!(claim_round.claimants, index, DistributorError::InvalidProof);
+        require!(!claim_round.is_claimed(index), DistributorError::AlreadyClaimed);
+        match &ctx.accounts.blocklist {
+            Some(blocklist) => require!(
+                blocklist.wallets.binary_search(ctx.accounts.claimant.key).is_err(),
+                DistributorError::RecipientBlocked
+            ),
+            None => require_eq!(
+                ctx.accounts.distributor_state.blocked_recipients,
+                0,
+                DistributorError::MissingBlocklist
+            ),
+        }
+        // the rule records every round since it was enabled
+        let no_repeat_winners_since = ctx.accounts.distributor_state.no_repeat_winners_since;
+        let recorded = no_repeat_winners_since.is_some_and(|since| claim_round.round >= since);
+        require!(
+            !recorded || ctx.accounts.round.is_some(),
+            DistributorError::MissingRoundRecord
+        );
+        if no_repeat_winners_since.is_some_and(|since| claim_round.round > since) {
+            let Some(previous_round) = &ctx.accounts.previous_round else {
+                return err!(DistributorError::MissingPreviousRound);
+            };
+            require!(
+                !previous_round
+                    .payouts
+                    .iter()
+                    .any(|payout| payout.wallet == *ctx.accounts.claimant.key),
+                DistributorError::RepeatWinner
+            );
+        }
+
+        let leaf = merkle::leaf(index, ctx.accounts.claimant.key, amount);
+        require!(
+            merkle::verify(&proof, &claim_round.merkle_root, leaf),
+            DistributorError::InvalidProof
+        );
+
+        // A wrong root must not let claimants drain more than the round escrowed
+        claim_round.claimed_amount = claim_round
+            .claimed_amount
+            .checked_add(amount)
+            .filter(|claimed| *claimed <= claim_round.total_amount)
+            .ok_or(DistributorError::InvalidProof)?;
+        claim_round.set_claimed(index);
+        if let Some(round_account) = &mut ctx.accounts.round {
+            round_account.payouts.push(Payout {
+                wallet: ctx.accounts.claimant.key(),
+                amount,
+            });
+        }
+        let referral_amount = if ctx.accounts.referral.is_some() {
+            (amount as u128 * claim_round.referral_bps as u128 / 10_000) as u64
+        } else {
+            0
+        };
+        claim_round.referral_amount += referral_amount;
+
+        let distributor_state = claim_round.distributor_state;
+        let round = claim_round.round.to_le_bytes();
+        let seeds = [ClaimRound::SEED_PREFIX, distributor_state.as_ref(), round.as_ref(), &[
+            claim_round.bump,
+        ]];
+        if let Some(referral) = &ctx.accounts.referral {
+            if referral_amount > 0 {
+                transfer_checked_with_hook(
+                    CpiContext::new_with_signer(
+                        ctx.accounts.token_program.to_account_info(),
+                        TransferChecked {
+                            from: ctx.accounts.escrow.to_account_info(),
+                            mint: ctx.accounts.mint.to_account_info(),
+                            to: referral.to_account_info(),
+                            authority: ctx.accounts.claim_round.to_account_info(),
+                        },
+                        &[&seeds],
+                    )
+                    .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+                    referral_amount,
+                    ctx.accounts.mint.decimals,
+                )?;
+            }
+        }
+        transfer_checked_with_hook(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                TransferChecked {
+                    from: ctx.accounts.escrow.to_account_info(),
+                    mint: ctx.accounts.mint.to_account_info(),
+                    to: ctx.accounts.token_account.to_account_info(),
+                    authority: ctx.accounts.claim_round.to_account_info(),
+                },
+                &[&seeds],
+            )
+            .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+            amount - referral_amount,
+            ctx.accounts.mint.decimals,
+        )
+    }
+
+    /// Disposes of the unclaimed shares of an expired round according to `unclaimed_policy`, the rent goes back
+    /// to the payer of the round
+    pub fn close_claims<'c: 'info, 'info>(ctx: Context<'_, '_, 'c, 'info, CloseClaims<'info>>) -> Result<()> {
+        let claim_round = &ctx.accounts.claim_round;
+        require_gte!(
+            Clock::get()?.unix_timestamp,
+            claim_round.expires_at,
+            DistributorError::ClaimsNotExpired
+        );
+
+        let distributor_state = claim_round.distributor_state;
+        let round = claim_round.round.to_le_bytes();
+        let seeds = [ClaimRound::SEED_PREFIX, distributor_state.as_ref(), round.as_ref(), &[
+            claim_round.bump,
+        ]];
+
+        let unclaimed = ctx.accounts.escrow.amount;
+        if unclaimed > 0 {
+            match ctx.accounts.distributor_state.unclaimed_policy {
+                UnclaimedPolicy::Burn => token_interface::burn(
+                    CpiContext::new_with_signer(
+                        ctx.accounts.token_program.to_account_info(),
+                        Burn {
+                            mint: ctx.accounts.mint.to_account_info(),
+                            from: ctx.accounts.escrow.to_account_info(),
+                            authority: ctx.accounts.claim_round.to_account_info(),
+                        },
+                        &[&seeds],
+                    ),
+                    unclaimed,
+                )?,
+                UnclaimedPolicy::ReturnToVault => transfer_checked_with_hook(
+                    CpiContext::new_with_signer(
+                        ctx.accounts.token_program.to_account_info(),
+                        TransferChecked {
+                            from: ctx.accounts.escrow.to_account_info(),
+                            mint: ctx.accounts.mint.to_account_info(),
+                            to: ctx.accounts.vault.to_account_info(),
+                            authority: ctx.accounts.claim_round.to_account_info(),
+                        },
+                        &[&seeds],
+                    )
+                    .with_remaining_accounts(ctx.remaining_accounts.to_vec()),
+                    unclaimed,
+                    ctx.accounts.mint.decimals,
+                )?,
+            }
+        }
+
+        token_interface::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: ctx.accounts.escrow.to_account_info(),
+                destination: ctx.accounts.payer.to_account_info(),
+                authority: ctx.accounts.claim_round.to_account_info(),
+            },
+            &[&seeds],
+        ))?;
+
+        let distributor_state = &mut ctx.accounts.distributor_state;
+        distributor_state.open_claim_rounds = distributor_state.open_claim_rounds.saturating_sub(1);
+        Ok(())
+    }
+
+    /// Starts a round of the chunked mode, the winners are paid by `distribute_chunk` and the round is completed by
+    /// `finalize_round`. The share parameters are fixed for the whole round at this point
+    pub fn begin_round(ctx: Context<BeginRound>) -> Result<()> {
+        let vault_amount = ctx.accounts.vault.amount;
+        let threshold = ctx.accounts.distributor_state.threshold()?;
+        require_gte!(vault_amount, threshold, DistributorError::ThresholdNotMet);
+
+        let distributor_state = &ctx.accounts.distributor_state;
+        distributor_state.check_interval(Clock::get()?.unix_timestamp)?;
+        distributor_state.check_rounds_left(1)?;
+        distributor_state.check_cosigners(ctx.remaining_accounts)?;
+        require!(
+            !distributor_state.requires_round_record() || ctx.accounts.round.is_some(),
+            DistributorError::MissingRoundRecord
+        );
+        require!(
+            !distributor_state.requires_committed_draw
+                && !distributor_state.requires_holder_snapshot
+                && !distributor_state.requires_vrf_draw,
+            DistributorError::WrongDistributionMode
+        );
+        pay_lamport_fee(
+            &ctx.accounts.distributor_state,
+            &ctx.accounts.payer,
+            ctx.accounts.fee_vault.as_mut(),
+            &ctx.accounts.system_program,
+        )?;
+        let distributor_state = &ctx.accounts.distributor_state;
+        ctx.accounts.round_progress.set_inner(RoundProgress {
+            distributor_state: distributor_state.key(),
+            round: distributor_state.round,
+            share_size: distributor_state.share_size,
+            number_of_shares: distributor_state.number_of_shares,
+            burn_shares: distributor_state.burn_shares,
+            paid: 0,
+            fees: 0,
+            payer: ctx.accounts.payer.key(),
+            bump: ctx.bumps.round_progress,
+        });
+        if let Some(round_account) = &mut ctx.accounts.round {
+            round_account.set_inner(DistributionRound {
+                distributor_state: distributor_state.key(),
+                round: distributor_state.round,
+                timestamp: Clock::get()?.unix_timestamp,
+                payouts: vec![],
+                burned: 0,
+                treasury: 0,
+                rollover: 0,
+                bump: ctx.bumps.round,
+                holders_count: None,
+                snapshot_root: None,
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Settles `rounds` rounds of `distribute` at once if the vault accumulated their thresholds. The remaining
+    /// accounts are the winners of every round one round after another, followed by the transfer hook accounts.
+    /// The rounds can't be recorded, drawn on-chain or exclude the previous winners, as these accounts are bound
+    /// to a single round. The minimal interval applies to the first round only
+    pub fn distribute_rounds<'c: 'info, 'info>(
+        mut ctx: Context<'_, '_, 'c, 'info, Distribute<'info>>,
+        rounds: u8,
+    ) -> Result<()> {
+        require_gt!(rounds, 0, DistributorError::InvalidParameters);
+        let distributor_state = &ctx.accounts.distributor_state;
+        let threshold = distributor_state.threshold_of_rounds(rounds as u64)?;
+        require_gte!(ctx.accounts.vault.amount, threshold, DistributorError::ThresholdNotMet);
+        distributor_state.check_interval(Clock::get()?.unix_timestamp)?;
+        distributor_state.check_rounds_left(rounds as u64)?;
+        distributor_state.check_cosigners(ctx.remaining_accounts)?;
+        require!(
+            !distributor_state.requires_round_record()
+                && !distributor_state.requires_committed_draw
+                && !distributor_state.requires_holder_snapshot
+                && !distributor_state.requires_vrf_draw
+                && distributor_state.no_repeat_winners_since.is_none()
+                && ctx.accounts.round.is_none()
+                && ctx.accounts.draw.is_none()
+                && ctx.accounts.holder_snapshot.is_none(),
+            DistributorError::WrongDistributionMode
+        );
+
+        let paid_shares = distributor_state.paid_shares() as usize;
+        let share_size = distributor_state.share_size;
+        let round_accounts = paid_shares * accounts_per_winner(distributor_state);
+        require_gte!(
+            ctx.remaining_accounts.len(),
+            round_accounts * rounds as usize,
+            DistributorError::MissingRemainingAccounts
+        );
+        let (winner_accounts, hook_accounts) = ctx.remaining_accounts.split_at(round_accounts * rounds as usize);
+        for winner_accounts in winner_accounts.chunks(round_accounts) {
+            let amounts = vec![share_size; paid_shares];
+            pay_round(&mut ctx, winner_accounts, hook_accounts, amounts, vec![], true, None, 0)?;
+        }
+        Ok(())
+    }
+
+    /// Pays the winners of the shares starting at `first_share`, which has to be the number of shares paid so far.
+    /// A chunk which already landed is rejected on a retry instead of being paid twice. The remaining accounts are
+    /// the same as of `distribute`, the hook accounts start with the hook program. `notes` are either empty or one per
+    /// winner of the chunk
+    pub fn distribute_chunk<'c: 'info, 'info>(
+        ctx: Context<'_, '_, 'c, 'info, DistributeChunk<'info>>,
+        first_share: u64,
+        notes: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        let progress = &ctx.accounts.round_progress;
+        require_eq!(first_share, progress.paid, DistributorError::ChunkOutOfOrder);
+        require!(
+            !ctx.accounts.distributor_state.requires_round_record() || ctx.accounts.round.is_some(),
+            DistributorError::MissingRoundRecord
+        );
+
+        let accounts_per_winner = accounts_per_winner(&ctx.accounts.distributor_state);
+        let (winner_accounts, hook_accounts) = split_at_hook_accounts(&ctx.accounts.mint, ctx.remaining_accounts)?;
+        let winners = (winner_accounts.len() / accounts_per_winner) as u64;
+        let remaining = progress
+            .paid_shares()
+            .checked_sub(progress.paid)
+            .ok_or(DistributorError::ArithmeticOverflow)?;
+        let winners = winners.min(remaining);
+        require_gt!(winners, 0, DistributorError::MissingRemainingAccounts);
+        require!(
+            notes.is_empty() || notes.len() as u64 == winners,
+            DistributorError::NotesMismatch
+        );
+        let winner_accounts = &winner_accounts[..winners as usize * accounts_per_winner];
+
+        let mint = ctx.accounts.mint.key();
+        let mint_marker = ctx.accounts.distributor_state.marker_mint;
+        let share_size = ctx.accounts.distributor_state.initial_share_size.to_le_bytes();
+        let number_of_shares = ctx.accounts.distributor_state.initial_number_of_shares.to_le_bytes();
+        let seeds = [
+            mint.as_ref(),
+            mint_marker.as_ref(),
+            share_size.as_ref(),
+            number_of_shares.as_ref(),
+            &[ctx.accounts.distributor_state.distributor_state_bump],
+        ];
+
+        let amounts = vec![progress.share_size; winners as usize];
+        let ata_rent = Cell::new(0);
+        let (payouts, delivered, fees) = pay_shares(
+            PayoutAccounts {
+                payer: &ctx.accounts.payer,
+                distributor_state: &ctx.accounts.distributor_state,
+                mint: &ctx.accounts.mint,
+                vault: &ctx.accounts.vault,
+                system_program: &ctx.accounts.system_program,
+                token_program: &ctx.accounts.token_program,
+                associated_token_program: &ctx.accounts.associated_token_program,
+                native_unwrap: ctx.accounts.native_unwrap.as_ref(),
+                native_unwrap_bump: ctx.bumps.native_unwrap,
+                treasury: None,
+                blocklist: ctx.accounts.blocklist.as_ref(),
+                previous_round: ctx.accounts.previous_round.as_ref(),
+                vesting_escrow: ctx.accounts.vesting_escrow.as_ref(),
+                ata_rent: &ata_rent,
+            },
+            winner_accounts,
+            hook_accounts,
+            amounts,
+            &seeds,
+        )?;
+        reimburse_ata_rent(&mut ctx.accounts.distributor_state, &ctx.accounts.payer, ata_rent.get())?;
+
+        let progress = &mut ctx.accounts.round_progress;
+        progress.paid += winners;
+        progress.fees += fees;
+        if let Some(round_account) = &mut ctx.accounts.round {
+            round_account.payouts.extend_from_slice(&payouts);
+        }
+
+        emit_cpi!(DistributeChunkEvent {
+            round: progress.round,
+            first_share,
+            winners: payouts.iter().map(|payout| payout.wallet).collect(),
+            amounts: payouts.iter().map(|payout| payout.amount).collect(),
+            delivered,
+            notes,
+            fees,
+        });
+
+        Ok(())
+    }
+
+    /// Completes the chunked round once every share is paid: disposes of the terminal share, advances the round
+    /// and returns the rent of the progress account to its payer
+    pub fn finalize_round<'c: 'info, 'info>(ctx: Context<'_, '_, 'c, 'info, FinalizeRound<'info>>) -> Result<()> {
+        let progress = &ctx.accounts.round_progress;
+        require_eq!(
+            progress.paid,
+            progress.paid_shares(),
+            DistributorError::RoundNotComplete
+        );
+
+        let mint = ctx.accounts.mint.key();
+        let mint_marker = ctx.accounts.distributor_state.marker_mint;
+        let share_size = ctx.accounts.distributor_state.initial_share_size.to_le_bytes();
+        let number_of_shares = ctx.accounts.distributor_state.initial_number_of_shares.to_le_bytes();
+        let seeds = [
+            mint.as_ref(),
+            mint_marker.as_ref(),
+            share_size.as_ref(),
+            number_of_shares.as_ref(),
+            &[ctx.accounts.distributor_state.distributor_state_bump],
+        ];
+
+        // the round is charged for the shares fixed by `begin_round`
+        let protocol_fee = pay_protocol_fee(
+            &ctx.accounts.distributor_state,
+            ctx.accounts
+                .distributor_state
+                .protocol_fee_of(shares_amount(progress.share_size, progress.number_of_shares)?)?,
+            &ctx.accounts.mint,
+            &ctx.accounts.vault,
+            ctx.accounts.fee_account.as_ref(),
+            &ctx.accounts.token_program,
+            ctx.remaining_accounts,
+            &seeds,
+        )?;
+        let terminal_share = shares_amount(progress.share_size, progress.burn_shares)?
+            .checked_sub(protocol_fee)
+            .ok_or(DistributorError::InvalidProtocolFee)?;
+        let (burn, treasury, rollover) = dispose_terminal_share(
+            &ctx.accounts.distributor_state,
+            terminal_share,
+            &ctx.accounts.mint,
+            &ctx.accounts.vault,
+            ctx.accounts.treasury.as_ref(),
+            &ctx.accounts.token_program,
+            ctx.remaining_accounts,
+            &seeds,
+        )?;
+        let trigger_bonus = pay_trigger_bonus(
+            &ctx.accounts.distributor_state,
+            &mut ctx.accounts.vault,
+            ctx.accounts.trigger_bonus_account.as_ref(),
+            &ctx.accounts.mint,
+            &ctx.accounts.token_program,
+            ctx.remaining_accounts,
+            &seeds,
+        )?;
+        let fees = progress.fees
+            + transfer_fee(&ctx.accounts.mint, treasury)?
+            + transfer_fee(&ctx.accounts.mint, protocol_fee)?
+            + transfer_fee(&ctx.accounts.mint, trigger_bonus)?;
+
+        let now = Clock::get()?.unix_timestamp;
+        let holders_count = drawn_entrants(ctx.accounts.draw.as_ref())?;
+        let distributor_state = &mut ctx.accounts.distributor_state;
+        let round = distributor_state.round;
+        distributor_state.round += 1;
+        distributor_state.last_round_at = now;
+        distributor_state.last_depositor = None;
+
+        if let Some(round_account) = &mut ctx.accounts.round {
+            round_account.timestamp = now;
+            round_account.burned = burn;
+            round_account.treasury = treasury;
+            round_account.rollover = rollover;
+            round_account.holders_count = holders_count;
+        }
+
+        emit_cpi!(RoundFinalizedEvent {
+            round,
+            shares: progress.paid,
+            share_size: progress.share_size,
+            burned: burn,
+            treasury,
+            rollover,
+            fees,
+            holders_count,
+            protocol_fee,
+        });
+
+        Ok(())
+    }
+
+    /// Binds the current round to the VRF randomness request of `seed`, the request itself is sent to the VRF
+    /// program earlier in the same transaction. A request which is already fulfilled is rejected, its randomness
+    /// would be known before the round is bound to it. `entrants` is the number of entrants the winner indices are
+    /// drawn from, the holders count of the snapshot. It is recorded in the round record and the events of the
+    /// round, so anyone can check it against the published snapshot. While the distributor requires a VRF draw,
+    /// the holders snapshot of the round has to be committed first and `entrants` has to be its holders count
+    pub fn request_draw(ctx: Context<RequestDraw>, seed: [u8; 32], entrants: u64) -> Result<()> {
+        let distributor_state = &ctx.accounts.distributor_state;
+        require_gte!(
+            entrants,
+            distributor_state.paid_shares(),
+            DistributorError::InvalidParameters
+        );
+        if distributor_state.requires_vrf_draw {
+            let Some(holder_snapshot) = &ctx.accounts.holder_snapshot else {
+                return err!(DistributorError::MissingHolderSnapshot);
+            };
+            require_eq!(
+                entrants,
+                holder_snapshot.holders_count,
+                DistributorError::SnapshotMismatch
+            );
+        }
+        {
+            let data = ctx.accounts.randomness.try_borrow_data()?;
+            verify_randomness_pending(&data, &seed)?;
+        }
+
+        ctx.accounts.draw.set_inner(Draw {
+            distributor_state: ctx.accounts.distributor_state.key(),
+            round: ctx.accounts.distributor_state.round,
+            seed,
+            randomness: ctx.accounts.randomness.key(),
+            entrants,
+            value: None,
+            bump: ctx.bumps.draw,
+            winners_number: ctx.accounts.distributor_state.paid_shares(),
+            winners: Vec::new(),
+        });
+
+        Ok(())
+    }
+
+    /// Copies the fulfilled randomness to the draw, the winner indices are `draw::DrawIndices` of it
+    pub fn reveal_draw(ctx: Context<RevealDraw>) -> Result<()> {
+        let draw = &mut ctx.accounts.draw;
+        require!(draw.value.is_none(), DistributorError::DrawAlreadyRevealed);
+
+        let value = {
+            let data = ctx.accounts.randomness.try_borrow_data()?;
+            vrf_randomness(&data, &draw.seed)?
+        };
+        draw.value = Some(value);
+
+        emit!(DrawRevealedEvent {
+            round: draw.round,
+            randomness: value,
+            entrants: draw.entrants,
+        });
+
+        Ok(())
+    }
+
+    /// Commits the marker holders snapshot the draw of the current round is based on: `merkle_root` is the root of
+    /// the `holders_count` holders at `slot`, see `merkle::snapshot_leaf`. The snapshot of a round can't be
+    /// replaced, so anyone can dispute a draw against it
+    pub fn commit_snapshot(
+        ctx: Context<CommitSnapshot>,
+        merkle_root: [u8; 32],
+        holders_count: u64,
+        slot: u64,
+    ) -> Result<()> {
+        let distributor_state = &ctx.accounts.distributor_state;
+        require_gte!(
+            holders_count,
+            distributor_state.paid_shares(),
+            DistributorError::InvalidParameters
+        );
+        require_gte!(Clock::get()?.slot, slot, DistributorError::InvalidParameters);
+
+        ctx.accounts.holder_snapshot.set_inner(HolderSnapshot {
+            distributor_state: distributor_state.key(),
+            round: distributor_state.round,
+            merkle_root,
+            holders_count,
+            slot,
+            bump: ctx.bumps.holder_snapshot,
+        });
+
+        emit!(SnapshotCommittedEvent {
+            round: distributor_state.round,
+            merkle_root,
+            holders_count,
+            slot,
+        });
+
+        Ok(())
+    }
+
+    /// Commits the authority to the draw of the current round before its threshold is reached: `commitment` is the
+    /// hash of the seed revealed later by `reveal_committed_draw`, `snapshot_root` is the Merkle root of the
+    /// `entrants` holders the winners are drawn from, see `merkle::snapshot_leaf`
+    pub fn commit_draw(
+        ctx: Context<CommitDraw>,
+        commitment: [u8; 32],
+        snapshot_root: [u8; 32],
+        entrants: u64,
+    ) -> Result<()> {
+        let distributor_state = &ctx.accounts.distributor_state;
+        let winners = distributor_state.paid_shares();
+        require_gte!(entrants, winners, DistributorError::InvalidParameters);
+        require_gt!(
+            distributor_state.threshold()?,
+            ctx.accounts.vault.amount,
+            DistributorError::DrawCommittedLate
+        );
+
+        ctx.accounts.committed_draw.set_inner(CommittedDraw {
+            distributor_state: distributor_state.key(),
+            round: distributor_state.round,
+            commitment,
+            snapshot_root,
+            entrants,
+            seed: None,
+            winners_number: winners,
+            bump: ctx.bumps.committed_draw,
+            winners: Vec::new(),
+        });
+
+        Ok(())
+    }
+
+    /// Reveals the seed of the committed draw, the winner indices are `draw::DrawIndices` of
+    /// `CommittedDraw::randomness`
+    pub fn reveal_committed_draw(ctx: Context<RevealCommittedDraw>, seed: [u8; 32]) -> Result<()> {
+        let committed_draw = &mut ctx.accounts.committed_draw;
+        require!(committed_draw.seed.is_none(), DistributorError::DrawAlreadyRevealed);
+        require!(
+            hash(&seed).to_bytes() == committed_draw.commitment,
+            DistributorError::InvalidDrawSeed
+        );
+        committed_draw.seed = Some(seed);
+
+        emit!(DrawRevealedEvent {
+            round: committed_draw.round,
+            randomness: committed_draw.randomness().unwrap_or([0; 64]),
+            entrants: committed_draw.entrants,
+        });
+
+        Ok(())
+    }
+
+    /// Verifies the next winner of the revealed committed draw: `wallet` has to be the holder of the snapshot at
+    /// the next drawn index, `proof` is its Merkle proof. Anyone can verify the winners
+    pub fn verify_committed_winner(
+        ctx: Context<VerifyCommittedWinner>,
+        wallet: Pubkey,
+        proof: Vec<[u8; 32]>,
+    ) -> Result<()> {
+        let committed_draw = &mut ctx.accounts.committed_draw;
+        let Some(randomness) = committed_draw.randomness() else {
+            return err!(DistributorError::RandomnessNotFulfilled);
+        };
+        require_gt!(
+            committed_draw.winners_number,
+            committed_draw.winners.len() as u64,
+            DistributorError::DrawComplete
+        );
+
+        let index = DrawIndices::new(randomness, committed_draw.entrants)
+            .nth(committed_draw.winners.len())
+            .ok_or(DistributorError::DrawComplete)?;
+        require!(
+            merkle::verify(
+                &proof,
+                &committed_draw.snapshot_root,
+                merkle::snapshot_leaf(index, &wallet)
+            ),
+            DistributorError::InvalidProof
+        );
+        committed_draw.winners.push(wallet);
+
+        Ok(())
+    }
+
+    /// Verifies the next winner of the revealed VRF draw: `wallet` has to be the holder of the snapshot committed
+    /// for the round at the next drawn index, `proof` is its Merkle proof. Anyone can verify the winners
+    pub fn verify_draw_winner(ctx: Context<VerifyDrawWinner>, wallet: Pubkey, proof: Vec<[u8; 32]>) -> Result<()> {
+        let draw = &mut ctx.accounts.draw;
+        let holder_snapshot = &ctx.accounts.holder_snapshot;
+        let Some(randomness) = draw.value else {
+            return err!(DistributorError::RandomnessNotFulfilled);
+        };
+        require_gt!(
+            draw.winners_number,
+            draw.winners.len() as u64,
+            DistributorError::DrawComplete
+        );
+        require_eq!(
+            draw.entrants,
+            holder_snapshot.holders_count,
+            DistributorError::SnapshotMismatch
+        );
+
+        let index = DrawIndices::new(randomness, draw.entrants)
+            .nth(draw.winners.len())
+            .ok_or(DistributorError::DrawComplete)?;
+        require!(
+            merkle::verify(
+                &proof,
+                &holder_snapshot.merkle_root,
+                merkle::snapshot_leaf(index, &wallet)
+            ),
+            DistributorError::InvalidProof
+        );
+        draw.winners.push(wallet);
+
+        Ok(())
+    }
+}
+
+/// Pays `share_size` to every winner once the threshold is reached and disposes of the burned shares
+fn distribute_shares<'c: 'info, 'info>(
+    ctx: Context<'_, '_, 'c, 'info, Distribute<'info>>,
+    notes: Vec<[u8; 32]>,
+    snapshot_slot: Option<u64>,
+) -> Result<()> {
+    let vault_amount = ctx.accounts.vault.amount;
+    let threshold = ctx.accounts.distributor_state.threshold()?;
+    require_gte!(vault_amount, threshold, DistributorError::ThresholdNotMet);
+
+    let distributor_state = &ctx.accounts.distributor_state;
+    distributor_state.check_interval(Clock::get()?.unix_timestamp)?;
+    distributor_state.check_rounds_left(1)?;
+    let amounts = vec![distributor_state.share_size; distributor_state.paid_shares() as usize];
+    pay_winners(ctx, amounts, notes, true, snapshot_slot, 0)
+}
+
+/// Pays every winner its own amount out of the vault balance, there is no terminal share and the protocol fee is
+/// charged on top of the amounts. `rounding_remainder` is the remainder of a weighted split, see `RoundingPolicy`
+fn distribute_amounts<'c: 'info, 'info>(
+    ctx: Context<'_, '_, 'c, 'info, Distribute<'info>>,
+    amounts: Vec<u64>,
+    notes: Vec<[u8; 32]>,
+    snapshot_slot: Option<u64>,
+    rounding_remainder: i64,
+) -> Result<()> {
+    let total = amounts
+        .iter()
+        .try_fold(0u64, |total, amount| total.checked_add(*amount))
+        .and_then(|total| {
+            let protocol_fee = ctx.accounts.distributor_state.protocol_fee_of(total).ok()?;
+            total.checked_add(protocol_fee)
+        })
+        .ok_or(DistributorError::InsufficientVaultBalance)?;
+    require_gte!(
+        ctx.accounts.vault.amount,
+        total,
+        DistributorError::InsufficientVaultBalance
+    );
+    let distributor_state = &ctx.accounts.distributor_state;
+    distributor_state.check_interval(Clock::get()?.unix_timestamp)?;
+    distributor_state.check_rounds_left(1)?;
+    pay_winners(ctx, amounts, notes, false, snapshot_slot, rounding_remainder)
+}
+
+/// Pays the winners passed in the remaining accounts and finishes the round, the terminal share is disposed of
+/// only if `terminal_share` is set
+fn pay_winners<'c: 'info, 'info>(
+    mut ctx: Context<'_, '_, 'c, 'info, Distribute<'info>>,
+    amounts: Vec<u64>,
+    notes: Vec<[u8; 32]>,
+    terminal_share: bool,
+    snapshot_slot: Option<u64>,
+    rounding_remainder: i64,
+) -> Result<()> {
+    let paid_shares = ctx.accounts.distributor_state.paid_shares();
+    ctx.accounts.distributor_state.check_cosigners(ctx.remaining_accounts)?;
+    // There is have to be paid_shares * accounts_per_winner accounts for each share without the burned ones,
+    // followed by the transfer hook accounts of the mint if it has one
+    let winner_accounts = paid_shares as usize * accounts_per_winner(&ctx.accounts.distributor_state);
+    require_gte!(
+        ctx.remaining_accounts.len(),
+        winner_accounts,
+        DistributorError::MissingRemainingAccounts
+    );
+    let (winner_accounts, hook_accounts) = ctx.remaining_accounts.split_at(winner_accounts);
+    require_eq!(
+        amounts.len() as u64,
+        paid_shares,
+        DistributorError::MissingRemainingAccounts
+    );
+    require!(
+        notes.is_empty() || notes.len() as u64 == paid_shares,
+        DistributorError::NotesMismatch
+    );
+    require!(
+        !ctx.accounts.distributor_state.requires_round_record() || ctx.accounts.round.is_some(),
+        DistributorError::MissingRoundRecord
+    );
+    verify_committed_winners(
+        &ctx.accounts.distributor_state,
+        ctx.accounts.committed_draw.as_ref(),
+        winner_accounts,
+    )?;
+    verify_drawn_winners(
+        &ctx.accounts.distributor_state,
+        ctx.accounts.draw.as_ref(),
+        winner_accounts,
+    )?;
+    let snapshot_slot = verify_holder_snapshot(
+        &ctx.accounts.distributor_state,
+        ctx.accounts.holder_snapshot.as_ref(),
+        snapshot_slot,
+    )?;
+
+    pay_round(
+        &mut ctx,
+        winner_accounts,
+        hook_accounts,
+        amounts,
+        notes,
+        terminal_share,
+        snapshot_slot,
+        rounding_remainder,
+    )
+}
+
+/// Pays a single round to `winner_accounts` and moves the distributor to the next round. A positive
+/// `rounding_remainder` is burned if the rounding policy burns it
+#[allow(clippy::too_many_arguments)]
+fn pay_round<'c: 'info, 'info>(
+    ctx: &mut Context<'_, '_, 'c, 'info, Distribute<'info>>,
+    winner_accounts: &'info [AccountInfo<'info>],
+    hook_accounts: &'info [AccountInfo<'info>],
+    amounts: Vec<u64>,
+    notes: Vec<[u8; 32]>,
+    terminal_share: bool,
+    snapshot_slot: Option<u64>,
+    rounding_remainder: i64,
+) -> Result<()> {
+    let mint = ctx.accounts.mint.key();
+    let mint_marker = ctx.accounts.distributor_state.marker_mint;
+    let share_size = ctx.accounts.distributor_state.initial_share_size.to_le_bytes();
+    let number_of_shares = ctx.accounts.distributor_state.initial_number_of_shares.to_le_bytes();
+
+    let seeds = [
+        mint.as_ref(),
+        mint_marker.as_ref(),
+        share_size.as_ref(),
+        number_of_shares.as_ref(),
+        &[ctx.accounts.distributor_state.distributor_state_bump],
+    ];
+
+    let paid_total = amounts
+        .iter()
+        .try_fold(0u64, |total, amount| total.checked_add(*amount))
+        .ok_or(DistributorError::ArithmeticOverflow)?;
+    let ata_rent = Cell::new(0);
+    let (payouts, delivered, mut fees) = pay_shares(
+        PayoutAccounts {
+            payer: &ctx.accounts.payer,
+            distributor_state: &ctx.accounts.distributor_state,
+            mint: &ctx.accounts.mint,
+            vault: &ctx.accounts.vault,
+            system_program: &ctx.accounts.system_program,
+            token_program: &ctx.accounts.token_program,
+            associated_token_program: &ctx.accounts.associated_token_program,
+            native_unwrap: ctx.accounts.native_unwrap.as_ref(),
+            native_unwrap_bump: ctx.bumps.native_unwrap,
+            treasury: ctx.accounts.treasury.as_ref(),
+            blocklist: ctx.accounts.blocklist.as_ref(),
+            previous_round: ctx.accounts.previous_round.as_ref(),
+            vesting_escrow: ctx.accounts.vesting_escrow.as_ref(),
+            ata_rent: &ata_rent,
+        },
+        winner_accounts,
+        hook_accounts,
+        amounts,
+        &seeds,
+    )?;
+    reimburse_ata_rent(&mut ctx.accounts.distributor_state, &ctx.accounts.payer, ata_rent.get())?;
+    if rounding_remainder > 0 && ctx.accounts.distributor_state.rounding_policy == RoundingPolicy::FloorBurn {
+        token_interface::burn(
+            CpiContext::new_with_signer(
+                ctx.accounts.token_program.to_account_info(),
+                Burn {
+                    mint: ctx.accounts.mint.to_account_info(),
+                    from: ctx.accounts.vault.to_account_info(),
+                    authority: ctx.accounts.distributor_state.to_account_info(),
+                },
+                &[&seeds],
+            ),
+            rounding_remainder as u64,
+        )?;
+    }
+
+    // a round without a terminal share is charged on top of its payouts
+    let protocol_fee = if terminal_share {
+        ctx.accounts.distributor_state.protocol_fee()?
+    } else {
+        ctx.accounts.distributor_state.protocol_fee_of(paid_total)?
+    };
+    pay_protocol_fee(
+        &ctx.accounts.distributor_state,
+        protocol_fee,
+        &ctx.accounts.mint,
+        &ctx.accounts.vault,
+        ctx.accounts.fee_account.as_ref(),
+        &ctx.accounts.token_program,
+        hook_accounts,
+        &seeds,
+    )?;
+    fees += transfer_fee(&ctx.accounts.mint, protocol_fee)?;
+    pay_lamport_fee(
+        &ctx.accounts.distributor_state,
+        &ctx.accounts.payer,
+        ctx.accounts.fee_vault.as_mut(),
+        &ctx.accounts.system_program,
+    )?;
+
+    let (burn, treasury, rollover) = if terminal_share {
+        let terminal_share = ctx
+            .accounts
+            .distributor_state
+            .terminal_amount()?
+            .checked_sub(protocol_fee)
+            .ok_or(DistributorError::InvalidProtocolFee)?;
+        dispose_terminal_share(
+            &ctx.accounts.distributor_state,
+            terminal_share,
+            &ctx.accounts.mint,
+            &ctx.accounts.vault,
+            ctx.accounts.treasury.as_ref(),
+            &ctx.accounts.token_program,
+            hook_accounts,
+            &seeds,
+        )?
+    } else {
+        (0, 0, 0)
+    };
+    fees += transfer_fee(&ctx.accounts.mint, treasury)?;
+
+    let trigger_bonus = pay_trigger_bonus(
+        &ctx.accounts.distributor_state,
+        &mut ctx.accounts.vault,
+        ctx.accounts.trigger_bonus_account.as_ref(),
+        &ctx.accounts.mint,
+        &ctx.accounts.token_program,
+        hook_accounts,
+        &seeds,
+    )?;
+    fees += transfer_fee(&ctx.accounts.mint, trigger_bonus)?;
+
+    let now = Clock::get()?.unix_timestamp;
+    let holders_count = drawn_entrants(ctx.accounts.draw.as_ref())?
+        .or(ctx.accounts.committed_draw.as_ref().map(|draw| draw.entrants))
+        .or(ctx
+            .accounts
+            .holder_snapshot
+            .as_ref()
+            .map(|snapshot| snapshot.holders_count));
+    let snapshot_root = ctx
+        .accounts
+        .holder_snapshot
+        .as_ref()
+        .map(|snapshot| snapshot.merkle_root);
+    let distributor_state = &mut ctx.accounts.distributor_state;
+    let round = distributor_state.round;
+    distributor_state.round += 1;
+    distributor_state.last_round_at = now;
+    distributor_state.last_depositor = None;
+
+    let winners = payouts.iter().map(|payout| payout.wallet).collect();
+    let amounts = payouts.iter().map(|payout| payout.amount).collect();
+    if let Some(round_account) = &mut ctx.accounts.round {
+        round_account.set_inner(DistributionRound {
+            distributor_state: distributor_state.key(),
+            round,
+            timestamp: now,
+            payouts,
+            burned: burn,
+            treasury,
+            rollover,
+            bump: ctx.bumps.round,
+            holders_count,
+            snapshot_root,
+        });
+    }
+
+    emit_cpi!(DistributeEvent {
+        round,
+        winners,
+        amounts,
+        delivered,
+        notes,
+        share_size: distributor_state.share_size,
+        burned: burn,
+        treasury,
+        rollover,
+        fees,
+        holders_count,
+        protocol_fee,
+        snapshot_slot,
+        snapshot_root,
+        rounding_remainder,
+        trigger_bonus,
+    });
+
+    Ok(())
+}
+
+/// Accounts every payout of a share needs
+struct PayoutAccounts<'a, 'info> {
+    payer: &'a Signer<'info>,
+    distributor_state: &'a Account<'info, DistributorState>,
+    mint: &'a InterfaceAccount<'info, Mint>,
+    vault: &'a InterfaceAccount<'info, TokenAccount>,
+    system_program: &'a Program<'info, System>,
+    token_program: &'a Interface<'info, TokenInterface>,
+    associated_token_program: &'a Program<'info, AssociatedToken>,
+    native_unwrap: Option<&'a UncheckedAccount<'info>>,
+    native_unwrap_bump: u8,
+    /// Receives the skipped shares of frozen token accounts by the treasury policy
+    treasury: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+    blocklist: Option<&'a Account<'info, Blocklist>>,
+    previous_round: Option<&'a Account<'info, DistributionRound>>,
+    vesting_escrow: Option<&'a InterfaceAccount<'info, TokenAccount>>,
+    /// Rent the payer paid for the token accounts created by the payouts
+    ata_rent: &'a Cell<u64>,
+}
+
+/// Pays the payer back for the `rent` of the created token accounts as far as the rent buffer of the distributor
+/// covers it
+fn reimburse_ata_rent(distributor_state: &mut Account<DistributorState>, payer: &Signer, rent: u64) -> Result<()> {
+    let reimbursed = rent.min(distributor_state.rent_buffer);
+    if reimbursed > 0 {
+        distributor_state.rent_buffer -= reimbursed;
+        distributor_state.sub_lamports(reimbursed)?;
+        payer.add_lamports(reimbursed)?;
+    }
+    Ok(())
+}
+
+/// Authority and token account of every winner, followed by the marker token account if the marker balance is
+/// required or weighs a dividend and the `PayoutAcceptance` account of the authority if there is a large payout
+/// threshold. Native shares are paid to the authority itself, it has no token account. Vested shares and the large
+/// shares which weren't accepted take the `Vesting` account of the winner in place of the token account, the escrowed
+/// shares take its `EscrowedShare` account
+fn accounts_per_winner(distributor_state: &DistributorState) -> usize {
+    let payout_accounts = if distributor_state.is_native() { 1 } else { 2 };
+    payout_accounts
+        + distributor_state.required_marker_balance().is_some() as usize
+        + 2 * distributor_state.marker_collection.is_some() as usize
+        + distributor_state.large_payout_threshold.is_some() as usize
+}
+
+/// Position of the NFT token account of a winner among its accounts, the metadata account follows it
+fn collection_accounts_index(distributor_state: &DistributorState) -> usize {
+    let payout_accounts = if distributor_state.is_native() { 1 } else { 2 };
+    payout_accounts + distributor_state.required_marker_balance().is_some() as usize
+}
+
+/// Whether `payout_acceptance` is the opt-in of `authority` to the large payouts, a missing one isn't
+fn accepts_large_payouts(authority: &AccountInfo, payout_acceptance: &AccountInfo) -> bool {
+    if payout_acceptance.owner != &ID {
+        return false;
+    }
+    let Ok(data) = payout_acceptance.try_borrow_data() else {
+        return false;
+    };
+    PayoutAcceptance::try_deserialize(&mut &data[..])
+        .is_ok_and(|payout_acceptance| payout_acceptance.wallet == *authority.key)
+}
+
+/// Transfers `amounts` from the vault to the winners of `winner_accounts` creating their token accounts if
+/// needed. Returns the payouts, the amounts the winners received and the withheld transfer fees. Winners skipped
+/// for a frozen token account have a payout of zero
+fn pay_shares<'info>(
+    ctx: PayoutAccounts<'_, 'info>,
+    winner_accounts: &'info [AccountInfo<'info>],
+    hook_accounts: &[AccountInfo<'info>],
+    amounts: Vec<u64>,
+    seeds: &[&[u8]],
+) -> Result<(Vec<Payout>, Vec<u64>, u64)> {
+    verify_unique_recipients(ctx.distributor_state, winner_accounts)?;
+    verify_no_self_recipients(&ctx, winner_accounts)?;
+    verify_not_blocked(&ctx, winner_accounts)?;
+    verify_no_repeat_winners(&ctx, winner_accounts)?;
+    verify_collection_holders(ctx.distributor_state, winner_accounts)?;
+    if ctx.distributor_state.is_native() {
+        return pay_native_shares(ctx, winner_accounts, amounts, seeds);
+    }
+
+    let mint_marker = ctx.distributor_state.marker_mint;
+    let min_marker_balance = ctx.distributor_state.required_marker_balance();
+    let accounts_per_winner = accounts_per_winner(ctx.distributor_state);
+    let mut payouts = Vec::with_capacity(amounts.len());
+    let mut delivered = Vec::with_capacity(amounts.len());
+    let mut fees = 0u64;
+    let mut skipped = 0u64;
+    for (accounts, amount) in winner_accounts.chunks_exact(accounts_per_winner).zip(amounts) {
+        let authority = &accounts[0];
+        if let Some(min_marker_balance) = min_marker_balance {
+            verify_marker_balance(authority, &accounts[2], &mint_marker, min_marker_balance)?;
+        }
+
+        if let Some(claim_period) = ctx.distributor_state.escrow_claim_period {
+            let escrow_address =
+                escrowed_share_address(&ctx.distributor_state.key(), ctx.distributor_state.round, authority.key);
+            if *accounts[1].key == escrow_address.0 {
+                let fee = escrow_share(
+                    &ctx,
+                    authority,
+                    &accounts[1],
+                    hook_accounts,
+                    amount,
+                    claim_period,
+                    escrow_address.1,
+                    seeds,
+                )?;
+                fees += fee;
+                delivered.push(amount - fee);
+                payouts.push(Payout {
+                    wallet: *authority.key,
+                    amount,
+                });
+                continue;
+            }
+        }
+
+        // an unsolicited large share waits in the vesting escrow until the winner claims it
+        let unsolicited = ctx.distributor_state.large_payout_threshold.is_some_and(|threshold| {
+            amount > threshold && !accepts_large_payouts(authority, &accounts[accounts_per_winner - 1])
+        });
+        let vesting_duration = ctx.distributor_state.vesting_duration.or(unsolicited.then_some(0));
+        if let Some(duration) = vesting_duration {
+            let fee = lock_vested_share(&ctx, authority, &accounts[1], hook_accounts, amount, duration, seeds)?;
+            fees += fee;
+            delivered.push(amount - fee);
+            payouts.push(Payout {
+                wallet: *authority.key,
+                amount,
+            });
+            continue;
+        }
+
+        let Some(fee) = pay_token_share(&ctx, authority, &accounts[1], hook_accounts, amount, seeds)? else {
+            skipped += amount;
+            delivered.push(0);
+            payouts.push(Payout {
+                wallet: *authority.key,
+                amount: 0,
+            });
+            continue;
+        };
+        fees += fee;
+        delivered.push(amount - fee);
+        payouts.push(Payout {
+            wallet: *authority.key,
+            amount,
+        });
+    }
+    fees += dispose_skipped_shares(&ctx, skipped, hook_accounts, seeds)?;
+
+    Ok((payouts, delivered, fees))
+}
+
+/// Whether `distribute` would pay `amount` to the winner of `accounts`, the checks of `pay_shares` without the
+/// transfers
+fn is_valid_winner<'info>(ctx: &ValidateWinners<'info>, accounts: &'info [AccountInfo<'info>], amount: u64) -> bool {
+    let distributor_state = &ctx.distributor_state;
+    let authority = &accounts[0];
+    // the payer of `distribute` isn't known to the view
+    let own = [
+        distributor_state.key(),
+        distributor_state.vault,
+        distributor_state.distributor_authority,
+    ];
+    if own.contains(authority.key) {
+        return false;
+    }
+    if let Some(blocklist) = &ctx.blocklist {
+        if blocklist.wallets.binary_search(authority.key).is_ok() {
+            return false;
+        }
+    }
+    if let Some(previous_round) = &ctx.previous_round {
+        let no_repeat_winners = distributor_state
+            .no_repeat_winners_since
+            .is_some_and(|since| distributor_state.round > since);
+        if no_repeat_winners
+            && previous_round
+                .payouts
+                .iter()
+                .any(|payout| payout.wallet == *authority.key)
+        {
+            return false;
+        }
+    }
+    let payout_accounts = if distributor_state.is_native() { 1 } else { 2 };
+    if let Some(min_marker_balance) = distributor_state.required_marker_balance() {
+        let marker_account = &accounts[payout_accounts];
+        if verify_marker_balance(
+            authority,
+            marker_account,
+            &distributor_state.marker_mint,
+            min_marker_balance,
+        )
+        .is_err()
+        {
+            return false;
+        }
+    }
+    if let Some(collection) = distributor_state.marker_collection {
+        let index = collection_accounts_index(distributor_state);
+        if verify_collection_nft(authority, &accounts[index], &accounts[index + 1], &collection).is_err() {
+            return false;
+        }
+    }
+    if distributor_state.is_native() {
+        return authority.owner == &system_program::ID;
+    }
+    if distributor_state.escrow_claim_period.is_some()
+        && *accounts[1].key
+            == escrowed_share_address(&distributor_state.key(), distributor_state.round, authority.key).0
+    {
+        // a winner has at most one share per round, the address of a new escrowed share is free
+        return accounts[1].owner == &system_program::ID;
+    }
+
+    let unsolicited = distributor_state.large_payout_threshold.is_some_and(|threshold| {
+        amount > threshold && !accepts_large_payouts(authority, &accounts[accounts.len() - 1])
+    });
+    if distributor_state.vesting_duration.is_some() || unsolicited {
+        let (address, _) = Pubkey::find_program_address(
+            &[
+                Vesting::SEED_PREFIX,
+                distributor_state.key().as_ref(),
+                distributor_state.round.to_le_bytes().as_ref(),
+                authority.key.as_ref(),
+            ],
+            &ID,
+        );
+        return *accounts[1].key == address;
+    }
+
+    let token_account = &accounts[1];
+    let is_ata = *token_account.key
+        == get_associated_token_address_with_program_id(authority.key, &ctx.mint.key(), ctx.token_program.key);
+    if !is_ata && !distributor_state.allow_non_ata_recipients {
+        return false;
+    }
+    // a missing ATA is created by `distribute`
+    if is_ata && token_account.owner == &system_program::ID {
+        return true;
+    }
+    let Ok(token_account) = InterfaceAccount::<TokenAccount>::try_from(token_account) else {
+        return false;
+    };
+    token_account.mint == ctx.mint.key() && token_account.owner == *authority.key && !token_account.is_frozen()
+}
+
+/// Rejects winners which aren't the verified winners of the committed draw of the round in the same order, while
+/// the distributor requires a committed draw
+fn verify_committed_winners(
+    distributor_state: &DistributorState,
+    committed_draw: Option<&Account<CommittedDraw>>,
+    winner_accounts: &[AccountInfo],
+) -> Result<()> {
+    if !distributor_state.requires_committed_draw {
+        return Ok(());
+    }
+    let Some(committed_draw) = committed_draw else {
+        return err!(DistributorError::MissingCommittedDraw);
+    };
+    let wallets = winner_accounts
+        .chunks_exact(accounts_per_winner(distributor_state))
+        .map(|accounts| accounts[0].key);
+    require!(
+        committed_draw.winners.iter().eq(wallets),
+        DistributorError::InvalidCommittedWinner
+    );
+    Ok(())
+}
+
+/// Rejects winners which aren't the verified winners of the VRF draw of the round in the same order, while the
+/// distributor requires a VRF draw
+fn verify_drawn_winners(
+    distributor_state: &DistributorState,
+    draw: Option<&Account<Draw>>,
+    winner_accounts: &[AccountInfo],
+) -> Result<()> {
+    if !distributor_state.requires_vrf_draw {
+        return Ok(());
+    }
+    let Some(draw) = draw else {
+        return err!(DistributorError::MissingDraw);
+    };
+    let wallets = winner_accounts
+        .chunks_exact(accounts_per_winner(distributor_state))
+        .map(|accounts| accounts[0].key);
+    require!(draw.winners.iter().eq(wallets), DistributorError::InvalidDrawWinner);
+    Ok(())
+}
+
+/// Holders count the winners of the round were drawn from by its VRF draw, the draw has to be revealed. `None` for
+/// a round without a VRF draw
+fn drawn_entrants(draw: Option<&Account<Draw>>) -> Result<Option<u64>> {
+    let Some(draw) = draw else {
+        return Ok(None);
+    };
+    require!(draw.value.is_some(), DistributorError::RandomnessNotFulfilled);
+    Ok(Some(draw.entrants))
+}
+
+/// Binds the round to the holders snapshot committed for it, the snapshot is required while the distributor requires
+/// one. Returns the slot of the snapshot the winners were drawn from
+fn verify_holder_snapshot(
+    distributor_state: &DistributorState,
+    holder_snapshot: Option<&Account<HolderSnapshot>>,
+    snapshot_slot: Option<u64>,
+) -> Result<Option<u64>> {
+    let Some(holder_snapshot) = holder_snapshot else {
+        require!(
+            !distributor_state.requires_holder_snapshot,
+            DistributorError::MissingHolderSnapshot
+        );
+        return Ok(snapshot_slot);
+    };
+    require!(
+        snapshot_slot.is_none() || snapshot_slot == Some(holder_snapshot.slot),
+        DistributorError::SnapshotMismatch
+    );
+    Ok(Some(holder_snapshot.slot))
+}
+
+/// Rejects the round if a wallet is paid more than one share of it. Only the winners of a single call are compared,
+/// the chunks of a round are checked separately
+fn verify_unique_recipients(distributor_state: &DistributorState, winner_accounts: &[AccountInfo]) -> Result<()> {
+    let mut wallets = winner_accounts
+        .chunks_exact(accounts_per_winner(distributor_state))
+        .map(|accounts| accounts[0].key)
+        .collect::<Vec<_>>();
+    wallets.sort_unstable();
+    if let Some(wallets) = wallets.windows(2).find(|wallets| wallets[0] == wallets[1]) {
+        msg!("Recipient {} is paid more than once", wallets[0]);
+        return err!(DistributorError::DuplicateRecipient);
+    }
+    Ok(())
+}
+
+/// Rejects the round if the distributor itself, its vault, the payer or the authority is a winner, so the vault can't
+/// win its own share and the operators can't pay themselves
+fn verify_no_self_recipients(ctx: &PayoutAccounts, winner_accounts: &[AccountInfo]) -> Result<()> {
+    let own = [
+        ctx.distributor_state.key(),
+        ctx.vault.key(),
+        ctx.payer.key(),
+        ctx.distributor_state.distributor_authority,
+    ];
+    for accounts in winner_accounts.chunks_exact(accounts_per_winner(ctx.distributor_state)) {
+        if own.contains(accounts[0].key) {
+            msg!("Recipient {} is the distributor or its operator", accounts[0].key);
+            return err!(DistributorError::SelfRecipient);
+        }
+    }
+    Ok(())
+}
+
+/// Rejects the round if any winner is on the blocklist, which is required as soon as it isn't empty
+fn verify_not_blocked(ctx: &PayoutAccounts, winner_accounts: &[AccountInfo]) -> Result<()> {
+    let Some(blocklist) = ctx.blocklist else {
+        require_eq!(
+            ctx.distributor_state.blocked_recipients,
+            0,
+            DistributorError::MissingBlocklist
+        );
+        return Ok(());
+    };
+    for accounts in winner_accounts.chunks_exact(accounts_per_winner(ctx.distributor_state)) {
+        if blocklist.wallets.binary_search(accounts[0].key).is_ok() {
+            msg!("Recipient {} is blocked", accounts[0].key);
+            return err!(DistributorError::RecipientBlocked);
+        }
+    }
+    Ok(())
+}
+
+/// Rejects the winners of the previous round while the rule is enabled. The first round of the rule isn't checked,
+/// its previous round may be not recorded
+fn verify_no_repeat_winners(ctx: &PayoutAccounts, winner_accounts: &[AccountInfo]) -> Result<()> {
+    let distributor_state = ctx.distributor_state;
+    let Some(since) = distributor_state.no_repeat_winners_since else {
+        return Ok(());
+    };
+    if distributor_state.round <= since {
+        return Ok(());
+    }
+    let Some(previous_round) = ctx.previous_round else {
+        return err!(DistributorError::MissingPreviousRound);
+    };
+    for accounts in winner_accounts.chunks_exact(accounts_per_winner(distributor_state)) {
+        if previous_round
+            .payouts
+            .iter()
+            .any(|payout| payout.wallet == *accounts[0].key)
+        {
+            msg!("Recipient {} won the previous round", accounts[0].key);
+            return err!(DistributorError::RepeatWinner);
+        }
+    }
+    Ok(())
+}
+
+/// Transfers `amount` from the vault to the associated token account of `authority` creating it if needed.
+/// Returns the withheld transfer fee
+fn pay_token_share<'info>(
+    ctx: &PayoutAccounts<'_, 'info>,
+    authority: &AccountInfo<'info>,
+    token_account: &'info AccountInfo<'info>,
+    hook_accounts: &[AccountInfo<'info>],
+    amount: u64,
+    seeds: &[&[u8]],
+) -> Result<Option<u64>> {
+    let is_ata = *token_account.key
+        == get_associated_token_address_with_program_id(authority.key, &ctx.mint.key(), ctx.token_program.key);
+    require!(
+        is_ata || ctx.distributor_state.allow_non_ata_recipients,
+        DistributorError::InvalidAssociatedTokenAccount
+    );
+
+    // token account is not initialized, it may be created by someone else between the check of the backend and
+    // this transaction, or hold lamports sent to its address, so the creation has to be idempotent. Only the ATA
+    // can be created, other token accounts have to exist
+    if is_ata && token_account.owner == &system_program::ID {
+        let lamports = token_account.lamports();
+        associated_token::create_idempotent(CpiContext::new(
+            ctx.associated_token_program.to_account_info(),
+            CreateAta {
+                payer: ctx.payer.to_account_info(),
+                associated_token: token_account.to_account_info(),
+                authority: authority.to_account_info(),
+                mint: ctx.mint.to_account_info(),
+                system_program: ctx.system_program.to_account_info(),
+                token_program: ctx.token_program.to_account_info(),
+            },
+        ))?;
+        ctx.ata_rent
+            .set(ctx.ata_rent.get() + token_account.lamports() - lamports);
+    }
+    let token_account = InterfaceAccount::<TokenAccount>::try_from(token_account)?;
+    require_keys_eq!(
+        token_account.mint,
+        ctx.mint.key(),
+        DistributorError::InvalidAssociatedTokenAccount
+    );
+    require_keys_eq!(
+        token_account.owner,
+        *authority.key,
+        DistributorError::InvalidAssociatedTokenAccount
+    );
+    if token_account.is_frozen() && ctx.distributor_state.frozen_recipient_policy.is_some() {
+        msg!("Skipping frozen token account {}", token_account.key());
+        return Ok(None);
+    }
+
+    transfer_checked_with_hook(
+        CpiContext::new_with_signer(
+            ctx.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.vault.to_account_info(),
+                mint: ctx.mint.to_account_info(),
+                to: token_account.to_account_info(),
+                authority: ctx.distributor_state.to_account_info(),
+            },
+            &[seeds],
+        )
+        .with_remaining_accounts(hook_accounts.to_vec()),
+        amount,
+        ctx.mint.decimals,
+    )?;
+    transfer_fee(ctx.mint, amount).map(Some)
+}
+
+/// Vesting variant of `pay_token_share`: transfers `amount` from the vault to the vesting escrow and records it in
+/// a new `Vesting` account of `authority` at `vesting_account`, the payer covers its rent. Returns the withheld
+/// transfer fee
+fn lock_vested_share<'info>(
+    ctx: &PayoutAccounts<'_, 'info>,
+    authority: &AccountInfo<'info>,
+    vesting_account: &'info AccountInfo<'info>,
+    hook_accounts: &[AccountInfo<'info>],
+    amount: u64,
+    duration: i64,
+    seeds: &[&[u8]],
+) -> Result<u64> {
+    let Some(vesting_escrow) = ctx.vesting_escrow else {
+        return err!(DistributorError::MissingVestingEscrow);
+    };
+    let distributor_state = ctx.distributor_state.key();
+    let round = ctx.distributor_state.round;
+    let round_bytes = round.to_le_bytes();
+    let (address, bump) = Pubkey::find_program_address(
+        &[
+            Vesting::SEED_PREFIX,
+            distributor_state.as_ref(),
+            round_bytes.as_ref(),
+            authority.key.as_ref(),
+        ],
+        &ID,
+    );
+    require_keys_eq!(*vesting_account.key, address, DistributorError::InvalidVestingAccount);
+
+    transfer_checked_with_hook(
+        CpiContext::new_with_signer(
+            ctx.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.vault.to_account_info(),
+                mint: ctx.mint.to_account_info(),
+                to: vesting_escrow.to_account_info(),
+                authority: ctx.distributor_state.to_account_info(),
+            },
+            &[seeds],
+        )
+        .with_remaining_accounts(hook_accounts.to_vec()),
+        amount,
+        ctx.mint.decimals,
+    )?;
+    let fee = transfer_fee(ctx.mint, amount)?;
+
+    // a winner has at most one share per round, so the account can only be funded by someone else yet
+    create_pda_account(ctx, vesting_account, 8 + Vesting::INIT_SPACE, &ID, &[
+        Vesting::SEED_PREFIX,
+        distributor_state.as_ref(),
+        round_bytes.as_ref(),
+        authority.key.as_ref(),
+        &[bump],
+    ])?;
+    let vesting = Vesting {
+        distributor_state,
+        wallet: *authority.key,
+        payer: ctx.payer.key(),
+        round,
+        total: amount - fee,
+        released: 0,
+        start: Clock::get()?.unix_timestamp,
+        duration,
+        bump,
+    };
+    vesting.try_serialize(&mut &mut vesting_account.try_borrow_mut_data()?[..])?;
+
+    Ok(fee)
+}
+
+/// `EscrowedShare` of `wallet` for the round of the distributor and its bump
+fn escrowed_share_address(distributor_state: &Pubkey, round: u64, wallet: &Pubkey) -> (Pubkey, u8) {
+    Pubkey::find_program_address(
+        &[
+            EscrowedShare::SEED_PREFIX,
+            distributor_state.as_ref(),
+            round.to_le_bytes().as_ref(),
+            wallet.as_ref(),
+        ],
+        &ID,
+    )
+}
+
+/// Escrow variant of `pay_token_share`: transfers `amount` from the vault to the vesting escrow and records it in a
+/// new `EscrowedShare` of `authority` at `escrow_account`, the payer covers its rent. Returns the withheld transfer
+/// fee
+#[allow(clippy::too_many_arguments)]
+fn escrow_share<'info>(
+    ctx: &PayoutAccounts<'_, 'info>,
+    authority: &AccountInfo<'info>,
+    escrow_account: &'info AccountInfo<'info>,
+    hook_accounts: &[AccountInfo<'info>],
+    amount: u64,
+    claim_period: i64,
+    bump: u8,
+    seeds: &[&[u8]],
+) -> Result<u64> {
+    let Some(vesting_escrow) = ctx.vesting_escrow else {
+        return err!(DistributorError::MissingVestingEscrow);
+    };
+    let distributor_state = ctx.distributor_state.key();
+    let round = ctx.distributor_state.round;
+    let round_bytes = round.to_le_bytes();
+
+    transfer_checked_with_hook(
+        CpiContext::new_with_signer(
+            ctx.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.vault.to_account_info(),
+                mint: ctx.mint.to_account_info(),
+                to: vesting_escrow.to_account_info(),
+                authority: ctx.distributor_state.to_account_info(),
+            },
+            &[seeds],
+        )
+        .with_remaining_accounts(hook_accounts.to_vec()),
+        amount,
+        ctx.mint.decimals,
+    )?;
+    let fee = transfer_fee(ctx.mint, amount)?;
+
+    // a winner has at most one share per round, so the account can only be funded by someone else yet
+    create_pda_account(ctx, escrow_account, 8 + EscrowedShare::INIT_SPACE, &ID, &[
+        EscrowedShare::SEED_PREFIX,
+        distributor_state.as_ref(),
+        round_bytes.as_ref(),
+        authority.key.as_ref(),
+        &[bump],
+    ])?;
+    let expires_at = Clock::get()?
+        .unix_timestamp
+        .checked_add(claim_period)
+        .ok_or(DistributorError::ArithmeticOverflow)?;
+    let escrowed_share = EscrowedShare {
+        distributor_state,
+        wallet: *authority.key,
+        payer: ctx.payer.key(),
+        round,
+        amount: amount - fee,
+        expires_at,
+        bump,
+    };
+    escrowed_share.try_serialize(&mut &mut escrow_account.try_borrow_mut_data()?[..])?;
+    msg!("Escrowed the share of {}", authority.key);
+
+    Ok(fee)
+}
+
+/// Transfers an escrowed share from the vesting escrow to `to`, the winner's token account or the vault
+fn release_escrowed_share<'info>(
+    distributor_state: &Account<'info, DistributorState>,
+    mint: &InterfaceAccount<'info, Mint>,
+    vesting_escrow: &InterfaceAccount<'info, TokenAccount>,
+    to: AccountInfo<'info>,
+    token_program: &Interface<'info, TokenInterface>,
+    hook_accounts: &[AccountInfo<'info>],
+    amount: u64,
+) -> Result<()> {
+    let share_size = distributor_state.initial_share_size.to_le_bytes();
+    let number_of_shares = distributor_state.initial_number_of_shares.to_le_bytes();
+    let seeds = [
+        distributor_state.mint.as_ref(),
+        distributor_state.marker_mint.as_ref(),
+        share_size.as_ref(),
+        number_of_shares.as_ref(),
+        &[distributor_state.distributor_state_bump],
+    ];
+    transfer_checked_with_hook(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            TransferChecked {
+                from: vesting_escrow.to_account_info(),
+                mint: mint.to_account_info(),
+                to,
+                authority: distributor_state.to_account_info(),
+            },
+            &[&seeds],
+        )
+        .with_remaining_accounts(hook_accounts.to_vec()),
+        amount,
+        mint.decimals,
+    )
+}
+
+/// Disposes of the shares skipped for frozen token accounts by the frozen recipient policy, returns the transfer
+/// fee withheld from a treasury transfer
+fn dispose_skipped_shares<'info>(
+    ctx: &PayoutAccounts<'_, 'info>,
+    skipped: u64,
+    hook_accounts: &[AccountInfo<'info>],
+    seeds: &[&[u8]],
+) -> Result<u64> {
+    if skipped == 0 {
+        return Ok(0);
+    }
+    match ctx.distributor_state.frozen_recipient_policy {
+        Some(FrozenRecipientPolicy::Burn) => {
+            token_interface::burn(
+                CpiContext::new_with_signer(
+                    ctx.token_program.to_account_info(),
+                    Burn {
+                        mint: ctx.mint.to_account_info(),
+                        from: ctx.vault.to_account_info(),
+                        authority: ctx.distributor_state.to_account_info(),
+                    },
+                    &[seeds],
+                ),
+                skipped,
+            )?;
+            Ok(0)
+        },
+        Some(FrozenRecipientPolicy::Treasury) => {
+            let Some(treasury) = ctx.treasury else {
+                return err!(DistributorError::MissingTreasury);
+            };
+            transfer_checked_with_hook(
+                CpiContext::new_with_signer(
+                    ctx.token_program.to_account_info(),
+                    TransferChecked {
+                        from: ctx.vault.to_account_info(),
+                        mint: ctx.mint.to_account_info(),
+                        to: treasury.to_account_info(),
+                        authority: ctx.distributor_state.to_account_info(),
+                    },
+                    &[seeds],
+                )
+                .with_remaining_accounts(hook_accounts.to_vec()),
+                skipped,
+                ctx.mint.decimals,
+            )?;
+            transfer_fee(ctx.mint, skipped)
+        },
+        // the shares stay in the vault for the next round
+        Some(FrozenRecipientPolicy::Rollover) | None => Ok(0),
+    }
+}
+
+/// `pay_shares` of a native mint distributor: the shares are unwrapped into the distributor state at once, which
+/// credits them to the winner wallets. A share has to cover the rent exemption of a wallet which doesn't exist yet
+fn pay_native_shares<'info>(
+    ctx: PayoutAccounts<'_, 'info>,
+    winner_accounts: &'info [AccountInfo<'info>],
+    amounts: Vec<u64>,
+    seeds: &[&[u8]],
+) -> Result<(Vec<Payout>, Vec<u64>, u64)> {
+    let mint_marker = ctx.distributor_state.marker_mint;
+    let min_marker_balance = ctx.distributor_state.required_marker_balance();
+    let accounts_per_winner = accounts_per_winner(ctx.distributor_state);
+    for accounts in winner_accounts.chunks_exact(accounts_per_winner) {
+        if let Some(min_marker_balance) = min_marker_balance {
+            verify_marker_balance(&accounts[0], &accounts[1], &mint_marker, min_marker_balance)?;
+        }
+        require_keys_eq!(
+            *accounts[0].owner,
+            system_program::ID,
+            DistributorError::InvalidNativeRecipient
+        );
+    }
+
+    let total = amounts
+        .iter()
+        .try_fold(0u64, |total, amount| total.checked_add(*amount))
+        .ok_or(DistributorError::InsufficientVaultBalance)?;
+    unwrap_native(&ctx, total, seeds)?;
+
+    let mut payouts = Vec::with_capacity(amounts.len());
+    for (accounts, amount) in winner_accounts.chunks_exact(accounts_per_winner).zip(amounts) {
+        let wallet = &accounts[0];
+        ctx.distributor_state.sub_lamports(amount)?;
+        wallet.add_lamports(amount)?;
+        payouts.push(Payout {
+            wallet: *wallet.key,
+            amount,
+        });
+    }
+    let delivered = payouts.iter().map(|payout| payout.amount).collect();
+
+    Ok((payouts, delivered, 0))
+}
+
+/// Creates the PDA `account` of `space` bytes owned by `owner`, tops a prefunded address up, returns the rent paid
+fn create_pda_account<'info>(
+    ctx: &PayoutAccounts<'_, 'info>,
+    account: &AccountInfo<'info>,
+    space: usize,
+    owner: &Pubkey,
+    signer_seeds: &[&[u8]],
+) -> Result<u64> {
+    let rent = Rent::get()?.minimum_balance(space);
+    let rent_paid = rent.saturating_sub(account.lamports());
+    if account.lamports() == 0 {
+        system_program::create_account(
+            CpiContext::new_with_signer(
+                ctx.system_program.to_account_info(),
+                system_program::CreateAccount {
+                    from: ctx.payer.to_account_info(),
+                    to: account.clone(),
+                },
+                &[signer_seeds],
+            ),
+            rent,
+            space as u64,
+            owner,
+        )?;
+        return Ok(rent_paid);
+    }
+
+    if rent_paid > 0 {
+        system_program::transfer(
+            CpiContext::new(ctx.system_program.to_account_info(), system_program::Transfer {
+                from: ctx.payer.to_account_info(),
+                to: account.clone(),
+            }),
+            rent_paid,
+        )?;
+    }
+    system_program::allocate(
+        CpiContext::new_with_signer(
+            ctx.system_program.to_account_info(),
+            system_program::Allocate {
+                account_to_allocate: account.clone(),
+            },
+            &[signer_seeds],
+        ),
+        space as u64,
+    )?;
+    system_program::assign(
+        CpiContext::new_with_signer(
+            ctx.system_program.to_account_info(),
+            system_program::Assign {
+                account_to_assign: account.clone(),
+            },
+            &[signer_seeds],
+        ),
+        owner,
+    )?;
+    Ok(rent_paid)
+}
+
+/// Moves `amount` of wrapped SOL from the vault to the lamports of the distributor state through the temporary
+/// `native_unwrap` account, the payer gets back the rent of the temporary account
+fn unwrap_native(ctx: &PayoutAccounts<'_, '_>, amount: u64, seeds: &[&[u8]]) -> Result<()> {
+    let Some(native_unwrap) = ctx.native_unwrap else {
+        return err!(DistributorError::MissingNativeUnwrap);
+    };
+    let distributor_state = ctx.distributor_state.key();
+    let unwrap_seeds = [
+        NATIVE_UNWRAP_SEED,
+        distributor_state.as_ref(),
+        &[ctx.native_unwrap_bump],
+    ];
+
+    let rent_paid = create_pda_account(
+        ctx,
+        &native_unwrap.to_account_info(),
+        spl_token::state::Account::LEN,
+        ctx.token_program.key,
+        &unwrap_seeds,
+    )?;
+
+    token_interface::initialize_account3(CpiContext::new(
+        ctx.token_program.to_account_info(),
+        InitializeAccount3 {
+            account: native_unwrap.to_account_info(),
+            mint: ctx.mint.to_account_info(),
+            authority: ctx.distributor_state.to_account_info(),
+        },
+    ))?;
+    token_interface::transfer_checked(
+        CpiContext::new_with_signer(
+            ctx.token_program.to_account_info(),
+            TransferChecked {
+                from: ctx.vault.to_account_info(),
+                mint: ctx.mint.to_account_info(),
+                to: native_unwrap.to_account_info(),
+                authority: ctx.distributor_state.to_account_info(),
+            },
+            &[seeds],
+        ),
+        amount,
+        ctx.mint.decimals,
+    )?;
+    token_interface::close_account(CpiContext::new_with_signer(
+        ctx.token_program.to_account_info(),
+        CloseAccount {
+            account: native_unwrap.to_account_info(),
+            destination: ctx.distributor_state.to_account_info(),
+            authority: ctx.distributor_state.to_account_info(),
+        },
+        &[seeds],
+    ))?;
+
+    ctx.distributor_state.sub_lamports(rent_paid)?;
+    ctx.payer.add_lamports(rent_paid)?;
+    Ok(())
+}
+
+/// Randomness of an ORAO request of `seed`, zeroed until the request is fulfilled. `None` if the data isn't a
+/// request of `seed`
+fn vrf_request_randomness(data: &[u8], seed: &[u8; 32]) -> Option<[u8; 64]> {
+    if data.get(..8)? != vrf::RANDOMNESS_DISCRIMINATOR || data.get(vrf::SEED_RANGE)? != seed {
+        return None;
+    }
+    data.get(vrf::RANDOMNESS_RANGE)?.try_into().ok()
+}
+
+/// Randomness of a fulfilled ORAO request of `seed`
+fn vrf_randomness(data: &[u8], seed: &[u8; 32]) -> Result<[u8; 64]> {
+    vrf_request_randomness(data, seed)
+        .filter(|value| *value != [0; 64])
+        .ok_or(DistributorError::RandomnessNotFulfilled.into())
+}
+
+/// A pending ORAO request of `seed`: its randomness is still zeroed
+fn verify_randomness_pending(data: &[u8], seed: &[u8; 32]) -> Result<()> {
+    require!(
+        vrf_request_randomness(data, seed) == Some([0; 64]),
+        DistributorError::InvalidRandomnessRequest
+    );
+    Ok(())
+}
+
+/// The winner has to hold at least `min_marker_balance` of the marker token in its associated token account
+fn verify_marker_balance<'info>(
+    authority: &AccountInfo<'info>,
+    marker_account: &'info AccountInfo<'info>,
+    marker_mint: &Pubkey,
+    min_marker_balance: u64,
+) -> Result<()> {
+    require_keys_eq!(
+        *marker_account.key,
+        get_associated_token_address_with_program_id(authority.key, marker_mint, marker_account.owner),
+        DistributorError::InvalidAssociatedTokenAccount
+    );
+    let marker_account = InterfaceAccount::<TokenAccount>::try_from(marker_account)?;
+    require_gte!(
+        marker_account.amount,
+        min_marker_balance,
+        DistributorError::MarkerBalanceTooLow
+    );
+    Ok(())
+}
+
+/// Every winner has to own an NFT of the marker collection if the distributor has one
+fn verify_collection_holders<'info>(
+    distributor_state: &DistributorState,
+    winner_accounts: &'info [AccountInfo<'info>],
+) -> Result<()> {
+    let Some(collection) = distributor_state.marker_collection else {
+        return Ok(());
+    };
+    let index = collection_accounts_index(distributor_state);
+    for accounts in winner_accounts.chunks_exact(accounts_per_winner(distributor_state)) {
+        verify_collection_nft(&accounts[0], &accounts[index], &accounts[index + 1], &collection)?;
+    }
+    Ok(())
+}
+
+/// `nft_account` of the winner has to hold an NFT whose `metadata` account is verified as a member of `collection`
+fn verify_collection_nft<'info>(
+    authority: &AccountInfo<'info>,
+    nft_account: &'info AccountInfo<'info>,
+    metadata: &AccountInfo<'info>,
+    collection: &Pubkey,
+) -> Result<()> {
+    let nft_account = InterfaceAccount::<TokenAccount>::try_from(nft_account)?;
+    require_keys_eq!(nft_account.owner, *authority.key, DistributorError::NotCollectionHolder);
+    require_gt!(nft_account.amount, 0, DistributorError::NotCollectionHolder);
+    require_keys_eq!(
+        *metadata.key,
+        metadata::metadata_address(&nft_account.mint),
+        DistributorError::NotCollectionHolder
+    );
+    require_keys_eq!(*metadata.owner, metadata::ID, DistributorError::NotCollectionHolder);
+    require!(
+        metadata::verified_collection(&metadata.try_borrow_data()?, &nft_account.mint) == Some(*collection),
+        DistributorError::NotCollectionHolder
+    );
+    Ok(())
+}
+
+/// Marker balances of the winners of the round, the marker account of every winner has to be its associated token
+/// account. The marker accounts are passed only if the distributor has `min_marker_balance` or pays dividends
+fn marker_weights<'info>(
+    distributor_state: &DistributorState,
+    remaining_accounts: &'info [AccountInfo<'info>],
+) -> Result<Vec<u64>> {
+    require!(
+        distributor_state.required_marker_balance().is_some(),
+        DistributorError::ProRataWithoutMarker
+    );
+    let accounts_per_winner = accounts_per_winner(distributor_state);
+    let winner_accounts = distributor_state.paid_shares() as usize * accounts_per_winner;
+    require_gte!(
+        remaining_accounts.len(),
+        winner_accounts,
+        DistributorError::MissingRemainingAccounts
+    );
+    let payout_accounts = if distributor_state.is_native() { 1 } else { 2 };
+    remaining_accounts[..winner_accounts]
+        .chunks_exact(accounts_per_winner)
+        .map(|accounts| {
+            let (authority, marker_account) = (&accounts[0], &accounts[payout_accounts]);
+            require_keys_eq!(
+                *marker_account.key,
+                get_associated_token_address_with_program_id(
+                    authority.key,
+                    &distributor_state.marker_mint,
+                    marker_account.owner
+                ),
+                DistributorError::InvalidAssociatedTokenAccount
+            );
+            Ok(InterfaceAccount::<TokenAccount>::try_from(marker_account)?.amount)
+        })
+        .collect()
+}
+
+/// `token_interface::transfer_checked` which also passes the remaining accounts of the context to a Token-2022
+/// transfer hook: the hook program, its extra account metas account and the extra accounts they list
+fn transfer_checked_with_hook<'info>(
+    ctx: CpiContext<'_, '_, '_, 'info, TransferChecked<'info>>,
+    amount: u64,
+    decimals: u8,
+) -> Result<()> {
+    invoke_transfer_checked(
+        ctx.program.key,
+        ctx.accounts.from,
+        ctx.accounts.mint,
+        ctx.accounts.to,
+        ctx.accounts.authority,
+        &ctx.remaining_accounts,
+        amount,
+        decimals,
+        ctx.signer_seeds,
+    )
+    .map_err(Into::into)
+}
+
+/// Splits the remaining accounts of a chunk at the transfer hook program of the mint: the winner accounts come before
+/// it, the hook accounts from it on. Every account is a winner account if the mint has no transfer hook
+fn split_at_hook_accounts<'a, 'info>(
+    mint: &InterfaceAccount<'info, Mint>,
+    accounts: &'a [AccountInfo<'info>],
+) -> Result<(&'a [AccountInfo<'info>], &'a [AccountInfo<'info>])> {
+    let mint_info = mint.to_account_info();
+    if *mint_info.owner != token_2022::ID {
+        return Ok((accounts, &[]));
+    }
+    let data = mint_info.try_borrow_data()?;
+    let mint = StateWithExtensions::<Token2022Mint>::unpack(&data)?;
+    let Some(hook_program) = transfer_hook::get_program_id(&mint) else {
+        return Ok((accounts, &[]));
+    };
+    let hook_start = accounts
+        .iter()
+        .position(|account| *account.key == hook_program)
+        .unwrap_or(accounts.len());
+    Ok(accounts.split_at(hook_start))
+}
+
+/// Fee withheld from a transfer of `amount` by the Token-2022 `TransferFee` extension, zero for mints without it
+fn transfer_fee(mint: &InterfaceAccount<Mint>, amount: u64) -> Result<u64> {
+    let mint_info = mint.to_account_info();
+    if *mint_info.owner != token_2022::ID {
+        return Ok(0);
+    }
+    let data = mint_info.try_borrow_data()?;
+    let mint = StateWithExtensions::<Token2022Mint>::unpack(&data)?;
+    let Ok(transfer_fee_config) = mint.get_extension::<TransferFeeConfig>() else {
+        return Ok(0);
+    };
+    transfer_fee_config
+        .calculate_epoch_fee(Clock::get()?.epoch, amount)
+        .ok_or_else(|| error!(DistributorError::InvalidParameters))
+}
+
+/// Transfers `protocol_fee` from the vault to the fee collector, returns the fee
+#[allow(clippy::too_many_arguments)]
+fn pay_protocol_fee<'info>(
+    distributor_state: &Account<'info, DistributorState>,
+    protocol_fee: u64,
+    mint: &InterfaceAccount<'info, Mint>,
+    vault: &InterfaceAccount<'info, TokenAccount>,
+    fee_account: Option<&InterfaceAccount<'info, TokenAccount>>,
+    token_program: &Interface<'info, TokenInterface>,
+    hook_accounts: &[AccountInfo<'info>],
+    signer_seeds: &[&[u8]],
+) -> Result<u64> {
+    if protocol_fee == 0 {
+        return Ok(0);
+    }
+    let Some(fee_account) = fee_account else {
+        return err!(DistributorError::MissingFeeAccount);
+    };
+    transfer_checked_with_hook(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            TransferChecked {
+                from: vault.to_account_info(),
+                mint: mint.to_account_info(),
+                to: fee_account.to_account_info(),
+                authority: distributor_state.to_account_info(),
+            },
+            &[signer_seeds],
+        )
+        .with_remaining_accounts(hook_accounts.to_vec()),
+        protocol_fee,
+        mint.decimals,
+    )?;
+    Ok(protocol_fee)
+}
+
+/// Transfers the trigger bonus from the vault to the depositor who triggered the round, returns the bonus. The bonus
+/// is capped at what the vault holds after the payouts, the account of the depositor is required while it is owed.
+/// Only the first round after the trigger pays it, the account passed to a multi-round call is checked once
+fn pay_trigger_bonus<'info>(
+    distributor_state: &Account<'info, DistributorState>,
+    vault: &mut InterfaceAccount<'info, TokenAccount>,
+    trigger_bonus_account: Option<&InterfaceAccount<'info, TokenAccount>>,
+    mint: &InterfaceAccount<'info, Mint>,
+    token_program: &Interface<'info, TokenInterface>,
+    hook_accounts: &[AccountInfo<'info>],
+    signer_seeds: &[&[u8]],
+) -> Result<u64> {
+    let Some(trigger_bonus) = distributor_state.trigger_bonus else {
+        return Ok(0);
+    };
+    if distributor_state.last_depositor.is_none() {
+        return Ok(0);
+    }
+    let Some(trigger_bonus_account) = trigger_bonus_account else {
+        return err!(DistributorError::MissingTriggerBonusAccount);
+    };
+    vault.reload()?;
+    let trigger_bonus = trigger_bonus.min(vault.amount);
+    if trigger_bonus == 0 {
+        return Ok(0);
+    }
+    transfer_checked_with_hook(
+        CpiContext::new_with_signer(
+            token_program.to_account_info(),
+            TransferChecked {
+                from: vault.to_account_info(),
+                mint: mint.to_account_info(),
+                to: trigger_bonus_account.to_account_info(),
+                authority: distributor_state.to_account_info(),
+            },
+            &[signer_seeds],
+        )
+        .with_remaining_accounts(hook_accounts.to_vec()),
+        trigger_bonus,
+        mint.decimals,
+    )?;
+    Ok(trigger_bonus)
+}
+
+/// Lamport fee of the round paid by the payer into the fee vault, the distribution vault isn't touched
+fn pay_lamport_fee<'info>(
+    distributor_state: &Account<'info, DistributorState>,
+    payer: &Signer<'info>,
+    fee_vault: Option<&mut Account<'info, FeeVault>>,
+    system_program: &Program<'info, System>,
+) -> Result<()> {
+    let lamport_fee = distributor_state.lamport_fee;
+    if lamport_fee == 0 {
+        return Ok(());
+    }
+    let Some(fee_vault) = fee_vault else {
+        return err!(DistributorError::MissingFeeVault);
+    };
+    system_program::transfer(
+        CpiContext::new(system_program.to_account_info(), system_program::Transfer {
+            from: payer.to_account_info(),
+            to: fee_vault.to_account_info(),
+        }),
+        lamport_fee,
+    )?;
+    fee_vault.collected += lamport_fee;
+    Ok(())
+}
+
+/// The burned shares (`terminal_share`) are split between burn, treasury and the next round (stays in the vault) by the
+/// remainder policy
+#[allow(clippy::too_many_arguments)]
+fn dispose_terminal_share<'info>(
+    distributor_state: &Account<'info, DistributorState>,
+    terminal_share: u64,
+    mint: &InterfaceAccount<'info, Mint>,
+    vault: &InterfaceAccount<'info, TokenAccount>,
+    treasury: Option<&InterfaceAccount<'info, TokenAccount>>,
+    token_program: &Interface<'info, TokenInterface>,
+    hook_accounts: &[AccountInfo<'info>],
+    signer_seeds: &[&[u8]],
+) -> Result<(u64, u64, u64)> {
+    let (burn, treasury_part, rollover) = distributor_state.remainder_policy.apply(terminal_share);
+
+    if burn > 0 {
+        token_interface::burn(
+            CpiContext::new_with_signer(
+                token_program.to_account_info(),
+                Burn {
+                    mint: mint.to_account_info(),
+                    from: vault.to_account_info(),
+                    authority: distributor_state.to_account_info(),
+                },
+                &[signer_seeds],
+            ),
+            burn,
+        )?;
+    }
+
+    if treasury_part > 0 {
+        let Some(treasury) = treasury else {
+            return err!(DistributorError::MissingTreasury);
+        };
+        transfer_checked_with_hook(
+            CpiContext::new_with_signer(
+                token_program.to_account_info(),
+                TransferChecked {
+                    from: vault.to_account_info(),
+                    mint: mint.to_account_info(),
+                    to: treasury.to_account_info(),
+                    authority: distributor_state.to_account_info(),
+                },
+                &[signer_seeds],
+            )
+            .with_remaining_accounts(hook_accounts.to_vec()),
+            treasury_part,
+            mint.decimals,
+        )?;
+    }
+
+    Ok((burn, treasury_part, rollover))
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug)]
+pub struct InitializeParams {
+    pub share_size: u64,
+    pub number_of_shares: u64,
+    pub remainder_policy: RemainderPolicy,
+    pub distribution_mode: DistributionMode,
+    pub co_authority: Option<Pubkey>,
+    /// Shares of every round which aren't paid to the winners, see `DistributorState::burn_shares`
+    pub burn_shares: u64,
+    /// Label of the distributor, empty if not set
+    pub name: String,
+    /// Link to the off-chain description of the distributor, empty if not set
+    pub uri: String,
+    /// Rounds after which the distributor is finished, `None` if it runs indefinitely
+    pub max_rounds: Option<u64>,
+    /// Decimal places the share size may have in the UI amount, e.g. `Some(0)` for whole tokens only. Not checked
+    /// if not set
+    pub share_size_decimals: Option<u8>,
+}
+
+#[derive(Accounts)]
+#[instruction(params: InitializeParams)]
+pub struct Initialize<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + DistributorState::INIT_SPACE,
+        seeds = [
+            mint.key().as_ref(),
+            marker_mint.key().as_ref(),
+            params.share_size.to_le_bytes().as_ref(),
+            params.number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        init,
+        payer = payer,
+        seeds = [distributor_state.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = distributor_state,
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub marker_mint: InterfaceAccount<'info, Mint>,
+
+    /// CHECK: will be used only for key
+    pub distributor_authority: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[account]
+#[derive(InitSpace)]
+pub struct DistributorState {
+    pub vault: Pubkey,
+    pub mint: Pubkey,
+    pub marker_mint: Pubkey,
+    pub distributor_authority: Pubkey,
+
+    pub share_size: u64,
+    pub number_of_shares: u64,
+
+    pub distributor_state_bump: u8,
+    pub vault_bump: u8,
+
+    /// How the share which is not paid out to winners is disposed of
+    pub remainder_policy: RemainderPolicy,
+
+    /// Share parameters the state address was derived from, `share_size` and `number_of_shares` can be changed
+    /// later by `update_config`
+    pub initial_share_size: u64,
+    pub initial_number_of_shares: u64,
+
+    /// Deposits and distributions are rejected while the distributor is paused
+    pub paused: bool,
+
+    /// Authority nominated by the current one, it takes over after signing `accept_authority`
+    pub pending_authority: Option<Pubkey>,
+
+    /// Number of completed distributions
+    pub round: u64,
+
+    /// Seconds the winners have to claim their shares in the claim mode
+    pub claim_period: i64,
+    /// What happens to the shares which weren't claimed in time
+    pub unclaimed_policy: UnclaimedPolicy,
+
+    /// Decimals of the mint at initialize, `share_size` is in base units of these decimals
+    pub decimals: u8,
+
+    /// How the winners are paid out, chosen at initialize and can't be changed
+    pub distribution_mode: DistributionMode,
+
+    /// Minimal marker token balance of the winners of `distribute`, their marker token accounts are passed after
+    /// their token accounts. The winners aren't checked on-chain if it is `None`
+    pub min_marker_balance: Option<u64>,
+
+    /// Cooldown between the rounds, a round can't start earlier than `min_interval_seconds` after the previous one
+    /// was completed
+    pub min_interval_seconds: i64,
+    /// Unix timestamp of the completion of the last round
+    pub last_round_at: i64,
+
+    /// Number of times the vault was replaced, the vault of nonce 0 is derived from the state address alone and
+    /// the later ones from the state address and the nonce
+    pub vault_nonce: u32,
+    /// Vault created by `create_replacement_vault`, `migrate_vault` switches the distributor to it
+    pub replacement_vault: Option<Pubkey>,
+
+    /// Emergency withdraw requested by the authority, it can be executed after its timelock
+    pub pending_withdraw: Option<PendingWithdraw>,
+
+    /// `deposit` rejects smaller amounts, direct transfers to the vault can't be checked
+    pub min_deposit: u64,
+
+    /// Number of wallets in the `Blocklist`, the payouts require the blocklist unless it is zero
+    pub blocked_recipients: u32,
+
+    /// Round since which the winners of the previous round can't win again, the rule is disabled if `None`
+    pub no_repeat_winners_since: Option<u64>,
+
+    /// Second signer of `update_config`, `nominate_authority`, `request_emergency_withdraw` and
+    /// `set_co_authority`, distinct from the authority. Only the authority signs them if it is `None`
+    pub co_authority: Option<Pubkey>,
+
+    /// Share of the threshold in basis points `distribute` pays to the associated token account of
+    /// `fee_collector`, there is no fee without a collector
+    pub fee_bps: u16,
+    pub fee_collector: Option<Pubkey>,
+
+    /// What happens to the share of a winner whose token account is frozen, the payout fails if it is `None`
+    pub frozen_recipient_policy: Option<FrozenRecipientPolicy>,
+
+    /// Winners can be paid to any initialized token account they own, not only to their ATA which is created if
+    /// missing
+    pub allow_non_ata_recipients: bool,
+
+    /// Backend identity which has to sign `distribute` and `distribute_weighted`
+    pub service_signer: Option<ServiceSigner>,
+
+    /// Shares are released linearly over this many seconds since their round, see `set_vesting`
+    pub vesting_duration: Option<i64>,
+
+    /// `distribute` pays only the verified winners of the committed draw of the round
+    pub requires_committed_draw: bool,
+
+    /// Share of a claim in basis points the claimant can route to a referral in the claim mode, fixed for a round
+    /// when its claims are committed
+    pub referral_bps: u16,
+
+    /// Lamports the payer pays into the fee vault on every `distribute` round
+    pub lamport_fee: u64,
+
+    /// Layout version, zero for the states created before the versioning. New fields go after it, so an older
+    /// state reads them as zeroes once `migrate_state` grows it
+    pub version: u8,
+
+    /// Keys which have to co-sign the payouts along with the authority
+    pub cosigners: Option<Cosigners>,
+
+    /// Shares above it are paid only to the winners who accepted large payouts, the others claim them
+    pub large_payout_threshold: Option<u64>,
+
+    /// Shares of every round which aren't paid to the winners but disposed of by the remainder policy, the other
+    /// `number_of_shares - burn_shares` are drawn
+    pub burn_shares: u64,
+
+    /// Human-readable label of the distributor for explorers and frontends, empty if not set
+    #[max_len(MAX_NAME_LEN)]
+    pub name: String,
+    /// Link to the off-chain description of the distributor, e.g. a JSON document, empty if not set
+    #[max_len(MAX_URI_LEN)]
+    pub uri: String,
+
+    /// Only the depositors with a `DepositAllowance` of the distributor can deposit
+    pub deposit_allowlist: bool,
+
+    /// Lamports of the state set aside by the authority to reimburse the payer for the token accounts the payouts
+    /// create
+    pub rent_buffer: u64,
+
+    /// Total rounds after which the distributor is finished and refuses new rounds, unlimited if `None`
+    pub max_rounds: Option<u64>,
+
+    /// `distribute` references the holders snapshot committed for the round by `commit_snapshot`
+    pub requires_holder_snapshot: bool,
+
+    /// How the amounts of a weighted split are rounded to base units, see `distribute_v2`
+    pub rounding_policy: RoundingPolicy,
+
+    /// Verified Metaplex collection the winners have to own an NFT of, see `set_marker_collection`
+    pub marker_collection: Option<Pubkey>,
+
+    /// Shares the token accounts of their winners can't receive are escrowed for this many seconds, see
+    /// `set_escrow_claim_period`
+    pub escrow_claim_period: Option<i64>,
+
+    /// Vault balance which triggers a round, at least the payout sum of the shares. The rest stays in the vault as
+    /// a float. The payout sum triggers the rounds if not set
+    pub threshold: Option<u64>,
+    /// Paid from the vault to the depositor whose deposit pushed the vault over the threshold, `None` pays no bonus
+    pub trigger_bonus: Option<u64>,
+    /// Depositor whose deposit pushed the vault over the threshold since the last round
+    pub last_depositor: Option<Pubkey>,
+
+    /// Claim rounds published by `distribute_claims` and not closed by `close_claims` yet, the distributor can't be
+    /// closed while there are any. The rounds published before version 15 aren't counted
+    pub open_claim_rounds: u64,
+
+    /// `distribute` pays only the verified winners of the revealed VRF draw of the round
+    pub requires_vrf_draw: bool,
+}
+
+/// Layout of the states created by the first version of the program, before any field was added after `vault_bump`
+#[derive(AnchorDeserialize, Debug)]
+pub struct LegacyDistributorState {
+    pub vault: Pubkey,
+    pub mint: Pubkey,
+    pub marker_mint: Pubkey,
+    pub distributor_authority: Pubkey,
+    pub share_size: u64,
+    pub number_of_shares: u64,
+    pub distributor_state_bump: u8,
+    pub vault_bump: u8,
+}
+
+impl LegacyDistributorState {
+    /// Size of a state account of the first layout, discriminator included
+    pub const SPACE: usize = 8 + 32 * 4 + 8 * 2 + 2;
+}
+
+/// M-of-N co-signers of the payouts
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Debug, PartialEq, Eq)]
+pub struct Cosigners {
+    pub threshold: u8,
+    #[max_len(MAX_COSIGNERS)]
+    pub keys: Vec<Pubkey>,
+}
+
+impl Cosigners {
+    pub fn is_valid(&self) -> bool {
+        let mut keys = self.keys.clone();
+        keys.sort_unstable();
+        keys.dedup();
+        keys.len() == self.keys.len()
+            && self.keys.len() <= MAX_COSIGNERS
+            && (1..=keys.len()).contains(&(self.threshold as usize))
+    }
+
+    /// Distinct co-signers among the signers of `accounts`
+    pub fn signed(&self, accounts: &[AccountInfo]) -> usize {
+        self.keys
+            .iter()
+            .filter(|key| accounts.iter().any(|account| account.is_signer && account.key == *key))
+            .count()
+    }
+}
+
+pub const MAX_COSIGNERS: usize = 10;
+
+/// Longest `DistributorState::name` in bytes
+pub const MAX_NAME_LEN: usize = 32;
+/// Longest `DistributorState::uri` in bytes
+pub const MAX_URI_LEN: usize = 200;
+/// Longest `DistributorMetadata::symbol` in bytes, as the one of the token metadata
+pub const MAX_SYMBOL_LEN: usize = 10;
+
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ServiceSigner {
+    pub key: Pubkey,
+    /// The service signer signs the rounds in place of the authority instead of co-signing them with it
+    pub crank: bool,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PendingWithdraw {
+    pub amount: u64,
+    /// Token account of the distributor mint which receives the amount
+    pub destination: Pubkey,
+    /// Unix timestamp since which `emergency_withdraw` can be executed
+    pub unlocks_at: i64,
+}
+
+/// 30 days
+pub const DEFAULT_CLAIM_PERIOD: i64 = 30 * 24 * 60 * 60;
+/// 48 hours between `request_emergency_withdraw` and `emergency_withdraw`
+pub const EMERGENCY_WITHDRAW_TIMELOCK: i64 = 48 * 60 * 60;
+/// Seed of the temporary account native shares are unwrapped through
+pub const NATIVE_UNWRAP_SEED: &[u8] = b"native_unwrap";
+/// Seed of the token account holding the vested shares
+pub const VESTING_ESCROW_SEED: &[u8] = b"vesting_escrow";
+
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum FrozenRecipientPolicy {
+    Burn,
+    Treasury,
+    /// The share stays in the vault for the next round
+    Rollover,
+}
+
+/// Rounding of the amounts of a weighted split to base units. The states before it read as `FloorBurn`
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RoundingPolicy {
+    /// Amounts are rounded down, the remainder is burned
+    FloorBurn,
+    /// Amounts are rounded down, the remainder goes to the first winner
+    FloorToFirst,
+    /// Amounts are rounded half to even, the vault keeps or covers the difference to the split total
+    Bankers,
+}
+
+impl RoundingPolicy {
+    /// Splits `total` in proportion to `weights`. Returns the amounts and the remainder of `total` which isn't paid
+    /// to them, negative if the rounding pays more than `total`
+    pub fn split(&self, total: u64, weights: &[u64]) -> Result<(Vec<u64>, i64)> {
+        let weights_sum = weights.iter().map(|weight| *weight as u128).sum::<u128>();
+        require_gt!(weights_sum, 0, DistributorError::InvalidParameters);
+
+        let mut amounts = weights
+            .iter()
+            .map(|weight| {
+                let exact = total as u128 * *weight as u128;
+                let (floor, rest) = (exact / weights_sum, exact % weights_sum);
+                let round_up = *self == RoundingPolicy::Bankers
+                    && (rest * 2 > weights_sum || (rest * 2 == weights_sum && floor % 2 == 1));
+                u64::try_from(floor + round_up as u128).map_err(|_| error!(DistributorError::ArithmeticOverflow))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let paid = amounts.iter().map(|amount| *amount as i128).sum::<i128>();
+        let mut remainder = total as i128 - paid;
+        if *self == RoundingPolicy::FloorToFirst {
+            if let Some(first) = amounts.first_mut() {
+                *first += remainder as u64;
+                remainder = 0;
+            }
+        }
+        i64::try_from(remainder)
+            .map_err(|_| error!(DistributorError::ArithmeticOverflow))
+            .map(|remainder| (amounts, remainder))
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnclaimedPolicy {
+    Burn,
+    ReturnToVault,
+}
+
+/// Limits the number of shares to what the payout path of the mode can handle
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DistributionMode {
+    /// All winners are paid by a single `distribute` transaction
+    SingleTx,
+    /// Winners are paid by several transactions of one round
+    Chunked,
+    /// Winners claim their shares from a merkle root committed by `distribute_claims`
+    Claim,
+    /// Nothing is drawn, `distribute_dividend` splits the vault balance over the holders it is passed by their
+    /// marker balances
+    Dividend,
+}
+
+impl DistributionMode {
+    /// Accounts a transaction can lock. A v0 transaction looks the accounts of the winners up in a lookup table,
+    /// so the lock limit rather than the 1232 bytes of a transaction bounds the winners of a round
+    pub const MAX_TX_ACCOUNTS: usize = 64;
+    /// Accounts of a `distribute` transaction besides the winners: the `distribute` accounts with every optional
+    /// one passed, the memo and the compute budget programs
+    pub const DISTRIBUTE_ACCOUNTS: usize = 26;
+    /// Accounts the winners of a `distribute` transaction can take on top of the `distribute` accounts, more
+    /// winners have to be paid in the chunked mode
+    pub const SINGLE_TX_WINNER_ACCOUNTS: usize = Self::MAX_TX_ACCOUNTS - Self::DISTRIBUTE_ACCOUNTS;
+    /// Payouts of the whole round have to fit a `DistributionRound` account created by CPI (10 KiB)
+    pub const MAX_CHUNKED_WINNERS: u64 = 249;
+    /// Claimed bitmap of the round has to fit a `ClaimRound` account created by CPI (10 KiB)
+    pub const MAX_CLAIM_WINNERS: u64 = 65_535;
+
+    /// Most winners a round of the mode can pay with `accounts_per_winner` accounts each, the burned shares don't
+    /// count
+    pub fn max_winners(&self, accounts_per_winner: usize) -> u64 {
+        match self {
+            DistributionMode::SingleTx | DistributionMode::Dividend => {
+                (Self::SINGLE_TX_WINNER_ACCOUNTS / accounts_per_winner) as u64
+            },
+            DistributionMode::Chunked => Self::MAX_CHUNKED_WINNERS,
+            DistributionMode::Claim => Self::MAX_CLAIM_WINNERS,
+        }
+    }
+}
+
+/// Amount of `shares` shares of `share_size`
+pub fn shares_amount(share_size: u64, shares: u64) -> Result<u64> {
+    share_size
+        .checked_mul(shares)
+        .ok_or_else(|| error!(DistributorError::ArithmeticOverflow))
+}
+
+impl DistributorState {
+    pub const VAULT_SEED_PREFIX: &'static [u8] = b"vault";
+    /// Bumped whenever fields are added to the state
+    pub const VERSION: u8 = 16;
+
+    /// Rounds are recorded while the winners of the previous round are rejected, the next round checks the record
+    pub fn requires_round_record(&self) -> bool {
+        self.no_repeat_winners_since.is_some()
+    }
+
+    /// Distributes SOL: deposits and payouts are in lamports, the vault holds them as wrapped SOL
+    pub fn is_native(&self) -> bool {
+        self.mint == spl_token::native_mint::ID
+    }
+
+    /// Marker balance the winners pass their marker accounts to prove: `min_marker_balance`, or any balance if the
+    /// balances weigh a dividend. `None` if the winners pass no marker accounts
+    pub fn required_marker_balance(&self) -> Option<u64> {
+        self.min_marker_balance
+            .or((self.distribution_mode == DistributionMode::Dividend).then_some(0))
+    }
+
+    /// Vault balance that triggers a round, the trigger bonus is budgeted on top so paying it never eats into the
+    /// shares
+    pub fn threshold(&self) -> Result<u64> {
+        let payout_sum = self.payout_sum()?;
+        self.threshold
+            .map_or(payout_sum, |threshold| threshold.max(payout_sum))
+            .checked_add(self.trigger_bonus.unwrap_or(0))
+            .ok_or_else(|| error!(DistributorError::ArithmeticOverflow))
+    }
+
+    /// Amount a round takes from the vault, the paid and the burned shares
+    pub fn payout_sum(&self) -> Result<u64> {
+        shares_amount(self.share_size, self.number_of_shares)
+    }
+
+    /// Vault balance `rounds` consecutive rounds need, the float is kept once after the last of them and the trigger
+    /// bonus is paid once with the first
+    pub fn threshold_of_rounds(&self, rounds: u64) -> Result<u64> {
+        let float = self.threshold()? - self.payout_sum()?;
+        self.payout_sum()?
+            .checked_mul(rounds)
+            .and_then(|payouts| payouts.checked_add(float))
+            .ok_or_else(|| error!(DistributorError::ThresholdNotMet))
+    }
+
+    /// The explicit threshold can't be below the payout sum, a round has to be able to pay all of its shares
+    pub fn validate_threshold(&self) -> Result<()> {
+        if let Some(threshold) = self.threshold {
+            require_gte!(threshold, self.payout_sum()?, DistributorError::ThresholdBelowPayout);
+        }
+        Ok(())
+    }
+
+    /// Records `depositor` as the one who triggers the next round if its deposit moved the vault balance from
+    /// `balance_before` to `balance_after` over the threshold
+    pub fn record_trigger(&mut self, depositor: Pubkey, balance_before: u64, balance_after: u64) -> Result<()> {
+        let threshold = self.threshold()?;
+        if balance_before < threshold && balance_after >= threshold {
+            self.last_depositor = Some(depositor);
+        }
+        Ok(())
+    }
+
+    /// Associated token account of the depositor who triggered the round, `None` if no bonus is due
+    pub fn trigger_bonus_account(&self, token_program: &Pubkey) -> Option<Pubkey> {
+        self.trigger_bonus?;
+        self.last_depositor
+            .map(|depositor| get_associated_token_address_with_program_id(&depositor, &self.mint, token_program))
+    }
+
+    /// Number of winners of a round
+    pub fn paid_shares(&self) -> u64 {
+        self.number_of_shares - self.burn_shares
+    }
+
+    /// Part of the threshold disposed of by the remainder policy instead of being paid
+    pub fn terminal_amount(&self) -> Result<u64> {
+        shares_amount(self.share_size, self.burn_shares)
+    }
+
+    /// Rounds are signed by the authority and co-signed by the service signer if there is one. In the crank mode
+    /// the service signer signs them alone in place of the authority
+    pub fn authorizes_round(&self, authority: &Pubkey, service_signer: Option<Pubkey>) -> bool {
+        match self.service_signer {
+            None => *authority == self.distributor_authority,
+            Some(ServiceSigner { key, crank: true }) => *authority == key,
+            Some(ServiceSigner { key, crank: false }) => {
+                *authority == self.distributor_authority && service_signer == Some(key)
+            },
+        }
+    }
+
+    /// Fails unless the threshold of the co-signers, if there are any, signed the instruction
+    pub fn check_cosigners(&self, accounts: &[AccountInfo]) -> Result<()> {
+        if let Some(cosigners) = &self.cosigners {
+            require_gte!(
+                cosigners.signed(accounts),
+                cosigners.threshold as usize,
+                DistributorError::MissingCosignatures
+            );
+        }
+        Ok(())
+    }
+
+    /// Protocol fee of a round paying its shares, zero without a fee collector
+    pub fn protocol_fee(&self) -> Result<u64> {
+        self.protocol_fee_of(self.payout_sum()?)
+    }
+
+    /// Protocol fee of a round paying out `amount`, zero without a fee collector
+    pub fn protocol_fee_of(&self, amount: u64) -> Result<u64> {
+        if self.fee_collector.is_none() {
+            return Ok(0);
+        }
+        u64::try_from(amount as u128 * self.fee_bps as u128 / 10_000)
+            .map_err(|_| error!(DistributorError::ArithmeticOverflow))
+    }
+
+    /// The protocol fee of a round paying its shares is taken out of the burned shares, it can't exceed them
+    pub fn validate_protocol_fee(&self) -> Result<()> {
+        require_gte!(
+            self.terminal_amount()?,
+            self.protocol_fee()?,
+            DistributorError::InvalidProtocolFee
+        );
+        Ok(())
+    }
+
+    /// Associated token account of the fee collector for the distributed mint
+    pub fn fee_account(&self, token_program: &Pubkey) -> Option<Pubkey> {
+        self.fee_collector.map(|fee_collector| {
+            get_associated_token_address_with_program_id(&fee_collector, &self.mint, token_program)
+        })
+    }
+
+    /// Share size in whole tokens, for display only
+    pub fn ui_share_size(&self) -> f64 {
+        self.share_size as f64 / 10f64.powi(self.decimals as i32)
+    }
+
+    pub fn validate_shares(
+        share_size: u64,
+        number_of_shares: u64,
+        burn_shares: u64,
+        distribution_mode: DistributionMode,
+        accounts_per_winner: usize,
+    ) -> Result<()> {
+        require_gt!(share_size, 0, DistributorError::InvalidParameters);
+        // at least one share has to be paid
+        require_gt!(number_of_shares, burn_shares, DistributorError::InvalidParameters);
+        require_gte!(
+            distribution_mode.max_winners(accounts_per_winner),
+            number_of_shares - burn_shares,
+            DistributorError::TooManyShares
+        );
+        require!(
+            share_size.checked_mul(number_of_shares).is_some(),
+            DistributorError::InvalidParameters
+        );
+        Ok(())
+    }
+
+    /// Rejects a share size with more than `share_size_decimals` decimal places in the UI amount of a mint with
+    /// `decimals`, it has to be a multiple of `10^(decimals - share_size_decimals)` base units
+    pub fn validate_share_size_decimals(share_size: u64, decimals: u8, share_size_decimals: u8) -> Result<()> {
+        require_gte!(
+            decimals,
+            share_size_decimals,
+            DistributorError::InvalidShareSizeDecimals
+        );
+        let unit = 10u64
+            .checked_pow((decimals - share_size_decimals) as u32)
+            .ok_or(DistributorError::InvalidShareSizeDecimals)?;
+        require_eq!(share_size % unit, 0, DistributorError::InvalidShareSizeDecimals);
+        Ok(())
+    }
+
+    pub fn validate_metadata(name: &str, uri: &str) -> Result<()> {
+        require_gte!(MAX_NAME_LEN, name.len(), DistributorError::MetadataTooLong);
+        require_gte!(MAX_URI_LEN, uri.len(), DistributorError::MetadataTooLong);
+        Ok(())
+    }
+
+    /// Rejects the deposits of the depositors without an allowance while the allowlist is enabled, the seeds of the
+    /// allowance bind it to the depositor
+    pub fn check_depositor(&self, deposit_allowance: Option<&Account<DepositAllowance>>) -> Result<()> {
+        require!(
+            !self.deposit_allowlist || deposit_allowance.is_some(),
+            DistributorError::DepositorNotAllowed
+        );
+        Ok(())
+    }
+
+    /// Amounts of the winners splitting the paid shares of the round by `weights` and the remainder of the split,
+    /// see `RoundingPolicy::split`. Wrapped SOL can't be burned, so its remainder can't be burned either
+    pub fn split_weighted(&self, weights: &[u64]) -> Result<(Vec<u64>, i64)> {
+        require_eq!(
+            weights.len() as u64,
+            self.paid_shares(),
+            DistributorError::MissingRemainingAccounts
+        );
+        require!(
+            !self.is_native() || self.rounding_policy != RoundingPolicy::FloorBurn,
+            DistributorError::InvalidRoundingPolicy
+        );
+        let total = shares_amount(self.share_size, self.paid_shares())?;
+        self.rounding_policy.split(total, weights)
+    }
+
+    /// Amounts of the holders splitting the `vault_amount` by `weights` like a dividend and the remainder of the
+    /// split, see `RoundingPolicy::split`. The trigger bonus owed to the last depositor and the protocol fee charged
+    /// on top of the payouts are kept in the vault
+    pub fn split_dividend(&self, vault_amount: u64, weights: &[u64]) -> Result<(Vec<u64>, i64)> {
+        require_eq!(
+            weights.len() as u64,
+            self.paid_shares(),
+            DistributorError::MissingRemainingAccounts
+        );
+        require!(
+            !self.is_native() || self.rounding_policy != RoundingPolicy::FloorBurn,
+            DistributorError::InvalidRoundingPolicy
+        );
+        let trigger_bonus = match self.last_depositor {
+            Some(_) => self.trigger_bonus.unwrap_or(0),
+            None => 0,
+        };
+        let available = vault_amount.saturating_sub(trigger_bonus);
+        let total = available
+            .checked_sub(self.protocol_fee_of(available)?)
+            .ok_or(DistributorError::InvalidProtocolFee)?;
+        self.rounding_policy.split(total, weights)
+    }
+
+    /// Whether the distributor completed its `max_rounds` and only its withdraw and close paths remain
+    pub fn is_finished(&self) -> bool {
+        self.max_rounds.is_some_and(|max_rounds| self.round >= max_rounds)
+    }
+
+    /// Rejects starting `rounds` more rounds beyond `max_rounds`
+    pub fn check_rounds_left(&self, rounds: u64) -> Result<()> {
+        if let Some(max_rounds) = self.max_rounds {
+            let last = self
+                .round
+                .checked_add(rounds)
+                .ok_or(DistributorError::ArithmeticOverflow)?;
+            require_gte!(max_rounds, last, DistributorError::MaxRoundsReached);
+        }
+        Ok(())
+    }
+
+    /// Rejects a round started before the cooldown since the last round is over
+    pub fn check_interval(&self, now: i64) -> Result<()> {
+        require_gte!(
+            now,
+            self.last_round_at.saturating_add(self.min_interval_seconds),
+            DistributorError::IntervalNotElapsed
+        );
+        Ok(())
+    }
+
+    /// Rechecks the winners of a round against the cap of the distribution mode, an option adding accounts per
+    /// winner lowers the cap of the single transaction rounds
+    pub fn validate_winner_accounts(&self) -> Result<()> {
+        Self::validate_shares(
+            self.share_size,
+            self.number_of_shares,
+            self.burn_shares,
+            self.distribution_mode,
+            accounts_per_winner(self),
+        )
+    }
+
+    /// Checks the invariants the instructions maintain, a violation means the account is corrupted
+    pub fn validate(&self) -> Result<()> {
+        self.validate_winner_accounts()?;
+        Self::validate_shares(
+            self.initial_share_size,
+            self.initial_number_of_shares,
+            self.burn_shares,
+            self.distribution_mode,
+            accounts_per_winner(self),
+        )?;
+        require!(
+            self.remainder_policy.is_valid() && !(self.is_native() && self.remainder_policy.burns()),
+            DistributorError::InvalidRemainderPolicy
+        );
+        require_gt!(self.claim_period, 0, DistributorError::InvalidParameters);
+        require_gte!(self.min_interval_seconds, 0, DistributorError::InvalidParameters);
+        self.validate_threshold()
+    }
+}
+
+#[derive(Accounts)]
+pub struct CreateReplacementVault<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub distributor_authority: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = distributor_authority,
+        has_one = mint,
+        seeds = [
+            mint.key().as_ref(),
+            distributor_state.marker_mint.as_ref(),
+            distributor_state.initial_share_size.to_le_bytes().as_ref(),
+            distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump = distributor_state.distributor_state_bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = payer,
+        seeds = [
+            DistributorState::VAULT_SEED_PREFIX,
+            distributor_state.key().as_ref(),
+            (distributor_state.vault_nonce + 1).to_le_bytes().as_ref()
+        ],
+        bump,
+        token::mint = mint,
+        token::authority = distributor_state,
+    )]
+    pub replacement_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateVault<'info> {
+    pub distributor_authority: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = distributor_authority,
+        has_one = mint,
+        has_one = vault,
+        constraint = distributor_state.replacement_vault == Some(replacement_vault.key())
+            @ DistributorError::InvalidParameters,
+        seeds = [
+            mint.key().as_ref(),
+            distributor_state.marker_mint.as_ref(),
+            distributor_state.initial_share_size.to_le_bytes().as_ref(),
+            distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump = distributor_state.distributor_state_bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, token::mint = mint)]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(
+        mut,
+        seeds = [
+            DistributorState::VAULT_SEED_PREFIX,
+            distributor_state.key().as_ref(),
+            (distributor_state.vault_nonce + 1).to_le_bytes().as_ref()
+        ],
+        bump,
+        token::mint = mint,
+        token::authority = distributor_state,
+    )]
+    pub replacement_vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct EmergencyWithdraw<'info> {
+    pub distributor_authority: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = distributor_authority,
+        has_one = mint,
+        has_one = vault,
+        seeds = [
+            mint.key().as_ref(),
+            distributor_state.marker_mint.as_ref(),
+            distributor_state.initial_share_size.to_le_bytes().as_ref(),
+            distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump = distributor_state.distributor_state_bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, token::mint = mint)]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, token::mint = mint)]
+    pub destination: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct MigrateState<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub distributor_authority: Signer<'info>,
+
+    /// CHECK: an older state doesn't deserialize before it grows, the instruction checks the owner, the
+    /// discriminator, the authority and the address itself
+    #[account(mut, owner = crate::ID)]
+    pub distributor_state: UncheckedAccount<'info>,
+
+    /// Mint of the distributor, the decimals of a state of the first layout are read from it
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CloseDistributor<'info> {
+    pub distributor_authority: Signer<'info>,
+    pub co_authority: Option<Signer<'info>>,
+
+    #[account(
+        mut,
+        close = rent_recipient,
+        has_one = distributor_authority,
+        has_one = vault,
+        constraint = distributor_state.co_authority.is_none()
+            || distributor_state.co_authority == co_authority.as_ref().map(|co_authority| co_authority.key())
+            @ DistributorError::MissingCoAuthority,
+        constraint = distributor_state.paused || distributor_state.is_finished() @ DistributorError::NotPaused,
+        seeds = [
+            distributor_state.mint.as_ref(),
+            distributor_state.marker_mint.as_ref(),
+            distributor_state.initial_share_size.to_le_bytes().as_ref(),
+            distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump = distributor_state.distributor_state_bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    #[account(mut)]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// CHECK: vesting escrow of the distributor, closed if it was opened, see `close_distributor`
+    #[account(mut, seeds = [VESTING_ESCROW_SEED, distributor_state.key().as_ref()], bump)]
+    pub vesting_escrow: UncheckedAccount<'info>,
+
+    /// CHECK: fee vault of the distributor, closed if it was opened, see `close_distributor`
+    #[account(mut, seeds = [FeeVault::SEED_PREFIX, distributor_state.key().as_ref()], bump)]
+    pub fee_vault: UncheckedAccount<'info>,
+
+    /// CHECK: receives the rent of the vault and of the state
+    #[account(mut)]
+    pub rent_recipient: UncheckedAccount<'info>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAuthority<'info> {
+    pub pending_authority: Signer<'info>,
+
+    #[account(
+        mut,
+        constraint = distributor_state.pending_authority == Some(pending_authority.key())
+            @ DistributorError::NotPendingAuthority,
+        seeds = [
+            distributor_state.mint.as_ref(),
+            distributor_state.marker_mint.as_ref(),
+            distributor_state.initial_share_size.to_le_bytes().as_ref(),
+            distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump = distributor_state.distributor_state_bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default)]
+pub struct DistributeArgs {
+    /// Round the transaction was built for
+    pub expected_round: u64,
+    /// Amount of every winner as in `distribute_weighted`, `None` pays `share_size` to each as `distribute` does
+    pub amounts: Option<Vec<u64>>,
+    /// Caller notes of the winners as in `distribute`
+    pub notes: Vec<[u8; 32]>,
+    /// Slot of the holders snapshot the winners were drawn from, emitted in `DistributeEvent`
+    pub snapshot_slot: Option<u64>,
+    /// Weights of the winners in the remaining accounts order, the paid shares of the round are split in their
+    /// proportion. Can't be passed along with `amounts`
+    pub weights: Option<Vec<u64>>,
+    /// Weights of the winners are their marker balances, the marker accounts are required by
+    /// `min_marker_balance`. Can't be passed along with `amounts` or `weights`
+    pub pro_rata: bool,
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Debug, Default)]
+pub struct UpdateConfigArgs {
+    pub share_size: Option<u64>,
+    pub number_of_shares: Option<u64>,
+    pub claim_period: Option<i64>,
+    pub unclaimed_policy: Option<UnclaimedPolicy>,
+    pub min_interval_seconds: Option<i64>,
+    pub min_deposit: Option<u64>,
+    pub referral_bps: Option<u16>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateConfig<'info> {
+    pub distributor_authority: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = distributor_authority,
+        seeds = [
+            distributor_state.mint.as_ref(),
+            distributor_state.marker_mint.as_ref(),
+            distributor_state.initial_share_size.to_le_bytes().as_ref(),
+            distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump = distributor_state.distributor_state_bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+}
+
+#[derive(Accounts)]
+pub struct SetMetadata<'info> {
+    pub distributor_authority: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = distributor_authority,
+        seeds = [
+            distributor_state.mint.as_ref(),
+            distributor_state.marker_mint.as_ref(),
+            distributor_state.initial_share_size.to_le_bytes().as_ref(),
+            distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump = distributor_state.distributor_state_bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    #[account(
+        mut,
+        has_one = distributor_state,
+        seeds = [DistributorMetadata::SEED_PREFIX, distributor_state.key().as_ref()],
+        bump = distributor_metadata.bump
+    )]
+    pub distributor_metadata: Option<Account<'info, DistributorMetadata>>,
+}
+
+/// `UpdateConfig` which also requires the co-authority of the distributor if it has one
+#[derive(Accounts)]
+pub struct SensitiveConfig<'info> {
+    pub distributor_authority: Signer<'info>,
+    pub co_authority: Option<Signer<'info>>,
+
+    #[account(
+        mut,
+        has_one = distributor_authority,
+        constraint = distributor_state.co_authority.is_none()
+            || distributor_state.co_authority == co_authority.as_ref().map(|co_authority| co_authority.key())
+            @ DistributorError::MissingCoAuthority,
+        seeds = [
+            distributor_state.mint.as_ref(),
+            distributor_state.marker_mint.as_ref(),
+            distributor_state.initial_share_size.to_le_bytes().as_ref(),
+            distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump = distributor_state.distributor_state_bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+}
+
+#[derive(Accounts)]
+pub struct OpenDistributorMetadata<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub distributor_authority: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = distributor_authority,
+        seeds = [
+            distributor_state.mint.as_ref(),
+            distributor_state.marker_mint.as_ref(),
+            distributor_state.initial_share_size.to_le_bytes().as_ref(),
+            distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump = distributor_state.distributor_state_bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + DistributorMetadata::INIT_SPACE,
+        seeds = [DistributorMetadata::SEED_PREFIX, distributor_state.key().as_ref()],
+        bump
+    )]
+    pub distributor_metadata: Account<'info, DistributorMetadata>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateDistributorMetadata<'info> {
+    pub distributor_authority: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = distributor_authority,
+        seeds = [
+            distributor_state.mint.as_ref(),
+            distributor_state.marker_mint.as_ref(),
+            distributor_state.initial_share_size.to_le_bytes().as_ref(),
+            distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump = distributor_state.distributor_state_bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    #[account(
+        mut,
+        has_one = distributor_state,
+        seeds = [DistributorMetadata::SEED_PREFIX, distributor_state.key().as_ref()],
+        bump = distributor_metadata.bump
+    )]
+    pub distributor_metadata: Account<'info, DistributorMetadata>,
+}
+
+#[derive(Accounts)]
+pub struct CloseDistributorMetadata<'info> {
+    #[account(mut)]
+    pub distributor_authority: Signer<'info>,
+
+    #[account(
+        has_one = distributor_authority,
+        seeds = [
+            distributor_state.mint.as_ref(),
+            distributor_state.marker_mint.as_ref(),
+            distributor_state.initial_share_size.to_le_bytes().as_ref(),
+            distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump = distributor_state.distributor_state_bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    #[account(
+        mut,
+        close = distributor_authority,
+        has_one = distributor_state,
+        seeds = [DistributorMetadata::SEED_PREFIX, distributor_state.key().as_ref()],
+        bump = distributor_metadata.bump
+    )]
+    pub distributor_metadata: Account<'info, DistributorMetadata>,
+}
+
+/// Split of the burned shares in basis points, the parts have to sum up to the whole of them
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TerminalSplit {
+    pub burn_bps: u16,
+    pub treasury_bps: u16,
+    pub rollover_bps: u16,
+}
+
+impl TerminalSplit {
+    pub const BPS: u16 = 10_000;
+
+    pub fn is_valid(&self) -> bool {
+        self.burn_bps as u32 + self.treasury_bps as u32 + self.rollover_bps as u32 == Self::BPS as u32
+    }
+
+    /// Returns (burn, treasury, rollover) parts of the amount, rounding leftovers go to rollover
+    pub fn apply(&self, amount: u64) -> (u64, u64, u64) {
+        let part = |bps: u16| (amount as u128 * bps as u128 / Self::BPS as u128) as u64;
+        let burn = part(self.burn_bps);
+        let treasury = part(self.treasury_bps);
+        (burn, treasury, amount - burn - treasury)
+    }
+}
+
+/// What happens to the burned shares which are not paid out to winners
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RemainderPolicy {
+    Burn,
+    /// Transfer to the token account
+    SendToTreasury(Pubkey),
+    /// Keep in the vault for the next round
+    Rollover,
+    /// Split between burn, treasury and rollover
+    Split {
+        split: TerminalSplit,
+        treasury: Pubkey,
+    },
+}
+
+impl RemainderPolicy {
+    pub fn is_valid(&self) -> bool {
+        match self {
+            RemainderPolicy::Burn | RemainderPolicy::Rollover => true,
+            RemainderPolicy::SendToTreasury(treasury) => *treasury != Pubkey::default(),
+            RemainderPolicy::Split { split, treasury } => {
+                split.is_valid() && (split.treasury_bps == 0 || *treasury != Pubkey::default())
+            },
+        }
+    }
+
+    pub fn burns(&self) -> bool {
+        match self {
+            RemainderPolicy::Burn => true,
+            RemainderPolicy::Split { split, .. } => split.burn_bps > 0,
+            RemainderPolicy::SendToTreasury(_) | RemainderPolicy::Rollover => false,
+        }
+    }
+
+    /// Token account receiving the treasury part
+    pub fn treasury(&self) -> Option<Pubkey> {
+        match self {
+            RemainderPolicy::SendToTreasury(treasury) => Some(*treasury),
+            RemainderPolicy::Split { split, treasury } if split.treasury_bps > 0 => Some(*treasury),
+            _ => None,
+        }
+    }
+
+    /// Returns (burn, treasury, rollover) parts of the amount
+    pub fn apply(&self, amount: u64) -> (u64, u64, u64) {
+        match self {
+            RemainderPolicy::Burn => (amount, 0, 0),
+            RemainderPolicy::SendToTreasury(_) => (0, amount, 0),
+            RemainderPolicy::Rollover => (0, 0, amount),
+            RemainderPolicy::Split { split, .. } => split.apply(amount),
+        }
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct Deposit<'info> {
+    #[account(
+        mut,
+        has_one = mint,
+        constraint = !distributor_state.paused @ DistributorError::Paused,
+        has_one = vault,
+        seeds = [
+            mint.key().as_ref(),
+            distributor_state.marker_mint.as_ref(),
+            distributor_state.initial_share_size.to_le_bytes().as_ref(),
+            distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump = distributor_state.distributor_state_bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        constraint = !vault.is_frozen() @ DistributorError::VaultFrozen,
+        constraint = vault.delegate.is_none() @ DistributorError::VaultDelegated,
+        constraint = vault.close_authority.is_none() @ DistributorError::VaultCloseAuthoritySet,
+        token::mint = mint,
+        token::authority = distributor_state,
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub authority: Signer<'info>,
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = authority,
+    )]
+    pub token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Accounting record of the depositor, skipped if not passed
+    #[account(
+        mut,
+        seeds = [
+            DepositorRecord::SEED_PREFIX,
+            distributor_state.key().as_ref(),
+            authority.key().as_ref()
+        ],
+        bump = depositor_record.bump,
+    )]
+    pub depositor_record: Option<Account<'info, DepositorRecord>>,
+
+    /// Allowance of the depositor, required while the deposit allowlist is enabled
+    #[account(
+        seeds = [
+            DepositAllowance::SEED_PREFIX,
+            distributor_state.key().as_ref(),
+            authority.key().as_ref()
+        ],
+        bump = deposit_allowance.bump,
+    )]
+    pub deposit_allowance: Option<Account<'info, DepositAllowance>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct DepositNative<'info> {
+    #[account(
+        mut,
+        has_one = vault,
+        constraint = !distributor_state.paused @ DistributorError::Paused,
+        constraint = distributor_state.is_native() @ DistributorError::NotNativeMint,
+        seeds = [
+            distributor_state.mint.as_ref(),
+            distributor_state.marker_mint.as_ref(),
+            distributor_state.initial_share_size.to_le_bytes().as_ref(),
+            distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump = distributor_state.distributor_state_bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    #[account(
+        mut,
+        constraint = !vault.is_frozen() @ DistributorError::VaultFrozen,
+        constraint = vault.delegate.is_none() @ DistributorError::VaultDelegated,
+        constraint = vault.close_authority.is_none() @ DistributorError::VaultCloseAuthoritySet,
+        token::authority = distributor_state,
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    /// Accounting record of the depositor, skipped if not passed
+    #[account(
+        mut,
+        seeds = [
+            DepositorRecord::SEED_PREFIX,
+            distributor_state.key().as_ref(),
+            authority.key().as_ref()
+        ],
+        bump = depositor_record.bump,
+    )]
+    pub depositor_record: Option<Account<'info, DepositorRecord>>,
+
+    /// Allowance of the depositor, required while the deposit allowlist is enabled
+    #[account(
+        seeds = [
+            DepositAllowance::SEED_PREFIX,
+            distributor_state.key().as_ref(),
+            authority.key().as_ref()
+        ],
+        bump = deposit_allowance.bump,
+    )]
+    pub deposit_allowance: Option<Account<'info, DepositAllowance>>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Accounts of `deposit_with_delegate`, there is no depositor signature: the token account approved the distributor
+/// state as its delegate
+#[event_cpi]
+#[derive(Accounts)]
+pub struct DepositWithDelegate<'info> {
+    #[account(
+        mut,
+        has_one = mint,
+        constraint = !distributor_state.paused @ DistributorError::Paused,
+        has_one = vault,
+        seeds = [
+            mint.key().as_ref(),
+            distributor_state.marker_mint.as_ref(),
+            distributor_state.initial_share_size.to_le_bytes().as_ref(),
+            distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump = distributor_state.distributor_state_bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        constraint = !vault.is_frozen() @ DistributorError::VaultFrozen,
+        constraint = vault.delegate.is_none() @ DistributorError::VaultDelegated,
+        constraint = vault.close_authority.is_none() @ DistributorError::VaultCloseAuthoritySet,
+        token::mint = mint,
+        token::authority = distributor_state,
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = token_account.delegate.contains(&distributor_state.key()) @ DistributorError::NotDelegated,
+        token::mint = mint,
+    )]
+    pub token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Accounting record of the owner of the token account, skipped if not passed
+    #[account(
+        mut,
+        seeds = [
+            DepositorRecord::SEED_PREFIX,
+            distributor_state.key().as_ref(),
+            token_account.owner.as_ref()
+        ],
+        bump = depositor_record.bump,
+    )]
+    pub depositor_record: Option<Account<'info, DepositorRecord>>,
+
+    /// Allowance of the depositor, required while the deposit allowlist is enabled
+    #[account(
+        seeds = [
+            DepositAllowance::SEED_PREFIX,
+            distributor_state.key().as_ref(),
+            token_account.owner.as_ref()
+        ],
+        bump = deposit_allowance.bump,
+    )]
+    pub deposit_allowance: Option<Account<'info, DepositAllowance>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct OpenDepositorRecord<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [
+            distributor_state.mint.as_ref(),
+            distributor_state.marker_mint.as_ref(),
+            distributor_state.initial_share_size.to_le_bytes().as_ref(),
+            distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump = distributor_state.distributor_state_bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + DepositorRecord::INIT_SPACE,
+        seeds = [
+            DepositorRecord::SEED_PREFIX,
+            distributor_state.key().as_ref(),
+            authority.key().as_ref()
+        ],
+        bump
+    )]
+    pub depositor_record: Account<'info, DepositorRecord>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Wallets which can't receive shares, sorted
+#[account]
+pub struct Blocklist {
+    pub distributor_state: Pubkey,
+    pub bump: u8,
+    pub wallets: Vec<Pubkey>,
+}
+
+impl Blocklist {
+    pub const SEED_PREFIX: &'static [u8] = b"blocklist";
+
+    pub fn space(wallets: usize) -> usize {
+        8 + 32 + 1 + 4 + wallets * 32
+    }
+}
+
+/// Human-readable identity of a distributor for wallets and explorers, the campaign JSON is at `uri`. The name and
+/// the uri mirror `DistributorState::name` and `DistributorState::uri`, every instruction setting them sets both
+#[account]
+#[derive(InitSpace)]
+pub struct DistributorMetadata {
+    pub distributor_state: Pubkey,
+    pub bump: u8,
+    #[max_len(MAX_NAME_LEN)]
+    pub name: String,
+    #[max_len(MAX_SYMBOL_LEN)]
+    pub symbol: String,
+    #[max_len(MAX_URI_LEN)]
+    pub uri: String,
+}
+
+impl DistributorMetadata {
+    pub const SEED_PREFIX: &'static [u8] = b"distributor_metadata";
+
+    pub fn validate(name: &str, symbol: &str, uri: &str) -> Result<()> {
+        DistributorState::validate_metadata(name, uri)?;
+        require_gte!(MAX_SYMBOL_LEN, symbol.len(), DistributorError::MetadataTooLong);
+        Ok(())
+    }
+
+    /// Copies the name and the uri of the distributor
+    pub fn sync(&mut self, distributor_state: &DistributorState) {
+        self.name = distributor_state.name.clone();
+        self.uri = distributor_state.uri.clone();
+    }
+}
+
+/// Lamport fees of the distributor, kept apart from the payer and the distribution vault until collected
+#[account]
+#[derive(InitSpace)]
+pub struct FeeVault {
+    pub distributor_state: Pubkey,
+    pub bump: u8,
+    /// Lamports paid into the vault over its lifetime
+    pub collected: u64,
+}
+
+impl FeeVault {
+    pub const SEED_PREFIX: &'static [u8] = b"fee_vault";
+}
+
+#[derive(Accounts)]
+pub struct OpenFeeVault<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub distributor_authority: Signer<'info>,
+
+    #[account(
+        has_one = distributor_authority,
+        seeds = [
+            distributor_state.mint.as_ref(),
+            distributor_state.marker_mint.as_ref(),
+            distributor_state.initial_share_size.to_le_bytes().as_ref(),
+            distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump = distributor_state.distributor_state_bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + FeeVault::INIT_SPACE,
+        seeds = [FeeVault::SEED_PREFIX, distributor_state.key().as_ref()],
+        bump
+    )]
+    pub fee_vault: Account<'info, FeeVault>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RentBuffer<'info> {
+    #[account(mut)]
+    pub distributor_authority: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = distributor_authority,
+        seeds = [
+            distributor_state.mint.as_ref(),
+            distributor_state.marker_mint.as_ref(),
+            distributor_state.initial_share_size.to_le_bytes().as_ref(),
+            distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump = distributor_state.distributor_state_bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct CollectFees<'info> {
+    #[account(constraint = distributor_state.fee_collector == Some(fee_collector.key()) @ DistributorError::NotFeeCollector)]
+    pub fee_collector: Signer<'info>,
+
+    #[account(
+        seeds = [
+            distributor_state.mint.as_ref(),
+            distributor_state.marker_mint.as_ref(),
+            distributor_state.initial_share_size.to_le_bytes().as_ref(),
+            distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump = distributor_state.distributor_state_bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    #[account(
+        mut,
+        has_one = distributor_state,
+        seeds = [FeeVault::SEED_PREFIX, distributor_state.key().as_ref()],
+        bump = fee_vault.bump,
+    )]
+    pub fee_vault: Account<'info, FeeVault>,
+
+    /// CHECK: receives the collected lamports
+    #[account(mut)]
+    pub destination: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+#[instruction(depositor: Pubkey)]
+pub struct AllowDepositor<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub distributor_authority: Signer<'info>,
+
+    #[account(
+        has_one = distributor_authority,
+        seeds = [
+            distributor_state.mint.as_ref(),
+            distributor_state.marker_mint.as_ref(),
+            distributor_state.initial_share_size.to_le_bytes().as_ref(),
+            distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump = distributor_state.distributor_state_bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + DepositAllowance::INIT_SPACE,
+        seeds = [DepositAllowance::SEED_PREFIX, distributor_state.key().as_ref(), depositor.as_ref()],
+        bump
+    )]
+    pub deposit_allowance: Account<'info, DepositAllowance>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct DisallowDepositor<'info> {
+    pub distributor_authority: Signer<'info>,
+
+    #[account(
+        has_one = distributor_authority,
+        seeds = [
+            distributor_state.mint.as_ref(),
+            distributor_state.marker_mint.as_ref(),
+            distributor_state.initial_share_size.to_le_bytes().as_ref(),
+            distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump = distributor_state.distributor_state_bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    #[account(
+        mut,
+        close = rent_recipient,
+        has_one = distributor_state,
+        seeds = [
+            DepositAllowance::SEED_PREFIX,
+            distributor_state.key().as_ref(),
+            deposit_allowance.depositor.as_ref()
+        ],
+        bump = deposit_allowance.bump,
+    )]
+    pub deposit_allowance: Account<'info, DepositAllowance>,
+
+    /// CHECK: receives the rent of the allowance
+    #[account(mut)]
+    pub rent_recipient: UncheckedAccount<'info>,
+}
+
+#[derive(Accounts)]
+pub struct OpenBlocklist<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub distributor_authority: Signer<'info>,
+
+    #[account(
+        has_one = distributor_authority,
+        seeds = [
+            distributor_state.mint.as_ref(),
+            distributor_state.marker_mint.as_ref(),
+            distributor_state.initial_share_size.to_le_bytes().as_ref(),
+            distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump = distributor_state.distributor_state_bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = Blocklist::space(0),
+        seeds = [Blocklist::SEED_PREFIX, distributor_state.key().as_ref()],
+        bump
+    )]
+    pub blocklist: Account<'info, Blocklist>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct AddToBlocklist<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub distributor_authority: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = distributor_authority,
+        seeds = [
+            distributor_state.mint.as_ref(),
+            distributor_state.marker_mint.as_ref(),
+            distributor_state.initial_share_size.to_le_bytes().as_ref(),
+            distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump = distributor_state.distributor_state_bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    #[account(
+        mut,
+        seeds = [Blocklist::SEED_PREFIX, distributor_state.key().as_ref()],
+        bump = blocklist.bump,
+        realloc = Blocklist::space(blocklist.wallets.len() + 1),
+        realloc::payer = payer,
+        realloc::zero = false,
+    )]
+    pub blocklist: Account<'info, Blocklist>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RemoveFromBlocklist<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub distributor_authority: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = distributor_authority,
+        seeds = [
+            distributor_state.mint.as_ref(),
+            distributor_state.marker_mint.as_ref(),
+            distributor_state.initial_share_size.to_le_bytes().as_ref(),
+            distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump = distributor_state.distributor_state_bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    #[account(
+        mut,
+        seeds = [Blocklist::SEED_PREFIX, distributor_state.key().as_ref()],
+        bump = blocklist.bump,
+        realloc = Blocklist::space(blocklist.wallets.len().saturating_sub(1)),
+        realloc::payer = payer,
+        realloc::zero = false,
+    )]
+    pub blocklist: Account<'info, Blocklist>,
+
+    pub system_program: Program<'info, System>,
+}
+
+/// Deposits of an authority made through `deposit`, direct transfers to the vault aren't accounted
+#[account]
+#[derive(InitSpace)]
+pub struct DepositorRecord {
+    pub distributor_state: Pubkey,
+    pub authority: Pubkey,
+    pub total_deposited: u64,
+    pub deposits: u64,
+    pub last_deposit_slot: u64,
+    /// Round the last deposit was made in, it funded this round or a later one
+    pub last_deposit_round: u64,
+    pub bump: u8,
+}
+
+impl DepositorRecord {
+    pub const SEED_PREFIX: &'static [u8] = b"depositor";
+
+    pub fn record_deposit(&mut self, amount: u64, round: u64) -> Result<()> {
+        self.total_deposited = self.total_deposited.saturating_add(amount);
+        self.deposits += 1;
+        self.last_deposit_slot = Clock::get()?.slot;
+        self.last_deposit_round = round;
+        Ok(())
+    }
+}
+
+/// Permission of a depositor to deposit while the deposit allowlist of the distributor is enabled
+#[account]
+#[derive(InitSpace)]
+pub struct DepositAllowance {
+    pub distributor_state: Pubkey,
+    pub depositor: Pubkey,
+    pub bump: u8,
+}
+
+impl DepositAllowance {
+    pub const SEED_PREFIX: &'static [u8] = b"deposit_allowance";
+}
+
+impl<'a, 'b, 'c, 'info> From<&mut Deposit<'info>> for CpiContext<'a, 'b, 'c, 'info, TransferChecked<'info>> {
+    fn from(accounts: &mut Deposit<'info>) -> CpiContext<'a, 'b, 'c, 'info, TransferChecked<'info>> {
+        let cpi_accounts = TransferChecked {
+            from: accounts.token_account.to_account_info(),
+            mint: accounts.mint.to_account_info(),
+            to: accounts.vault.to_account_info(),
+            authority: accounts.authority.to_account_info(),
+        };
+        let cpi_program = accounts.token_program.to_account_info();
+        CpiContext::new(cpi_program, cpi_accounts)
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct Distribute<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// The authority, or the service signer in its crank mode
+    pub distributor_authority: Signer<'info>,
+    /// Backend identity co-signing the rounds, required if the distributor has a service signer out of crank mode
+    pub service_signer: Option<Signer<'info>>,
+
+    #[account(
+        mut,
+        constraint = distributor_state.authorizes_round(
+            distributor_authority.key,
+            service_signer.as_ref().map(|service_signer| service_signer.key())
+        ) @ DistributorError::UnauthorizedRound,
+        has_one = mint,
+        has_one = vault,
+        constraint = !distributor_state.paused @ DistributorError::Paused,
+        constraint = distributor_state.decimals == mint.decimals @ DistributorError::DecimalsMismatch,
+        seeds = [
+                mint.key().as_ref(),
+                distributor_state.marker_mint.as_ref(),
+                distributor_state.initial_share_size.to_le_bytes().as_ref(),
+                distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump = distributor_state.distributor_state_bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    #[account(mut)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        constraint = !vault.is_frozen() @ DistributorError::VaultFrozen,
+        constraint = vault.delegate.is_none() @ DistributorError::VaultDelegated,
+        constraint = vault.close_authority.is_none() @ DistributorError::VaultCloseAuthoritySet,
+        token::mint = mint,
+        token::authority = distributor_state,
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = distributor_state.remainder_policy.treasury() == Some(treasury.key())
+            @ DistributorError::InvalidTreasury,
+        token::mint = mint,
+    )]
+    pub treasury: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// History record of the round, skipped if not passed
+    #[account(
+        init,
+        payer = payer,
+        space = DistributionRound::space(distributor_state.paid_shares()),
+        seeds = [distributor_state.key().as_ref(), distributor_state.round.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub round: Option<Account<'info, DistributionRound>>,
+
+    /// Progress account of a chunked round at the current round, the round can't be paid another way while it's open
+    /// CHECK: only its owner is checked
+    #[account(
+        seeds = [
+            RoundProgress::SEED_PREFIX,
+            distributor_state.key().as_ref(),
+            distributor_state.round.to_le_bytes().as_ref()
+        ],
+        bump,
+        constraint = *round_progress.owner != crate::ID @ DistributorError::RoundInProgress,
+    )]
+    pub round_progress: UncheckedAccount<'info>,
+
+    /// VRF draw of the round, its `entrants` is the committed holders count of the round
+    #[account(
+        has_one = distributor_state,
+        seeds = [
+            Draw::SEED_PREFIX,
+            distributor_state.key().as_ref(),
+            distributor_state.round.to_le_bytes().as_ref()
+        ],
+        bump = draw.bump,
+    )]
+    pub draw: Option<Account<'info, Draw>>,
+
+    /// Temporary wrapped SOL account the shares are unwrapped through, required only by a native mint distributor
+    /// CHECK: created and closed by the instruction itself
+    #[account(mut, seeds = [NATIVE_UNWRAP_SEED, distributor_state.key().as_ref()], bump)]
+    pub native_unwrap: Option<UncheckedAccount<'info>>,
+
+    /// Required if any wallet is blocked
+    #[account(seeds = [Blocklist::SEED_PREFIX, distributor_state.key().as_ref()], bump = blocklist.bump)]
+    pub blocklist: Option<Account<'info, Blocklist>>,
+
+    /// Record of the previous round, required while its winners can't win again
+    #[account(
+        seeds = [distributor_state.key().as_ref(), distributor_state.round.saturating_sub(1).to_le_bytes().as_ref()],
+        bump = previous_round.bump,
+    )]
+    pub previous_round: Option<Account<'info, DistributionRound>>,
+
+    /// Associated token account of the fee collector, required if the distributor charges a protocol fee
+    #[account(
+        mut,
+        constraint = distributor_state.fee_account(token_program.key) == Some(fee_account.key())
+            @ DistributorError::InvalidFeeAccount,
+    )]
+    pub fee_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Required if the distributor charges a lamport fee
+    #[account(
+        mut,
+        has_one = distributor_state,
+        seeds = [FeeVault::SEED_PREFIX, distributor_state.key().as_ref()],
+        bump = fee_vault.bump,
+    )]
+    pub fee_vault: Option<Account<'info, FeeVault>>,
+
+    /// Associated token account of the depositor who triggered the round, required while a trigger bonus is owed
+    #[account(
+        mut,
+        constraint = distributor_state.trigger_bonus_account(token_program.key) == Some(trigger_bonus_account.key())
+            @ DistributorError::InvalidTriggerBonusAccount,
+    )]
+    pub trigger_bonus_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Committed draw of the round, required while the distributor requires one
+    #[account(
+        has_one = distributor_state,
+        seeds = [
+            CommittedDraw::SEED_PREFIX,
+            distributor_state.key().as_ref(),
+            distributor_state.round.to_le_bytes().as_ref()
+        ],
+        bump = committed_draw.bump,
+    )]
+    pub committed_draw: Option<Account<'info, CommittedDraw>>,
+
+    /// Required while the shares are vested
+    #[account(mut, seeds = [VESTING_ESCROW_SEED, distributor_state.key().as_ref()], bump)]
+    pub vesting_escrow: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Holders snapshot committed for the round, required while the distributor requires one
+    #[account(
+        has_one = distributor_state,
+        seeds = [
+            HolderSnapshot::SEED_PREFIX,
+            distributor_state.key().as_ref(),
+            distributor_state.round.to_le_bytes().as_ref()
+        ],
+        bump = holder_snapshot.bump,
+    )]
+    pub holder_snapshot: Option<Account<'info, HolderSnapshot>>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+pub struct ValidateWinners<'info> {
+    #[account(
+        has_one = mint,
+        seeds = [
+                mint.key().as_ref(),
+                distributor_state.marker_mint.as_ref(),
+                distributor_state.initial_share_size.to_le_bytes().as_ref(),
+                distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump = distributor_state.distributor_state_bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    /// Required if any wallet is blocked
+    #[account(seeds = [Blocklist::SEED_PREFIX, distributor_state.key().as_ref()], bump = blocklist.bump)]
+    pub blocklist: Option<Account<'info, Blocklist>>,
+
+    /// Record of the previous round, required while its winners can't win again
+    #[account(
+        seeds = [distributor_state.key().as_ref(), distributor_state.round.saturating_sub(1).to_le_bytes().as_ref()],
+        bump = previous_round.bump,
+    )]
+    pub previous_round: Option<Account<'info, DistributionRound>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Payouts of a single distribution, lets anyone verify the history without trusting the backend
+#[account]
+pub struct DistributionRound {
+    pub distributor_state: Pubkey,
+    pub round: u64,
+    pub timestamp: i64,
+    pub payouts: Vec<Payout>,
+    pub burned: u64,
+    pub treasury: u64,
+    pub rollover: u64,
+    pub bump: u8,
+    /// Holders count committed by the draw of the round, `None` if the round had no draw
+    pub holders_count: Option<u64>,
+    /// Merkle root of the holders snapshot the winners were drawn from, `None` if the round had no snapshot
+    pub snapshot_root: Option<[u8; 32]>,
+}
+
+impl DistributionRound {
+    pub fn space(winners: u64) -> usize {
+        8 + 32 + 8 + 8 + 4 + winners as usize * Payout::INIT_SPACE + 8 + 8 + 8 + 1 + 1 + 8 + 1 + 32
+    }
+}
+
+#[derive(AnchorSerialize, AnchorDeserialize, InitSpace, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Payout {
+    pub wallet: Pubkey,
+    pub amount: u64,
+}
+
+#[derive(Accounts)]
+pub struct BeginRound<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// The authority, or the service signer in its crank mode
+    pub distributor_authority: Signer<'info>,
+    /// Backend identity co-signing the rounds, required if the distributor has a service signer out of crank mode
+    pub service_signer: Option<Signer<'info>>,
+
+    #[account(
+        constraint = distributor_state.authorizes_round(
+            distributor_authority.key,
+            service_signer.as_ref().map(|service_signer| service_signer.key())
+        ) @ DistributorError::UnauthorizedRound,
+        has_one = vault,
+        constraint = !distributor_state.paused @ DistributorError::Paused,
+        constraint = distributor_state.distribution_mode == DistributionMode::Chunked
+            @ DistributorError::WrongDistributionMode,
+        seeds = [
+            distributor_state.mint.as_ref(),
+            distributor_state.marker_mint.as_ref(),
+            distributor_state.initial_share_size.to_le_bytes().as_ref(),
+            distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump = distributor_state.distributor_state_bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    #[account(
+        constraint = !vault.is_frozen() @ DistributorError::VaultFrozen,
+        constraint = vault.delegate.is_none() @ DistributorError::VaultDelegated,
+        constraint = vault.close_authority.is_none() @ DistributorError::VaultCloseAuthoritySet,
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + RoundProgress::INIT_SPACE,
+        seeds = [
+            RoundProgress::SEED_PREFIX,
+            distributor_state.key().as_ref(),
+            distributor_state.round.to_le_bytes().as_ref()
+        ],
+        bump,
+    )]
+    pub round_progress: Account<'info, RoundProgress>,
+
+    /// History record of the round, skipped if not passed
+    #[account(
+        init,
+        payer = payer,
+        space = DistributionRound::space(distributor_state.paid_shares()),
+        seeds = [distributor_state.key().as_ref(), distributor_state.round.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub round: Option<Account<'info, DistributionRound>>,
+
+    /// Required if the distributor charges a lamport fee
+    #[account(
+        mut,
+        has_one = distributor_state,
+        seeds = [FeeVault::SEED_PREFIX, distributor_state.key().as_ref()],
+        bump = fee_vault.bump,
+    )]
+    pub fee_vault: Option<Account<'info, FeeVault>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct DistributeChunk<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// The authority, or the service signer in its crank mode
+    pub distributor_authority: Signer<'info>,
+    /// Backend identity co-signing the rounds, required if the distributor has a service signer out of crank mode
+    pub service_signer: Option<Signer<'info>>,
+
+    #[account(
+        mut,
+        constraint = distributor_state.authorizes_round(
+            distributor_authority.key,
+            service_signer.as_ref().map(|service_signer| service_signer.key())
+        ) @ DistributorError::UnauthorizedRound,
+        has_one = mint,
+        has_one = vault,
+        constraint = !distributor_state.paused @ DistributorError::Paused,
+        constraint = distributor_state.decimals == mint.decimals @ DistributorError::DecimalsMismatch,
+        seeds = [
+            mint.key().as_ref(),
+            distributor_state.marker_mint.as_ref(),
+            distributor_state.initial_share_size.to_le_bytes().as_ref(),
+            distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump = distributor_state.distributor_state_bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    #[account(mut)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        constraint = !vault.is_frozen() @ DistributorError::VaultFrozen,
+        constraint = vault.delegate.is_none() @ DistributorError::VaultDelegated,
+        constraint = vault.close_authority.is_none() @ DistributorError::VaultCloseAuthoritySet,
+        token::mint = mint,
+        token::authority = distributor_state,
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        has_one = distributor_state,
+        seeds = [
+            RoundProgress::SEED_PREFIX,
+            distributor_state.key().as_ref(),
+            distributor_state.round.to_le_bytes().as_ref()
+        ],
+        bump = round_progress.bump,
+    )]
+    pub round_progress: Account<'info, RoundProgress>,
+
+    /// History record of the round, skipped if it wasn't created by `begin_round`
+    #[account(
+        mut,
+        seeds = [distributor_state.key().as_ref(), distributor_state.round.to_le_bytes().as_ref()],
+        bump = round.bump,
+    )]
+    pub round: Option<Account<'info, DistributionRound>>,
+
+    /// Temporary wrapped SOL account the shares are unwrapped through, required only by a native mint distributor
+    /// CHECK: created and closed by the instruction itself
+    #[account(mut, seeds = [NATIVE_UNWRAP_SEED, distributor_state.key().as_ref()], bump)]
+    pub native_unwrap: Option<UncheckedAccount<'info>>,
+
+    /// Required if any wallet is blocked
+    #[account(seeds = [Blocklist::SEED_PREFIX, distributor_state.key().as_ref()], bump = blocklist.bump)]
+    pub blocklist: Option<Account<'info, Blocklist>>,
+
+    /// Record of the previous round, required while its winners can't win again
+    #[account(
+        seeds = [distributor_state.key().as_ref(), distributor_state.round.saturating_sub(1).to_le_bytes().as_ref()],
+        bump = previous_round.bump,
+    )]
+    pub previous_round: Option<Account<'info, DistributionRound>>,
+
+    /// Required while the shares are vested
+    #[account(mut, seeds = [VESTING_ESCROW_SEED, distributor_state.key().as_ref()], bump)]
+    pub vesting_escrow: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct FinalizeRound<'info> {
+    /// CHECK: receives the rent of the progress account, checked against the recorded payer
+    #[account(mut, address = round_progress.payer)]
+    pub payer: UncheckedAccount<'info>,
+
+    /// The authority, or the service signer in its crank mode
+    pub distributor_authority: Signer<'info>,
+    /// Backend identity co-signing the rounds, required if the distributor has a service signer out of crank mode
+    pub service_signer: Option<Signer<'info>>,
+
+    #[account(
+        mut,
+        constraint = distributor_state.authorizes_round(
+            distributor_authority.key,
+            service_signer.as_ref().map(|service_signer| service_signer.key())
+        ) @ DistributorError::UnauthorizedRound,
+        has_one = mint,
+        has_one = vault,
+        constraint = !distributor_state.paused @ DistributorError::Paused,
+        seeds = [
+            mint.key().as_ref(),
+            distributor_state.marker_mint.as_ref(),
+            distributor_state.initial_share_size.to_le_bytes().as_ref(),
+            distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump = distributor_state.distributor_state_bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    #[account(mut)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        constraint = !vault.is_frozen() @ DistributorError::VaultFrozen,
+        constraint = vault.delegate.is_none() @ DistributorError::VaultDelegated,
+        constraint = vault.close_authority.is_none() @ DistributorError::VaultCloseAuthoritySet,
+        token::mint = mint,
+        token::authority = distributor_state,
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = distributor_state.remainder_policy.treasury() == Some(treasury.key())
+            @ DistributorError::InvalidTreasury,
+        token::mint = mint,
+    )]
+    pub treasury: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        close = payer,
+        has_one = distributor_state,
+        seeds = [
+            RoundProgress::SEED_PREFIX,
+            distributor_state.key().as_ref(),
+            distributor_state.round.to_le_bytes().as_ref()
+        ],
+        bump = round_progress.bump,
+    )]
+    pub round_progress: Account<'info, RoundProgress>,
+
+    /// History record of the round, skipped if it wasn't created by `begin_round`
+    #[account(
+        mut,
+        seeds = [distributor_state.key().as_ref(), distributor_state.round.to_le_bytes().as_ref()],
+        bump = round.bump,
+    )]
+    pub round: Option<Account<'info, DistributionRound>>,
+
+    /// VRF draw of the round, its `entrants` is the committed holders count of the round
+    #[account(
+        has_one = distributor_state,
+        seeds = [
+            Draw::SEED_PREFIX,
+            distributor_state.key().as_ref(),
+            distributor_state.round.to_le_bytes().as_ref()
+        ],
+        bump = draw.bump,
+    )]
+    pub draw: Option<Account<'info, Draw>>,
+
+    /// Associated token account of the fee collector, required if the distributor charges a protocol fee
+    #[account(
+        mut,
+        constraint = distributor_state.fee_account(token_program.key) == Some(fee_account.key())
+            @ DistributorError::InvalidFeeAccount,
+    )]
+    pub fee_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Associated token account of the depositor who triggered the round, required while a trigger bonus is owed
+    #[account(
+        mut,
+        constraint = distributor_state.trigger_bonus_account(token_program.key) == Some(trigger_bonus_account.key())
+            @ DistributorError::InvalidTriggerBonusAccount,
+    )]
+    pub trigger_bonus_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Extra mint paid to the winners of every round from its own vault, next to the distributor mint
+#[account]
+#[derive(InitSpace)]
+pub struct VaultConfig {
+    pub distributor_state: Pubkey,
+    pub mint: Pubkey,
+    pub vault: Pubkey,
+    /// Amount every winner gets
+    pub share_size: u64,
+    /// Round paid last, `None` until the first one
+    pub last_round: Option<u64>,
+    /// Number of winners of `last_round` paid so far
+    pub paid: u64,
+    pub bump: u8,
+}
+
+impl VaultConfig {
+    pub const SEED_PREFIX: &'static [u8] = b"vault_config";
+    pub const VAULT_SEED_PREFIX: &'static [u8] = b"extra_vault";
+}
+
+#[derive(Accounts)]
+pub struct AddPayoutMint<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub distributor_authority: Signer<'info>,
+
+    #[account(
+        has_one = distributor_authority,
+        seeds = [
+            distributor_state.mint.as_ref(),
+            distributor_state.marker_mint.as_ref(),
+            distributor_state.initial_share_size.to_le_bytes().as_ref(),
+            distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump = distributor_state.distributor_state_bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    #[account(
+        constraint = mint.key() != distributor_state.mint @ DistributorError::InvalidParameters,
+        constraint = mint.key() != spl_token::native_mint::ID @ DistributorError::InvalidParameters,
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + VaultConfig::INIT_SPACE,
+        seeds = [VaultConfig::SEED_PREFIX, distributor_state.key().as_ref(), mint.key().as_ref()],
+        bump,
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(
+        init,
+        payer = payer,
+        seeds = [VaultConfig::VAULT_SEED_PREFIX, distributor_state.key().as_ref(), mint.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = distributor_state,
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct UpdateVaultConfig<'info> {
+    pub distributor_authority: Signer<'info>,
+
+    #[account(
+        has_one = distributor_authority,
+        seeds = [
+            distributor_state.mint.as_ref(),
+            distributor_state.marker_mint.as_ref(),
+            distributor_state.initial_share_size.to_le_bytes().as_ref(),
+            distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump = distributor_state.distributor_state_bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    #[account(mut, has_one = distributor_state)]
+    pub vault_config: Account<'info, VaultConfig>,
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct DistributeExtraMint<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// The authority, or the service signer in its crank mode
+    pub distributor_authority: Signer<'info>,
+    /// Backend identity co-signing the rounds, required if the distributor has a service signer out of crank mode
+    pub service_signer: Option<Signer<'info>>,
+
+    #[account(
+        mut,
+        constraint = distributor_state.authorizes_round(
+            distributor_authority.key,
+            service_signer.as_ref().map(|service_signer| service_signer.key())
+        ) @ DistributorError::UnauthorizedRound,
+        constraint = !distributor_state.paused @ DistributorError::Paused,
+        seeds = [
+            distributor_state.mint.as_ref(),
+            distributor_state.marker_mint.as_ref(),
+            distributor_state.initial_share_size.to_le_bytes().as_ref(),
+            distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump = distributor_state.distributor_state_bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    #[account(
+        mut,
+        has_one = distributor_state,
+        has_one = mint,
+        has_one = vault,
+        seeds = [VaultConfig::SEED_PREFIX, distributor_state.key().as_ref(), mint.key().as_ref()],
+        bump = vault_config.bump,
+    )]
+    pub vault_config: Account<'info, VaultConfig>,
+
+    #[account(mut)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        constraint = !vault.is_frozen() @ DistributorError::VaultFrozen,
+        constraint = vault.delegate.is_none() @ DistributorError::VaultDelegated,
+        constraint = vault.close_authority.is_none() @ DistributorError::VaultCloseAuthoritySet,
+        token::mint = mint,
+        token::authority = distributor_state,
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    /// Record of a completed round, its payouts are the winners
+    #[account(
+        constraint = round.round < distributor_state.round @ DistributorError::InvalidParameters,
+        seeds = [distributor_state.key().as_ref(), round.round.to_le_bytes().as_ref()],
+        bump = round.bump,
+    )]
+    pub round: Account<'info, DistributionRound>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+/// Progress of a round of the chunked mode, exists only while the round is in progress
+#[account]
+#[derive(InitSpace)]
+pub struct RoundProgress {
+    pub distributor_state: Pubkey,
+    pub round: u64,
+    /// Share parameters at `begin_round`, the whole round is paid with them
+    pub share_size: u64,
+    pub number_of_shares: u64,
+    /// Number of shares paid by the chunks so far
+    pub paid: u64,
+    /// Transfer fees withheld from the chunks so far
+    pub fees: u64,
+    /// Paid the rent of the account and gets it back at `finalize_round`
+    pub payer: Pubkey,
+    pub bump: u8,
+    /// Shares of the round disposed of by `finalize_round` instead of being paid
+    pub burn_shares: u64,
+}
+
+impl RoundProgress {
+    pub const SEED_PREFIX: &'static [u8] = b"round_progress";
+
+    /// Number of winners of the round
+    pub fn paid_shares(&self) -> u64 {
+        self.number_of_shares - self.burn_shares
+    }
+}
+
+#[event_cpi]
+#[derive(Accounts)]
+pub struct DistributeClaims<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// The authority, or the service signer in its crank mode
+    pub distributor_authority: Signer<'info>,
+    /// Backend identity co-signing the rounds, required if the distributor has a service signer out of crank mode
+    pub service_signer: Option<Signer<'info>>,
+
+    #[account(
+        mut,
+        constraint = distributor_state.authorizes_round(
+            distributor_authority.key,
+            service_signer.as_ref().map(|service_signer| service_signer.key())
+        ) @ DistributorError::UnauthorizedRound,
+        has_one = mint,
+        has_one = vault,
+        constraint = !distributor_state.paused @ DistributorError::Paused,
+        constraint = distributor_state.decimals == mint.decimals @ DistributorError::DecimalsMismatch,
+        seeds = [
+                mint.key().as_ref(),
+                distributor_state.marker_mint.as_ref(),
+                distributor_state.initial_share_size.to_le_bytes().as_ref(),
+                distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump = distributor_state.distributor_state_bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    #[account(mut)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        constraint = !vault.is_frozen() @ DistributorError::VaultFrozen,
+        constraint = vault.delegate.is_none() @ DistributorError::VaultDelegated,
+        constraint = vault.close_authority.is_none() @ DistributorError::VaultCloseAuthoritySet,
+        token::mint = mint,
+        token::authority = distributor_state,
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = distributor_state.remainder_policy.treasury() == Some(treasury.key())
+            @ DistributorError::InvalidTreasury,
+        token::mint = mint,
+    )]
+    pub treasury: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = ClaimRound::space(distributor_state.paid_shares()),
+        seeds = [
+            ClaimRound::SEED_PREFIX,
+            distributor_state.key().as_ref(),
+            distributor_state.round.to_le_bytes().as_ref()
+        ],
+        bump,
+    )]
+    pub claim_round: Account<'info, ClaimRound>,
+
+    /// Holds the shares until they are claimed
+    #[account(
+        init,
+        payer = payer,
+        seeds = [claim_round.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = claim_round,
+    )]
+    pub escrow: InterfaceAccount<'info, TokenAccount>,
+
+    /// History record of the round, required while the winners of the previous round can't win again. It starts
+    /// without payouts, every `claim` adds its claimant
+    #[account(
+        init,
+        payer = payer,
+        space = DistributionRound::space(0),
+        seeds = [distributor_state.key().as_ref(), distributor_state.round.to_le_bytes().as_ref()],
+        bump,
+    )]
+    pub round: Option<Account<'info, DistributionRound>>,
+
+    /// Progress account of a chunked round at the current round, the round can't be paid another way while it's open
+    /// CHECK: only its owner is checked
+    #[account(
+        seeds = [
+            RoundProgress::SEED_PREFIX,
+            distributor_state.key().as_ref(),
+            distributor_state.round.to_le_bytes().as_ref()
+        ],
+        bump,
+        constraint = *round_progress.owner != crate::ID @ DistributorError::RoundInProgress,
+    )]
+    pub round_progress: UncheckedAccount<'info>,
+
+    /// VRF draw of the round, its `entrants` is the committed holders count of the round
+    #[account(
+        has_one = distributor_state,
+        seeds = [
+            Draw::SEED_PREFIX,
+            distributor_state.key().as_ref(),
+            distributor_state.round.to_le_bytes().as_ref()
+        ],
+        bump = draw.bump,
+    )]
+    pub draw: Option<Account<'info, Draw>>,
+
+    /// Associated token account of the fee collector, required if the distributor charges a protocol fee
+    #[account(
+        mut,
+        constraint = distributor_state.fee_account(token_program.key) == Some(fee_account.key())
+            @ DistributorError::InvalidFeeAccount,
+    )]
+    pub fee_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    /// Required if the distributor charges a lamport fee
+    #[account(
+        mut,
+        has_one = distributor_state,
+        seeds = [FeeVault::SEED_PREFIX, distributor_state.key().as_ref()],
+        bump = fee_vault.bump,
+    )]
+    pub fee_vault: Option<Account<'info, FeeVault>>,
+
+    /// Associated token account of the depositor who triggered the round, required while a trigger bonus is owed
+    #[account(
+        mut,
+        constraint = distributor_state.trigger_bonus_account(token_program.key) == Some(trigger_bonus_account.key())
+            @ DistributorError::InvalidTriggerBonusAccount,
+    )]
+    pub trigger_bonus_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct Claim<'info> {
+    pub claimant: Signer<'info>,
+    /// Pays the growth of the round record
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = escrow,
+        seeds = [
+            ClaimRound::SEED_PREFIX,
+            claim_round.distributor_state.as_ref(),
+            claim_round.round.to_le_bytes().as_ref()
+        ],
+        bump = claim_round.bump
+    )]
+    pub claim_round: Account<'info, ClaimRound>,
+
+    #[account(address = claim_round.distributor_state)]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    /// Required if any wallet is blocked
+    #[account(seeds = [Blocklist::SEED_PREFIX, distributor_state.key().as_ref()], bump = blocklist.bump)]
+    pub blocklist: Option<Account<'info, Blocklist>>,
+
+    /// Record of the claim round, required if the round was recorded
+    #[account(
+        mut,
+        seeds = [distributor_state.key().as_ref(), claim_round.round.to_le_bytes().as_ref()],
+        bump = round.bump,
+        realloc = DistributionRound::space(round.payouts.len() as u64 + 1),
+        realloc::payer = payer,
+        realloc::zero = false,
+    )]
+    pub round: Option<Account<'info, DistributionRound>>,
+
+    /// Record of the round before the claim round, required while its winners can't win again
+    #[account(
+        seeds = [distributor_state.key().as_ref(), claim_round.round.saturating_sub(1).to_le_bytes().as_ref()],
+        bump = previous_round.bump,
+    )]
+    pub previous_round: Option<Account<'info, DistributionRound>>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, token::mint = mint)]
+    pub escrow: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = claimant,
+    )]
+    pub token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Token account of whoever helped the claimant, receives `referral_bps` of the claim
+    #[account(mut, token::mint = mint)]
+    pub referral: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Share of a winner locked in the vesting escrow
+#[account]
+#[derive(InitSpace)]
+pub struct Vesting {
+    pub distributor_state: Pubkey,
+    pub wallet: Pubkey,
+    /// Paid the rent of the account and gets it back once the share is fully released
+    pub payer: Pubkey,
+    pub round: u64,
+    /// Share after the transfer fee
+    pub total: u64,
+    pub released: u64,
+    /// Unix timestamp of the round
+    pub start: i64,
+    pub duration: i64,
+    pub bump: u8,
+}
+
+impl Vesting {
+    pub const SEED_PREFIX: &'static [u8] = b"vesting";
+
+    /// Vested but not yet released part of the share at `now`, a share without a duration is vested at once
+    pub fn releasable(&self, now: i64) -> u64 {
+        if self.duration == 0 {
+            return self.total - self.released;
+        }
+        let elapsed = now.saturating_sub(self.start).clamp(0, self.duration);
+        let vested = (self.total as u128 * elapsed as u128 / self.duration as u128) as u64;
+        vested - self.released
+    }
+}
+
+#[derive(Accounts)]
+pub struct OpenVestingEscrow<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub distributor_authority: Signer<'info>,
+
+    #[account(
+        has_one = distributor_authority,
+        has_one = mint,
+        seeds = [
+            distributor_state.mint.as_ref(),
+            distributor_state.marker_mint.as_ref(),
+            distributor_state.initial_share_size.to_le_bytes().as_ref(),
+            distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump = distributor_state.distributor_state_bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        init,
+        payer = payer,
+        seeds = [VESTING_ESCROW_SEED, distributor_state.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = distributor_state,
+    )]
+    pub vesting_escrow: InterfaceAccount<'info, TokenAccount>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Opt-in of a wallet to the shares above the large payout threshold
+#[account]
+#[derive(InitSpace)]
+pub struct PayoutAcceptance {
+    pub wallet: Pubkey,
+    pub bump: u8,
+}
+
+impl PayoutAcceptance {
+    pub const SEED_PREFIX: &'static [u8] = b"payout_acceptance";
+}
+
+#[derive(Accounts)]
+pub struct AcceptLargePayouts<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub wallet: Signer<'info>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + PayoutAcceptance::INIT_SPACE,
+        seeds = [PayoutAcceptance::SEED_PREFIX, wallet.key().as_ref()],
+        bump
+    )]
+    pub payout_acceptance: Account<'info, PayoutAcceptance>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct RevokeLargePayouts<'info> {
+    #[account(mut)]
+    pub wallet: Signer<'info>,
+
+    #[account(
+        mut,
+        close = wallet,
+        has_one = wallet,
+        seeds = [PayoutAcceptance::SEED_PREFIX, wallet.key().as_ref()],
+        bump = payout_acceptance.bump
+    )]
+    pub payout_acceptance: Account<'info, PayoutAcceptance>,
+}
+
+#[derive(Accounts)]
+pub struct ClaimVested<'info> {
+    pub wallet: Signer<'info>,
+
+    #[account(
+        mut,
+        has_one = wallet,
+        has_one = distributor_state,
+        seeds = [
+            Vesting::SEED_PREFIX,
+            distributor_state.key().as_ref(),
+            vesting.round.to_le_bytes().as_ref(),
+            wallet.key().as_ref()
+        ],
+        bump = vesting.bump
+    )]
+    pub vesting: Account<'info, Vesting>,
+
+    /// CHECK: receives the rent of the vesting account, checked against the recorded payer
+    #[account(mut, address = vesting.payer)]
+    pub payer: UncheckedAccount<'info>,
+
+    #[account(has_one = mint)]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, seeds = [VESTING_ESCROW_SEED, distributor_state.key().as_ref()], bump)]
+    pub vesting_escrow: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = wallet,
+    )]
+    pub token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Share of a winner whose token account couldn't receive it, held in the vesting escrow until it is claimed or
+/// swept back to the vault
+#[account]
+#[derive(InitSpace)]
+pub struct EscrowedShare {
+    pub distributor_state: Pubkey,
+    pub wallet: Pubkey,
+    /// Paid the rent of the account and gets it back once the share is claimed or swept
+    pub payer: Pubkey,
+    pub round: u64,
+    /// Share after the transfer fee
+    pub amount: u64,
+    /// Unix timestamp the share can't be claimed since, it can be swept instead
+    pub expires_at: i64,
+    pub bump: u8,
+}
+
+impl EscrowedShare {
+    pub const SEED_PREFIX: &'static [u8] = b"escrowed_share";
+}
+
+#[derive(Accounts)]
+pub struct ClaimEscrowed<'info> {
+    pub wallet: Signer<'info>,
+
+    #[account(
+        mut,
+        close = payer,
+        has_one = wallet,
+        has_one = distributor_state,
+        seeds = [
+            EscrowedShare::SEED_PREFIX,
+            distributor_state.key().as_ref(),
+            escrowed_share.round.to_le_bytes().as_ref(),
+            wallet.key().as_ref()
+        ],
+        bump = escrowed_share.bump
+    )]
+    pub escrowed_share: Account<'info, EscrowedShare>,
+
+    /// CHECK: receives the rent of the escrowed share, checked against the recorded payer
+    #[account(mut, address = escrowed_share.payer)]
+    pub payer: UncheckedAccount<'info>,
+
+    #[account(has_one = mint)]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, seeds = [VESTING_ESCROW_SEED, distributor_state.key().as_ref()], bump)]
+    pub vesting_escrow: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        token::mint = mint,
+        token::authority = wallet,
+    )]
+    pub token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct SweepExpired<'info> {
+    #[account(
+        mut,
+        close = payer,
+        has_one = distributor_state,
+        seeds = [
+            EscrowedShare::SEED_PREFIX,
+            distributor_state.key().as_ref(),
+            escrowed_share.round.to_le_bytes().as_ref(),
+            escrowed_share.wallet.as_ref()
+        ],
+        bump = escrowed_share.bump
+    )]
+    pub escrowed_share: Account<'info, EscrowedShare>,
+
+    /// CHECK: receives the rent of the escrowed share, checked against the recorded payer
+    #[account(mut, address = escrowed_share.payer)]
+    pub payer: UncheckedAccount<'info>,
+
+    #[account(has_one = mint, has_one = vault)]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(mut, seeds = [VESTING_ESCROW_SEED, distributor_state.key().as_ref()], bump)]
+    pub vesting_escrow: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut)]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct CloseClaims<'info> {
+    /// CHECK: receives the rent of the round, checked against the recorded payer
+    #[account(mut, address = claim_round.payer)]
+    pub payer: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        has_one = mint,
+        has_one = vault,
+        seeds = [
+                mint.key().as_ref(),
+                distributor_state.marker_mint.as_ref(),
+                distributor_state.initial_share_size.to_le_bytes().as_ref(),
+                distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
+        ],
+        bump = distributor_state.distributor_state_bump
+    )]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    #[account(
+        mut,
+        close = payer,
+        has_one = distributor_state,
+        has_one = escrow,
+        seeds = [
+            ClaimRound::SEED_PREFIX,
+            distributor_state.key().as_ref(),
+            claim_round.round.to_le_bytes().as_ref()
+        ],
+        bump = claim_round.bump
+    )]
+    pub claim_round: Account<'info, ClaimRound>,
+
+    #[account(mut)]
+    pub mint: InterfaceAccount<'info, Mint>,
+    #[account(
+        mut,
+        constraint = vault.delegate.is_none() @ DistributorError::VaultDelegated,
+        constraint = vault.close_authority.is_none() @ DistributorError::VaultCloseAuthoritySet,
+        token::mint = mint,
+    )]
+    pub vault: InterfaceAccount<'info, TokenAccount>,
+    #[account(mut, token::mint = mint)]
+    pub escrow: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+/// Round of the claim mode, the winners are known only as the Merkle root
+#[account]
+pub struct ClaimRound {
+    pub distributor_state: Pubkey,
+    pub round: u64,
+    pub merkle_root: [u8; 32],
+    pub escrow: Pubkey,
+    /// Paid the rent of the round and gets it back when the round is closed
+    pub payer: Pubkey,
+    pub claimants: u64,
+    pub total_amount: u64,
+    pub claimed_amount: u64,
+    /// Share of a claim paid to the referral if the claimant passes one
+    pub referral_bps: u16,
+    /// Part of `claimed_amount` paid to referrals
+    pub referral_amount: u64,
+    pub expires_at: i64,
+    pub bump: u8,
+    /// Bit per claimant index
+    pub claimed: Vec<u8>,
+}
+
+impl ClaimRound {
+    pub const SEED_PREFIX: &'static [u8] = b"claims";
+
+    pub fn bitmap_len(claimants: u64) -> usize {
+        claimants.div_ceil(8) as usize
+    }
+
+    pub fn space(claimants: u64) -> usize {
+        8 + 32 + 8 + 32 + 32 + 32 + 8 + 8 + 8 + 2 + 8 + 8 + 1 + 4 + Self::bitmap_len(claimants)
+    }
+
+    pub fn is_claimed(&self, index: u64) -> bool {
+        self.claimed[index as usize / 8] & (1 << (index % 8)) != 0
     }
+
+    fn set_claimed(&mut self, index: u64) {
+        self.claimed[index as usize / 8] |= 1 << (index % 8);
+    }
+}
+
+/// ORAO VRF program the randomness of the draws is requested from
+pub mod vrf {
+    use super::*;
+
+    declare_id!("VRFzZoJdhFWL8rkvu87LpKM3RbcVezpMEc6X5GVDr7y");
+
+    pub const RANDOMNESS_SEED: &[u8] = b"orao-vrf-randomness-request";
+
+    /// Anchor discriminator of the `Randomness` request account, the first field of its layout
+    pub const RANDOMNESS_DISCRIMINATOR: [u8; 8] = [188, 96, 216, 248, 93, 94, 49, 112];
+    /// `Randomness::seed`, the seed the request was sent with
+    pub const SEED_RANGE: std::ops::Range<usize> = 8..40;
+    /// `Randomness::randomness`, zeroed until the request is fulfilled. The responses of the fulfilling nodes
+    /// follow it
+    pub const RANDOMNESS_RANGE: std::ops::Range<usize> = 40..104;
 }
 
 #[derive(Accounts)]
-#[instruction(share_size: u64, number_of_shares: u64)]
-pub struct Initialize<'info> {
+#[instruction(seed: [u8; 32])]
+pub struct RequestDraw<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
+    /// The authority, or the service signer in its crank mode
+    pub distributor_authority: Signer<'info>,
+    /// Backend identity co-signing the rounds, required if the distributor has a service signer out of crank mode
+    pub service_signer: Option<Signer<'info>>,
 
     #[account(
-        init,
-        payer = payer,
-        space = 8 + DistributorState::INIT_SPACE,
+        constraint = distributor_state.authorizes_round(
+            distributor_authority.key,
+            service_signer.as_ref().map(|service_signer| service_signer.key())
+        ) @ DistributorError::UnauthorizedRound,
         seeds = [
-            mint.key().as_ref(),
-            marker_mint.key().as_ref(),
-            share_size.to_le_bytes().as_ref(),
-            number_of_shares.to_le_bytes().as_ref()
+            distributor_state.mint.as_ref(),
+            distributor_state.marker_mint.as_ref(),
+            distributor_state.initial_share_size.to_le_bytes().as_ref(),
+            distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
         ],
-        bump
+        bump = distributor_state.distributor_state_bump
     )]
     pub distributor_state: Account<'info, DistributorState>,
 
-    pub mint: InterfaceAccount<'info, Mint>,
+    /// CHECK: pending randomness request of the VRF program for `seed`, checked by `verify_randomness_pending`
     #[account(
-        init,
-        payer = payer,
-        seeds = [distributor_state.key().as_ref()],
+        owner = vrf::ID,
+        seeds = [vrf::RANDOMNESS_SEED, seed.as_ref()],
         bump,
-        token::mint = mint,
-        token::authority = distributor_state,
+        seeds::program = vrf::ID
     )]
-    pub vault: InterfaceAccount<'info, TokenAccount>,
+    pub randomness: UncheckedAccount<'info>,
 
-    pub marker_mint: InterfaceAccount<'info, Mint>,
+    /// Holders snapshot committed for the round, required while the distributor requires a VRF draw
+    #[account(
+        has_one = distributor_state,
+        seeds = [
+            HolderSnapshot::SEED_PREFIX,
+            distributor_state.key().as_ref(),
+            distributor_state.round.to_le_bytes().as_ref()
+        ],
+        bump = holder_snapshot.bump,
+    )]
+    pub holder_snapshot: Option<Account<'info, HolderSnapshot>>,
 
-    /// CHECK: will be used only for key
-    pub distributor_authority: UncheckedAccount<'info>,
+    #[account(
+        init,
+        payer = payer,
+        space = Draw::space(distributor_state.paid_shares()),
+        seeds = [
+            Draw::SEED_PREFIX,
+            distributor_state.key().as_ref(),
+            distributor_state.round.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub draw: Account<'info, Draw>,
 
     pub system_program: Program<'info, System>,
-    pub token_program: Interface<'info, TokenInterface>,
 }
 
-#[account]
-#[derive(InitSpace)]
-pub struct DistributorState {
-    pub vault: Pubkey,
-    pub mint: Pubkey,
-    pub marker_mint: Pubkey,
-    pub distributor_authority: Pubkey,
+#[derive(Accounts)]
+pub struct RevealDraw<'info> {
+    #[account(
+        mut,
+        has_one = randomness,
+        seeds = [Draw::SEED_PREFIX, draw.distributor_state.as_ref(), draw.round.to_le_bytes().as_ref()],
+        bump = draw.bump
+    )]
+    pub draw: Account<'info, Draw>,
 
-    pub share_size: u64,
-    pub number_of_shares: u64,
+    /// CHECK: the data is parsed by `vrf_randomness`
+    #[account(owner = vrf::ID)]
+    pub randomness: UncheckedAccount<'info>,
+}
 
-    pub distributor_state_bump: u8,
-    pub vault_bump: u8,
+#[derive(Accounts)]
+pub struct VerifyDrawWinner<'info> {
+    #[account(
+        mut,
+        seeds = [Draw::SEED_PREFIX, draw.distributor_state.as_ref(), draw.round.to_le_bytes().as_ref()],
+        bump = draw.bump
+    )]
+    pub draw: Account<'info, Draw>,
+
+    #[account(
+        seeds = [HolderSnapshot::SEED_PREFIX, draw.distributor_state.as_ref(), draw.round.to_le_bytes().as_ref()],
+        bump = holder_snapshot.bump
+    )]
+    pub holder_snapshot: Account<'info, HolderSnapshot>,
 }
 
-impl DistributorState {
-    pub fn threshold(&self) -> u64 {
-        self.share_size * self.number_of_shares
+/// VRF randomness the winners of a round are drawn with, one per round
+#[account]
+pub struct Draw {
+    pub distributor_state: Pubkey,
+    pub round: u64,
+    pub seed: [u8; 32],
+    /// Randomness request account of the VRF program
+    pub randomness: Pubkey,
+    pub entrants: u64,
+    /// Randomness once the request is fulfilled and revealed
+    pub value: Option<[u8; 64]>,
+    pub bump: u8,
+    pub winners_number: u64,
+    /// Winners verified against the holders snapshot of the round so far in the draw order
+    pub winners: Vec<Pubkey>,
+}
+
+impl Draw {
+    pub const SEED_PREFIX: &'static [u8] = b"draw";
+
+    pub fn space(winners: u64) -> usize {
+        8 + 32 + 8 + 32 + 32 + 8 + 65 + 1 + 8 + 4 + winners as usize * 32
     }
 }
 
 #[derive(Accounts)]
-pub struct Deposit<'info> {
+pub struct CommitSnapshot<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// The authority, or the service signer in its crank mode
+    pub distributor_authority: Signer<'info>,
+    pub service_signer: Option<Signer<'info>>,
+
     #[account(
-        has_one = mint,
-        has_one = vault,
+        constraint = distributor_state.authorizes_round(
+            distributor_authority.key,
+            service_signer.as_ref().map(|service_signer| service_signer.key())
+        ) @ DistributorError::UnauthorizedRound,
         seeds = [
-            mint.key().as_ref(),
+            distributor_state.mint.as_ref(),
             distributor_state.marker_mint.as_ref(),
-            distributor_state.share_size.to_le_bytes().as_ref(),
-            distributor_state.number_of_shares.to_le_bytes().as_ref()
+            distributor_state.initial_share_size.to_le_bytes().as_ref(),
+            distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
         ],
         bump = distributor_state.distributor_state_bump
     )]
     pub distributor_state: Account<'info, DistributorState>,
 
-    pub mint: InterfaceAccount<'info, Mint>,
     #[account(
-        mut,
-        seeds = [distributor_state.key().as_ref()],
-        bump = distributor_state.vault_bump,
-        token::mint = mint,
-        token::authority = distributor_state,
+        init,
+        payer = payer,
+        space = 8 + HolderSnapshot::INIT_SPACE,
+        seeds = [
+            HolderSnapshot::SEED_PREFIX,
+            distributor_state.key().as_ref(),
+            distributor_state.round.to_le_bytes().as_ref()
+        ],
+        bump
     )]
-    pub vault: InterfaceAccount<'info, TokenAccount>,
+    pub holder_snapshot: Account<'info, HolderSnapshot>,
 
-    pub authority: Signer<'info>,
-    #[account(
-        mut,
-        token::mint = mint,
-        token::authority = authority,
-    )]
-    pub token_account: InterfaceAccount<'info, TokenAccount>,
+    pub system_program: Program<'info, System>,
+}
 
-    pub token_program: Interface<'info, TokenInterface>,
+/// Marker holders snapshot a round is drawn from, committed before the round is paid
+#[account]
+#[derive(InitSpace)]
+pub struct HolderSnapshot {
+    pub distributor_state: Pubkey,
+    pub round: u64,
+    /// Merkle root of the `(index, holder)` leaves of the snapshot
+    pub merkle_root: [u8; 32],
+    pub holders_count: u64,
+    /// Slot the holders were read at
+    pub slot: u64,
+    pub bump: u8,
 }
 
-impl<'a, 'b, 'c, 'info> From<&mut Deposit<'info>> for CpiContext<'a, 'b, 'c, 'info, TransferChecked<'info>> {
-    fn from(accounts: &mut Deposit<'info>) -> CpiContext<'a, 'b, 'c, 'info, TransferChecked<'info>> {
-        let cpi_accounts = TransferChecked {
-            from: accounts.token_account.to_account_info(),
-            mint: accounts.mint.to_account_info(),
-            to: accounts.vault.to_account_info(),
-            authority: accounts.authority.to_account_info(),
-        };
-        let cpi_program = accounts.token_program.to_account_info();
-        CpiContext::new(cpi_program, cpi_accounts)
-    }
+impl HolderSnapshot {
+    pub const SEED_PREFIX: &'static [u8] = b"holder_snapshot";
 }
 
 #[derive(Accounts)]
-pub struct Distribute<'info> {
+pub struct CommitDraw<'info> {
     #[account(mut)]
     pub payer: Signer<'info>,
-
     pub distributor_authority: Signer<'info>,
 
     #[account(
         has_one = distributor_authority,
-        has_one = mint,
         has_one = vault,
         seeds = [
-                mint.key().as_ref(),
-                distributor_state.marker_mint.as_ref(),
-                distributor_state.share_size.to_le_bytes().as_ref(),
-                distributor_state.number_of_shares.to_le_bytes().as_ref()
+            distributor_state.mint.as_ref(),
+            distributor_state.marker_mint.as_ref(),
+            distributor_state.initial_share_size.to_le_bytes().as_ref(),
+            distributor_state.initial_number_of_shares.to_le_bytes().as_ref()
         ],
         bump = distributor_state.distributor_state_bump
     )]
     pub distributor_state: Account<'info, DistributorState>,
 
-    #[account(mut)]
-    pub mint: InterfaceAccount<'info, Mint>,
     #[account(
-        mut,
-        seeds = [distributor_state.key().as_ref()],
-        bump = distributor_state.vault_bump,
-        token::mint = mint,
-        token::authority = distributor_state,
+        constraint = vault.delegate.is_none() @ DistributorError::VaultDelegated,
+        constraint = vault.close_authority.is_none() @ DistributorError::VaultCloseAuthoritySet,
     )]
     pub vault: InterfaceAccount<'info, TokenAccount>,
 
+    #[account(
+        init,
+        payer = payer,
+        space = CommittedDraw::space(distributor_state.paid_shares()),
+        seeds = [
+            CommittedDraw::SEED_PREFIX,
+            distributor_state.key().as_ref(),
+            distributor_state.round.to_le_bytes().as_ref()
+        ],
+        bump
+    )]
+    pub committed_draw: Account<'info, CommittedDraw>,
+
     pub system_program: Program<'info, System>,
-    pub token_program: Interface<'info, TokenInterface>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
+}
+
+#[derive(Accounts)]
+pub struct RevealCommittedDraw<'info> {
+    pub distributor_authority: Signer<'info>,
+
+    #[account(has_one = distributor_authority)]
+    pub distributor_state: Account<'info, DistributorState>,
+
+    #[account(
+        mut,
+        has_one = distributor_state,
+        seeds = [
+            CommittedDraw::SEED_PREFIX,
+            distributor_state.key().as_ref(),
+            committed_draw.round.to_le_bytes().as_ref()
+        ],
+        bump = committed_draw.bump
+    )]
+    pub committed_draw: Account<'info, CommittedDraw>,
+}
+
+#[derive(Accounts)]
+pub struct VerifyCommittedWinner<'info> {
+    #[account(
+        mut,
+        seeds = [
+            CommittedDraw::SEED_PREFIX,
+            committed_draw.distributor_state.as_ref(),
+            committed_draw.round.to_le_bytes().as_ref()
+        ],
+        bump = committed_draw.bump
+    )]
+    pub committed_draw: Account<'info, CommittedDraw>,
+}
+
+/// Draw the authority committed to before the threshold of the round was reached, its winners can be checked
+/// against the holders snapshot by anyone
+#[account]
+pub struct CommittedDraw {
+    pub distributor_state: Pubkey,
+    pub round: u64,
+    /// Hash of the seed
+    pub commitment: [u8; 32],
+    /// Merkle root of the holders snapshot
+    pub snapshot_root: [u8; 32],
+    pub entrants: u64,
+    /// Seed once it is revealed
+    pub seed: Option<[u8; 32]>,
+    pub winners_number: u64,
+    pub bump: u8,
+    /// Winners verified so far in the draw order
+    pub winners: Vec<Pubkey>,
+}
+
+impl CommittedDraw {
+    pub const SEED_PREFIX: &'static [u8] = b"committed_draw";
+
+    pub fn space(winners: u64) -> usize {
+        8 + 32 + 8 + 32 + 32 + 8 + 33 + 8 + 1 + 4 + winners as usize * 32
+    }
+
+    /// Randomness of the revealed seed, bound to the snapshot so neither can be changed without the other
+    pub fn randomness(&self) -> Option<[u8; 64]> {
+        self.seed.map(|seed| {
+            let mut randomness = [0; 64];
+            randomness[..32].copy_from_slice(&seed);
+            randomness[32..].copy_from_slice(&self.snapshot_root);
+            randomness
+        })
+    }
+}
+
+#[event]
+pub struct SnapshotCommittedEvent {
+    pub round: u64,
+    pub merkle_root: [u8; 32],
+    pub holders_count: u64,
+    pub slot: u64,
+}
+
+#[event]
+pub struct DrawRevealedEvent {
+    pub round: u64,
+    pub randomness: [u8; 64],
+    pub entrants: u64,
+}
+
+#[event]
+pub struct ClaimsCommittedEvent {
+    pub round: u64,
+    pub merkle_root: [u8; 32],
+    pub claimants: u64,
+    pub total_amount: u64,
+    pub expires_at: i64,
+    pub burned: u64,
+    pub treasury: u64,
+    pub rollover: u64,
+    /// Holders count committed by the draw of the round, `None` if the round had no draw
+    pub holders_count: Option<u64>,
+    /// Taken out of the terminal share and paid to the fee collector
+    pub protocol_fee: u64,
+}
+
+#[event]
+pub struct VaultMigratedEvent {
+    pub previous_vault: Pubkey,
+    pub vault: Pubkey,
+    pub vault_nonce: u32,
+    /// Balance moved from the previous vault, zero if it was abandoned frozen
+    pub moved: u64,
+}
+
+#[event]
+pub struct EmergencyWithdrawRequestedEvent {
+    pub amount: u64,
+    pub destination: Pubkey,
+    pub unlocks_at: i64,
+}
+
+#[event]
+pub struct EmergencyWithdrawEvent {
+    pub amount: u64,
+    pub destination: Pubkey,
+}
+
+#[event]
+pub struct FeesCollectedEvent {
+    pub destination: Pubkey,
+    pub amount: u64,
+}
+
+#[event]
+pub struct StateMigratedEvent {
+    pub from_version: u8,
+    pub to_version: u8,
+}
+
+#[event]
+pub struct DistributorClosedEvent {
+    pub rent_recipient: Pubkey,
+}
+
+/// Emitted by `deposit`, direct transfers to the vault don't emit it
+#[event]
+pub struct DepositEvent {
+    pub depositor: Pubkey,
+    pub amount: u64,
+    /// Vault balance after the deposit
+    pub vault_balance: u64,
+}
+
+#[event]
+pub struct DistributeEvent {
+    pub round: u64,
+    /// Wallets which received a share
+    pub winners: Vec<Pubkey>,
+    /// Amounts sent to the winners, all equal to `share_size` unless the distribution is weighted
+    pub amounts: Vec<u64>,
+    /// Amounts actually received by the winners, less than `amounts` if the mint charges a transfer fee
+    pub delivered: Vec<u64>,
+    /// Caller notes of the winners, empty if none were passed
+    pub notes: Vec<[u8; 32]>,
+    pub share_size: u64,
+    pub burned: u64,
+    pub treasury: u64,
+    pub rollover: u64,
+    /// Transfer fees withheld from the winners' and treasury transfers
+    pub fees: u64,
+    /// Holders count committed by the draw of the round, `None` if the round had no draw
+    pub holders_count: Option<u64>,
+    /// Taken out of the terminal share and paid to the fee collector, or on top of the payouts of a round without
+    /// burned shares
+    pub protocol_fee: u64,
+    /// Slot of the holders snapshot the winners were drawn from, passed to `distribute_v2` or committed by
+    /// `commit_snapshot`
+    pub snapshot_slot: Option<u64>,
+    /// Merkle root of the holders snapshot committed for the round, `None` if none was
+    pub snapshot_root: Option<[u8; 32]>,
+    /// Part of the weighted split of the round the rounding didn't pay to the winners, burned or kept in the vault
+    /// by the rounding policy. Negative if the rounding paid more than the split, zero without weights
+    pub rounding_remainder: i64,
+    /// Paid to the depositor whose deposit pushed the vault over the threshold
+    pub trigger_bonus: u64,
+}
+
+#[event]
+pub struct ExtraMintDistributeEvent {
+    pub round: u64,
+    pub mint: Pubkey,
+    /// Position of the first winner of the call in the round record
+    pub first_payout: u64,
+    pub winners: Vec<Pubkey>,
+    pub share_size: u64,
+    pub delivered: Vec<u64>,
+    pub fees: u64,
+}
+
+#[event]
+pub struct DistributeChunkEvent {
+    pub round: u64,
+    /// Number of shares paid before the chunk
+    pub first_share: u64,
+    pub winners: Vec<Pubkey>,
+    pub amounts: Vec<u64>,
+    pub delivered: Vec<u64>,
+    pub notes: Vec<[u8; 32]>,
+    pub fees: u64,
+}
+
+/// Emitted when a chunked round is completed, its payouts are in the `DistributeChunkEvent`s of the round
+#[event]
+pub struct RoundFinalizedEvent {
+    pub round: u64,
+    pub shares: u64,
+    pub share_size: u64,
+    pub burned: u64,
+    pub treasury: u64,
+    pub rollover: u64,
+    /// Transfer fees withheld in the whole round
+    pub fees: u64,
+    /// Holders count committed by the draw of the round, `None` if the round had no draw
+    pub holders_count: Option<u64>,
+    /// Taken out of the terminal share and paid to the fee collector
+    pub protocol_fee: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn is_overflow(result: Result<u64>) -> bool {
+        result.is_err_and(|err| err == DistributorError::ArithmeticOverflow.into())
+    }
+
+    #[test]
+    fn should_cap_single_tx_winners_by_their_accounts() {
+        assert_eq!(38, DistributionMode::SingleTx.max_winners(1));
+        assert_eq!(19, DistributionMode::SingleTx.max_winners(2));
+        // marker, collection NFT and metadata, payout acceptance
+        assert_eq!(6, DistributionMode::SingleTx.max_winners(6));
+        assert_eq!(
+            DistributionMode::MAX_CHUNKED_WINNERS,
+            DistributionMode::Chunked.max_winners(6)
+        );
+
+        assert!(DistributorState::validate_shares(1, 20, 1, DistributionMode::SingleTx, 2).is_ok());
+        assert!(DistributorState::validate_shares(1, 20, 1, DistributionMode::SingleTx, 3).is_err());
+    }
+
+    /// Data of a randomness request account fixture of the validator of the TS tests
+    fn vrf_request(fixture: &str) -> Vec<u8> {
+        use base64::Engine;
+
+        let fixture: serde_json::Value = serde_json::from_str(fixture).unwrap();
+        base64::engine::general_purpose::STANDARD
+            .decode(fixture["account"]["data"][0].as_str().unwrap())
+            .unwrap()
+    }
+
+    #[test]
+    fn should_parse_the_orao_randomness_requests() {
+        let pending = vrf_request(include_str!("../../../tests/fixtures/vrf_request_pending.json"));
+        let fulfilled = vrf_request(include_str!("../../../tests/fixtures/vrf_request_fulfilled.json"));
+
+        assert!(verify_randomness_pending(&pending, &[1; 32]).is_ok());
+        assert!(vrf_randomness(&pending, &[1; 32]).is_err());
+        assert_eq!([9; 64], vrf_randomness(&fulfilled, &[2; 32]).unwrap());
+        assert!(verify_randomness_pending(&fulfilled, &[2; 32]).is_err());
+        // another seed or another account
+        assert!(vrf_randomness(&fulfilled, &[1; 32]).is_err());
+        assert!(verify_randomness_pending(&pending[..vrf::RANDOMNESS_RANGE.end - 1], &[1; 32]).is_err());
+        let mut other = pending.clone();
+        other[0] ^= 1;
+        assert!(verify_randomness_pending(&other, &[1; 32]).is_err());
+    }
+
+    #[test]
+    fn should_read_a_state_of_the_first_layout() {
+        let mint = Pubkey::new_unique();
+        let mut data = <DistributorState as anchor_lang::Discriminator>::discriminator().to_vec();
+        data.extend_from_slice(&[[1; 32], mint.to_bytes(), [3; 32], [4; 32]].concat());
+        data.extend_from_slice(&1_000_000_000u64.to_le_bytes());
+        data.extend_from_slice(&5u64.to_le_bytes());
+        data.extend_from_slice(&[254, 253]);
+        assert_eq!(LegacyDistributorState::SPACE, data.len());
+
+        let legacy = LegacyDistributorState::deserialize(&mut &data[8..]).unwrap();
+        assert_eq!(mint, legacy.mint);
+        assert_eq!(1_000_000_000, legacy.share_size);
+        assert_eq!(5, legacy.number_of_shares);
+        assert_eq!((254, 253), (legacy.distributor_state_bump, legacy.vault_bump));
+    }
+
+    #[test]
+    fn should_reject_overflowing_amounts_at_the_boundary() {
+        assert_eq!(u64::MAX, shares_amount(u64::MAX, 1).unwrap());
+        assert_eq!(0, shares_amount(u64::MAX, 0).unwrap());
+        assert!(is_overflow(shares_amount(u64::MAX, 2)));
+        assert!(is_overflow(shares_amount(u64::MAX / 2 + 1, 2)));
+        assert_eq!(u64::MAX - 1, shares_amount(u64::MAX / 2, 2).unwrap());
+
+        assert!(DistributorState::validate_shares(u64::MAX, 2, 1, DistributionMode::SingleTx, 2).is_err());
+        assert!(DistributorState::validate_shares(u64::MAX / 2, 2, 1, DistributionMode::SingleTx, 2).is_ok());
+    }
+
+    #[test]
+    fn should_round_weighted_split_by_policy() {
+        // 10 split 1:1:1 is 3.33 each
+        let weights = [1, 1, 1];
+        assert_eq!(
+            (vec![3, 3, 3], 1),
+            RoundingPolicy::FloorBurn.split(10, &weights).unwrap()
+        );
+        assert_eq!(
+            (vec![4, 3, 3], 0),
+            RoundingPolicy::FloorToFirst.split(10, &weights).unwrap()
+        );
+        assert_eq!((vec![3, 3, 3], 1), RoundingPolicy::Bankers.split(10, &weights).unwrap());
+        // 10 split 1:1:1:1 is 2.5 each, the halves are rounded to the even 2
+        assert_eq!(
+            (vec![2, 2, 2, 2], 2),
+            RoundingPolicy::Bankers.split(10, &[1, 1, 1, 1]).unwrap()
+        );
+        // 14 split 1:1:1:1 is 3.5 each, rounded up to the even 4 the winners get more than the split
+        assert_eq!(
+            (vec![4, 4, 4, 4], -2),
+            RoundingPolicy::Bankers.split(14, &[1, 1, 1, 1]).unwrap()
+        );
+        assert_eq!(
+            (vec![u64::MAX, 0], 0),
+            RoundingPolicy::FloorBurn.split(u64::MAX, &[u64::MAX, 0]).unwrap()
+        );
+        assert!(RoundingPolicy::FloorBurn.split(10, &[0, 0]).is_err());
+    }
+
+    #[test]
+    fn should_reject_share_size_with_more_decimals() {
+        assert!(DistributorState::validate_share_size_decimals(331_000_000_000, 9, 0).is_ok());
+        assert!(DistributorState::validate_share_size_decimals(331_500_000_000, 9, 0).is_err());
+        assert!(DistributorState::validate_share_size_decimals(331_500_000_000, 9, 1).is_ok());
+        assert!(DistributorState::validate_share_size_decimals(1, 9, 9).is_ok());
+        assert!(DistributorState::validate_share_size_decimals(1, 9, 10).is_err());
+        assert!(DistributorState::validate_share_size_decimals(u64::MAX, u8::MAX, 0).is_err());
+    }
+
+    #[test]
+    fn should_read_verified_collection_of_metadata() {
+        let (mint, collection) = (Pubkey::new_unique(), Pubkey::new_unique());
+        let metadata = |key: u8, collection: Option<(bool, Pubkey)>| {
+            let mut data = (key, Pubkey::new_unique(), mint, "Pass".to_string(), "PASS".to_string())
+                .try_to_vec()
+                .unwrap();
+            let creators = Some(vec![(Pubkey::new_unique(), true, 100u8)]);
+            data.extend(
+                (
+                    String::new(),
+                    500u16,
+                    creators,
+                    false,
+                    true,
+                    None::<u8>,
+                    Some(0u8),
+                    collection,
+                )
+                    .try_to_vec()
+                    .unwrap(),
+            );
+            // metadata accounts are zero padded
+            data.resize(679, 0);
+            data
+        };
+
+        assert_eq!(
+            Some(collection),
+            metadata::verified_collection(&metadata(4, Some((true, collection))), &mint)
+        );
+        assert_eq!(
+            None,
+            metadata::verified_collection(&metadata(4, Some((false, collection))), &mint)
+        );
+        assert_eq!(None, metadata::verified_collection(&metadata(4, None), &mint));
+        assert_eq!(
+            None,
+            metadata::verified_collection(&metadata(5, Some((true, collection))), &mint)
+        );
+        assert_eq!(
+            None,
+            metadata::verified_collection(&metadata(4, Some((true, collection))), &Pubkey::new_unique())
+        );
+    }
+
+    proptest! {
+        #[test]
+        fn should_match_wide_multiplication(share_size: u64, shares: u64) {
+            let wide = share_size as u128 * shares as u128;
+            match shares_amount(share_size, shares) {
+                Ok(amount) => prop_assert_eq!(wide, amount as u128),
+                Err(_) => prop_assert!(wide > u64::MAX as u128),
+            }
+        }
+
+        #[test]
+        fn should_fit_amounts_of_valid_shares(
+            (share_size, number_of_shares, burn_shares) in (1..=DistributionMode::MAX_CLAIM_WINNERS)
+                .prop_flat_map(|number_of_shares| {
+                    (1..=u64::MAX / number_of_shares, Just(number_of_shares), 0..number_of_shares)
+                })
+        ) {
+            DistributorState::validate_shares(share_size, number_of_shares, burn_shares, DistributionMode::Claim, 2)
+                .unwrap();
+            let threshold = shares_amount(share_size, number_of_shares).unwrap();
+            let terminal_amount = shares_amount(share_size, burn_shares).unwrap();
+            let paid_amount = shares_amount(share_size, number_of_shares - burn_shares).unwrap();
+            prop_assert_eq!(threshold, terminal_amount + paid_amount);
+        }
+    }
 }
@@ -0,0 +1,32 @@
+//! Merkle trees of the claims of the claim mode and of the holders snapshots of the committed draws, nodes are
+//! hashed as sorted pairs so proofs don't need directions.
+
+use anchor_lang::{prelude::*, solana_program::hash::hashv};
+
+const LEAF_PREFIX: &[u8] = &[0];
+const NODE_PREFIX: &[u8] = &[1];
+const SNAPSHOT_LEAF_PREFIX: &[u8] = &[2];
+
+pub fn leaf(index: u64, wallet: &Pubkey, amount: u64) -> [u8; 32] {
+    hashv(&[
+        LEAF_PREFIX,
+        &index.to_le_bytes(),
+        wallet.as_ref(),
+        &amount.to_le_bytes(),
+    ])
+    .to_bytes()
+}
+
+/// Holder at `index` of a holders snapshot
+pub fn snapshot_leaf(index: u64, wallet: &Pubkey) -> [u8; 32] {
+    hashv(&[SNAPSHOT_LEAF_PREFIX, &index.to_le_bytes(), wallet.as_ref()]).to_bytes()
+}
+
+pub fn node(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let (left, right) = if left <= right { (left, right) } else { (right, left) };
+    hashv(&[NODE_PREFIX, left, right]).to_bytes()
+}
+
+pub fn verify(proof: &[[u8; 32]], root: &[u8; 32], leaf: [u8; 32]) -> bool {
+    proof.iter().fold(leaf, |hash, sibling| node(&hash, sibling)) == *root
+}
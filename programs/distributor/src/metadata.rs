@@ -0,0 +1,58 @@
+//! Verified collection of an NFT read from its Metaplex Token Metadata account, without the Metaplex crates.
+
+use anchor_lang::prelude::*;
+
+declare_id!("metaqbxxUerdq28cj1RbAWkYQm3ybzjb6a8bt518x1s");
+
+pub const SEED_PREFIX: &[u8] = b"metadata";
+/// `Key::MetadataV1`, the first byte of a metadata account
+const METADATA_V1: u8 = 4;
+
+/// Metadata account of `mint`
+pub fn metadata_address(mint: &Pubkey) -> Pubkey {
+    Pubkey::find_program_address(&[SEED_PREFIX, ID.as_ref(), mint.as_ref()], &ID).0
+}
+
+#[derive(AnchorDeserialize)]
+struct Creator {
+    _address: Pubkey,
+    _verified: bool,
+    _share: u8,
+}
+
+#[derive(AnchorDeserialize)]
+struct Collection {
+    verified: bool,
+    key: Pubkey,
+}
+
+/// Leading fields of a metadata account up to its collection, the ones after it aren't read. The accounts are
+/// zero padded, so the fields missing in the old accounts read as `None`
+#[derive(AnchorDeserialize)]
+struct Metadata {
+    key: u8,
+    _update_authority: Pubkey,
+    mint: Pubkey,
+    _name: String,
+    _symbol: String,
+    _uri: String,
+    _seller_fee_basis_points: u16,
+    _creators: Option<Vec<Creator>>,
+    _primary_sale_happened: bool,
+    _is_mutable: bool,
+    _edition_nonce: Option<u8>,
+    _token_standard: Option<u8>,
+    collection: Option<Collection>,
+}
+
+/// Collection of the metadata account `data` of `mint`, `None` if it isn't verified by the collection authority
+pub fn verified_collection(data: &[u8], mint: &Pubkey) -> Option<Pubkey> {
+    let metadata = Metadata::deserialize(&mut &data[..]).ok()?;
+    if metadata.key != METADATA_V1 || metadata.mint != *mint {
+        return None;
+    }
+    metadata
+        .collection
+        .filter(|collection| collection.verified)
+        .map(|collection| collection.key)
+}
@@ -10,7 +10,8 @@ import * as chai from 'chai';
 import chaiAsPromised from 'chai-as-promised';
 
 import * as fs from "fs";
-import {createMintIfRequired, createToken, getATA, mintTo} from "./utils";
+import {createHash} from "crypto";
+import {buildMerkleTree, buildSnapshotTree, createMintIfRequired, createToken, getATA, mintTo} from "./utils";
 
 chai.use(chaiAsPromised);
 
@@ -20,6 +21,32 @@ function deriveDistributorStateAddress(mint: PublicKey, markerMint: PublicKey, s
   ], programId)[0];
 }
 
+function deriveClaimRoundAddress(distributorState: PublicKey, round: BN, programId: PublicKey): PublicKey {
+  return PublicKey.findProgramAddressSync([Buffer.from("claims"), distributorState.toBuffer(), round.toBuffer("le", 8)], programId)[0];
+}
+
+function deriveRoundAddress(distributorState: PublicKey, round: BN, programId: PublicKey): PublicKey {
+  return PublicKey.findProgramAddressSync([distributorState.toBuffer(), round.toBuffer("le", 8)], programId)[0];
+}
+
+function deriveDrawAddress(distributorState: PublicKey, round: BN, programId: PublicKey): PublicKey {
+  return PublicKey.findProgramAddressSync([Buffer.from("draw"), distributorState.toBuffer(), round.toBuffer("le", 8)], programId)[0];
+}
+
+async function fetchCpiEvents(program: Program<Distributor>, signature: string) {
+  const tx = await program.provider.connection.getTransaction(signature, {commitment: "confirmed"});
+  return tx.meta.innerInstructions
+    .flatMap((inner) => inner.instructions)
+    .map((ix) => anchor.utils.bytes.bs58.decode(ix.data))
+    // skip the event instruction discriminator
+    .map((data) => program.coder.events.decode(anchor.utils.bytes.base64.encode(data.subarray(8))))
+    .filter((event) => event !== null);
+}
+
+function deriveRoundProgressAddress(distributorState: PublicKey, round: BN, programId: PublicKey): PublicKey {
+  return PublicKey.findProgramAddressSync([Buffer.from("round_progress"), distributorState.toBuffer(), round.toBuffer("le", 8)], programId)[0];
+}
+
 function deriveVaultAddress(distributorState: PublicKey, programId: PublicKey): PublicKey {
   return PublicKey.findProgramAddressSync([distributorState.toBuffer()], programId)[0];
 }
@@ -44,6 +71,7 @@ describe("distributor", () => {
   let numberOfShares = new BN(10);
 
   const funderToken = Keypair.generate();
+  const markerToken = Keypair.generate();
 
   before(async () => {
     await createMintIfRequired(splProgram, mint, provider.wallet.publicKey);
@@ -52,10 +80,69 @@ describe("distributor", () => {
     await createToken(splProgram, funderToken, mint.publicKey, provider.wallet.publicKey);
     const supply = (new BN(1_000_000)).mul(new BN(1_000_000_000));
     await mintTo(splProgram, supply, mint.publicKey, funderToken.publicKey, provider.wallet.publicKey);
+    // a distributor can't be created for a marker nobody holds
+    await createToken(splProgram, markerToken, markerMint.publicKey, provider.wallet.publicKey);
+    await mintTo(splProgram, new BN(1), markerMint.publicKey, markerToken.publicKey, provider.wallet.publicKey);
   });
 
   it("Should initialize", async () => {
-    await program.methods.initialize(shareSize, numberOfShares)
+    // more winners than a single transaction can pay
+    await expect(program.methods.initialize({shareSize, numberOfShares: new BN(21), remainderPolicy: {burn: {}}, distributionMode: {singleTx: {}}, coAuthority: null, burnShares: new BN(1), name: "", uri: "", maxRounds: null, shareSizeDecimals: null})
+      .accounts({
+        payer: provider.wallet.publicKey,
+        mint: mint.publicKey,
+        markerMint: markerMint.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+      })
+      .rpc()).to.be.rejected;
+    // the burned shares don't make room for more winners than a transaction can pay
+    await expect(program.methods.initialize({shareSize, numberOfShares, remainderPolicy: {burn: {}}, distributionMode: {singleTx: {}}, coAuthority: null, burnShares: new BN(0), name: "", uri: "", maxRounds: null, shareSizeDecimals: null})
+      .accounts({
+        payer: provider.wallet.publicKey,
+        mint: mint.publicKey,
+        markerMint: markerMint.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+      })
+      .rpc()).to.be.rejectedWith(/TooManyShares/);
+    // no share would be left to pay
+    await expect(program.methods.initialize({shareSize, numberOfShares, remainderPolicy: {burn: {}}, distributionMode: {singleTx: {}}, coAuthority: null, burnShares: numberOfShares, name: "", uri: "", maxRounds: null, shareSizeDecimals: null})
+      .accounts({
+        payer: provider.wallet.publicKey,
+        mint: mint.publicKey,
+        markerMint: markerMint.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+      })
+      .rpc()).to.be.rejected;
+    // the payout mint can't be its own marker
+    await expect(program.methods.initialize({shareSize, numberOfShares, remainderPolicy: {burn: {}}, distributionMode: {singleTx: {}}, coAuthority: null, burnShares: new BN(1), name: "", uri: "", maxRounds: null, shareSizeDecimals: null})
+      .accounts({
+        payer: provider.wallet.publicKey,
+        mint: mint.publicKey,
+        markerMint: mint.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+      })
+      .rpc()).to.be.rejectedWith(/MarkerMintIsPayoutMint/);
+    // the share size has more decimal places than allowed
+    await expect(program.methods.initialize({shareSize: shareSize.addn(1), numberOfShares, remainderPolicy: {burn: {}}, distributionMode: {singleTx: {}}, coAuthority: null, burnShares: new BN(1), name: "", uri: "", maxRounds: null, shareSizeDecimals: 1})
+      .accounts({
+        payer: provider.wallet.publicKey,
+        mint: mint.publicKey,
+        markerMint: markerMint.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+      })
+      .rpc()).to.be.rejectedWith(/InvalidShareSizeDecimals/);
+    const emptyMarkerMint = Keypair.generate();
+    await createMintIfRequired(splProgram, emptyMarkerMint, provider.wallet.publicKey);
+    await expect(program.methods.initialize({shareSize, numberOfShares, remainderPolicy: {burn: {}}, distributionMode: {singleTx: {}}, coAuthority: null, burnShares: new BN(1), name: "", uri: "", maxRounds: null, shareSizeDecimals: null})
+      .accounts({
+        payer: provider.wallet.publicKey,
+        mint: mint.publicKey,
+        markerMint: emptyMarkerMint.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+      })
+      .rpc()).to.be.rejectedWith(/EmptyMarkerMint/);
+
+    await program.methods.initialize({shareSize, numberOfShares, remainderPolicy: {burn: {}}, distributionMode: {singleTx: {}}, coAuthority: null, burnShares: new BN(1), name: "", uri: "", maxRounds: null, shareSizeDecimals: 0})
       .accounts({
         payer: provider.wallet.publicKey,
         mint: mint.publicKey,
@@ -63,17 +150,384 @@ describe("distributor", () => {
         distributorAuthority: distributorAuthority.publicKey,
       })
       .rpc();
+
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const state = await program.account.distributorState.fetch(distributorState);
+    expect(state.decimals).to.equal(9);
+    expect(state.distributionMode).to.deep.equal({singleTx: {}});
+    expect(state.burnShares.toNumber()).to.equal(1);
+  });
+
+  it("Should label the distributor", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const setMetadata = (name: string, uri: string) => program.methods.setMetadata(name, uri)
+      .accounts({
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+        distributorMetadata: null,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+
+    await expect(setMetadata("x".repeat(33), "")).to.be.rejected;
+    await setMetadata("Used phone giveaway", "https://example.com/distributor.json");
+
+    const state = await program.account.distributorState.fetch(distributorState);
+    expect(state.name).to.equal("Used phone giveaway");
+    expect(state.uri).to.equal("https://example.com/distributor.json");
+  });
+
+  it("Should manage the distributor metadata account", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const [distributorMetadata] = PublicKey.findProgramAddressSync([Buffer.from("distributor_metadata"), distributorState.toBuffer()], program.programId);
+    const accounts = {
+      distributorAuthority: distributorAuthority.publicKey,
+      distributorState,
+      distributorMetadata,
+    };
+
+    await expect(program.methods.openDistributorMetadata("Used phone giveaway", "X".repeat(11), "")
+      .accounts({...accounts, payer: provider.wallet.publicKey, systemProgram: web3.SystemProgram.programId})
+      .signers([distributorAuthority])
+      .rpc()).to.be.rejectedWith(/MetadataTooLong/);
+    await program.methods.openDistributorMetadata("Used phone giveaway", "PHONE", "")
+      .accounts({...accounts, payer: provider.wallet.publicKey, systemProgram: web3.SystemProgram.programId})
+      .signers([distributorAuthority])
+      .rpc();
+    await program.methods.updateDistributorMetadata("Used phone giveaway", "PHONES", "https://example.com/distributor.json")
+      .accounts(accounts)
+      .signers([distributorAuthority])
+      .rpc();
+
+    let metadata = await program.account.distributorMetadata.fetch(distributorMetadata);
+    expect(metadata.distributorState.toBase58()).to.equal(distributorState.toBase58());
+    expect(metadata.symbol).to.equal("PHONES");
+    expect(metadata.uri).to.equal("https://example.com/distributor.json");
+
+    // set_metadata keeps the account in sync with the distributor
+    await program.methods.setMetadata("Used phones", "https://example.com/phones.json")
+      .accounts({distributorAuthority: distributorAuthority.publicKey, distributorState, distributorMetadata})
+      .signers([distributorAuthority])
+      .rpc();
+    metadata = await program.account.distributorMetadata.fetch(distributorMetadata);
+    const state = await program.account.distributorState.fetch(distributorState);
+    expect(metadata.name).to.equal(state.name);
+    expect(metadata.uri).to.equal(state.uri);
+
+    await program.methods.closeDistributorMetadata().accounts(accounts).signers([distributorAuthority]).rpc();
+    expect(await provider.connection.getAccountInfo(distributorMetadata)).to.be.null;
+  });
+
+  it("Should fund the rent buffer", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const accounts = {
+      distributorAuthority: distributorAuthority.publicKey,
+      distributorState,
+    };
+
+    await program.methods.fundRentBuffer(new BN(10_000_000))
+      .accounts(accounts)
+      .preInstructions([web3.SystemProgram.transfer({
+        fromPubkey: provider.wallet.publicKey,
+        toPubkey: distributorAuthority.publicKey,
+        lamports: 20_000_000,
+      })])
+      .signers([distributorAuthority])
+      .rpc();
+    expect((await program.account.distributorState.fetch(distributorState)).rentBuffer.toNumber()).to.equal(10_000_000);
+
+    await expect(program.methods.withdrawRentBuffer(new BN(10_000_001))
+      .accounts(accounts)
+      .signers([distributorAuthority])
+      .rpc()).to.be.rejectedWith(/InsufficientRentBuffer/);
+    await program.methods.withdrawRentBuffer(new BN(4_000_000))
+      .accounts(accounts)
+      .signers([distributorAuthority])
+      .rpc();
+    expect((await program.account.distributorState.fetch(distributorState)).rentBuffer.toNumber()).to.equal(6_000_000);
+  });
+
+  it("Should bind the round to a VRF request", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const vrfProgram = new PublicKey("VRFzZoJdhFWL8rkvu87LpKM3RbcVezpMEc6X5GVDr7y");
+    // requests loaded from tests/fixtures, the randomness of the fulfilled one is already known
+    const fulfilledSeed = Buffer.alloc(32, 2);
+    const fulfilled = PublicKey.findProgramAddressSync([Buffer.from("orao-vrf-randomness-request"), fulfilledSeed], vrfProgram)[0];
+    await expect(program.methods.requestDraw(Array.from(fulfilledSeed), new BN(100))
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        serviceSigner: null,
+        distributorState,
+        randomness: fulfilled,
+        holderSnapshot: null,
+      })
+      .signers([distributorAuthority])
+      .rpc()).to.be.rejectedWith(/InvalidRandomnessRequest/);
+
+    const seed = Buffer.alloc(32, 1);
+    const randomness = PublicKey.findProgramAddressSync([Buffer.from("orao-vrf-randomness-request"), seed], vrfProgram)[0];
+
+    // fewer entrants than winners
+    await expect(program.methods.requestDraw(Array.from(seed), new BN(1))
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        serviceSigner: null,
+        distributorState,
+        randomness,
+        holderSnapshot: null,
+      })
+      .signers([distributorAuthority])
+      .rpc()).to.be.rejected;
+
+    // the draw of a distributor requiring one is drawn from the committed holders snapshot
+    const sensitiveConfig = {distributorAuthority: distributorAuthority.publicKey, coAuthority: null, distributorState};
+    await program.methods.setRequiresVrfDraw(true).accounts(sensitiveConfig).signers([distributorAuthority]).rpc();
+    await expect(program.methods.requestDraw(Array.from(seed), new BN(100))
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        serviceSigner: null,
+        distributorState,
+        randomness,
+        holderSnapshot: null,
+      })
+      .signers([distributorAuthority])
+      .rpc()).to.be.rejectedWith(/MissingHolderSnapshot/);
+    await program.methods.setRequiresVrfDraw(false).accounts(sensitiveConfig).signers([distributorAuthority]).rpc();
+
+    await program.methods.requestDraw(Array.from(seed), new BN(100))
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        serviceSigner: null,
+        distributorState,
+        randomness,
+        holderSnapshot: null,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+
+    const draw = await program.account.draw.fetch(deriveDrawAddress(distributorState, new BN(0), program.programId));
+    expect(draw.randomness.toString()).to.equal(randomness.toString());
+    expect(draw.entrants.toNumber()).to.equal(100);
+    expect(draw.value).to.be.null;
+    expect(draw.winnersNumber.toNumber()).to.equal(numberOfShares.toNumber() - 1);
+    expect(draw.winners).to.be.empty;
+  });
+
+  it("Should reveal only the committed seed of the draw", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const [committedDraw] = PublicKey.findProgramAddressSync(
+      [Buffer.from("committed_draw"), distributorState.toBuffer(), new BN(0).toBuffer("le", 8)],
+      program.programId,
+    );
+    const seed = Keypair.generate().publicKey.toBuffer();
+    const commitment = createHash("sha256").update(seed).digest();
+    const holders = Array.from({length: 20}, () => Keypair.generate().publicKey);
+    const snapshotRoot = buildSnapshotTree(holders).root;
+
+    await program.methods.commitDraw(Array.from(commitment), Array.from(snapshotRoot), new BN(holders.length))
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+        vault: deriveVaultAddress(distributorState, program.programId),
+        committedDraw,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+
+    const reveal = (revealed: Buffer) => program.methods.revealCommittedDraw(Array.from(revealed))
+      .accounts({
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+        committedDraw,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+    await expect(reveal(Keypair.generate().publicKey.toBuffer())).to.be.rejected;
+    await reveal(seed);
+    await expect(reveal(seed)).to.be.rejected;
+
+    const draw = await program.account.committedDraw.fetch(committedDraw);
+    expect(Buffer.from(draw.seed).equals(seed)).to.be.true;
+    expect(draw.entrants.toNumber()).to.equal(holders.length);
+    expect(draw.winners).to.be.empty;
+  });
+
+  it("Should commit the holders snapshot of the round once", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const [holderSnapshot] = PublicKey.findProgramAddressSync(
+      [Buffer.from("holder_snapshot"), distributorState.toBuffer(), new BN(0).toBuffer("le", 8)],
+      program.programId,
+    );
+    const holders = Array.from({length: 20}, () => Keypair.generate().publicKey);
+    const merkleRoot = buildSnapshotTree(holders).root;
+    const commitSnapshot = (slot: number) => program.methods.commitSnapshot(Array.from(merkleRoot), new BN(holders.length), new BN(slot))
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        serviceSigner: null,
+        distributorState,
+        holderSnapshot,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+
+    const slot = await provider.connection.getSlot();
+    await expect(commitSnapshot(slot + 1_000_000)).to.be.rejectedWith(/InvalidParameters/);
+    await commitSnapshot(slot);
+    await expect(commitSnapshot(slot)).to.be.rejected;
+
+    const snapshot = await program.account.holderSnapshot.fetch(holderSnapshot);
+    expect(Buffer.from(snapshot.merkleRoot).equals(merkleRoot)).to.be.true;
+    expect(snapshot.holdersCount.toNumber()).to.equal(holders.length);
+    expect(snapshot.slot.toNumber()).to.equal(slot);
+
+    // the winners of the VRF draw of the round can't be verified before it is revealed
+    const tree = buildSnapshotTree(holders);
+    await expect(program.methods.verifyDrawWinner(holders[0], tree.proof(0).map((node) => Array.from(node)))
+      .accounts({
+        draw: deriveDrawAddress(distributorState, new BN(0), program.programId),
+        holderSnapshot,
+      })
+      .rpc()).to.be.rejectedWith(/RandomnessNotFulfilled/);
   });
 
   it("Should deposit by calling contract", async () => {
     const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
     const amount = shareSize.mul(numberOfShares.subn(1));
-    await program.methods.deposit(amount).accounts({
+    const depositorRecord = PublicKey.findProgramAddressSync([
+      Buffer.from("depositor"), distributorState.toBuffer(), provider.wallet.publicKey.toBuffer()
+    ], program.programId)[0];
+    await program.methods.openDepositorRecord()
+      .accounts({
+        authority: provider.wallet.publicKey,
+        distributorState,
+        depositorRecord,
+      })
+      .rpc();
+
+    const signature = await program.methods.deposit(amount).accounts({
+      distributorState,
+      mint: mint.publicKey,
+      authority: provider.wallet.publicKey,
+      tokenAccount: funderToken.publicKey,
+      depositorRecord,
+      depositAllowance: null,
+    }).rpc({commitment: "confirmed"});
+
+    const [event] = await fetchCpiEvents(program, signature);
+    expect(event.name).to.equal("DepositEvent");
+    expect(event.data.amount.toString()).to.equal(amount.toString());
+    expect(event.data.vaultBalance.toString()).to.equal(amount.toString());
+
+    const record = await program.account.depositorRecord.fetch(depositorRecord);
+    expect(record.totalDeposited.toString()).to.equal(amount.toString());
+    expect(record.deposits.toNumber()).to.equal(1);
+    expect(record.lastDepositRound.toNumber()).to.equal(0);
+  });
+
+  it("Shouldn't deposit less than the minimal deposit", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const setMinDeposit = (minDeposit: BN) => program.methods.updateConfig({shareSize: null, numberOfShares: null, claimPeriod: null, unclaimedPolicy: null, minIntervalSeconds: null, minDeposit, referralBps: null})
+      .accounts({
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+        coAuthority: null,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+
+    await setMinDeposit(new BN(1_000));
+    await expect(program.methods.deposit(new BN(999)).accounts({
+      distributorState,
+      mint: mint.publicKey,
+      authority: provider.wallet.publicKey,
+      tokenAccount: funderToken.publicKey,
+      depositorRecord: null,
+      depositAllowance: null,
+    }).rpc()).to.be.rejectedWith(/DepositTooSmall/);
+    await setMinDeposit(new BN(0));
+  });
+
+  it("Shouldn't deposit while paused", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+
+    await program.methods.pause()
+      .accounts({
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+
+    await expect(program.methods.deposit(new BN(1)).accounts({
+      distributorState,
+      mint: mint.publicKey,
+      authority: provider.wallet.publicKey,
+      tokenAccount: funderToken.publicKey,
+      depositorRecord: null,
+      depositAllowance: null,
+    }).rpc()).to.be.rejected;
+
+    await program.methods.resume()
+      .accounts({
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+  });
+
+  it("Should accept deposits of the allowed depositors only", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const depositAllowance = PublicKey.findProgramAddressSync([
+      Buffer.from("deposit_allowance"), distributorState.toBuffer(), provider.wallet.publicKey.toBuffer()
+    ], program.programId)[0];
+    const setDepositAllowlist = (enabled: boolean) => program.methods.setDepositAllowlist(enabled)
+      .accounts({
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+    const deposit = (allowance: PublicKey | null) => program.methods.deposit(new BN(0)).accounts({
       distributorState,
       mint: mint.publicKey,
       authority: provider.wallet.publicKey,
       tokenAccount: funderToken.publicKey,
+      depositorRecord: null,
+      depositAllowance: allowance,
     }).rpc();
+
+    await setDepositAllowlist(true);
+    await expect(deposit(null)).to.be.rejectedWith(/DepositorNotAllowed/);
+
+    await program.methods.allowDepositor(provider.wallet.publicKey)
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+        depositAllowance,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+    await deposit(depositAllowance);
+
+    await program.methods.disallowDepositor()
+      .accounts({
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+        depositAllowance,
+        rentRecipient: provider.wallet.publicKey,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+    await expect(deposit(null)).to.be.rejectedWith(/DepositorNotAllowed/);
+    await setDepositAllowlist(false);
   });
 
   it("Shouldn't distribute before threshold", async () => {
@@ -91,21 +545,82 @@ describe("distributor", () => {
       remainingAccounts.push({pubkey: ata, isWritable: true, isSigner: false});
     }
 
-    await expect(program.methods.distribute()
+    await expect(program.methods.distribute([])
       .accounts({
         payer: provider.wallet.publicKey,
         distributorAuthority: distributorAuthority.publicKey,
+        serviceSigner: null,
+        committedDraw: null,
+        holderSnapshot: null,
         distributorState,
         mint: mint.publicKey,
+        treasury: null,
+        round: null,
+        draw: null,
+        nativeUnwrap: null,
+        blocklist: null,
+        previousRound: null,
+        vestingEscrow: null,
+        feeAccount: null,
+        feeVault: null,
+        triggerBonusAccount: null,
       }).remainingAccounts(remainingAccounts)
       .signers([distributorAuthority])
       .rpc()).to.be.rejected;
   });
 
+  it("Should report the winners which would fail the distribute checks", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const receivers = [Keypair.generate(), Keypair.generate(), Keypair.generate()];
+    const remainingAccounts: AccountMeta[] = receivers.flatMap((receiver, i) => [
+      {pubkey: receiver.publicKey, isWritable: false, isSigner: false},
+      // the second winner passes the ATA of the first one
+      {pubkey: getATA(receivers[i == 1 ? 0 : i].publicKey, mint.publicKey), isWritable: false, isSigner: false},
+    ]);
+
+    const bitmap = await program.methods.validateWinners(receivers.map(() => shareSize))
+      .accounts({
+        distributorState,
+        mint: mint.publicKey,
+        blocklist: null,
+        previousRound: null,
+      }).remainingAccounts(remainingAccounts)
+      .view();
+    expect(Array.from(bitmap)).to.deep.equal([0b101]);
+  });
+
+  it("Should pull a deposit from a delegated token account", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const amount = shareSize.divn(2);
+    const pull = (pullAmount: BN | null) => program.methods.depositWithDelegate(pullAmount).accounts({
+      distributorState,
+      mint: mint.publicKey,
+      tokenAccount: funderToken.publicKey,
+      depositorRecord: null,
+      depositAllowance: null,
+    }).rpc({commitment: "confirmed"});
+
+    await expect(pull(null)).to.be.rejectedWith(/NotDelegated/);
+    await splProgram.methods.approve(amount).accounts({
+      source: funderToken.publicKey,
+      delegate: distributorState,
+      owner: provider.wallet.publicKey,
+    }).rpc();
+    await expect(pull(amount.addn(1))).to.be.rejectedWith(/NotDelegated/);
+
+    const signature = await pull(null);
+    const [event] = await fetchCpiEvents(program, signature);
+    expect(event.name).to.equal("DepositEvent");
+    expect(event.data.depositor.toBase58()).to.equal(provider.wallet.publicKey.toBase58());
+    expect(event.data.amount.toString()).to.equal(amount.toString());
+    expect((await splProgram.account.account.fetch(funderToken.publicKey)).delegatedAmount.toNumber()).to.equal(0);
+  });
+
   it("Should deposit by transferring tokens to vault", async () => {
     const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
     const vaultAddress = deriveVaultAddress(distributorState, program.programId);
-    await splProgram.methods.transferChecked(shareSize, 9).accounts({
+    // the other half of the share is pulled from the delegated token account
+    await splProgram.methods.transferChecked(shareSize.divn(2), 9).accounts({
       source: funderToken.publicKey,
       mint: mint.publicKey,
       destination: vaultAddress,
@@ -113,30 +628,1731 @@ describe("distributor", () => {
     }).rpc();
   });
 
-  it("Should distribute if threshold is reached", async () => {
+  it("Shouldn't set remainder split which doesn't sum up to one share", async () => {
     const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
 
-    const vaultAddress = deriveVaultAddress(distributorState, program.programId);
-    const vaultAccount = await splProgram.account.account.fetch(vaultAddress);
-    expect(vaultAccount.amount.toString()).to.equal((shareSize.mul(numberOfShares)).toString());
+    const split = {burnBps: 5000, treasuryBps: 0, rolloverBps: 4000};
+    await expect(program.methods.setRemainderPolicy({split: {split, treasury: PublicKey.default}})
+      .accounts({
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+      })
+      .signers([distributorAuthority])
+      .rpc()).to.be.rejected;
+  });
 
-    let remainingAccounts: AccountMeta[] = [];
+  it("Should set remainder policy", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+
+    await expect(program.methods.setRemainderPolicy({sendToTreasury: {0: PublicKey.default}})
+      .accounts({
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+      })
+      .signers([distributorAuthority])
+      .rpc()).to.be.rejected;
+
+    const split = {burnBps: 5000, treasuryBps: 0, rolloverBps: 5000};
+    await program.methods.setRemainderPolicy({split: {split, treasury: PublicKey.default}})
+      .accounts({
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+
+    const state = await program.account.distributorState.fetch(distributorState);
+    expect(state.remainderPolicy.split.split.rolloverBps).to.equal(5000);
+  });
+
+  it("Shouldn't distribute to blocked recipients", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const blocklist = PublicKey.findProgramAddressSync([Buffer.from("blocklist"), distributorState.toBuffer()], program.programId)[0];
+    const blocked = Keypair.generate();
+    const blocklistAccounts = {
+      payer: provider.wallet.publicKey,
+      distributorAuthority: distributorAuthority.publicKey,
+      distributorState,
+      blocklist,
+    };
+    await program.methods.openBlocklist().accounts(blocklistAccounts).signers([distributorAuthority]).rpc();
+    await program.methods.addToBlocklist(blocked.publicKey).accounts(blocklistAccounts).signers([distributorAuthority]).rpc();
+    await expect(program.methods.addToBlocklist(blocked.publicKey).accounts(blocklistAccounts).signers([distributorAuthority]).rpc())
+      .to.be.rejected;
+    expect((await program.account.distributorState.fetch(distributorState)).blockedRecipients).to.equal(1);
+
+    const winners = [blocked, ...Array.from({length: numberOfShares.toNumber() - 2}, () => Keypair.generate())];
+    const remainingAccounts: AccountMeta[] = winners.flatMap((winner) => [
+      {pubkey: winner.publicKey, isWritable: false, isSigner: false},
+      {pubkey: getATA(winner.publicKey, mint.publicKey), isWritable: true, isSigner: false},
+    ]);
+    const distribute = (blocklist: PublicKey | null) => program.methods.distribute([])
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        serviceSigner: null,
+        committedDraw: null,
+        holderSnapshot: null,
+        distributorState,
+        mint: mint.publicKey,
+        treasury: null,
+        round: null,
+        draw: null,
+        nativeUnwrap: null,
+        blocklist,
+        previousRound: null,
+        vestingEscrow: null,
+        feeAccount: null,
+        feeVault: null,
+        triggerBonusAccount: null,
+      }).remainingAccounts(remainingAccounts)
+      .signers([distributorAuthority])
+      .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({units: 1_400_000})])
+      .rpc();
+    await expect(distribute(null)).to.be.rejectedWith(/MissingBlocklist/);
+    await expect(distribute(blocklist)).to.be.rejectedWith(/RecipientBlocked/);
+
+    await program.methods.removeFromBlocklist(blocked.publicKey).accounts(blocklistAccounts).signers([distributorAuthority]).rpc();
+    const state = await program.account.distributorState.fetch(distributorState);
+    expect(state.blockedRecipients).to.equal(0);
+    expect((await program.account.blocklist.fetch(blocklist)).wallets).to.be.empty;
+  });
+
+  it("Shouldn't pay a recipient twice in one round", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const winners = Array.from({length: numberOfShares.toNumber() - 2}, () => Keypair.generate());
+    const remainingAccounts: AccountMeta[] = [winners[0], ...winners].flatMap((winner) => [
+      {pubkey: winner.publicKey, isWritable: false, isSigner: false},
+      {pubkey: getATA(winner.publicKey, mint.publicKey), isWritable: true, isSigner: false},
+    ]);
+    await expect(program.methods.distribute([])
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        serviceSigner: null,
+        committedDraw: null,
+        holderSnapshot: null,
+        distributorState,
+        mint: mint.publicKey,
+        treasury: null,
+        round: null,
+        draw: null,
+        nativeUnwrap: null,
+        blocklist: null,
+        previousRound: null,
+        vestingEscrow: null,
+        feeAccount: null,
+        feeVault: null,
+        triggerBonusAccount: null,
+      }).remainingAccounts(remainingAccounts)
+      .signers([distributorAuthority])
+      .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({units: 1_400_000})])
+      .rpc()).to.be.rejectedWith(/DuplicateRecipient/);
+  });
+
+  it("Shouldn't pay the distributor or its operators", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const distribute = (recipient: PublicKey) => {
+      const winners = [recipient, ...Array.from({length: numberOfShares.toNumber() - 2}, () => Keypair.generate().publicKey)];
+      const remainingAccounts: AccountMeta[] = winners.flatMap((winner) => [
+        {pubkey: winner, isWritable: false, isSigner: false},
+        {pubkey: getATA(winner, mint.publicKey), isWritable: true, isSigner: false},
+      ]);
+      return program.methods.distribute([])
+        .accounts({
+          payer: provider.wallet.publicKey,
+          distributorAuthority: distributorAuthority.publicKey,
+          serviceSigner: null,
+          committedDraw: null,
+          holderSnapshot: null,
+          distributorState,
+          mint: mint.publicKey,
+          treasury: null,
+          round: null,
+          draw: null,
+          nativeUnwrap: null,
+          blocklist: null,
+          previousRound: null,
+          vestingEscrow: null,
+          feeAccount: null,
+          feeVault: null,
+          triggerBonusAccount: null,
+        }).remainingAccounts(remainingAccounts)
+        .signers([distributorAuthority])
+        .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({units: 1_400_000})])
+        .rpc();
+    };
+
+    const recipients = [distributorState, deriveVaultAddress(distributorState, program.programId), provider.wallet.publicKey, distributorAuthority.publicKey];
+    for (const recipient of recipients) {
+      await expect(distribute(recipient)).to.be.rejectedWith(/SelfRecipient/);
+    }
+  });
+
+  it("Should toggle the no repeat winners rule", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const accounts = {
+      distributorAuthority: distributorAuthority.publicKey,
+      distributorState,
+    };
+    await program.methods.setNoRepeatWinners(true).accounts(accounts).signers([distributorAuthority]).rpc();
+    let state = await program.account.distributorState.fetch(distributorState);
+    expect(state.noRepeatWinnersSince.toString()).to.equal(state.round.toString());
+
+    await program.methods.setNoRepeatWinners(false).accounts(accounts).signers([distributorAuthority]).rpc();
+    state = await program.account.distributorState.fetch(distributorState);
+    expect(state.noRepeatWinnersSince).to.be.null;
+  });
+
+  it("Should set the frozen recipient policy", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const setPolicy = (policy: object | null) => program.methods.setFrozenRecipientPolicy(policy)
+      .accounts({
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+
+    await setPolicy({rollover: {}});
+    let state = await program.account.distributorState.fetch(distributorState);
+    expect(state.frozenRecipientPolicy).to.deep.equal({rollover: {}});
+
+    await setPolicy(null);
+    state = await program.account.distributorState.fetch(distributorState);
+    expect(state.frozenRecipientPolicy).to.be.null;
+  });
+
+  it("Should set the rounding policy of the weighted splits", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const setPolicy = (policy: object) => program.methods.setRoundingPolicy(policy)
+      .accounts({
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+
+    expect((await program.account.distributorState.fetch(distributorState)).roundingPolicy).to.deep.equal({floorBurn: {}});
+    await setPolicy({bankers: {}});
+    expect((await program.account.distributorState.fetch(distributorState)).roundingPolicy).to.deep.equal({bankers: {}});
+    await setPolicy({floorBurn: {}});
+  });
+
+  it("Should set the claim period of the escrowed shares", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const setClaimPeriod = (claimPeriod: BN | null) => program.methods.setEscrowClaimPeriod(claimPeriod)
+      .accounts({
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+
+    await expect(setClaimPeriod(new BN(0))).to.be.rejectedWith(/InvalidParameters/);
+    await setClaimPeriod(new BN(30 * 24 * 60 * 60));
+    expect((await program.account.distributorState.fetch(distributorState)).escrowClaimPeriod.toNumber()).to.equal(30 * 24 * 60 * 60);
+    await setClaimPeriod(null);
+    expect((await program.account.distributorState.fetch(distributorState)).escrowClaimPeriod).to.be.null;
+  });
+
+  it("Should set the NFT collection the winners have to hold", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const setCollection = (collection: PublicKey | null) => program.methods.setMarkerCollection(collection)
+      .accounts({
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+
+    const collection = Keypair.generate().publicKey;
+    await setCollection(collection);
+    expect((await program.account.distributorState.fetch(distributorState)).markerCollection.toBase58()).to.equal(collection.toBase58());
+    await setCollection(null);
+    expect((await program.account.distributorState.fetch(distributorState)).markerCollection).to.be.null;
+  });
+
+  it("Should toggle non-ATA recipients", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const accounts = {
+      distributorAuthority: distributorAuthority.publicKey,
+      distributorState,
+    };
+    await program.methods.setAllowNonAtaRecipients(true).accounts(accounts).signers([distributorAuthority]).rpc();
+    expect((await program.account.distributorState.fetch(distributorState)).allowNonAtaRecipients).to.be.true;
+
+    await program.methods.setAllowNonAtaRecipients(false).accounts(accounts).signers([distributorAuthority]).rpc();
+    expect((await program.account.distributorState.fetch(distributorState)).allowNonAtaRecipients).to.be.false;
+  });
+
+  it("Should toggle the vesting of the shares", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const [vestingEscrow] = PublicKey.findProgramAddressSync([Buffer.from("vesting_escrow"), distributorState.toBuffer()], program.programId);
+    await program.methods.openVestingEscrow()
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+        mint: mint.publicKey,
+        vestingEscrow,
+        systemProgram: web3.SystemProgram.programId,
+        tokenProgram: SPL_TOKEN_PROGRAM_ID,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+
+    const accounts = {
+      distributorAuthority: distributorAuthority.publicKey,
+      distributorState,
+    };
+    await expect(program.methods.setVesting(new BN(0)).accounts(accounts).signers([distributorAuthority]).rpc())
+      .to.be.rejected;
+    await program.methods.setVesting(new BN(3600)).accounts(accounts).signers([distributorAuthority]).rpc();
+    expect((await program.account.distributorState.fetch(distributorState)).vestingDuration.toNumber()).to.equal(3600);
+
+    await program.methods.setVesting(null).accounts(accounts).signers([distributorAuthority]).rpc();
+    expect((await program.account.distributorState.fetch(distributorState)).vestingDuration).to.be.null;
+  });
+
+  it("Should opt in to the large payouts", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const accounts = {
+      distributorAuthority: distributorAuthority.publicKey,
+      distributorState,
+    };
+    await expect(program.methods.setLargePayoutThreshold(new BN(0)).accounts(accounts).signers([distributorAuthority]).rpc())
+      .to.be.rejectedWith(/InvalidParameters/);
+    await program.methods.setLargePayoutThreshold(shareSize.subn(1)).accounts(accounts).signers([distributorAuthority]).rpc();
+    expect((await program.account.distributorState.fetch(distributorState)).largePayoutThreshold.toString())
+      .to.equal(shareSize.subn(1).toString());
+    await program.methods.setLargePayoutThreshold(null).accounts(accounts).signers([distributorAuthority]).rpc();
+
+    const wallet = Keypair.generate();
+    const [payoutAcceptance] = PublicKey.findProgramAddressSync([Buffer.from("payout_acceptance"), wallet.publicKey.toBuffer()], program.programId);
+    await program.methods.acceptLargePayouts()
+      .accounts({payer: provider.wallet.publicKey, wallet: wallet.publicKey, payoutAcceptance})
+      .signers([wallet])
+      .rpc();
+    expect((await program.account.payoutAcceptance.fetch(payoutAcceptance)).wallet.toBase58()).to.equal(wallet.publicKey.toBase58());
+
+    await program.methods.revokeLargePayouts()
+      .accounts({wallet: wallet.publicKey, payoutAcceptance})
+      .signers([wallet])
+      .rpc();
+    expect(await program.account.payoutAcceptance.fetchNullable(payoutAcceptance)).to.be.null;
+  });
+
+  it("Shouldn't migrate a state of the current version", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    expect((await program.account.distributorState.fetch(distributorState)).version).to.equal(16);
+
+    await expect(program.methods.migrateState()
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+        mint: mint.publicKey,
+      })
+      .signers([distributorAuthority])
+      .rpc()).to.be.rejectedWith(/AlreadyMigrated/);
+  });
+
+  it("Should migrate a state of the first layout", async () => {
+    // loaded from tests/fixtures, the state ends at the vault bump
+    const legacyAuthority = Keypair.fromSecretKey(new Uint8Array(JSON.parse(fs.readFileSync('tests/keys/legacy_authority.json').toString())));
+    const legacyShareSize = new BN(1_000_000_000);
+    const legacyNumberOfShares = new BN(5);
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, legacyShareSize, legacyNumberOfShares, program.programId);
+    expect((await provider.connection.getAccountInfo(distributorState)).data.length).to.equal(154);
+    const migrateState = () => program.methods.migrateState()
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: legacyAuthority.publicKey,
+        distributorState,
+        mint: mint.publicKey,
+      })
+      .signers([legacyAuthority])
+      .rpc();
+
+    await migrateState();
+
+    const state = await program.account.distributorState.fetch(distributorState);
+    expect(state.version).to.equal(16);
+    expect(state.shareSize.toString()).to.equal(legacyShareSize.toString());
+    expect(state.initialShareSize.toString()).to.equal(legacyShareSize.toString());
+    expect(state.initialNumberOfShares.toString()).to.equal(legacyNumberOfShares.toString());
+    expect(state.decimals).to.equal(9);
+    expect(state.claimPeriod.toNumber()).to.equal(30 * 24 * 60 * 60);
+    expect(state.burnShares.toNumber()).to.equal(1);
+    await expect(migrateState()).to.be.rejectedWith(/AlreadyMigrated/);
+  });
+
+  it("Shouldn't distribute without the service signer", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const serviceSigner = Keypair.generate();
+    const setServiceSigner = (signer: object | null) => program.methods.setServiceSigner(signer)
+      .accounts({
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+        coAuthority: null,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+
+    await setServiceSigner({key: serviceSigner.publicKey, crank: false});
+    await expect(program.methods.distribute([])
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        serviceSigner: null,
+        committedDraw: null,
+        holderSnapshot: null,
+        distributorState,
+        mint: mint.publicKey,
+        treasury: null,
+        round: null,
+        draw: null,
+        nativeUnwrap: null,
+        blocklist: null,
+        previousRound: null,
+        vestingEscrow: null,
+        feeAccount: null,
+        feeVault: null,
+        triggerBonusAccount: null,
+      })
+      .signers([distributorAuthority])
+      .rpc()).to.be.rejectedWith(/UnauthorizedRound/);
+    await expect(program.methods.beginRound()
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        serviceSigner: null,
+        distributorState,
+        vault: deriveVaultAddress(distributorState, program.programId),
+        roundProgress: deriveRoundProgressAddress(distributorState, new BN(0), program.programId),
+        round: null,
+        feeVault: null,
+      })
+      .signers([distributorAuthority])
+      .rpc()).to.be.rejectedWith(/UnauthorizedRound/);
+
+    await setServiceSigner(null);
+    expect((await program.account.distributorState.fetch(distributorState)).serviceSigner).to.be.null;
+  });
+
+  it("Should charge the protocol fee only with the fee account", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const feeCollector = Keypair.generate();
+    const setProtocolFee = (feeBps: number, collector: PublicKey | null) => program.methods.setProtocolFee(feeBps, collector)
+      .accounts({
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+        coAuthority: null,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+
+    // the fee can't exceed the terminal share
+    await expect(setProtocolFee(10_000, feeCollector.publicKey)).to.be.rejectedWith(/InvalidProtocolFee/);
+    await setProtocolFee(100, feeCollector.publicKey);
+    const state = await program.account.distributorState.fetch(distributorState);
+    expect(state.feeBps).to.equal(100);
+    expect(state.feeCollector.toBase58()).to.equal(feeCollector.publicKey.toBase58());
+
+    const remainingAccounts: AccountMeta[] = Array.from({length: numberOfShares.toNumber() - 1}, () => Keypair.generate())
+      .flatMap((winner) => [
+        {pubkey: winner.publicKey, isWritable: false, isSigner: false},
+        {pubkey: getATA(winner.publicKey, mint.publicKey), isWritable: true, isSigner: false},
+      ]);
+    await expect(program.methods.distribute([])
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        serviceSigner: null,
+        committedDraw: null,
+        holderSnapshot: null,
+        distributorState,
+        mint: mint.publicKey,
+        treasury: null,
+        round: null,
+        draw: null,
+        nativeUnwrap: null,
+        blocklist: null,
+        previousRound: null,
+        vestingEscrow: null,
+        feeAccount: null,
+        feeVault: null,
+        triggerBonusAccount: null,
+      }).remainingAccounts(remainingAccounts)
+      .signers([distributorAuthority])
+      .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({units: 1_400_000})])
+      .rpc()).to.be.rejectedWith(/MissingFeeAccount/);
+
+    await setProtocolFee(0, null);
+  });
+
+  it("Should charge the lamport fee only into the fee vault", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const feeVault = PublicKey.findProgramAddressSync([Buffer.from("fee_vault"), distributorState.toBuffer()], program.programId)[0];
+    const feeCollector = Keypair.generate();
+    const sensitiveAccounts = {
+      distributorAuthority: distributorAuthority.publicKey,
+      distributorState,
+      coAuthority: null,
+    };
+
+    // the fee needs a collector
+    await expect(program.methods.setLamportFee(new BN(5_000)).accounts(sensitiveAccounts).signers([distributorAuthority]).rpc())
+      .to.be.rejectedWith(/InvalidProtocolFee/);
+    await program.methods.setProtocolFee(0, feeCollector.publicKey).accounts(sensitiveAccounts).signers([distributorAuthority]).rpc();
+    await program.methods.setLamportFee(new BN(5_000)).accounts(sensitiveAccounts).signers([distributorAuthority]).rpc();
+    expect((await program.account.distributorState.fetch(distributorState)).lamportFee.toNumber()).to.equal(5_000);
+
+    const remainingAccounts: AccountMeta[] = Array.from({length: numberOfShares.toNumber() - 1}, () => Keypair.generate())
+      .flatMap((winner) => [
+        {pubkey: winner.publicKey, isWritable: false, isSigner: false},
+        {pubkey: getATA(winner.publicKey, mint.publicKey), isWritable: true, isSigner: false},
+      ]);
+    await expect(program.methods.distribute([])
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        serviceSigner: null,
+        committedDraw: null,
+        holderSnapshot: null,
+        distributorState,
+        mint: mint.publicKey,
+        treasury: null,
+        round: null,
+        draw: null,
+        nativeUnwrap: null,
+        blocklist: null,
+        previousRound: null,
+        vestingEscrow: null,
+        feeAccount: null,
+        feeVault: null,
+        triggerBonusAccount: null,
+      }).remainingAccounts(remainingAccounts)
+      .signers([distributorAuthority])
+      .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({units: 1_400_000})])
+      .rpc()).to.be.rejectedWith(/MissingFeeVault/);
+
+    await program.methods.openFeeVault()
+      .accounts({payer: provider.wallet.publicKey, distributorAuthority: distributorAuthority.publicKey, distributorState, feeVault})
+      .signers([distributorAuthority])
+      .rpc();
+    const collectFees = (signer: Keypair) => program.methods.collectFees()
+      .accounts({feeCollector: signer.publicKey, distributorState, feeVault, destination: feeCollector.publicKey})
+      .signers([signer])
+      .rpc();
+    await expect(collectFees(distributorAuthority)).to.be.rejectedWith(/NotFeeCollector/);
+    await expect(collectFees(feeCollector)).to.be.rejectedWith(/NothingToCollect/);
+    expect((await program.account.feeVault.fetch(feeVault)).collected.toNumber()).to.equal(0);
+
+    await program.methods.setLamportFee(new BN(0)).accounts(sensitiveAccounts).signers([distributorAuthority]).rpc();
+    await program.methods.setProtocolFee(0, null).accounts(sensitiveAccounts).signers([distributorAuthority]).rpc();
+  });
+
+  it("Should refuse the rounds beyond the maximum", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const sensitiveAccounts = {
+      distributorAuthority: distributorAuthority.publicKey,
+      distributorState,
+      coAuthority: null,
+    };
+    const setMaxRounds = (maxRounds: BN | null) => program.methods.setMaxRounds(maxRounds)
+      .accounts(sensitiveAccounts)
+      .signers([distributorAuthority])
+      .rpc();
+    const round = (await program.account.distributorState.fetch(distributorState)).round;
+
+    await setMaxRounds(round);
+    expect((await program.account.distributorState.fetch(distributorState)).maxRounds.toString()).to.equal(round.toString());
+
+    const remainingAccounts: AccountMeta[] = Array.from({length: numberOfShares.toNumber() - 1}, () => Keypair.generate())
+      .flatMap((winner) => [
+        {pubkey: winner.publicKey, isWritable: false, isSigner: false},
+        {pubkey: getATA(winner.publicKey, mint.publicKey), isWritable: true, isSigner: false},
+      ]);
+    await expect(program.methods.distribute([])
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        serviceSigner: null,
+        committedDraw: null,
+        holderSnapshot: null,
+        distributorState,
+        mint: mint.publicKey,
+        treasury: null,
+        round: null,
+        draw: null,
+        nativeUnwrap: null,
+        blocklist: null,
+        previousRound: null,
+        vestingEscrow: null,
+        feeAccount: null,
+        feeVault: null,
+        triggerBonusAccount: null,
+      }).remainingAccounts(remainingAccounts)
+      .signers([distributorAuthority])
+      .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({units: 1_400_000})])
+      .rpc()).to.be.rejectedWith(/MaxRoundsReached/);
+
+    await setMaxRounds(null);
+  });
+
+  it("Should trigger the rounds at an explicit threshold", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const setThreshold = (threshold: BN | null) => program.methods.setThreshold(threshold)
+      .accounts({
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+        coAuthority: null,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+    const state = await program.account.distributorState.fetch(distributorState);
+    const payoutSum = state.shareSize.mul(state.numberOfShares);
+
+    // a round has to be able to pay all of its shares
+    await expect(setThreshold(payoutSum.subn(1))).to.be.rejectedWith(/ThresholdBelowPayout/);
+    // keeps a 10% float in the vault
+    await setThreshold(payoutSum.muln(11).divn(10));
+    expect((await program.account.distributorState.fetch(distributorState)).threshold.toString()).to.equal(payoutSum.muln(11).divn(10).toString());
+    await expect(program.methods.updateConfig({shareSize: state.shareSize.muln(2), numberOfShares: null, claimPeriod: null, unclaimedPolicy: null, minIntervalSeconds: null, minDeposit: null, referralBps: null})
+      .accounts({
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+        coAuthority: null,
+      })
+      .signers([distributorAuthority])
+      .rpc()).to.be.rejectedWith(/ThresholdBelowPayout/);
+
+    await setThreshold(null);
+    expect((await program.account.distributorState.fetch(distributorState)).threshold).to.be.null;
+  });
+
+  it("Should set the trigger bonus", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const setTriggerBonus = (triggerBonus: BN | null) => program.methods.setTriggerBonus(triggerBonus)
+      .accounts({
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+        coAuthority: null,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+
+    await expect(setTriggerBonus(new BN(0))).to.be.rejectedWith(/InvalidParameters/);
+    await setTriggerBonus(new BN(1_000));
+    const state = await program.account.distributorState.fetch(distributorState);
+    expect(state.triggerBonus.toNumber()).to.equal(1_000);
+    expect(state.lastDepositor).to.be.null;
+
+    await setTriggerBonus(null);
+    expect((await program.account.distributorState.fetch(distributorState)).triggerBonus).to.be.null;
+  });
+
+  it("Should require the threshold of the co-signers", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const cosigners = [Keypair.generate(), Keypair.generate()];
+    const sensitiveAccounts = {
+      distributorAuthority: distributorAuthority.publicKey,
+      distributorState,
+      coAuthority: null,
+    };
+    const setCosigners = (threshold: number) => program.methods
+      .setCosigners({threshold, keys: cosigners.map((cosigner) => cosigner.publicKey)})
+      .accounts(sensitiveAccounts)
+      .signers([distributorAuthority])
+      .rpc();
+
+    await expect(setCosigners(0)).to.be.rejectedWith(/InvalidCosigners/);
+    await expect(setCosigners(3)).to.be.rejectedWith(/InvalidCosigners/);
+    await setCosigners(1);
+    expect((await program.account.distributorState.fetch(distributorState)).cosigners.threshold).to.equal(1);
+
+    const remainingAccounts: AccountMeta[] = Array.from({length: numberOfShares.toNumber() - 1}, () => Keypair.generate())
+      .flatMap((winner) => [
+        {pubkey: winner.publicKey, isWritable: false, isSigner: false},
+        {pubkey: getATA(winner.publicKey, mint.publicKey), isWritable: true, isSigner: false},
+      ]);
+    await expect(program.methods.distribute([])
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        serviceSigner: null,
+        committedDraw: null,
+        holderSnapshot: null,
+        distributorState,
+        mint: mint.publicKey,
+        treasury: null,
+        round: null,
+        draw: null,
+        nativeUnwrap: null,
+        blocklist: null,
+        previousRound: null,
+        vestingEscrow: null,
+        feeAccount: null,
+        feeVault: null,
+        triggerBonusAccount: null,
+      }).remainingAccounts(remainingAccounts)
+      .signers([distributorAuthority])
+      .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({units: 1_400_000})])
+      .rpc()).to.be.rejectedWith(/MissingCosignatures/);
+
+    await program.methods.setCosigners(null).accounts(sensitiveAccounts).signers([distributorAuthority]).rpc();
+  });
+
+  it("Should settle only the rounds the vault can pay", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const remainingAccounts: AccountMeta[] = Array.from({length: 2 * (numberOfShares.toNumber() - 1)}, () => Keypair.generate())
+      .flatMap((winner) => [
+        {pubkey: winner.publicKey, isWritable: false, isSigner: false},
+        {pubkey: getATA(winner.publicKey, mint.publicKey), isWritable: true, isSigner: false},
+      ]);
+    const distributeRounds = (rounds: number) => program.methods.distributeRounds(rounds)
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        serviceSigner: null,
+        committedDraw: null,
+        holderSnapshot: null,
+        distributorState,
+        mint: mint.publicKey,
+        treasury: null,
+        round: null,
+        draw: null,
+        nativeUnwrap: null,
+        blocklist: null,
+        previousRound: null,
+        vestingEscrow: null,
+        feeAccount: null,
+        feeVault: null,
+        triggerBonusAccount: null,
+      }).remainingAccounts(remainingAccounts)
+      .signers([distributorAuthority])
+      .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({units: 1_400_000})])
+      .rpc();
+
+    // the vault holds a single threshold
+    await expect(distributeRounds(0)).to.be.rejectedWith(/InvalidParameters/);
+    await expect(distributeRounds(2)).to.be.rejectedWith(/ThresholdNotMet/);
+    expect((await program.account.distributorState.fetch(distributorState)).round.toNumber()).to.equal(0);
+  });
+
+  it("Should distribute if threshold is reached", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+
+    const vaultAddress = deriveVaultAddress(distributorState, program.programId);
+    const vaultAccount = await splProgram.account.account.fetch(vaultAddress);
+    expect(vaultAccount.amount.toString()).to.equal((shareSize.mul(numberOfShares)).toString());
+
+    let remainingAccounts: AccountMeta[] = [];
     for (let i = 0; i < numberOfShares.toNumber() - 1; i++) {
       const receiver = Keypair.generate();
-      const ata = getATA(receiver.publicKey, mint.publicKey);
+      const ata = getATA(receiver.publicKey, mint.publicKey);
+      remainingAccounts.push({pubkey: receiver.publicKey, isWritable: false, isSigner: false});
+      remainingAccounts.push({pubkey: ata, isWritable: true, isSigner: false});
+    }
+
+    const round = deriveRoundAddress(distributorState, new BN(0), program.programId);
+    // a transaction built for another round doesn't land
+    await expect(program.methods.distributeV2({expectedRound: new BN(1), amounts: null, notes: [], snapshotSlot: new BN(1), weights: null, proRata: false})
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        serviceSigner: null,
+        committedDraw: null,
+        holderSnapshot: null,
+        distributorState,
+        mint: mint.publicKey,
+        treasury: null,
+        round: null,
+        draw: null,
+        nativeUnwrap: null,
+        blocklist: null,
+        previousRound: null,
+        vestingEscrow: null,
+        feeAccount: null,
+        feeVault: null,
+        triggerBonusAccount: null,
+      }).remainingAccounts(remainingAccounts)
+      .signers([distributorAuthority])
+      .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({units: 1_400_000})])
+      .rpc()).to.be.rejectedWith(/RoundMismatch/);
+    // the marker balances weigh a pro-rata round only if the marker accounts are passed
+    await expect(program.methods.distributeV2({expectedRound: new BN(0), amounts: null, notes: [], snapshotSlot: null, weights: null, proRata: true})
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        serviceSigner: null,
+        committedDraw: null,
+        holderSnapshot: null,
+        distributorState,
+        mint: mint.publicKey,
+        treasury: null,
+        round: null,
+        draw: null,
+        nativeUnwrap: null,
+        blocklist: null,
+        previousRound: null,
+        vestingEscrow: null,
+        feeAccount: null,
+        feeVault: null,
+        triggerBonusAccount: null,
+      }).remainingAccounts(remainingAccounts)
+      .signers([distributorAuthority])
+      .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({units: 1_400_000})])
+      .rpc()).to.be.rejectedWith(/ProRataWithoutMarker/);
+    // only a distributor of the dividend mode pays dividends
+    await expect(program.methods.distributeDividend(new BN(0))
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        serviceSigner: null,
+        committedDraw: null,
+        holderSnapshot: null,
+        distributorState,
+        mint: mint.publicKey,
+        treasury: null,
+        round: null,
+        draw: null,
+        nativeUnwrap: null,
+        blocklist: null,
+        previousRound: null,
+        vestingEscrow: null,
+        feeAccount: null,
+        feeVault: null,
+        triggerBonusAccount: null,
+      }).remainingAccounts(remainingAccounts)
+      .signers([distributorAuthority])
+      .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({units: 1_400_000})])
+      .rpc()).to.be.rejectedWith(/WrongDistributionMode/);
+    // notes have to be passed either for every winner or for none
+    await expect(program.methods.distribute([Array.from(Buffer.alloc(32, 1))])
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        serviceSigner: null,
+        committedDraw: null,
+        holderSnapshot: null,
+        distributorState,
+        mint: mint.publicKey,
+        treasury: null,
+        round: null,
+        draw: null,
+        nativeUnwrap: null,
+        blocklist: null,
+        previousRound: null,
+        vestingEscrow: null,
+        feeAccount: null,
+        feeVault: null,
+        triggerBonusAccount: null,
+      }).remainingAccounts(remainingAccounts)
+      .signers([distributorAuthority])
+      .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({units: 1_400_000})])
+      .rpc()).to.be.rejected;
+
+    // the draw requested for the round isn't revealed, its request is never fulfilled on the test validator
+    const distributeDrawn = (draw: PublicKey | null) => program.methods.distribute([])
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        serviceSigner: null,
+        committedDraw: null,
+        holderSnapshot: null,
+        distributorState,
+        mint: mint.publicKey,
+        treasury: null,
+        round,
+        draw,
+        nativeUnwrap: null,
+        blocklist: null,
+        previousRound: null,
+        vestingEscrow: null,
+        feeAccount: null,
+        feeVault: null,
+        triggerBonusAccount: null,
+      }).remainingAccounts(remainingAccounts)
+      .signers([distributorAuthority])
+      .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({units: 1_400_000})])
+      .rpc();
+    const unrevealedDraw = deriveDrawAddress(distributorState, new BN(0), program.programId);
+    await expect(distributeDrawn(unrevealedDraw)).to.be.rejectedWith(/RandomnessNotFulfilled/);
+    const sensitiveConfig = {distributorAuthority: distributorAuthority.publicKey, coAuthority: null, distributorState};
+    await program.methods.setRequiresVrfDraw(true).accounts(sensitiveConfig).signers([distributorAuthority]).rpc();
+    await expect(distributeDrawn(null)).to.be.rejectedWith(/MissingDraw/);
+    await expect(distributeDrawn(unrevealedDraw)).to.be.rejectedWith(/RandomnessNotFulfilled/);
+    await program.methods.setRequiresVrfDraw(false).accounts(sensitiveConfig).signers([distributorAuthority]).rpc();
+
+    const holderSnapshot = PublicKey.findProgramAddressSync(
+      [Buffer.from("holder_snapshot"), distributorState.toBuffer(), new BN(0).toBuffer("le", 8)],
+      program.programId,
+    )[0];
+    const snapshot = await program.account.holderSnapshot.fetch(holderSnapshot);
+    const signature = await program.methods.distribute([])
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        serviceSigner: null,
+        committedDraw: null,
+        // the snapshot committed for the round has 20 holders
+        holderSnapshot,
+        distributorState,
+        mint: mint.publicKey,
+        treasury: null,
+        round,
+        draw: null,
+        nativeUnwrap: null,
+        blocklist: null,
+        previousRound: null,
+        vestingEscrow: null,
+        feeAccount: null,
+        feeVault: null,
+        triggerBonusAccount: null,
+      }).remainingAccounts(remainingAccounts)
+      .signers([distributorAuthority])
+      .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({units: 1_400_000})])
+      .rpc({skipPreflight: true, commitment: "confirmed"});
+
+    const [event] = await fetchCpiEvents(program, signature);
+    expect(event.name).to.equal("DistributeEvent");
+    expect(event.data.round.toNumber()).to.equal(0);
+    expect(event.data.winners.length).to.equal(numberOfShares.toNumber() - 1);
+    expect(event.data.notes).to.be.empty;
+    expect(event.data.burned.toString()).to.equal(shareSize.divn(2).toString());
+    // the test mint has no transfer fee
+    expect(event.data.delivered.map((amount) => amount.toString()))
+      .to.deep.equal(event.data.amounts.map((amount) => amount.toString()));
+    expect(event.data.fees.toNumber()).to.equal(0);
+    expect(event.data.holdersCount.toNumber()).to.equal(20);
+
+    const roundAccount = await program.account.distributionRound.fetch(round);
+    expect(roundAccount.payouts.map((payout) => payout.wallet.toString()))
+      .to.deep.equal(event.data.winners.map((winner) => winner.toString()));
+    expect(roundAccount.rollover.toString()).to.equal(shareSize.divn(2).toString());
+    expect(roundAccount.holdersCount.toNumber()).to.equal(20);
+    expect(Buffer.from(roundAccount.snapshotRoot).equals(Buffer.from(snapshot.merkleRoot))).to.be.true;
+
+    // half of the last share is burned and half is rolled over to the next round
+    const vaultAfter = await splProgram.account.account.fetch(vaultAddress);
+    expect(vaultAfter.amount.toString()).to.equal(shareSize.divn(2).toString());
+  });
+
+  it("Should pay an extra mint to the winners of the last round", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const extraMint = Keypair.generate();
+    await createMintIfRequired(splProgram, extraMint, provider.wallet.publicKey);
+
+    const extraShareSize = new BN(1_000_000);
+    const vaultConfig = PublicKey.findProgramAddressSync([Buffer.from("vault_config"), distributorState.toBuffer(), extraMint.publicKey.toBuffer()], program.programId)[0];
+    const extraVault = PublicKey.findProgramAddressSync([Buffer.from("extra_vault"), distributorState.toBuffer(), extraMint.publicKey.toBuffer()], program.programId)[0];
+    await program.methods.addPayoutMint(extraShareSize)
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+        mint: extraMint.publicKey,
+        vaultConfig,
+        vault: extraVault,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+
+    const round = deriveRoundAddress(distributorState, new BN(0), program.programId);
+    const roundAccount = await program.account.distributionRound.fetch(round);
+    const winners = roundAccount.payouts.map((payout) => payout.wallet);
+    await mintTo(splProgram, extraShareSize.muln(winners.length), extraMint.publicKey, extraVault, provider.wallet.publicKey);
+
+    const remainingAccounts: AccountMeta[] = winners.flatMap((wallet) => [
+      {pubkey: wallet, isWritable: false, isSigner: false},
+      {pubkey: getATA(wallet, extraMint.publicKey), isWritable: true, isSigner: false},
+    ]);
+    const distributeExtraMint = (firstPayout: BN, accounts: AccountMeta[]) => program.methods.distributeExtraMint(firstPayout)
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        serviceSigner: null,
+        distributorState,
+        vaultConfig,
+        mint: extraMint.publicKey,
+        vault: extraVault,
+        round,
+      }).remainingAccounts(accounts)
+      .signers([distributorAuthority])
+      .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({units: 1_400_000})])
+      .rpc({commitment: "confirmed"});
+
+    // the payouts have to be paid in the order of the round record
+    await expect(distributeExtraMint(new BN(3), remainingAccounts.slice(6))).to.be.rejected;
+    await distributeExtraMint(new BN(0), remainingAccounts.slice(0, 6));
+    const signature = await distributeExtraMint(new BN(3), remainingAccounts.slice(6));
+    await expect(distributeExtraMint(new BN(0), remainingAccounts)).to.be.rejected;
+
+    const [event] = await fetchCpiEvents(program, signature);
+    expect(event.name).to.equal("ExtraMintDistributeEvent");
+    expect(event.data.firstPayout.toNumber()).to.equal(3);
+    expect(event.data.winners.length).to.equal(winners.length - 3);
+
+    const winnerAccount = await splProgram.account.account.fetch(getATA(winners[0], extraMint.publicKey));
+    expect(winnerAccount.amount.toString()).to.equal(extraShareSize.toString());
+    const vaultAccount = await splProgram.account.account.fetch(extraVault);
+    expect(vaultAccount.amount.toNumber()).to.equal(0);
+  });
+
+  it("Should let winners claim committed shares", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const vaultAddress = deriveVaultAddress(distributorState, program.programId);
+
+    await splProgram.methods.transferChecked(shareSize.mul(numberOfShares), 9).accounts({
+      source: funderToken.publicKey,
+      mint: mint.publicKey,
+      destination: vaultAddress,
+      authority: provider.wallet.publicKey,
+    }).rpc();
+
+    const winners = Array.from({length: numberOfShares.toNumber() - 1}, () => Keypair.generate());
+    const tree = buildMerkleTree(winners.map((winner) => [winner.publicKey, shareSize]));
+
+    // referrals get 10% of the claims of the round
+    await program.methods.updateConfig({shareSize: null, numberOfShares: null, claimPeriod: null, unclaimedPolicy: null, minIntervalSeconds: null, minDeposit: null, referralBps: 1000})
+      .accounts({
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+        coAuthority: null,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+
+    const claimRound = deriveClaimRoundAddress(distributorState, new BN(1), program.programId);
+    const escrow = deriveVaultAddress(claimRound, program.programId);
+    const distributeClaims = () => program.methods.distributeClaims(Array.from(tree.root))
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        serviceSigner: null,
+        distributorState,
+        mint: mint.publicKey,
+        treasury: null,
+        feeAccount: null,
+        claimRound,
+        escrow,
+        round: null,
+        draw: null,
+        feeVault: null,
+        triggerBonusAccount: null,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+
+    // the claimants can't be bound to a holders snapshot
+    const sensitiveConfig = {distributorAuthority: distributorAuthority.publicKey, coAuthority: null, distributorState};
+    await program.methods.setRequiresHolderSnapshot(true).accounts(sensitiveConfig).signers([distributorAuthority]).rpc();
+    await expect(distributeClaims()).to.be.rejectedWith(/WrongDistributionMode/);
+    await program.methods.setRequiresHolderSnapshot(false).accounts(sensitiveConfig).signers([distributorAuthority]).rpc();
+    // nor to the winners of a committed draw
+    await program.methods.setRequiresCommittedDraw(true).accounts(sensitiveConfig).signers([distributorAuthority]).rpc();
+    await expect(distributeClaims()).to.be.rejectedWith(/WrongDistributionMode/);
+    await program.methods.setRequiresCommittedDraw(false).accounts(sensitiveConfig).signers([distributorAuthority]).rpc();
+    // nor to the winners of a VRF draw
+    await program.methods.setRequiresVrfDraw(true).accounts(sensitiveConfig).signers([distributorAuthority]).rpc();
+    await expect(distributeClaims()).to.be.rejectedWith(/WrongDistributionMode/);
+    await program.methods.setRequiresVrfDraw(false).accounts(sensitiveConfig).signers([distributorAuthority]).rpc();
+
+    await distributeClaims();
+
+    const winnerToken = Keypair.generate();
+    await createToken(splProgram, winnerToken, mint.publicKey, winners[3].publicKey);
+
+    const claim = (amount: BN) => program.methods.claim(new BN(3), amount, tree.proof(3).map((node) => Array.from(node)))
+      .accounts({
+        claimant: winners[3].publicKey,
+        payer: provider.wallet.publicKey,
+        claimRound,
+        distributorState,
+        blocklist: null,
+        round: null,
+        previousRound: null,
+        mint: mint.publicKey,
+        escrow,
+        tokenAccount: winnerToken.publicKey,
+        referral: null,
+      })
+      .signers([winners[3]])
+      .rpc();
+
+    await expect(claim(shareSize.muln(2))).to.be.rejected;
+    await claim(shareSize);
+    await expect(claim(shareSize)).to.be.rejected;
+
+    const winnerAccount = await splProgram.account.account.fetch(winnerToken.publicKey);
+    expect(winnerAccount.amount.toString()).to.equal(shareSize.toString());
+
+    const referredToken = Keypair.generate();
+    const referralToken = Keypair.generate();
+    await createToken(splProgram, referredToken, mint.publicKey, winners[4].publicKey);
+    await createToken(splProgram, referralToken, mint.publicKey, Keypair.generate().publicKey);
+    await program.methods.claim(new BN(4), shareSize, tree.proof(4).map((node) => Array.from(node)))
+      .accounts({
+        claimant: winners[4].publicKey,
+        payer: provider.wallet.publicKey,
+        claimRound,
+        distributorState,
+        blocklist: null,
+        round: null,
+        previousRound: null,
+        mint: mint.publicKey,
+        escrow,
+        tokenAccount: referredToken.publicKey,
+        referral: referralToken.publicKey,
+      })
+      .signers([winners[4]])
+      .rpc();
+    const referralAmount = shareSize.muln(1000).divn(10000);
+    expect((await splProgram.account.account.fetch(referredToken.publicKey)).amount.toString()).to.equal(shareSize.sub(referralAmount).toString());
+    expect((await splProgram.account.account.fetch(referralToken.publicKey)).amount.toString()).to.equal(referralAmount.toString());
+    expect((await program.account.claimRound.fetch(claimRound)).referralAmount.toString()).to.equal(referralAmount.toString());
+
+    // the unclaimed shares stay in the escrow until the claim period is over
+    await expect(program.methods.closeClaims()
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorState,
+        claimRound,
+        mint: mint.publicKey,
+        vault: vaultAddress,
+        escrow,
+      })
+      .rpc()).to.be.rejected;
+  });
+
+  it("Should update share size and number of shares", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+
+    await expect(program.methods.updateConfig({shareSize: null, numberOfShares: new BN(1), claimPeriod: null, unclaimedPolicy: null, minIntervalSeconds: null, minDeposit: null, referralBps: null})
+      .accounts({
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+        coAuthority: null,
+      })
+      .signers([distributorAuthority])
+      .rpc()).to.be.rejected;
+
+    await program.methods.updateConfig({shareSize: shareSize.muln(2), numberOfShares: new BN(5), claimPeriod: null, unclaimedPolicy: null, minIntervalSeconds: null, minDeposit: null, referralBps: null})
+      .accounts({
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+        coAuthority: null,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+
+    // the address is still derived from the initial parameters
+    const state = await program.account.distributorState.fetch(distributorState);
+    expect(state.shareSize.toString()).to.equal(shareSize.muln(2).toString());
+    expect(state.numberOfShares.toNumber()).to.equal(5);
+    expect(state.initialShareSize.toString()).to.equal(shareSize.toString());
+  });
+
+  it("Should distribute weighted shares", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const vaultAddress = deriveVaultAddress(distributorState, program.programId);
+
+    // the config was updated to 5 shares, so there are 4 winners
+    let remainingAccounts: AccountMeta[] = [];
+    for (let i = 0; i < 4; i++) {
+      const receiver = Keypair.generate();
+      const ata = getATA(receiver.publicKey, mint.publicKey);
+      remainingAccounts.push({pubkey: receiver.publicKey, isWritable: false, isSigner: false});
+      remainingAccounts.push({pubkey: ata, isWritable: true, isSigner: false});
+    }
+
+    const vaultBefore = await splProgram.account.account.fetch(vaultAddress);
+    const distributeWeighted = (amounts: BN[]) => program.methods.distributeWeighted(amounts, [])
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        serviceSigner: null,
+        committedDraw: null,
+        holderSnapshot: null,
+        distributorState,
+        mint: mint.publicKey,
+        treasury: null,
+        round: null,
+        draw: null,
+        nativeUnwrap: null,
+        blocklist: null,
+        previousRound: null,
+        vestingEscrow: null,
+        feeAccount: null,
+        feeVault: null,
+        triggerBonusAccount: null,
+      }).remainingAccounts(remainingAccounts)
+      .signers([distributorAuthority])
+      .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({units: 1_400_000})])
+      .rpc();
+
+    await expect(distributeWeighted([vaultBefore.amount, new BN(1), new BN(0), new BN(0)])).to.be.rejected;
+
+    const first = vaultBefore.amount.divn(2);
+    const rest = vaultBefore.amount.sub(first).divn(3);
+    await distributeWeighted([first, rest, rest, rest]);
+
+    const firstAccount = await splProgram.account.account.fetch(remainingAccounts[1].pubkey);
+    expect(firstAccount.amount.toString()).to.equal(first.toString());
+    const vaultAfter = await splProgram.account.account.fetch(vaultAddress);
+    expect(vaultAfter.amount.toString()).to.equal(vaultBefore.amount.sub(first).sub(rest.muln(3)).toString());
+  });
+
+  it("Shouldn't distribute before the minimal interval since the last round", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const setMinInterval = (minIntervalSeconds: BN) => program.methods.updateConfig({shareSize: null, numberOfShares: null, claimPeriod: null, unclaimedPolicy: null, minIntervalSeconds, minDeposit: null, referralBps: null})
+      .accounts({
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+        coAuthority: null,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+
+    await setMinInterval(new BN(3600));
+
+    let remainingAccounts: AccountMeta[] = [];
+    for (let i = 0; i < 4; i++) {
+      const receiver = Keypair.generate();
       remainingAccounts.push({pubkey: receiver.publicKey, isWritable: false, isSigner: false});
-      remainingAccounts.push({pubkey: ata, isWritable: true, isSigner: false});
+      remainingAccounts.push({pubkey: getATA(receiver.publicKey, mint.publicKey), isWritable: true, isSigner: false});
     }
-
-    await program.methods.distribute()
+    await expect(program.methods.distributeWeighted([new BN(1), new BN(0), new BN(0), new BN(0)], [])
       .accounts({
         payer: provider.wallet.publicKey,
         distributorAuthority: distributorAuthority.publicKey,
+        serviceSigner: null,
+        committedDraw: null,
+        holderSnapshot: null,
         distributorState,
         mint: mint.publicKey,
+        treasury: null,
+        round: null,
+        draw: null,
+        nativeUnwrap: null,
+        blocklist: null,
+        previousRound: null,
+        vestingEscrow: null,
+        feeAccount: null,
+        feeVault: null,
+        triggerBonusAccount: null,
       }).remainingAccounts(remainingAccounts)
       .signers([distributorAuthority])
+      .rpc()).to.be.rejectedWith(/IntervalNotElapsed/);
+
+    await setMinInterval(new BN(0));
+  });
+
+  it("Should transfer authority in two steps", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const newAuthority = Keypair.generate();
+
+    await program.methods.nominateAuthority(newAuthority.publicKey)
+      .accounts({
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+        coAuthority: null,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+
+    await expect(program.methods.acceptAuthority()
+      .accounts({
+        pendingAuthority: provider.wallet.publicKey,
+        distributorState,
+      })
+      .rpc()).to.be.rejected;
+
+    await program.methods.acceptAuthority()
+      .accounts({
+        pendingAuthority: newAuthority.publicKey,
+        distributorState,
+      })
+      .signers([newAuthority])
+      .rpc();
+
+    let state = await program.account.distributorState.fetch(distributorState);
+    expect(state.distributorAuthority.toBase58()).to.equal(newAuthority.publicKey.toBase58());
+    expect(state.pendingAuthority).to.be.null;
+
+    // hand the distributor back for the following tests
+    await program.methods.nominateAuthority(distributorAuthority.publicKey)
+      .accounts({
+        distributorAuthority: newAuthority.publicKey,
+        distributorState,
+        coAuthority: null,
+      })
+      .signers([newAuthority])
+      .rpc();
+    await program.methods.acceptAuthority()
+      .accounts({
+        pendingAuthority: distributorAuthority.publicKey,
+        distributorState,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+  });
+
+  it("Should require the co-authority for sensitive changes once it is set", async () => {
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, shareSize, numberOfShares, program.programId);
+    const coAuthority = Keypair.generate();
+    const setCoAuthority = (newCoAuthority: PublicKey | null, signer: Keypair | null) => program.methods.setCoAuthority(newCoAuthority)
+      .accounts({
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+        coAuthority: signer?.publicKey ?? null,
+      })
+      .signers(signer ? [distributorAuthority, signer] : [distributorAuthority])
+      .rpc();
+
+    await expect(setCoAuthority(distributorAuthority.publicKey, null)).to.be.rejectedWith(/InvalidCoAuthority/);
+    await setCoAuthority(coAuthority.publicKey, null);
+    await expect(program.methods.nominateAuthority(Keypair.generate().publicKey)
+      .accounts({
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+        coAuthority: null,
+      })
+      .signers([distributorAuthority])
+      .rpc()).to.be.rejectedWith(/MissingCoAuthority/);
+    await expect(setCoAuthority(null, null)).to.be.rejectedWith(/MissingCoAuthority/);
+
+    await setCoAuthority(null, coAuthority);
+    const state = await program.account.distributorState.fetch(distributorState);
+    expect(state.coAuthority).to.be.null;
+  });
+
+  it("Should distribute a round in chunks", async () => {
+    // more winners than a single transaction can pay
+    const chunkedShareSize = new BN(7_000_000_000);
+    const chunkedShares = new BN(12);
+    await program.methods.initialize({shareSize: chunkedShareSize, numberOfShares: chunkedShares, remainderPolicy: {burn: {}}, distributionMode: {chunked: {}}, coAuthority: null, burnShares: new BN(1), name: "", uri: "", maxRounds: null, shareSizeDecimals: null})
+      .accounts({
+        payer: provider.wallet.publicKey,
+        mint: mint.publicKey,
+        markerMint: markerMint.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+      })
+      .rpc();
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, chunkedShareSize, chunkedShares, program.programId);
+    const vaultAddress = deriveVaultAddress(distributorState, program.programId);
+    await splProgram.methods.transferChecked(chunkedShareSize.mul(chunkedShares), 9).accounts({
+      source: funderToken.publicKey,
+      mint: mint.publicKey,
+      destination: vaultAddress,
+      authority: provider.wallet.publicKey,
+    }).rpc();
+
+    const roundProgress = deriveRoundProgressAddress(distributorState, new BN(0), program.programId);
+    const round = deriveRoundAddress(distributorState, new BN(0), program.programId);
+    await program.methods.beginRound()
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        serviceSigner: null,
+        distributorState,
+        vault: vaultAddress,
+        roundProgress,
+        round,
+        feeVault: null,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+
+    const winners = Array.from({length: chunkedShares.toNumber() - 1}, () => Keypair.generate());
+    const distributeChunk = (firstShare: number, chunk: Keypair[]) => program.methods.distributeChunk(new BN(firstShare), [])
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        serviceSigner: null,
+        distributorState,
+        mint: mint.publicKey,
+        roundProgress,
+        round,
+        nativeUnwrap: null,
+        blocklist: null,
+        previousRound: null,
+        vestingEscrow: null,
+      })
+      .remainingAccounts(chunk.flatMap((winner) => [
+        {pubkey: winner.publicKey, isWritable: false, isSigner: false},
+        {pubkey: getATA(winner.publicKey, mint.publicKey), isWritable: true, isSigner: false},
+      ]))
+      .signers([distributorAuthority])
+      .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({units: 1_400_000})])
+      .rpc();
+    const finalizeRound = () => program.methods.finalizeRound()
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        serviceSigner: null,
+        distributorState,
+        mint: mint.publicKey,
+        treasury: null,
+        feeAccount: null,
+        roundProgress,
+        round,
+        draw: null,
+        triggerBonusAccount: null,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+
+    await distributeChunk(0, winners.slice(0, 6));
+    // a retried chunk isn't paid twice
+    await expect(distributeChunk(0, winners.slice(0, 6))).to.be.rejected;
+    // the open round can't be paid by another instruction
+    await expect(program.methods.distribute([])
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        serviceSigner: null,
+        committedDraw: null,
+        holderSnapshot: null,
+        distributorState,
+        mint: mint.publicKey,
+        treasury: null,
+        round: null,
+        roundProgress,
+        draw: null,
+        nativeUnwrap: null,
+        blocklist: null,
+        previousRound: null,
+        vestingEscrow: null,
+        feeAccount: null,
+        feeVault: null,
+        triggerBonusAccount: null,
+      })
+      .remainingAccounts(winners.slice(6).flatMap((winner) => [
+        {pubkey: winner.publicKey, isWritable: false, isSigner: false},
+        {pubkey: getATA(winner.publicKey, mint.publicKey), isWritable: true, isSigner: false},
+      ]))
+      .signers([distributorAuthority])
+      .rpc()).to.be.rejectedWith(/RoundInProgress/);
+    await expect(finalizeRound()).to.be.rejected;
+    await distributeChunk(6, winners.slice(6));
+    await finalizeRound();
+
+    const state = await program.account.distributorState.fetch(distributorState);
+    expect(state.round.toNumber()).to.equal(1);
+    expect(await provider.connection.getAccountInfo(roundProgress)).to.be.null;
+
+    const roundAccount = await program.account.distributionRound.fetch(round);
+    expect(roundAccount.payouts.map((payout) => payout.wallet.toString()))
+      .to.deep.equal(winners.map((winner) => winner.publicKey.toString()));
+    expect(roundAccount.burned.toString()).to.equal(chunkedShareSize.toString());
+
+    const vaultAfter = await splProgram.account.account.fetch(vaultAddress);
+    expect(vaultAfter.amount.toNumber()).to.equal(0);
+  });
+
+  it("Should migrate the vault to a replacement vault", async () => {
+    const chunkedShareSize = new BN(7_000_000_000);
+    const chunkedShares = new BN(12);
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, chunkedShareSize, chunkedShares, program.programId);
+    const vaultAddress = deriveVaultAddress(distributorState, program.programId);
+    const replacementVault = PublicKey.findProgramAddressSync([
+      Buffer.from("vault"), distributorState.toBuffer(), new BN(1).toBuffer("le", 4)
+    ], program.programId)[0];
+    const amount = new BN(5_000_000_000);
+    await splProgram.methods.transferChecked(amount, 9).accounts({
+      source: funderToken.publicKey,
+      mint: mint.publicKey,
+      destination: vaultAddress,
+      authority: provider.wallet.publicKey,
+    }).rpc();
+
+    await program.methods.createReplacementVault()
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+        mint: mint.publicKey,
+        replacementVault,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+    await program.methods.migrateVault(false)
+      .accounts({
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+        mint: mint.publicKey,
+        vault: vaultAddress,
+        replacementVault,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+
+    const state = await program.account.distributorState.fetch(distributorState);
+    expect(state.vault.toBase58()).to.equal(replacementVault.toBase58());
+    expect(state.vaultNonce).to.equal(1);
+    expect(state.replacementVault).to.be.null;
+    expect((await splProgram.account.account.fetch(vaultAddress)).amount.toNumber()).to.equal(0);
+    expect((await splProgram.account.account.fetch(replacementVault)).amount.toString()).to.equal(amount.toString());
+  });
+
+  it("Should withdraw in an emergency only after the timelock", async () => {
+    const chunkedShareSize = new BN(7_000_000_000);
+    const chunkedShares = new BN(12);
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, chunkedShareSize, chunkedShares, program.programId);
+    const {vault} = await program.account.distributorState.fetch(distributorState);
+    const amount = new BN(1_000_000_000);
+
+    await program.methods.requestEmergencyWithdraw(amount, funderToken.publicKey)
+      .accounts({
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+        coAuthority: null,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+    const {pendingWithdraw} = await program.account.distributorState.fetch(distributorState);
+    expect(pendingWithdraw.amount.toString()).to.equal(amount.toString());
+    expect(pendingWithdraw.destination.toBase58()).to.equal(funderToken.publicKey.toBase58());
+    expect(pendingWithdraw.unlocksAt.toNumber()).to.be.greaterThan(Date.now() / 1000 + 47 * 60 * 60);
+
+    await expect(program.methods.emergencyWithdraw()
+      .accounts({
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+        mint: mint.publicKey,
+        vault,
+        destination: funderToken.publicKey,
+      })
+      .signers([distributorAuthority])
+      .rpc()).to.be.rejected;
+
+    await program.methods.cancelEmergencyWithdraw()
+      .accounts({
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+    const state = await program.account.distributorState.fetch(distributorState);
+    expect(state.pendingWithdraw).to.be.null;
+  });
+
+  it("Shouldn't close a distributor which isn't paused", async () => {
+    const chunkedShareSize = new BN(7_000_000_000);
+    const chunkedShares = new BN(12);
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, chunkedShareSize, chunkedShares, program.programId);
+    const {vault} = await program.account.distributorState.fetch(distributorState);
+
+    await expect(program.methods.closeDistributor()
+      .accounts({
+        distributorAuthority: distributorAuthority.publicKey,
+        coAuthority: null,
+        distributorState,
+        vault,
+        vestingEscrow: PublicKey.findProgramAddressSync([Buffer.from("vesting_escrow"), distributorState.toBuffer()], program.programId)[0],
+        feeVault: PublicKey.findProgramAddressSync([Buffer.from("fee_vault"), distributorState.toBuffer()], program.programId)[0],
+        rentRecipient: provider.wallet.publicKey,
+      })
+      .signers([distributorAuthority])
+      .rpc()).to.be.rejected;
+    expect(await program.account.distributorState.fetchNullable(distributorState)).to.not.be.null;
+  });
+
+  it("Should distribute native SOL to wallets", async () => {
+    const nativeMint = new PublicKey("So11111111111111111111111111111111111111112");
+    const nativeShareSize = new BN(1_000_000_000);
+    const nativeShares = new BN(3);
+    await expect(program.methods.initialize({shareSize: nativeShareSize, numberOfShares: nativeShares, remainderPolicy: {burn: {}}, distributionMode: {singleTx: {}}, coAuthority: null, burnShares: new BN(1), name: "", uri: "", maxRounds: null, shareSizeDecimals: null})
+      .accounts({
+        payer: provider.wallet.publicKey,
+        mint: nativeMint,
+        markerMint: markerMint.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+      })
+      .rpc()).to.be.rejected;
+    await program.methods.initialize({shareSize: nativeShareSize, numberOfShares: nativeShares, remainderPolicy: {rollover: {}}, distributionMode: {singleTx: {}}, coAuthority: null, burnShares: new BN(1), name: "", uri: "", maxRounds: null, shareSizeDecimals: null})
+      .accounts({
+        payer: provider.wallet.publicKey,
+        mint: nativeMint,
+        markerMint: markerMint.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+      })
+      .rpc();
+    const distributorState = deriveDistributorStateAddress(nativeMint, markerMint.publicKey, nativeShareSize, nativeShares, program.programId);
+    const vaultAddress = deriveVaultAddress(distributorState, program.programId);
+
+    await program.methods.depositNative(nativeShareSize.mul(nativeShares))
+      .accounts({
+        distributorState,
+        vault: vaultAddress,
+        authority: provider.wallet.publicKey,
+        depositorRecord: null,
+        depositAllowance: null,
+      })
+      .rpc();
+
+    const winners = Array.from({length: nativeShares.toNumber() - 1}, () => Keypair.generate());
+    await program.methods.distribute([])
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        serviceSigner: null,
+        committedDraw: null,
+        holderSnapshot: null,
+        distributorState,
+        mint: nativeMint,
+        treasury: null,
+        round: null,
+        draw: null,
+        nativeUnwrap: PublicKey.findProgramAddressSync([Buffer.from("native_unwrap"), distributorState.toBuffer()], program.programId)[0],
+        blocklist: null,
+        previousRound: null,
+        vestingEscrow: null,
+        feeAccount: null,
+        feeVault: null,
+        triggerBonusAccount: null,
+      })
+      .remainingAccounts(winners.map((winner) => ({pubkey: winner.publicKey, isWritable: true, isSigner: false})))
+      .signers([distributorAuthority])
+      .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({units: 1_400_000})])
+      .rpc();
+
+    for (const winner of winners) {
+      expect(await provider.connection.getBalance(winner.publicKey)).to.equal(nativeShareSize.toNumber());
+    }
+    const vault = await splProgram.account.account.fetch(vaultAddress);
+    expect(vault.amount.toString()).to.equal(nativeShareSize.toString());
+  });
+
+  it("Should escrow shares until they are claimed or expire", async () => {
+    const escrowShareSize = new BN(3_000_000_000);
+    const escrowShares = new BN(3);
+    await program.methods.initialize({shareSize: escrowShareSize, numberOfShares: escrowShares, remainderPolicy: {burn: {}}, distributionMode: {singleTx: {}}, coAuthority: null, burnShares: new BN(1), name: "", uri: "", maxRounds: null, shareSizeDecimals: null})
+      .accounts({
+        payer: provider.wallet.publicKey,
+        mint: mint.publicKey,
+        markerMint: markerMint.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+      })
+      .rpc();
+    const distributorState = deriveDistributorStateAddress(mint.publicKey, markerMint.publicKey, escrowShareSize, escrowShares, program.programId);
+    const vaultAddress = deriveVaultAddress(distributorState, program.programId);
+    await splProgram.methods.transferChecked(escrowShareSize.mul(escrowShares).muln(2), 9).accounts({
+      source: funderToken.publicKey,
+      mint: mint.publicKey,
+      destination: vaultAddress,
+      authority: provider.wallet.publicKey,
+    }).rpc();
+
+    const [vestingEscrow] = PublicKey.findProgramAddressSync([Buffer.from("vesting_escrow"), distributorState.toBuffer()], program.programId);
+    await program.methods.openVestingEscrow()
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+        mint: mint.publicKey,
+        vestingEscrow,
+        systemProgram: web3.SystemProgram.programId,
+        tokenProgram: SPL_TOKEN_PROGRAM_ID,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+    const setClaimPeriod = (claimPeriod: BN) => program.methods.setEscrowClaimPeriod(claimPeriod)
+      .accounts({
+        distributorAuthority: distributorAuthority.publicKey,
+        distributorState,
+      })
+      .signers([distributorAuthority])
+      .rpc();
+    const escrowedShareAddress = (round: number, wallet: PublicKey) => PublicKey.findProgramAddressSync(
+      [Buffer.from("escrowed_share"), distributorState.toBuffer(), new BN(round).toBuffer("le", 8), wallet.toBuffer()],
+      program.programId,
+    )[0];
+    // the first winner of the round gets its share escrowed, the second one is paid to its token account
+    const distribute = (round: number, escrowed: Keypair, paid: Keypair) => program.methods.distribute([])
+      .accounts({
+        payer: provider.wallet.publicKey,
+        distributorAuthority: distributorAuthority.publicKey,
+        serviceSigner: null,
+        committedDraw: null,
+        holderSnapshot: null,
+        distributorState,
+        mint: mint.publicKey,
+        treasury: null,
+        round: null,
+        draw: null,
+        nativeUnwrap: null,
+        blocklist: null,
+        previousRound: null,
+        vestingEscrow,
+        feeAccount: null,
+        feeVault: null,
+        triggerBonusAccount: null,
+      })
+      .remainingAccounts([
+        {pubkey: escrowed.publicKey, isWritable: false, isSigner: false},
+        {pubkey: escrowedShareAddress(round, escrowed.publicKey), isWritable: true, isSigner: false},
+        {pubkey: paid.publicKey, isWritable: false, isSigner: false},
+        {pubkey: getATA(paid.publicKey, mint.publicKey), isWritable: true, isSigner: false},
+      ])
+      .signers([distributorAuthority])
       .preInstructions([ComputeBudgetProgram.setComputeUnitLimit({units: 1_400_000})])
-      .rpc({skipPreflight: true});
+      .rpc();
+
+    await setClaimPeriod(new BN(24 * 60 * 60));
+    const claimant = Keypair.generate();
+    // anyone can fund the address of the escrowed share before the round
+    await provider.sendAndConfirm(new web3.Transaction().add(web3.SystemProgram.transfer({
+      fromPubkey: provider.wallet.publicKey,
+      toPubkey: escrowedShareAddress(0, claimant.publicKey),
+      lamports: 1_000_000,
+    })));
+    await distribute(0, claimant, Keypair.generate());
+    const escrowedShare = escrowedShareAddress(0, claimant.publicKey);
+    expect((await program.account.escrowedShare.fetch(escrowedShare)).amount.toString()).to.equal(escrowShareSize.toString());
+
+    const claimantToken = Keypair.generate();
+    await createToken(splProgram, claimantToken, mint.publicKey, claimant.publicKey);
+    await program.methods.claimEscrowed()
+      .accounts({
+        wallet: claimant.publicKey,
+        escrowedShare,
+        payer: provider.wallet.publicKey,
+        distributorState,
+        mint: mint.publicKey,
+        vestingEscrow,
+        tokenAccount: claimantToken.publicKey,
+      })
+      .signers([claimant])
+      .rpc();
+    expect((await splProgram.account.account.fetch(claimantToken.publicKey)).amount.toString()).to.equal(escrowShareSize.toString());
+    expect(await program.account.escrowedShare.fetchNullable(escrowedShare)).to.be.null;
+
+    await setClaimPeriod(new BN(2));
+    const absent = Keypair.generate();
+    await distribute(1, absent, Keypair.generate());
+    const expiring = escrowedShareAddress(1, absent.publicKey);
+    const sweep = () => program.methods.sweepExpired()
+      .accounts({
+        escrowedShare: expiring,
+        payer: provider.wallet.publicKey,
+        distributorState,
+        mint: mint.publicKey,
+        vestingEscrow,
+        vault: vaultAddress,
+      })
+      .rpc();
+    await expect(sweep()).to.be.rejectedWith(/ClaimsNotExpired/);
+
+    await new Promise((resolve) => setTimeout(resolve, 3000));
+    const vaultBefore = await splProgram.account.account.fetch(vaultAddress);
+    await sweep();
+    const vaultAfter = await splProgram.account.account.fetch(vaultAddress);
+    expect(vaultAfter.amount.sub(vaultBefore.amount).toString()).to.equal(escrowShareSize.toString());
+    expect(await program.account.escrowedShare.fetchNullable(expiring)).to.be.null;
   });
 });
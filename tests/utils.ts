@@ -1,4 +1,5 @@
 import * as anchor from "@coral-xyz/anchor";
+import {createHash} from "crypto";
 import { PublicKey, Keypair } from '@solana/web3.js';
 import {Program, web3, BN, AnchorProvider} from "@coral-xyz/anchor";
 
@@ -60,4 +61,56 @@ export function getATA(owner: PublicKey, mint: PublicKey) {
     anchor.utils.token.ASSOCIATED_PROGRAM_ID
   );
   return ata;
-}
\ No newline at end of file
+}
+function hash(...parts: Buffer[]): Buffer {
+  return createHash("sha256").update(Buffer.concat(parts)).digest();
+}
+
+function merkleLeaf(index: number, wallet: PublicKey, amount: BN): Buffer {
+  return hash(Buffer.from([0]), new BN(index).toBuffer("le", 8), wallet.toBuffer(), amount.toBuffer("le", 8));
+}
+
+function merkleNode(left: Buffer, right: Buffer): Buffer {
+  return Buffer.compare(left, right) <= 0 ? hash(Buffer.from([1]), left, right) : hash(Buffer.from([1]), right, left);
+}
+
+function snapshotLeaf(index: number, wallet: PublicKey): Buffer {
+  return hash(Buffer.from([2]), new BN(index).toBuffer("le", 8), wallet.toBuffer());
+}
+
+// Mirrors `distributor::client::MerkleTree::new`
+export function buildMerkleTree(claims: [PublicKey, BN][]) {
+  return buildTree(claims.map(([wallet, amount], index) => merkleLeaf(index, wallet, amount)));
+}
+
+// Mirrors `distributor::client::MerkleTree::snapshot`
+export function buildSnapshotTree(holders: PublicKey[]) {
+  return buildTree(holders.map((wallet, index) => snapshotLeaf(index, wallet)));
+}
+
+function buildTree(leaves: Buffer[]) {
+  const levels = [leaves];
+  while (levels[levels.length - 1].length > 1) {
+    const level = levels[levels.length - 1];
+    const next: Buffer[] = [];
+    for (let i = 0; i < level.length; i += 2) {
+      next.push(i + 1 < level.length ? merkleNode(level[i], level[i + 1]) : level[i]);
+    }
+    levels.push(next);
+  }
+
+  return {
+    root: levels[levels.length - 1][0],
+    proof(index: number): Buffer[] {
+      const proof: Buffer[] = [];
+      for (const level of levels.slice(0, -1)) {
+        const sibling = level[index ^ 1];
+        if (sibling !== undefined) {
+          proof.push(sibling);
+        }
+        index = Math.floor(index / 2);
+      }
+      return proof;
+    },
+  };
+}